@@ -13,7 +13,7 @@ use debugger::CliState;
 use probe_rs::{
     architecture::arm::{component::TraceSink, swo::SwoConfig},
     debug::debug_info::DebugInfo,
-    flashing::{erase_all, BinOptions, FileDownloadError, Format},
+    flashing::{erase_all, BinOptions, FileDownloadError, Format, Uf2Options},
     MemoryInterface, Probe,
 };
 
@@ -120,6 +120,9 @@ enum Cli {
         /// The number of bytes to skip at the start of the binary file. This is only considered when `bin` is selected as the format.
         #[clap(long, value_parser = parse_u32)]
         skip_bytes: Option<u32>,
+        /// Only flash the blocks for the chip family with this ID, instead of the family derived from the chip. This is only considered when `uf2` is selected as the format.
+        #[clap(long, value_parser = parse_u32)]
+        family_id: Option<u32>,
 
         /// The path to the file to be downloaded to the flash
         path: String,
@@ -302,13 +305,14 @@ fn main() -> Result<()> {
             format,
             base_address,
             skip_bytes,
+            family_id,
             path,
             chip_erase,
             disable_progressbars,
             disable_double_buffering,
         } => download_program_fast(
             common,
-            format.into(base_address, skip_bytes),
+            format.into(base_address, skip_bytes, family_id),
             &path,
             chip_erase,
             disable_progressbars,
@@ -423,6 +427,7 @@ fn download_program_fast(
         Format::Bin(options) => loader.load_bin_data(&mut file, options),
         Format::Elf => loader.load_elf_data(&mut file),
         Format::Hex => loader.load_hex_data(&mut file),
+        Format::Uf2(options) => loader.load_uf2_data(&mut file, options),
     }?;
 
     run_flash_download(
@@ -572,10 +577,11 @@ enum DownloadFileType {
     Elf,
     Hex,
     Bin,
+    Uf2,
 }
 
 impl DownloadFileType {
-    fn into(self, base_address: Option<u64>, skip: Option<u32>) -> Format {
+    fn into(self, base_address: Option<u64>, skip: Option<u32>, family_id: Option<u32>) -> Format {
         match self {
             DownloadFileType::Elf => Format::Elf,
             DownloadFileType::Hex => Format::Hex,
@@ -583,6 +589,7 @@ impl DownloadFileType {
                 base_address,
                 skip: skip.unwrap_or(0),
             }),
+            DownloadFileType::Uf2 => Format::Uf2(Uf2Options { family_id }),
         }
     }
 }
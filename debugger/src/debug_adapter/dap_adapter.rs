@@ -713,11 +713,13 @@ impl<P: ProtocolAdapter> DebugAdapter<P> {
                 ) =
                     source_path
                 {
-                    match target_core.core_data.debug_info.get_breakpoint_location(
-                        source_path,
-                        requested_breakpoint_line,
-                        requested_breakpoint_column,
-                    ) {
+                    match target_core.core_data.debug_info.get().and_then(|debug_info| {
+                        debug_info.get_breakpoint_location(
+                            source_path,
+                            requested_breakpoint_line,
+                            requested_breakpoint_column,
+                        )
+                    }) {
                         Ok((Some(valid_breakpoint_location), breakpoint_source_location)) => {
                                 match target_core.set_breakpoint(
                                     valid_breakpoint_location,
@@ -849,11 +851,14 @@ impl<P: ProtocolAdapter> DebugAdapter<P> {
                         breakpoint_response.instruction_reference =
                             Some(format!("{:#010x}", memory_reference));
                         // Try to resolve the source location for this breakpoint.
-                        match target_core
-                            .core_data
-                            .debug_info
-                            .get_source_location(memory_reference)
-                        {
+                        let source_location = match target_core.core_data.debug_info.get() {
+                            Ok(debug_info) => debug_info.get_source_location(memory_reference),
+                            Err(error) => {
+                                tracing::warn!("Failed to load debug information: {:?}", error);
+                                None
+                            }
+                        };
+                        match source_location {
                             Some(source_location) => {
                                 breakpoint_response.source = get_dap_source(&source_location);
                                 breakpoint_response.line =
@@ -978,6 +983,7 @@ impl<P: ProtocolAdapter> DebugAdapter<P> {
                 target_core.core_data.stack_frames = target_core
                     .core_data
                     .debug_info
+                    .get()?
                     .unwind(&mut target_core.core, pc)?;
             }
             CoreStatus::Running | CoreStatus::LockedUp | CoreStatus::Sleeping => {
@@ -1392,6 +1398,15 @@ impl<P: ProtocolAdapter> DebugAdapter<P> {
         // We will only include source location data in a resulting instruction, if it is different from the previous one.
         let mut stored_source_location = None;
 
+        // Without debug information, we can still disassemble, but without source locations.
+        let debug_info = match target_core.core_data.debug_info.get() {
+            Ok(debug_info) => Some(debug_info),
+            Err(error) => {
+                tracing::warn!("Failed to load debug information: {:?}", error);
+                None
+            }
+        };
+
         // The MS DAP spec requires that we always have to return a fixed number of instructions.
         while assembly_lines.len() < instruction_count as usize {
             if read_more_bytes {
@@ -1466,10 +1481,8 @@ impl<P: ProtocolAdapter> DebugAdapter<P> {
                             let mut location = None;
                             let mut line = None;
                             let mut column = None;
-                            if let Some(current_source_location) = target_core
-                                .core_data
-                                .debug_info
-                                .get_source_location(instruction.address()) {
+                            if let Some(current_source_location) = debug_info
+                                .and_then(|debug_info| debug_info.get_source_location(instruction.address())) {
                                 if let Some(previous_source_location) = stored_source_location.clone() {
                                     if current_source_location != previous_source_location {
                                         location = get_dap_source(&current_source_location);
@@ -1724,13 +1737,17 @@ impl<P: ProtocolAdapter> DebugAdapter<P> {
                         && !variable_cache.has_children(parent_variable)?
                     {
                         if let Some(stack_frame_registers) = stack_frame_registers {
-                            target_core.core_data.debug_info.cache_deferred_variables(
-                                variable_cache,
-                                &mut target_core.core,
-                                parent_variable,
-                                stack_frame_registers,
-                                frame_base,
-                            )?;
+                            target_core
+                                .core_data
+                                .debug_info
+                                .get()?
+                                .cache_deferred_variables(
+                                    variable_cache,
+                                    &mut target_core.core,
+                                    parent_variable,
+                                    stack_frame_registers,
+                                    frame_base,
+                                )?;
                         } else {
                             tracing::error!("Could not cache deferred child variables for variable: {}. No register data available.", parent_variable.name);
                         }
@@ -1916,9 +1933,10 @@ impl<P: ProtocolAdapter> DebugAdapter<P> {
         target_core: &mut CoreHandle,
         request: Request,
     ) -> Result<(), anyhow::Error> {
-        let (new_status, program_counter) = match stepping_granularity
-            .step(&mut target_core.core, &target_core.core_data.debug_info)
-        {
+        let (new_status, program_counter) = match stepping_granularity.step(
+            &mut target_core.core,
+            target_core.core_data.debug_info.get()?,
+        ) {
             Ok((new_status, program_counter)) => (new_status, program_counter),
             Err(error) => match &error {
                 probe_rs::debug::DebugError::NoValidHaltLocation {
@@ -6,14 +6,14 @@ use crate::{
     DebuggerError,
 };
 use anyhow::Result;
-use probe_rs::{debug::debug_info::DebugInfo, Core};
+use probe_rs::{debug::debug_info::LazyDebugInfo, Core};
 use probe_rs_cli_util::rtt::{self, ChannelMode, DataFormat};
 
 /// [CoreData] is used to cache data needed by the debugger, on a per-core basis.
 pub struct CoreData {
     pub(crate) core_index: usize,
     pub(crate) target_name: String,
-    pub(crate) debug_info: DebugInfo,
+    pub(crate) debug_info: LazyDebugInfo,
     pub(crate) core_peripherals: Option<SvdCache>,
     pub(crate) stack_frames: Vec<probe_rs::debug::stack_frame::StackFrame>,
     pub(crate) breakpoints: Vec<session_data::ActiveBreakpoint>,
@@ -601,7 +601,9 @@ impl Debugger {
                 let mut download_options = DownloadOptions::default();
                 download_options.keep_unwritten_bytes =
                     self.config.flashing_config.restore_unwritten_bytes;
-                download_options.do_chip_erase = self.config.flashing_config.full_chip_erase;
+                if self.config.flashing_config.full_chip_erase {
+                    download_options.erase_strategy = probe_rs::flashing::EraseStrategy::ChipErase;
+                }
                 let flash_result = {
                     let rc_debug_adapter = Rc::new(RefCell::new(debug_adapter));
                     let rc_debug_adapter_clone = rc_debug_adapter.clone();
@@ -624,11 +626,12 @@ impl Debugger {
                             fill_size_done: 0,
                         }));
 
-                        let flash_progress = if let Some(id) = progress_id {
-                            FlashProgress::new(move |event| {
-                                let mut flash_progress = flash_progress.borrow_mut();
-                                let mut debug_adapter = rc_debug_adapter_clone.borrow_mut();
-                                match event {
+                        let flash_progress =
+                            if let Some(id) = progress_id {
+                                FlashProgress::new(move |event| {
+                                    let mut flash_progress = flash_progress.borrow_mut();
+                                    let mut debug_adapter = rc_debug_adapter_clone.borrow_mut();
+                                    match event {
                                     probe_rs::flashing::ProgressEvent::Initialized {
                                         flash_layout,
                                     } => {
@@ -691,6 +694,9 @@ impl Debugger {
                                             )
                                             .ok();
                                     }
+                                    probe_rs::flashing::ProgressEvent::SelectedEraseStrategy {
+                                        ..
+                                    } => {}
                                     probe_rs::flashing::ProgressEvent::StartedErasing => {
                                         debug_adapter
                                             .update_progress(
@@ -715,6 +721,14 @@ impl Debugger {
                                             )
                                             .ok();
                                     }
+                                    probe_rs::flashing::ProgressEvent::SectorSkipped {
+                                        size,
+                                        page_size,
+                                        ..
+                                    } => {
+                                        flash_progress.sector_size_done += size as usize;
+                                        flash_progress.page_size_done += page_size as usize;
+                                    }
                                     probe_rs::flashing::ProgressEvent::FailedErasing => {
                                         debug_adapter
                                             .update_progress(
@@ -778,11 +792,66 @@ impl Debugger {
                                             )
                                             .ok();
                                     }
+                                    probe_rs::flashing::ProgressEvent::StartedWritingRam { .. } => {
+                                        debug_adapter
+                                            .update_progress(Some(0.0), Some("Writing RAM ..."), id)
+                                            .ok();
+                                    }
+                                    probe_rs::flashing::ProgressEvent::RamWritten { .. } => {}
+                                    probe_rs::flashing::ProgressEvent::FailedWritingRam => {
+                                        debug_adapter
+                                            .update_progress(
+                                                Some(1.0),
+                                                Some("Writing RAM Failed!"),
+                                                id,
+                                            )
+                                            .ok();
+                                    }
+                                    probe_rs::flashing::ProgressEvent::FinishedWritingRam => {
+                                        debug_adapter
+                                            .update_progress(
+                                                Some(1.0),
+                                                Some("Writing RAM Complete!"),
+                                                id,
+                                            )
+                                            .ok();
+                                    }
+                                    probe_rs::flashing::ProgressEvent::StartedVerifying { .. } => {
+                                        debug_adapter
+                                            .update_progress(Some(0.0), Some("Verifying ..."), id)
+                                            .ok();
+                                    }
+                                    probe_rs::flashing::ProgressEvent::SectorVerified {
+                                        ..
+                                    } => {}
+                                    probe_rs::flashing::ProgressEvent::FailedVerifying {
+                                        address,
+                                    } => {
+                                        debug_adapter
+                                            .update_progress(
+                                                Some(1.0),
+                                                Some(format!(
+                                                    "Verifying Failed at {:#010x}!",
+                                                    address
+                                                )),
+                                                id,
+                                            )
+                                            .ok();
+                                    }
+                                    probe_rs::flashing::ProgressEvent::FinishedVerifying => {
+                                        debug_adapter
+                                            .update_progress(
+                                                Some(1.0),
+                                                Some("Verifying Complete!"),
+                                                id,
+                                            )
+                                            .ok();
+                                    }
                                 }
-                            })
-                        } else {
-                            FlashProgress::new(|_event| {})
-                        };
+                                })
+                            } else {
+                                FlashProgress::new(|_event| {})
+                            };
                         download_options.progress = Some(&flash_progress);
                         download_file_with_options(
                             &mut session_data.session,
@@ -8,8 +8,8 @@ use crate::{
 };
 use anyhow::{anyhow, Result};
 use probe_rs::{
-    config::TargetSelector, debug::debug_info::DebugInfo, CoreStatus, DebugProbeError, Permissions,
-    Probe, ProbeCreationError, Session,
+    config::TargetSelector, debug::debug_info::LazyDebugInfo, CoreStatus, DebugProbeError,
+    Permissions, Probe, ProbeCreationError, Session,
 };
 use std::{env::set_current_dir, thread, time::Duration};
 
@@ -146,10 +146,9 @@ impl SessionData {
         let mut core_data_vec = vec![];
 
         for core_configuration in &valid_core_configs {
-            // Configure the [DebugInfo].
+            // Configure the [LazyDebugInfo]. It is only parsed when it is first needed.
             let debug_info = if let Some(binary_path) = &core_configuration.program_binary {
-                DebugInfo::from_file(binary_path)
-                    .map_err(|error| DebuggerError::Other(anyhow!(error)))?
+                LazyDebugInfo::new(binary_path)
             } else {
                 return Err(anyhow!(
                     "Please provide a valid `program_binary` for debug core: {:?}",
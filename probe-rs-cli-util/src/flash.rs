@@ -9,7 +9,7 @@ use std::{path::Path, sync::Arc, time::Instant};
 
 use colored::Colorize;
 use probe_rs::{
-    flashing::{DownloadOptions, FlashLoader, FlashProgress, ProgressEvent},
+    flashing::{DownloadOptions, EraseStrategy, FlashLoader, FlashProgress, ProgressEvent},
     Session,
 };
 
@@ -28,7 +28,9 @@ pub fn run_flash_download(
     let mut download_option = DownloadOptions::default();
     download_option.keep_unwritten_bytes = opt.restore_unwritten;
     download_option.dry_run = opt.probe_options.dry_run;
-    download_option.do_chip_erase = do_chip_erase;
+    if do_chip_erase {
+        download_option.erase_strategy = EraseStrategy::ChipErase;
+    }
     download_option.disable_double_buffering = opt.disable_double_buffering;
 
     if !opt.disable_progressbars {
@@ -107,6 +109,12 @@ pub fn run_flash_download(
                 SectorErased { size, .. } => {
                     erase_progress.inc(size);
                 }
+                SectorSkipped {
+                    size, page_size, ..
+                } => {
+                    erase_progress.inc(size);
+                    program_progress.inc(page_size);
+                }
                 PageFilled { size, .. } => {
                     if let Some(fp) = fill_progress.as_ref() {
                         fp.inc(size)
@@ -135,6 +143,21 @@ pub fn run_flash_download(
                         fp.finish()
                     };
                 }
+                FailedVerifying { address } => {
+                    logging::eprintln(format!(
+                        "    {} Flash contents don't match at {:#010x}",
+                        "Error".red().bold(),
+                        address
+                    ));
+                }
+                SelectedEraseStrategy { strategy } => {
+                    log::debug!("Erasing the flash with strategy {:?}", strategy);
+                }
+                StartedWritingRam { .. }
+                | RamWritten { .. }
+                | FailedWritingRam
+                | FinishedWritingRam => {}
+                StartedVerifying { .. } | SectorVerified { .. } | FinishedVerifying => {}
             }
         });
 
@@ -90,5 +90,10 @@ pub struct ArmCoreAccessOptions {
 }
 
 /// The data required to access a Risc-V core
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct RiscvCoreAccessOptions {}
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct RiscvCoreAccessOptions {
+    /// The index of the hart in the debug module, selected with `hartsel`.
+    /// If this is not set, hart 0 is used.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hart_id: Option<u32>,
+}
@@ -142,6 +142,17 @@ pub enum MemoryRegion {
     Nvm(NvmRegion),
 }
 
+impl MemoryRegion {
+    /// The address range of the region.
+    pub fn address_range(&self) -> Range<u64> {
+        match self {
+            MemoryRegion::Ram(region) => region.range.clone(),
+            MemoryRegion::Generic(region) => region.range.clone(),
+            MemoryRegion::Nvm(region) => region.range.clone(),
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
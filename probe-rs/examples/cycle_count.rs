@@ -0,0 +1,65 @@
+//! Measures the number of cycles the target spends between two instruction addresses, using the
+//! DWT cycle counter.
+
+use probe_rs::{Permissions, Probe};
+
+use clap::Parser;
+use std::num::ParseIntError;
+use std::time::Duration;
+
+use anyhow::Result;
+
+#[derive(clap::Parser)]
+struct Cli {
+    #[clap(long = "chip")]
+    chip: String,
+    /// The address at which the measurement starts.
+    #[clap(long = "start", value_parser = parse_hex)]
+    start: u64,
+    /// The address at which the measurement ends.
+    #[clap(long = "end", value_parser = parse_hex)]
+    end: u64,
+}
+
+fn parse_hex(src: &str) -> Result<u64, ParseIntError> {
+    u64::from_str_radix(src.trim_start_matches("0x"), 16)
+}
+
+fn main() -> Result<()> {
+    pretty_env_logger::init();
+
+    let matches = Cli::parse();
+
+    let probes = Probe::list_all();
+    let probe = probes[0].open()?;
+    let mut session = probe.attach(matches.chip, Permissions::default())?;
+
+    // Run to the start address.
+    run_to(&mut session, matches.start)?;
+
+    session.enable_cycle_counter(0)?;
+
+    // Run to the end address, the cycle counter only counts while the core is running.
+    run_to(&mut session, matches.end)?;
+
+    println!(
+        "{} cycles from {:#010x} to {:#010x}",
+        session.cycle_count()?,
+        matches.start,
+        matches.end
+    );
+
+    Ok(())
+}
+
+/// Runs the first core until it reaches `address`.
+fn run_to(session: &mut probe_rs::Session, address: u64) -> Result<()> {
+    let mut core = session.core(0)?;
+
+    core.set_hw_breakpoint(address)?;
+    core.run()?;
+    core.wait_for_core_halted(Duration::from_secs(5))?;
+    core.clear_hw_breakpoint(address)?;
+
+    Ok(())
+}
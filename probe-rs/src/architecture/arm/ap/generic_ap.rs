@@ -32,12 +32,16 @@ pub enum ApType {
     /// A AMBA based AHB3 AP (see E1.5).
     AmbaAhb3 = 0x1,
     /// A AMBA based AHB2 and AHB3 AP (see E1.8).
+    ///
+    /// Despite its name, this is an APB2 and APB3 AP.
     AmbaAhb2Ahb3 = 0x2,
     /// A AMBA based AXI3 and AXI4 AP (see E1.2).
     AmbaAxi3Axi4 = 0x4,
     /// A AMBA based AHB5 AP (see E1.6).
     AmbaAhb5 = 0x5,
     /// A AMBA based AHB4 AP (see E1.3).
+    ///
+    /// Despite its name, this is an APB4 and APB5 AP.
     AmbaAhb4 = 0x6,
     /// A AMBA based AXI5 AP (see E1.4).
     AmbaAxi5 = 0x7,
@@ -45,6 +49,16 @@ pub enum ApType {
     AmbaAhb5Hprot = 0x8,
 }
 
+impl ApType {
+    /// Returns `true` if this access port is connected to an APB bus.
+    ///
+    /// APB access ports only support word sized accesses and use a different
+    /// layout of the `PROT` and `CACHE` bits in the CSW register than AHB access ports.
+    pub fn is_apb(&self) -> bool {
+        matches!(self, ApType::AmbaAhb2Ahb3 | ApType::AmbaAhb4)
+    }
+}
+
 impl Default for ApType {
     fn default() -> Self {
         ApType::JtagComAp
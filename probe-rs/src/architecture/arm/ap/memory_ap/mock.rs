@@ -13,6 +13,8 @@ use std::convert::TryInto;
 #[derive(Debug)]
 pub struct MockMemoryAp {
     pub memory: Vec<u8>,
+    /// The number of writes to the TAR register.
+    pub tar_writes: usize,
     store: HashMap<u8, u32>,
 }
 
@@ -28,6 +30,7 @@ impl MockMemoryAp {
         store.insert(DRW::ADDRESS, 0);
         Self {
             memory: std::iter::repeat(1..=255).flatten().take(1 << 15).collect(),
+            tar_writes: 0,
             store,
         }
     }
@@ -194,6 +197,7 @@ impl ApAccess for MockMemoryAp {
             }
             TAR::ADDRESS => {
                 self.store.insert(TAR::ADDRESS, value);
+                self.tar_writes += 1;
                 Ok(())
             }
             _ => Err(anyhow!("MockMemoryAp: unknown register").into()),
@@ -1,7 +1,7 @@
 use super::{
     ap::{
-        valid_access_ports, AccessPort, ApAccess, ApClass, BaseaddrFormat, GenericAp, MemoryAp,
-        BASE, BASE2, CFG, CSW, IDR,
+        valid_access_ports, AccessPort, ApAccess, ApClass, ApType, BaseaddrFormat, GenericAp,
+        MemoryAp, BASE, BASE2, CFG, CSW, IDR,
     },
     dp::{Abort, Ctrl, DebugPortError, DebugPortVersion, DpAccess, Select, DPIDR},
     memory::{
@@ -9,7 +9,8 @@ use super::{
         Component,
     },
     sequences::{ArmDebugSequence, DefaultArmSequence},
-    ApAddress, DapAccess, DpAddress, PortType, RawDapAccess, SwoAccess, SwoConfig,
+    ApAddress, DapAccess, DpAddress, PortType, RawDapAccess, SwoAccess, SwoChunk, SwoConfig,
+    SwoStreamSource,
 };
 use crate::{
     architecture::arm::ap::DataSize, CommunicationInterface, DebugProbe, DebugProbeError,
@@ -258,6 +259,7 @@ impl ApInformation {
 
             Ok(ApInformation::MemoryAp(MemoryApInformation {
                 address: access_port.ap_address(),
+                ap_type: idr.TYPE,
                 supports_only_32bit_data_size: only_32bit_data_size,
                 debug_base_address: base_address,
                 supports_hnonsec,
@@ -281,6 +283,11 @@ pub struct MemoryApInformation {
     /// Zero-based port number of the access port. This is used in the debug port to select an AP.
     pub address: ApAddress,
 
+    /// The bus the access port is connected to, as read from the [`IDR`] register.
+    ///
+    /// Components on an APB bus have to be accessed using APB specific CSW settings.
+    pub ap_type: ApType,
+
     /// Some Memory APs only support 32 bit wide access to data, while others
     /// also support other widths. Based on this, 8 bit data access can either
     /// be performed directly, or has to be done as a 32 bit access.
@@ -637,12 +644,51 @@ impl SwoAccess for ArmCommunicationInterface<Initialized> {
         }
     }
 
+    fn enable_trace_port(&mut self, port_width: u8) -> Result<(), ProbeRsError> {
+        match self.probe.get_swo_interface_mut() {
+            Some(interface) => interface.enable_trace_port(port_width),
+            None => Err(ProbeRsError::ArchitectureRequired(&["ARMv7", "ARMv8"])),
+        }
+    }
+
     fn read_swo_timeout(&mut self, timeout: Duration) -> Result<Vec<u8>, ProbeRsError> {
         match self.probe.get_swo_interface_mut() {
             Some(interface) => interface.read_swo_timeout(timeout),
             None => Err(ProbeRsError::ArchitectureRequired(&["ARMv7", "ARMv8"])),
         }
     }
+
+    fn read_swo_timestamped(&mut self, timeout: Duration) -> Result<SwoChunk, ProbeRsError> {
+        match self.probe.get_swo_interface_mut() {
+            Some(interface) => interface.read_swo_timestamped(timeout),
+            None => Err(ProbeRsError::ArchitectureRequired(&["ARMv7", "ARMv8"])),
+        }
+    }
+
+    fn swo_buffered_bytes(&mut self) -> Result<usize, ProbeRsError> {
+        match self.probe.get_swo_interface_mut() {
+            Some(interface) => interface.swo_buffered_bytes(),
+            None => Ok(0),
+        }
+    }
+
+    fn swo_baud_rate(&mut self) -> Option<u32> {
+        self.probe
+            .get_swo_interface_mut()
+            .and_then(|interface| interface.swo_baud_rate())
+    }
+
+    fn swo_timestamp_frequency(&mut self) -> Option<u32> {
+        self.probe
+            .get_swo_interface_mut()
+            .and_then(|interface| interface.swo_timestamp_frequency())
+    }
+
+    fn swo_stream_source(&mut self) -> Option<Box<dyn SwoStreamSource>> {
+        self.probe
+            .get_swo_interface_mut()
+            .and_then(|interface| interface.swo_stream_source())
+    }
 }
 
 impl DapAccess for ArmCommunicationInterface<Initialized> {
@@ -0,0 +1,108 @@
+//! Host-side sampling of DWT data trace.
+//!
+//! The DWT cannot emit the value of a variable on a timer, see
+//! [`Dwt::enable_data_sampling`](super::Dwt::enable_data_sampling). It emits the value on every
+//! write instead, interleaved with periodic cycle count events. The [`DataSampler`] decodes this
+//! stream with an [`ItmDecoder`], and turns it into one sample per period, which is the last value
+//! written before the period ended.
+
+use std::collections::VecDeque;
+
+use super::{ItmDecoder, ItmPacket, ProfileCounterOverflow};
+
+/// The discriminator of the data value packet of a write access traced by comparator 0.
+///
+/// The discriminator of comparator `n` is `DATA_VALUE_WRITE_DISCRIMINATOR + 2 * n`.
+const DATA_VALUE_WRITE_DISCRIMINATOR: u8 = 0b10001;
+
+/// Turns the data trace of a variable into a time series with a fixed sampling rate.
+///
+/// The stream is passed in chunks of any size with [`DataSampler::feed`], e.g. as returned by
+/// [`Session::read_trace_data`](crate::Session::read_trace_data). The samples are returned by
+/// iterating over the sampler, one for every cycle count event. A sample is `None` until the
+/// first write of the variable has been traced.
+#[derive(Debug, Clone)]
+pub struct DataSampler {
+    discriminator: u8,
+    decoder: ItmDecoder,
+    value: Option<u32>,
+    samples: VecDeque<Option<u32>>,
+}
+
+impl DataSampler {
+    /// Create a sampler for the variable traced by the comparator `unit`.
+    pub fn new(unit: usize) -> Self {
+        Self {
+            discriminator: DATA_VALUE_WRITE_DISCRIMINATOR + 2 * unit as u8,
+            decoder: ItmDecoder::new(),
+            value: None,
+            samples: VecDeque::new(),
+        }
+    }
+
+    /// Add the next chunk of the ITM stream.
+    pub fn feed(&mut self, data: &[u8]) {
+        self.decoder.feed(data);
+
+        for packet in &mut self.decoder {
+            match packet {
+                ItmPacket::Hardware {
+                    discriminator,
+                    data,
+                } if discriminator == self.discriminator => {
+                    let mut value = [0; 4];
+                    for (byte, data) in value.iter_mut().zip(data) {
+                        *byte = data;
+                    }
+                    self.value = Some(u32::from_le_bytes(value));
+                }
+                _ => {
+                    if ProfileCounterOverflow::from_packet(&packet)
+                        .map_or(false, |overflow| overflow.cycle)
+                    {
+                        self.samples.push_back(self.value);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Iterator for DataSampler {
+    type Item = Option<u32>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.samples.pop_front()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::DataSampler;
+
+    #[test]
+    fn one_sample_per_period() {
+        let mut sampler = DataSampler::new(1);
+
+        // A cycle count event, two word writes traced by comparator 1, a cycle count event,
+        // a halfword write traced by comparator 0, and a cycle count event.
+        sampler.feed(&[
+            0x05, 0x20, 0x9F, 0x01, 0x00, 0x00, 0x00, 0x9F, 0x02, 0x00, 0x00, 0x00, 0x05, 0x20,
+            0x8E, 0x03, 0x00, 0x05, 0x20,
+        ]);
+
+        assert_eq!(sampler.collect::<Vec<_>>(), [None, Some(2), Some(2)]);
+    }
+
+    #[test]
+    fn holds_back_split_packets() {
+        let mut sampler = DataSampler::new(0);
+
+        sampler.feed(&[0x8D, 0x2A, 0x05]);
+        assert_eq!(sampler.next(), None);
+
+        sampler.feed(&[0x20, 0x8D]);
+        assert_eq!(sampler.next(), Some(Some(0x2A)));
+        assert_eq!(sampler.next(), None);
+    }
+}
@@ -9,9 +9,22 @@
 use bitfield::bitfield;
 
 use super::super::memory::romtable::CoresightComponent;
-use super::DebugRegister;
+use super::{ComponentError, DebugRegister, ItmPacket};
 use crate::architecture::arm::ArmProbeInterface;
 use crate::Error;
+use serde::{Deserialize, Serialize};
+
+/// The address of the DWT in the memory map of M-profile cores.
+pub(crate) const DWT_BASE_ADDRESS: u64 = 0xE000_1000;
+
+/// Returns the address of the register `R` of comparator `unit` in the memory map of M-profile
+/// cores.
+///
+/// This is used to access the DWT through the core, without discovering its component in the
+/// ROM table. For registers which don't belong to a comparator, `unit` is zero.
+pub(crate) fn register_address<R: DebugRegister>(unit: usize) -> u64 {
+    DWT_BASE_ADDRESS + R::ADDRESS as u64 + 16 * unit as u64
+}
 
 /// A struct representing a DWT unit on target.
 pub struct Dwt<'a> {
@@ -45,6 +58,13 @@ impl<'a> Dwt<'a> {
         Ok(())
     }
 
+    /// Returns `true` if the DWT has the ARMv8-M register layout, which is identified by its
+    /// `DEVARCH` register. ARMv6-M and ARMv7-M DWTs don't implement `DEVARCH`.
+    fn is_armv8m(&mut self) -> Result<bool, Error> {
+        let devarch = Devarch::load(self.component, self.interface)?;
+        Ok(devarch.present() && devarch.archid() == DEVARCH_ARCHID_ARMV8M)
+    }
+
     /// Enables the DWT component.
     pub fn enable(&mut self) -> Result<(), Error> {
         let mut ctrl = Ctrl::load(self.component, self.interface)?;
@@ -53,6 +73,129 @@ impl<'a> Dwt<'a> {
         ctrl.store(self.component, self.interface)
     }
 
+    /// Returns `true` if the DWT implements the optional cycle counter (DWT_CYCCNT).
+    pub fn has_cycle_counter(&mut self) -> Result<bool, Error> {
+        let ctrl = Ctrl::load(self.component, self.interface)?;
+        Ok(!ctrl.nocyccnt())
+    }
+
+    /// Returns an error if the DWT does not implement the cycle counter.
+    fn check_cycle_counter(&mut self) -> Result<(), Error> {
+        if self.has_cycle_counter()? {
+            Ok(())
+        } else {
+            Err(Error::architecture_specific(ComponentError::NoCycleCounter))
+        }
+    }
+
+    /// Enables the cycle counter, which counts the core clock cycles.
+    ///
+    /// # Note
+    /// The DWT is only clocked if `DEMCR.TRCENA` is set, see
+    /// [`enable_tracing`](super::enable_tracing).
+    pub fn enable_cycle_counter(&mut self) -> Result<(), Error> {
+        self.check_cycle_counter()?;
+
+        let mut ctrl = Ctrl::load(self.component, self.interface)?;
+        ctrl.set_cyccntena(true);
+        ctrl.store(self.component, self.interface)
+    }
+
+    /// Disables the cycle counter. The count is kept until the counter is reset.
+    pub fn disable_cycle_counter(&mut self) -> Result<(), Error> {
+        self.check_cycle_counter()?;
+
+        let mut ctrl = Ctrl::load(self.component, self.interface)?;
+        ctrl.set_cyccntena(false);
+        ctrl.store(self.component, self.interface)
+    }
+
+    /// Reads the current value of the cycle counter.
+    ///
+    /// The counter wraps around after `u32::MAX` cycles.
+    pub fn cycle_count(&mut self) -> Result<u32, Error> {
+        self.check_cycle_counter()?;
+        Ok(Cyccnt::load(self.component, self.interface)?.into())
+    }
+
+    /// Resets the cycle counter to zero, with a single write to DWT_CYCCNT.
+    pub fn reset_cycle_counter(&mut self) -> Result<(), Error> {
+        self.check_cycle_counter()?;
+        Cyccnt::from(0).store(self.component, self.interface)
+    }
+
+    /// Returns `true` if the DWT implements the optional profiling counters.
+    pub fn has_profile_counters(&mut self) -> Result<bool, Error> {
+        let ctrl = Ctrl::load(self.component, self.interface)?;
+        Ok(!ctrl.noprfcnt())
+    }
+
+    /// Returns an error if the DWT does not implement the profiling counters.
+    fn check_profile_counters(&mut self) -> Result<(), Error> {
+        if self.has_profile_counters()? {
+            Ok(())
+        } else {
+            Err(Error::architecture_specific(
+                ComponentError::NoProfileCounters,
+            ))
+        }
+    }
+
+    /// Resets and enables the profiling counters, see [`ProfileCounters`].
+    ///
+    /// # Note
+    /// The counters are only 8 bits wide. Whenever one of them wraps around, the DWT emits an
+    /// event counter packet over the ITM, which can be decoded with
+    /// [`ProfileCounterOverflow::from_packet`]. With [`Session::setup_tracing`](crate::Session::setup_tracing)
+    /// active, this can produce a lot of trace data, e.g. the CPI counter wraps around every 256
+    /// stall cycles.
+    pub fn enable_profile_counters(&mut self) -> Result<(), Error> {
+        self.set_profile_counters(true)?;
+
+        for address in [
+            Cpicnt::ADDRESS,
+            Exccnt::ADDRESS,
+            Sleepcnt::ADDRESS,
+            Lsucnt::ADDRESS,
+            Foldcnt::ADDRESS,
+        ] {
+            self.component.write_reg(self.interface, address, 0)?;
+        }
+
+        Ok(())
+    }
+
+    /// Disables the profiling counters. The counts are kept until the counters are enabled
+    /// again.
+    pub fn disable_profile_counters(&mut self) -> Result<(), Error> {
+        self.set_profile_counters(false)
+    }
+
+    fn set_profile_counters(&mut self, enabled: bool) -> Result<(), Error> {
+        self.check_profile_counters()?;
+
+        let mut ctrl = Ctrl::load(self.component, self.interface)?;
+        ctrl.set_cpievtena(enabled);
+        ctrl.set_excevtena(enabled);
+        ctrl.set_sleepevtena(enabled);
+        ctrl.set_lsuevtena(enabled);
+        ctrl.set_foldevtena(enabled);
+        ctrl.store(self.component, self.interface)
+    }
+
+    /// Reads the current values of the profiling counters.
+    pub fn read_profile_counters(&mut self) -> Result<ProfileCounters, Error> {
+        self.check_profile_counters()?;
+
+        Ok(ProfileCounters {
+            cpi: u32::from(Cpicnt::load(self.component, self.interface)?) as u8,
+            exception: u32::from(Exccnt::load(self.component, self.interface)?) as u8,
+            sleep: u32::from(Sleepcnt::load(self.component, self.interface)?) as u8,
+            lsu: u32::from(Lsucnt::load(self.component, self.interface)?) as u8,
+            fold: u32::from(Foldcnt::load(self.component, self.interface)?) as u8,
+        })
+    }
+
     /// Enables data tracing on a specific address in memory on a specific DWT unit.
     pub fn enable_data_trace(&mut self, unit: usize, address: u32) -> Result<(), Error> {
         let mut comp = Comp::load_unit(self.component, self.interface, unit)?;
@@ -73,6 +216,271 @@ impl<'a> Dwt<'a> {
         function.store_unit(self.component, self.interface, unit)
     }
 
+    /// Traces the value of the variable at `address` on a specific DWT unit, sampled every
+    /// `(divider + 1) * 1024` cycles.
+    ///
+    /// The DWT cannot emit the value of a variable on a timer. Instead, the value is emitted on
+    /// every write, and the `POSTCNT` counter emits a cycle count event at the end of every
+    /// sampling period. A [`DataSampler`](super::DataSampler) turns this stream into one sample
+    /// per period, e.g. to plot a state variable. Reads of the variable are not traced. The
+    /// `divider` is the reload value of the 4 bit `POSTCNT` counter, and must be at most 15.
+    ///
+    /// The cycle count events replace periodic PC samples, so PC sampling is disabled.
+    pub fn enable_data_sampling(
+        &mut self,
+        unit: usize,
+        address: u32,
+        divider: u8,
+    ) -> Result<(), Error> {
+        if divider > 0xF {
+            return Err(Error::architecture_specific(
+                ComponentError::InvalidSamplingDivider(divider),
+            ));
+        }
+
+        let armv8m = self.is_armv8m()?;
+
+        // Disable the comparator while it is reconfigured.
+        self.disable_data_trace(unit)?;
+
+        let mut comp = Comp::load_unit(self.component, self.interface, unit)?;
+        comp.set_comp(address);
+        comp.store_unit(self.component, self.interface, unit)?;
+
+        let mut function = Function::load_unit(self.component, self.interface, unit)?;
+        // Word sized data values.
+        function.set_datavsize(0b10);
+
+        if armv8m {
+            // Emit a data value packet on a data address match of a write access.
+            function.set_action(ACTION_DATA_TRACE);
+            function.set_function(0b0101);
+        } else {
+            let mut mask = Mask::load_unit(self.component, self.interface, unit)?;
+            mask.set_mask(0x0);
+            mask.store_unit(self.component, self.interface, unit)?;
+
+            function.set_emitrange(false);
+            function.set_datavmatch(false);
+            function.set_cycmatch(false);
+            // Emit the data value on write accesses.
+            function.set_function(0b1101);
+        }
+
+        function.store_unit(self.component, self.interface, unit)?;
+
+        // POSTINIT may only be written while the POSTCNT counter is disabled.
+        let mut ctrl = Ctrl::load(self.component, self.interface)?;
+        ctrl.set_pcsamplena(false);
+        ctrl.set_cycevtena(false);
+        ctrl.store(self.component, self.interface)?;
+
+        // The POSTCNT counter is clocked by CYCCNT[10], and generates a cycle count event on
+        // every underflow.
+        ctrl.set_cyctap(true);
+        ctrl.set_postinit(divider);
+        ctrl.set_postpreset(divider as u32);
+        ctrl.set_cyccntena(true);
+        ctrl.store(self.component, self.interface)?;
+
+        ctrl.set_cycevtena(true);
+        ctrl.store(self.component, self.interface)
+    }
+
+    /// Enables periodic PC samples, which are emitted as hardware source packets through the ITM.
+    ///
+    /// This is useful for statistical profiling. The samples are generated by the `POSTCNT`
+    /// counter, which is clocked by the cycle counter, so the cycle counter is enabled as well.
+    pub fn enable_pc_sampling(&mut self, rate: PcSampleRate) -> Result<(), Error> {
+        let divider = rate.divider();
+        if divider > 0xF {
+            return Err(Error::architecture_specific(
+                ComponentError::InvalidSamplingDivider(divider),
+            ));
+        }
+
+        // POSTINIT may only be written while the POSTCNT counter is disabled.
+        let mut ctrl = Ctrl::load(self.component, self.interface)?;
+        ctrl.set_pcsamplena(false);
+        ctrl.store(self.component, self.interface)?;
+
+        ctrl.set_cyctap(matches!(rate, PcSampleRate::Cycles1024(_)));
+        ctrl.set_postinit(divider);
+        ctrl.set_postpreset(divider as u32);
+        ctrl.set_cyccntena(true);
+        ctrl.store(self.component, self.interface)?;
+
+        ctrl.set_pcsamplena(true);
+        ctrl.store(self.component, self.interface)
+    }
+
+    /// Disables periodic PC samples.
+    pub fn disable_pc_sampling(&mut self) -> Result<(), Error> {
+        let mut ctrl = Ctrl::load(self.component, self.interface)?;
+        ctrl.set_pcsamplena(false);
+        ctrl.store(self.component, self.interface)
+    }
+
+    /// Read the control register (DWT_CTRL).
+    pub fn control(&mut self) -> Result<u32, Error> {
+        Ok(Ctrl::load(self.component, self.interface)?.into())
+    }
+
+    /// Write the control register (DWT_CTRL), e.g. to restore a value read with
+    /// [`control`](Self::control).
+    pub fn set_control(&mut self, value: u32) -> Result<(), Error> {
+        Ctrl::from(value).store(self.component, self.interface)
+    }
+
+    /// Returns the number of comparators implemented by the DWT.
+    pub fn num_comparators(&mut self) -> Result<u8, Error> {
+        let ctrl = Ctrl::load(self.component, self.interface)?;
+        Ok(ctrl.numcomp())
+    }
+
+    /// Configures the comparator of the given unit to generate a trigger (`CMPMATCH` event) when
+    /// the instruction at `address` is executed.
+    pub fn enable_trigger(&mut self, unit: usize, address: u32) -> Result<(), Error> {
+        let armv8m = self.is_armv8m()?;
+
+        let mut comp = Comp::load_unit(self.component, self.interface, unit)?;
+        comp.set_comp(address);
+        comp.store_unit(self.component, self.interface, unit)?;
+
+        let mut function = Function::load_unit(self.component, self.interface, unit)?;
+
+        if armv8m {
+            // Instruction address match, which needs a halfword DATAVSIZE.
+            function.set_action(ACTION_TRIGGER);
+            function.set_datavsize(0b01);
+            function.set_function(0b0010);
+        } else {
+            let mut mask = Mask::load_unit(self.component, self.interface, unit)?;
+            mask.set_mask(0x0);
+            mask.store_unit(self.component, self.interface, unit)?;
+
+            function.set_emitrange(false);
+            function.set_datavmatch(false);
+            function.set_cycmatch(false);
+            // CMPMATCH trigger on PC match
+            function.set_function(0b1000);
+        }
+
+        function.store_unit(self.component, self.interface, unit)
+    }
+
+    /// Configures the comparator of the given unit to emit a data trace packet when `value` is
+    /// accessed with the given `size` and `access` type.
+    ///
+    /// The comparator compares the data value. The `mask` is the number of least significant
+    /// bits which are ignored in the comparison, written to `DWT_MASK`. ARMv8-M comparators have
+    /// no mask register, so the mask has to be zero there.
+    ///
+    /// To restrict the match to an address, `linked_unit` can name another comparator, which has
+    /// to be configured to match the address, e.g. with [`Dwt::enable_data_trace`]. Otherwise, the
+    /// value matches at any address. On ARMv8-M, only the preceding comparator can be linked.
+    ///
+    /// Byte and halfword values are replicated over the whole comparator, as the comparison uses
+    /// the byte lanes of the access.
+    pub fn enable_data_value_trace(
+        &mut self,
+        unit: usize,
+        value: u32,
+        mask: u8,
+        size: DwtDataSize,
+        access: DwtAccess,
+        linked_unit: Option<usize>,
+    ) -> Result<(), Error> {
+        let replicated = match size {
+            DwtDataSize::Byte if value <= 0xFF => value * 0x0101_0101,
+            DwtDataSize::Halfword if value <= 0xFFFF => value * 0x0001_0001,
+            DwtDataSize::Word => value,
+            _ => {
+                return Err(Error::architecture_specific(
+                    ComponentError::InvalidDataValue { value, size },
+                ))
+            }
+        };
+
+        let armv8m = self.is_armv8m()?;
+
+        if armv8m && mask != 0 {
+            return Err(Error::architecture_specific(
+                ComponentError::UnsupportedComparatorMask { unit, mask },
+            ));
+        }
+
+        if let Some(linked_unit) =
+            linked_unit.filter(|&linked_unit| armv8m && linked_unit + 1 != unit)
+        {
+            return Err(Error::architecture_specific(
+                ComponentError::InvalidLinkedComparator { unit, linked_unit },
+            ));
+        }
+
+        // Disable the comparator while it is reconfigured.
+        self.disable_data_trace(unit)?;
+
+        let mut comp = Comp::load_unit(self.component, self.interface, unit)?;
+        comp.set_comp(replicated);
+        comp.store_unit(self.component, self.interface, unit)?;
+
+        let mut function = Function::load_unit(self.component, self.interface, unit)?;
+        function.set_datavsize(size as u8);
+
+        if armv8m {
+            function.set_action(ACTION_DATA_TRACE);
+            function.set_function(match (access, linked_unit) {
+                (_, Some(_)) => 0b1011,
+                (DwtAccess::ReadWrite, None) => 0b1000,
+                (DwtAccess::Write, None) => 0b1001,
+                (DwtAccess::Read, None) => 0b1010,
+            });
+        } else {
+            let mut mask_register = Mask::load_unit(self.component, self.interface, unit)?;
+            mask_register.set_mask(mask as u32);
+            mask_register.store_unit(self.component, self.interface, unit)?;
+
+            // The mask field only implements as many bits as the largest supported mask needs.
+            if Mask::load_unit(self.component, self.interface, unit)?.mask() != mask as u32 {
+                return Err(Error::architecture_specific(
+                    ComponentError::UnsupportedComparatorMask { unit, mask },
+                ));
+            }
+
+            // Linking a comparator to itself matches any address.
+            let linked_unit = linked_unit.unwrap_or(unit) as u8;
+
+            function.set_datavaddr0(linked_unit);
+            function.set_datavaddr1(linked_unit);
+            function.set_emitrange(false);
+            function.set_cycmatch(false);
+            function.set_datavmatch(true);
+            // Emit a data value packet.
+            function.set_function(match access {
+                DwtAccess::ReadWrite => 0b0010,
+                DwtAccess::Read => 0b1100,
+                DwtAccess::Write => 0b1101,
+            });
+        }
+
+        function.store_unit(self.component, self.interface, unit)?;
+
+        // Comparators which don't support data value matching read back a different match type.
+        // On ARMv7-M, DATAVMATCH is read-only zero then.
+        let readback = Function::load_unit(self.component, self.interface, unit)?;
+        if readback.function() != function.function()
+            || readback.datavmatch() != function.datavmatch()
+        {
+            self.disable_data_trace(unit)?;
+            return Err(Error::architecture_specific(
+                ComponentError::NoDataValueMatch(unit),
+            ));
+        }
+
+        Ok(())
+    }
+
     /// Disables data tracing on the given unit.
     pub fn disable_data_trace(&mut self, unit: usize) -> Result<(), Error> {
         let mut function = Function::load_unit(self.component, self.interface, unit)?;
@@ -80,7 +488,42 @@ impl<'a> Dwt<'a> {
         function.store_unit(self.component, self.interface, unit)
     }
 
+    /// Returns `true` if the comparator of the given unit matched since the flag was last read.
+    ///
+    /// Reading the function register clears its `MATCHED` flag, so this can be polled to find
+    /// out whether an access happened since the last check.
+    pub fn comparator_matched(&mut self, unit: usize) -> Result<bool, Error> {
+        let function = Function::load_unit(self.component, self.interface, unit)?;
+        Ok(function.matched())
+    }
+
+    /// Returns all comparators which matched since the last call, together with the address
+    /// they compare against.
+    ///
+    /// Reading the function register clears its `MATCHED` flag, so every match is only
+    /// reported once.
+    pub fn matched_comparators(&mut self) -> Result<Vec<DwtComparatorMatch>, Error> {
+        let mut matches = Vec::new();
+
+        for unit in 0..self.num_comparators()? as usize {
+            if self.comparator_matched(unit)? {
+                let comp = Comp::load_unit(self.component, self.interface, unit)?;
+                matches.push(DwtComparatorMatch {
+                    unit,
+                    address: comp.comp(),
+                });
+            }
+        }
+
+        Ok(matches)
+    }
+
     /// Enable exception tracing.
+    ///
+    /// # Note
+    /// The DWT traces the entry, exit and return of all exceptions, it cannot be limited to
+    /// specific exceptions. Use an [`ExceptionTraceFilter`](super::ExceptionTraceFilter) to
+    /// filter the trace data on the host instead.
     pub fn enable_exception_trace(&mut self) -> Result<(), Error> {
         let mut ctrl = Ctrl::load(self.component, self.interface)?;
         ctrl.set_exctrcena(true);
@@ -95,6 +538,126 @@ impl<'a> Dwt<'a> {
     }
 }
 
+/// The interval of periodic PC samples, see [`Dwt::enable_pc_sampling`].
+///
+/// The divider is the reload value of the 4 bit `POSTCNT` counter, and must be at most 15.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PcSampleRate {
+    /// A PC sample every `(divider + 1) * 64` cycles.
+    Cycles64(u8),
+    /// A PC sample every `(divider + 1) * 1024` cycles.
+    Cycles1024(u8),
+}
+
+impl PcSampleRate {
+    /// The reload value of the `POSTCNT` counter.
+    pub fn divider(&self) -> u8 {
+        match self {
+            PcSampleRate::Cycles64(divider) | PcSampleRate::Cycles1024(divider) => *divider,
+        }
+    }
+
+    /// The number of cycles between two PC samples.
+    pub fn interval(&self) -> u32 {
+        match self {
+            PcSampleRate::Cycles64(divider) => (*divider as u32 + 1) * 64,
+            PcSampleRate::Cycles1024(divider) => (*divider as u32 + 1) * 1024,
+        }
+    }
+}
+
+/// The values of the DWT profiling counters, see [`Dwt::read_profile_counters`].
+///
+/// Each counter is 8 bits wide and wraps around, see [`ProfileCounterOverflow`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ProfileCounters {
+    /// The additional cycles of multi-cycle instructions and instruction fetch stalls.
+    pub cpi: u8,
+    /// The cycles spent on exception entry and return.
+    pub exception: u8,
+    /// The cycles spent sleeping.
+    pub sleep: u8,
+    /// The additional cycles of load and store instructions.
+    pub lsu: u8,
+    /// The number of folded instructions, which took no cycles.
+    pub fold: u8,
+}
+
+/// The discriminator of event counter packets.
+const EVENT_COUNTER_DISCRIMINATOR: u8 = 0;
+
+/// The counters which wrapped around, as reported by an event counter packet.
+///
+/// See "ARMv7-M Architecture Reference Manual", appendix D4.3.1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ProfileCounterOverflow {
+    /// The CPI counter wrapped around.
+    pub cpi: bool,
+    /// The exception overhead counter wrapped around.
+    pub exception: bool,
+    /// The sleep counter wrapped around.
+    pub sleep: bool,
+    /// The load store unit counter wrapped around.
+    pub lsu: bool,
+    /// The folded instruction counter wrapped around.
+    pub fold: bool,
+    /// The 4 bit `POSTCNT` counter wrapped around, if the cycle count event is enabled.
+    pub cycle: bool,
+}
+
+impl ProfileCounterOverflow {
+    /// Decodes an ITM packet, and returns `None` if it is not an event counter packet.
+    pub fn from_packet(packet: &ItmPacket) -> Option<Self> {
+        let flags = match packet {
+            ItmPacket::Hardware {
+                discriminator: EVENT_COUNTER_DISCRIMINATOR,
+                data,
+            } => *data.first()?,
+            _ => return None,
+        };
+
+        Some(ProfileCounterOverflow {
+            cpi: flags & (1 << 0) != 0,
+            exception: flags & (1 << 1) != 0,
+            sleep: flags & (1 << 2) != 0,
+            lsu: flags & (1 << 3) != 0,
+            fold: flags & (1 << 4) != 0,
+            cycle: flags & (1 << 5) != 0,
+        })
+    }
+}
+
+/// The size of the accesses matched by a DWT data value comparator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DwtDataSize {
+    /// Byte accesses.
+    Byte = 0b00,
+    /// Halfword accesses.
+    Halfword = 0b01,
+    /// Word accesses.
+    Word = 0b10,
+}
+
+/// The type of the accesses matched by a DWT data value comparator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DwtAccess {
+    /// Read accesses.
+    Read,
+    /// Write accesses.
+    Write,
+    /// Read and write accesses.
+    ReadWrite,
+}
+
+/// A DWT comparator which matched an access.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DwtComparatorMatch {
+    /// The number of the comparator.
+    pub unit: usize,
+    /// The address the comparator compares against.
+    pub address: u32,
+}
+
 bitfield! {
     #[derive(Clone, Default)]
     pub struct Ctrl(u32);
@@ -210,6 +773,75 @@ impl DebugRegister for Exccnt {
     const NAME: &'static str = "DWT/EXCCNT";
 }
 
+bitfield! {
+    #[derive(Clone, Default)]
+    pub struct Sleepcnt(u32);
+    impl Debug;
+}
+
+impl From<u32> for Sleepcnt {
+    fn from(raw: u32) -> Self {
+        Sleepcnt(raw)
+    }
+}
+
+impl From<Sleepcnt> for u32 {
+    fn from(raw: Sleepcnt) -> Self {
+        raw.0
+    }
+}
+
+impl DebugRegister for Sleepcnt {
+    const ADDRESS: u32 = 0x10;
+    const NAME: &'static str = "DWT/SLEEPCNT";
+}
+
+bitfield! {
+    #[derive(Clone, Default)]
+    pub struct Lsucnt(u32);
+    impl Debug;
+}
+
+impl From<u32> for Lsucnt {
+    fn from(raw: u32) -> Self {
+        Lsucnt(raw)
+    }
+}
+
+impl From<Lsucnt> for u32 {
+    fn from(raw: Lsucnt) -> Self {
+        raw.0
+    }
+}
+
+impl DebugRegister for Lsucnt {
+    const ADDRESS: u32 = 0x14;
+    const NAME: &'static str = "DWT/LSUCNT";
+}
+
+bitfield! {
+    #[derive(Clone, Default)]
+    pub struct Foldcnt(u32);
+    impl Debug;
+}
+
+impl From<u32> for Foldcnt {
+    fn from(raw: u32) -> Self {
+        Foldcnt(raw)
+    }
+}
+
+impl From<Foldcnt> for u32 {
+    fn from(raw: Foldcnt) -> Self {
+        raw.0
+    }
+}
+
+impl DebugRegister for Foldcnt {
+    const ADDRESS: u32 = 0x18;
+    const NAME: &'static str = "DWT/FOLDCNT";
+}
+
 bitfield! {
     #[derive(Clone, Default)]
     pub struct Comp(u32);
@@ -273,6 +905,11 @@ bitfield! {
     pub datavmatch, set_datavmatch: 8;
     pub cycmatch, set_cycmatch: 7;
     pub emitrange, set_emitrange: 5;
+    /// The action on a match of ARMv8-M comparators, which overlaps `EMITRANGE`.
+    /// 00 Trigger only.
+    /// 01 Debug event.
+    /// 10 Data trace packets.
+    pub u8, action, set_action: 5, 4;
     pub function, set_function: 3, 0;
 }
 
@@ -292,3 +929,81 @@ impl DebugRegister for Function {
     const ADDRESS: u32 = 0x28;
     const NAME: &'static str = "DWT/FUNCTION";
 }
+
+/// `DWT_FUNCTION.ACTION` of ARMv8-M, which only generates a `CMPMATCH` trigger on a match.
+const ACTION_TRIGGER: u8 = 0b00;
+
+/// `DWT_FUNCTION.ACTION` of ARMv8-M, which emits data trace packets on a match.
+const ACTION_DATA_TRACE: u8 = 0b10;
+
+/// The `DEVARCH.ARCHID` of an ARMv8-M DWT.
+const DEVARCH_ARCHID_ARMV8M: u16 = 0x1A02;
+
+bitfield! {
+    #[derive(Clone, Default)]
+    pub struct Devarch(u32);
+    impl Debug;
+    pub present, _: 20;
+    pub u16, archid, _: 15, 0;
+}
+
+impl From<u32> for Devarch {
+    fn from(raw: u32) -> Self {
+        Devarch(raw)
+    }
+}
+
+impl From<Devarch> for u32 {
+    fn from(raw: Devarch) -> Self {
+        raw.0
+    }
+}
+
+impl DebugRegister for Devarch {
+    const ADDRESS: u32 = 0xFBC;
+    const NAME: &'static str = "DWT/DEVARCH";
+}
+
+#[cfg(test)]
+mod test {
+    use super::ProfileCounterOverflow;
+    use crate::architecture::arm::component::ItmPacket;
+
+    #[test]
+    fn decode_event_counter_packet() {
+        let packet = ItmPacket::Hardware {
+            discriminator: 0,
+            data: vec![0b10_1001],
+        };
+
+        assert_eq!(
+            ProfileCounterOverflow::from_packet(&packet),
+            Some(ProfileCounterOverflow {
+                cpi: true,
+                lsu: true,
+                cycle: true,
+                ..Default::default()
+            })
+        );
+    }
+
+    #[test]
+    fn ignore_other_packets() {
+        let exception_trace = ItmPacket::Hardware {
+            discriminator: 1,
+            data: vec![0x0F, 0x10],
+        };
+        let instrumentation = ItmPacket::Instrumentation {
+            port: 0,
+            data: vec![0x01],
+        };
+        let empty = ItmPacket::Hardware {
+            discriminator: 0,
+            data: vec![],
+        };
+
+        assert_eq!(ProfileCounterOverflow::from_packet(&exception_trace), None);
+        assert_eq!(ProfileCounterOverflow::from_packet(&instrumentation), None);
+        assert_eq!(ProfileCounterOverflow::from_packet(&empty), None);
+    }
+}
@@ -0,0 +1,120 @@
+//! Host-side filtering of DWT exception trace.
+//!
+//! The DWT can only enable or disable exception trace for all exceptions, see
+//! [`Dwt::enable_exception_trace`](super::Dwt::enable_exception_trace). On firmware with a high
+//! interrupt rate, this floods the trace stream. The [`ExceptionTraceFilter`] decodes an ITM byte
+//! stream with an [`ItmDecoder`], and drops the exception trace packets of all but the selected
+//! exceptions.
+
+use std::collections::BTreeSet;
+
+use super::{ExceptionEvent, ItmDecoder, ItmPacket};
+
+/// Removes exception trace packets of unselected exceptions from an ITM stream.
+///
+/// The stream is passed in chunks of any size with [`ExceptionTraceFilter::feed`], e.g. as
+/// returned by [`Session::read_trace_data`](crate::Session::read_trace_data). The decoded packets
+/// which are kept are returned by iterating over the filter. All packets other than exception
+/// trace are passed through unchanged.
+#[derive(Debug, Clone)]
+pub struct ExceptionTraceFilter {
+    exceptions: BTreeSet<u16>,
+    decoder: ItmDecoder,
+}
+
+impl ExceptionTraceFilter {
+    /// Create a filter which keeps the exception trace of the given exception numbers.
+    ///
+    /// The exception number of an interrupt is its IRQ number plus 16, e.g. the SysTick is
+    /// exception 15.
+    pub fn new(exceptions: impl IntoIterator<Item = u16>) -> Self {
+        Self {
+            exceptions: exceptions.into_iter().collect(),
+            decoder: ItmDecoder::new(),
+        }
+    }
+
+    /// Add the next chunk of the ITM stream.
+    pub fn feed(&mut self, data: &[u8]) {
+        self.decoder.feed(data);
+    }
+
+    /// Returns `true` if the packet is kept, i.e. it is not the exception trace of an unselected
+    /// exception.
+    pub fn keeps(&self, packet: &ItmPacket) -> bool {
+        match ExceptionEvent::from_packet(packet) {
+            Some(event) => self.exceptions.contains(&event.exception_number()),
+            None => true,
+        }
+    }
+}
+
+impl Iterator for ExceptionTraceFilter {
+    type Item = ItmPacket;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let packet = self.decoder.next()?;
+
+            if self.keeps(&packet) {
+                return Some(packet);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ExceptionTraceFilter;
+    use crate::architecture::arm::component::ItmPacket;
+
+    #[test]
+    fn removes_unselected_exceptions() {
+        let mut filter = ExceptionTraceFilter::new([15]);
+
+        // Entry of SysTick, entry of IRQ 0, and an instrumentation packet of port 0.
+        filter.feed(&[0x0E, 0x0F, 0x10, 0x0E, 0x10, 0x10, 0x01, 0x41]);
+
+        assert_eq!(
+            filter.collect::<Vec<_>>(),
+            [
+                ItmPacket::Hardware {
+                    discriminator: 1,
+                    data: vec![0x0F, 0x10],
+                },
+                ItmPacket::Instrumentation {
+                    port: 0,
+                    data: vec![0x41],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn holds_back_split_packets() {
+        let mut filter = ExceptionTraceFilter::new([]);
+
+        // A local timestamp with continuation, followed by an exception packet.
+        filter.feed(&[0xC0, 0x81]);
+        assert_eq!(filter.next(), None);
+
+        filter.feed(&[0x01, 0x0E, 0x10]);
+        assert_eq!(
+            filter.next(),
+            Some(ItmPacket::LocalTimestamp {
+                delta: 0x81,
+                relation: 0,
+            })
+        );
+        assert_eq!(filter.next(), None);
+
+        filter.feed(&[0x20, 0x01, 0x41]);
+        assert_eq!(
+            filter.collect::<Vec<_>>(),
+            [ItmPacket::Instrumentation {
+                port: 0,
+                data: vec![0x41],
+            }]
+        );
+    }
+}
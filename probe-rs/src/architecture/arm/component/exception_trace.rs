@@ -0,0 +1,160 @@
+//! Decoding of DWT exception trace.
+//!
+//! With exception trace enabled, see
+//! [`Dwt::enable_exception_trace`](super::Dwt::enable_exception_trace), the DWT emits a hardware
+//! source packet whenever the core enters, exits or returns to an exception. The
+//! [`ExceptionTrace`] decoder turns them into [`ExceptionEvent`]s, e.g. to measure interrupt
+//! latencies.
+//!
+//! See "ARMv7-M Architecture Reference Manual", appendix D4.3.2 for details about the packet
+//! format.
+
+use super::{ItmDecoder, ItmPacket};
+
+/// The discriminator of exception trace packets.
+const EXCEPTION_TRACE_DISCRIMINATOR: u8 = 1;
+/// The number of the first external interrupt.
+const FIRST_IRQ: u16 = 16;
+
+/// An exception trace event.
+///
+/// The exception number of an interrupt is its IRQ number plus 16, see [`exception_name`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExceptionEvent {
+    /// The core entered the exception.
+    Entered(u16),
+    /// The core exited the exception.
+    Exited(u16),
+    /// The core returned to the exception, after an exception which preempted it was exited.
+    /// Returning to thread mode is reported as exception `0`.
+    Returned(u16),
+}
+
+impl ExceptionEvent {
+    /// Decodes an ITM packet, and returns `None` if it is not an exception trace packet.
+    pub fn from_packet(packet: &ItmPacket) -> Option<Self> {
+        let (low, high) = match packet {
+            ItmPacket::Hardware {
+                discriminator: EXCEPTION_TRACE_DISCRIMINATOR,
+                data,
+            } => match data[..] {
+                [low, high] => (low, high),
+                _ => return None,
+            },
+            _ => return None,
+        };
+
+        let number = low as u16 | ((high as u16 & 0x1) << 8);
+
+        match (high >> 4) & 0b11 {
+            0b01 => Some(ExceptionEvent::Entered(number)),
+            0b10 => Some(ExceptionEvent::Exited(number)),
+            0b11 => Some(ExceptionEvent::Returned(number)),
+            _ => None,
+        }
+    }
+
+    /// The number of the exception.
+    pub fn exception_number(&self) -> u16 {
+        match self {
+            ExceptionEvent::Entered(number)
+            | ExceptionEvent::Exited(number)
+            | ExceptionEvent::Returned(number) => *number,
+        }
+    }
+
+    /// The name of the exception, see [`exception_name`].
+    pub fn exception_name(&self) -> Option<String> {
+        exception_name(self.exception_number())
+    }
+}
+
+/// Returns the name of a Cortex-M exception number, e.g. `SysTick` or `IRQ 3`.
+///
+/// Returns `None` for reserved exception numbers. Exception `0` is named `Thread`, as it is
+/// reported when the core returns to thread mode.
+pub fn exception_name(number: u16) -> Option<String> {
+    let name = match number {
+        0 => "Thread",
+        1 => "Reset",
+        2 => "NMI",
+        3 => "HardFault",
+        4 => "MemManage",
+        5 => "BusFault",
+        6 => "UsageFault",
+        7 => "SecureFault",
+        11 => "SVCall",
+        12 => "DebugMonitor",
+        14 => "PendSV",
+        15 => "SysTick",
+        FIRST_IRQ.. => return Some(format!("IRQ {}", number - FIRST_IRQ)),
+        _ => return None,
+    };
+
+    Some(name.to_string())
+}
+
+/// A decoder for the exception trace in an ITM byte stream.
+///
+/// The stream is passed in chunks of any size with [`ExceptionTrace::feed`], e.g. as returned by
+/// [`Session::read_trace_data`](crate::Session::read_trace_data). The decoded events are
+/// returned by iterating over the decoder, all other packets are skipped.
+#[derive(Debug, Clone, Default)]
+pub struct ExceptionTrace {
+    decoder: ItmDecoder,
+}
+
+impl ExceptionTrace {
+    /// Create a new decoder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the next chunk of the ITM stream.
+    pub fn feed(&mut self, data: &[u8]) {
+        self.decoder.feed(data);
+    }
+}
+
+impl Iterator for ExceptionTrace {
+    type Item = ExceptionEvent;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.decoder
+            .by_ref()
+            .find_map(|packet| ExceptionEvent::from_packet(&packet))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{exception_name, ExceptionEvent, ExceptionTrace};
+
+    #[test]
+    fn decodes_exception_events() {
+        let mut trace = ExceptionTrace::new();
+
+        // SysTick entered, "x" on port 0, SysTick exited, IRQ 240 (exception 256) entered and a
+        // return to thread mode.
+        trace.feed(&[
+            0x0E, 0x0F, 0x10, 0x01, b'x', 0x0E, 0x0F, 0x20, 0x0E, 0x00, 0x11, 0x0E, 0x00, 0x30,
+        ]);
+
+        assert_eq!(
+            trace.collect::<Vec<_>>(),
+            [
+                ExceptionEvent::Entered(15),
+                ExceptionEvent::Exited(15),
+                ExceptionEvent::Entered(256),
+                ExceptionEvent::Returned(0),
+            ]
+        );
+    }
+
+    #[test]
+    fn names_exceptions() {
+        assert_eq!(exception_name(15).as_deref(), Some("SysTick"));
+        assert_eq!(exception_name(19).as_deref(), Some("IRQ 3"));
+        assert_eq!(exception_name(8), None);
+    }
+}
@@ -0,0 +1,223 @@
+//! Arm Flash Patch and Breakpoint (FPB) unit
+//!
+//! # Description
+//! Besides hardware breakpoints, version 1 of the FPB can remap accesses to the Code memory
+//! region to a table in SRAM. This allows patching constants stored in flash without
+//! reprogramming the flash.
+//!
+//! Remapping is only available with FPB version 1, as implemented by the Cortex-M3 and
+//! Cortex-M4, and only if the implementation sets `FP_REMAP.RMPSPT`. The Cortex-M7 and ARMv8-M
+//! cores implement FPB version 2, which only supports breakpoints.
+//!
+//! See "ARMv7-M Architecture Reference Manual", chapter C1.11 for details about the registers
+//! of this component.
+use super::ComponentError;
+use crate::architecture::arm::memory::adi_v5_memory_interface::ArmProbe;
+use crate::architecture::arm::memory::romtable::CoresightComponent;
+use crate::architecture::arm::ArmProbeInterface;
+use crate::Error;
+
+/// Flash Patch Control Register.
+const REGISTER_OFFSET_FP_CTRL: u32 = 0x000;
+/// Flash Patch Remap Register.
+const REGISTER_OFFSET_FP_REMAP: u32 = 0x004;
+/// First Flash Patch Comparator Register, the others follow with a stride of 4 bytes.
+const REGISTER_OFFSET_FP_COMP0: u32 = 0x008;
+
+/// `FP_CTRL.KEY`, which has to be set on every write to `FP_CTRL`.
+const CTRL_KEY: u32 = 1 << 1;
+/// `FP_CTRL.ENABLE`
+const CTRL_ENABLE: u32 = 1 << 0;
+/// `FP_REMAP.RMPSPT`, set if the FPB supports remapping.
+const REMAP_SUPPORTED: u32 = 1 << 29;
+/// Mask of `FP_REMAP.REMAP`, bits[28:5] of the remap table address.
+const REMAP_ADDRESS_MASK: u32 = 0x1FFF_FFE0;
+
+/// The remap table has an entry for each of the up to 8 comparators, and must be aligned to its
+/// size of 32 bytes.
+const REMAP_TABLE_ALIGNMENT: u32 = 32;
+/// The remap table has to be placed in the SRAM region.
+const SRAM_REGION: std::ops::Range<u32> = 0x2000_0000..0x4000_0000;
+
+/// Returns the range of indices of the literal comparators, which follow the instruction
+/// comparators, for the value of `FP_CTRL`.
+fn literal_comparators(ctrl: u32) -> std::ops::Range<u32> {
+    let num_code = ((ctrl >> 8) & 0x70) | ((ctrl >> 4) & 0xF);
+    let num_lit = (ctrl >> 8) & 0xF;
+
+    num_code..num_code + num_lit
+}
+
+/// Returns the address of the entry of `comparator` in the remap table at `table`.
+fn remap_table_entry(table: u32, comparator: u32) -> u64 {
+    table as u64 + 4 * comparator as u64
+}
+
+/// Returns the value of `FP_COMPn` of an enabled literal comparator matching `flash_address`.
+fn literal_comparator_value(flash_address: u32) -> u32 {
+    // COMP holds bits[28:2] of the address, REPLACE is ignored for literal comparators.
+    (flash_address & 0x1FFF_FFFC) | 1
+}
+
+/// Flash Patch and Breakpoint unit
+pub struct Fpb<'a> {
+    component: &'a CoresightComponent,
+    interface: &'a mut dyn ArmProbeInterface,
+    /// The address of the remap table, once it has been set up with [`Fpb::set_remap_table`].
+    remap_table: Option<u32>,
+}
+
+impl<'a> Fpb<'a> {
+    /// Construct a new FPB component.
+    pub fn new(
+        interface: &'a mut dyn ArmProbeInterface,
+        component: &'a CoresightComponent,
+    ) -> Self {
+        Fpb {
+            component,
+            interface,
+            remap_table: None,
+        }
+    }
+
+    /// Returns `true` if the FPB supports remapping accesses to the Code region.
+    pub fn remap_supported(&mut self) -> Result<bool, Error> {
+        let ctrl = self
+            .component
+            .read_reg(self.interface, REGISTER_OFFSET_FP_CTRL)?;
+
+        // FP_CTRL.REV is 0 for version 1.
+        if ctrl >> 28 != 0 {
+            return Ok(false);
+        }
+
+        let remap = self
+            .component
+            .read_reg(self.interface, REGISTER_OFFSET_FP_REMAP)?;
+
+        Ok(remap & REMAP_SUPPORTED != 0)
+    }
+
+    /// Set the address of the remap table in RAM.
+    ///
+    /// The table has one word for each comparator. It has to be aligned to 32 bytes and placed
+    /// in the SRAM region at `0x2000_0000..0x4000_0000`. The memory of the table must not be
+    /// used by the firmware.
+    pub fn set_remap_table(&mut self, address: u32) -> Result<(), Error> {
+        if !self.remap_supported()? {
+            return Err(Error::architecture_specific(
+                ComponentError::FlashPatchNotSupported,
+            ));
+        }
+
+        if address % REMAP_TABLE_ALIGNMENT != 0 || !SRAM_REGION.contains(&address) {
+            return Err(Error::architecture_specific(
+                ComponentError::InvalidRemapTableAddress(address),
+            ));
+        }
+
+        self.component.write_reg(
+            self.interface,
+            REGISTER_OFFSET_FP_REMAP,
+            address & REMAP_ADDRESS_MASK,
+        )?;
+
+        self.remap_table = Some(address);
+
+        Ok(())
+    }
+
+    /// Remap the word at `flash_address` to `ram_value`.
+    ///
+    /// Afterwards, all data reads of the word return `ram_value` instead of the flash contents.
+    /// This uses a free literal comparator, and returns its index. The remap table has to be set
+    /// up with [`Fpb::set_remap_table`] on this `Fpb` before, otherwise an error is returned.
+    ///
+    /// The `flash_address` has to be word aligned and in the Code region below `0x2000_0000`.
+    pub fn remap_word(&mut self, flash_address: u32, ram_value: u32) -> Result<usize, Error> {
+        let table = self
+            .remap_table
+            .ok_or_else(|| Error::architecture_specific(ComponentError::RemapTableNotConfigured))?;
+
+        if !self.remap_supported()? {
+            return Err(Error::architecture_specific(
+                ComponentError::FlashPatchNotSupported,
+            ));
+        }
+
+        if flash_address % 4 != 0 || flash_address >= SRAM_REGION.start {
+            return Err(Error::architecture_specific(
+                ComponentError::InvalidRemapAddress(flash_address),
+            ));
+        }
+
+        let ctrl = self
+            .component
+            .read_reg(self.interface, REGISTER_OFFSET_FP_CTRL)?;
+
+        let mut comparator = None;
+        for index in literal_comparators(ctrl) {
+            let comp = self
+                .component
+                .read_reg(self.interface, REGISTER_OFFSET_FP_COMP0 + 4 * index)?;
+            if comp & 1 == 0 {
+                comparator = Some(index);
+                break;
+            }
+        }
+        let comparator = comparator.ok_or_else(|| {
+            Error::architecture_specific(ComponentError::NoFreeFlashPatchComparator)
+        })?;
+
+        // The value is stored in the table entry of the comparator.
+        let mut memory = self.interface.memory_interface(self.component.ap)?;
+        memory.write_word_32(remap_table_entry(table, comparator), ram_value)?;
+        memory.flush()?;
+        drop(memory);
+
+        self.component.write_reg(
+            self.interface,
+            REGISTER_OFFSET_FP_COMP0 + 4 * comparator,
+            literal_comparator_value(flash_address),
+        )?;
+
+        self.component.write_reg(
+            self.interface,
+            REGISTER_OFFSET_FP_CTRL,
+            CTRL_KEY | CTRL_ENABLE,
+        )?;
+
+        Ok(comparator as usize)
+    }
+
+    /// Disable the remapping done by the given comparator, as returned by [`Fpb::remap_word`].
+    pub fn clear_remap(&mut self, comparator: usize) -> Result<(), Error> {
+        self.component.write_reg(
+            self.interface,
+            REGISTER_OFFSET_FP_COMP0 + 4 * comparator as u32,
+            0,
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{literal_comparator_value, literal_comparators, remap_table_entry};
+
+    #[test]
+    fn literal_comparators_follow_code_comparators() {
+        // Cortex-M3: 6 instruction comparators, 2 literal comparators.
+        assert_eq!(literal_comparators(0x0000_0260), 6..8);
+        // NUM_CODE[6:4] in bits[14:12], no literal comparators.
+        assert_eq!(literal_comparators(0x0000_1020), 0x12..0x12);
+    }
+
+    #[test]
+    fn remap_addresses() {
+        assert_eq!(remap_table_entry(0x2000_0100, 0), 0x2000_0100);
+        assert_eq!(remap_table_entry(0x2000_0100, 7), 0x2000_011C);
+
+        assert_eq!(literal_comparator_value(0x0000_1234), 0x0000_1235);
+        assert_eq!(literal_comparator_value(0x1FFF_FFFC), 0x1FFF_FFFD);
+    }
+}
@@ -3,7 +3,7 @@
 //! ITM = Instrumentation Trace Macrocell
 
 use super::super::memory::romtable::CoresightComponent;
-use super::DebugRegister;
+use super::{ComponentError, DebugRegister};
 use crate::architecture::arm::ArmProbeInterface;
 use crate::Error;
 
@@ -29,10 +29,19 @@ pub struct Itm<'a> {
     interface: &'a mut dyn ArmProbeInterface,
 }
 
-const _REGISTER_OFFSET_ITM_TPR: u32 = 0xE40;
+const REGISTER_OFFSET_ITM_STIM: u32 = 0x000;
+const REGISTER_OFFSET_ITM_TPR: u32 = 0xE40;
 const REGISTER_OFFSET_ITM_TCR: u32 = 0xE80;
 const REGISTER_OFFSET_ACCESS: u32 = 0xFB0;
 
+/// The bits of the ITM_TCR register which are set by [`Itm::tx_enable`].
+pub(super) const TCR_TX_ENABLE: u32 = 1 << 0 // ITMENA: enable ITM (master switch)
+    | 1 << 1 // TSENA: enable local timestamps
+    | 1 << 2 // SYNENA: Enable sync pulses, note DWT_CTRL.SYNCTAP must be configured.
+    | 1 << 3 // TXENA: forward DWT packets to ITM
+    | 1 << 11 // GTSFREQ: generate global timestamp every 8192 cycles
+    | 13 << 16; // 7 bits trace bus ID
+
 impl<'a> Itm<'a> {
     /// Create a new ITM interface from a probe and a ROM table component.
     pub fn new(
@@ -65,12 +74,7 @@ impl<'a> Itm<'a> {
             .component
             .read_reg(self.interface, REGISTER_OFFSET_ITM_TCR)?;
 
-        value |= 1 << 0; // ITMENA: enable ITM (master switch)
-        value |= 1 << 1; // TSENA: enable local timestamps
-        value |= 1 << 2; // SYNENA: Enable sync pulses, note DWT_CTRL.SYNCTAP must be configured.
-        value |= 1 << 3; // TXENA: forward DWT packets to ITM
-        value |= 1 << 11; // GTSFREQ: generate global timestamp every 8192 cycles
-        value |= 13 << 16; // 7 bits trace bus ID
+        value |= TCR_TX_ENABLE;
         self.component
             .write_reg(self.interface, REGISTER_OFFSET_ITM_TCR, value)?;
 
@@ -83,6 +87,71 @@ impl<'a> Itm<'a> {
 
         Ok(())
     }
+
+    /// Read the trace control register (ITM_TCR).
+    pub fn trace_control(&mut self) -> Result<u32, Error> {
+        self.component
+            .read_reg(self.interface, REGISTER_OFFSET_ITM_TCR)
+    }
+
+    /// Read the trace enable register (ITM_TER), which has one enable bit per stimulus port.
+    pub fn trace_enable(&mut self) -> Result<u32, Error> {
+        self.component
+            .read_reg(self.interface, register::ITM_TER::ADDRESS)
+    }
+
+    /// Write the trace control register (ITM_TCR).
+    pub fn set_trace_control(&mut self, value: u32) -> Result<(), Error> {
+        self.component
+            .write_reg(self.interface, REGISTER_OFFSET_ITM_TCR, value)
+    }
+
+    /// Write the trace enable register (ITM_TER).
+    pub fn set_trace_enable(&mut self, value: u32) -> Result<(), Error> {
+        self.component
+            .write_reg(self.interface, register::ITM_TER::ADDRESS, value)
+    }
+
+    /// Enable only the stimulus ports set in `mask`, with one bit per port.
+    ///
+    /// Writes to disabled ports are ignored by the ITM, so this reduces the trace bandwidth to
+    /// the ports of interest.
+    pub fn enable_ports(&mut self, mask: u32) -> Result<(), Error> {
+        self.set_trace_enable(mask)
+    }
+
+    /// Write a word to a stimulus port (ITM_STIM), as software on the target would.
+    ///
+    /// The ITM emits it as an instrumentation packet, which allows checking the trace path from
+    /// the ITM to the host. Returns an error if the FIFO of the port is not ready, e.g. because
+    /// the ITM is disabled.
+    pub fn write_stimulus(&mut self, port: u8, value: u32) -> Result<(), Error> {
+        if port >= 32 {
+            return Err(Error::architecture_specific(
+                ComponentError::InvalidStimulusPort(port),
+            ));
+        }
+
+        let address = REGISTER_OFFSET_ITM_STIM + 4 * port as u32;
+
+        // Reading a stimulus port returns the FIFOREADY flag in bit 0.
+        if self.component.read_reg(self.interface, address)? & 1 == 0 {
+            return Err(Error::architecture_specific(
+                ComponentError::StimulusPortNotReady(port),
+            ));
+        }
+
+        self.component.write_reg(self.interface, address, value)
+    }
+
+    /// Write the trace privilege register (ITM_TPR).
+    ///
+    /// Every bit of `mask` controls a group of 8 stimulus ports, starting with ports 0 to 7. If a
+    /// bit is set, the ports of the group can only be written by privileged code.
+    pub fn set_privilege(&mut self, mask: u8) -> Result<(), Error> {
+        self.component
+            .write_reg(self.interface, REGISTER_OFFSET_ITM_TPR, mask as u32)
+    }
 }
 
 mod register {
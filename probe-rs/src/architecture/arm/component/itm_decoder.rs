@@ -0,0 +1,331 @@
+//! Decoding of the ITM packet protocol.
+//!
+//! The trace data read with [`Session::read_trace_data`](crate::Session::read_trace_data) is a raw
+//! ITM byte stream, which interleaves software instrumentation packets with DWT hardware packets
+//! and timestamps. The [`ItmDecoder`] splits it into [`ItmPacket`]s.
+//!
+//! See "ARMv7-M Architecture Reference Manual", appendix D4 for details about the packet format.
+
+/// The overflow packet.
+const OVERFLOW: u8 = 0x70;
+/// The last byte of a synchronization packet.
+const SYNC_END: u8 = 0x80;
+/// The minimum number of zero bytes preceding [`SYNC_END`] in a synchronization packet.
+const SYNC_ZEROS: usize = 5;
+/// The header of a global timestamp packet with the low bits of the timestamp.
+const GLOBAL_TIMESTAMP_1: u8 = 0x94;
+/// The header of a global timestamp packet with the high bits of the timestamp.
+const GLOBAL_TIMESTAMP_2: u8 = 0xB4;
+/// The continuation bit of timestamp and extension packets.
+const CONTINUATION: u8 = 0x80;
+
+/// A packet of the ITM protocol.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ItmPacket {
+    /// A synchronization packet, which is emitted periodically.
+    Synchronization,
+    /// The ITM FIFO overflowed, so packets were lost.
+    Overflow,
+    /// Data written by software to a stimulus port.
+    Instrumentation {
+        /// The stimulus port.
+        port: u8,
+        /// The written data, with a size of 1, 2 or 4 bytes.
+        data: Vec<u8>,
+    },
+    /// Data generated by the DWT, e.g. exception trace, PC samples or data trace.
+    Hardware {
+        /// The discriminator, which identifies the type of the packet.
+        discriminator: u8,
+        /// The payload, with a size of 1, 2 or 4 bytes.
+        data: Vec<u8>,
+    },
+    /// The number of timestamp clock cycles since the last local timestamp.
+    LocalTimestamp {
+        /// The number of cycles.
+        delta: u32,
+        /// The relation of the timestamp to the corresponding packet, the `TC` field. This is
+        /// `0` if the timestamp is synchronous to the packet.
+        relation: u8,
+    },
+    /// Bits of the global timestamp.
+    ///
+    /// The low and high bits of the timestamp are transmitted in separate packets. `value`
+    /// contains the transmitted bits at their position in the timestamp, so the full timestamp is
+    /// the combination of the last packets with `high` unset and set.
+    GlobalTimestamp {
+        /// The transmitted bits of the timestamp.
+        value: u64,
+        /// `true` if this packet contains the high bits, starting at bit 26.
+        high: bool,
+        /// The high bits of the timestamp changed since the last transmission of them.
+        wrap: bool,
+        /// The system clock changed since the last global timestamp.
+        clock_change: bool,
+    },
+    /// An extension packet, e.g. with the page of the following stimulus port numbers.
+    Extension {
+        /// The extension information.
+        value: u32,
+        /// `true` if the extension belongs to hardware source packets.
+        hardware: bool,
+    },
+    /// A packet with a reserved header.
+    Reserved(u8),
+}
+
+/// A decoder for an ITM byte stream.
+///
+/// The stream is passed in chunks of any size with [`ItmDecoder::feed`], e.g. as returned by
+/// [`Session::read_trace_data`](crate::Session::read_trace_data). The decoded packets are
+/// returned by iterating over the decoder. Packets split between two chunks are held back until
+/// they are complete.
+#[derive(Debug, Clone, Default)]
+pub struct ItmDecoder {
+    buffer: Vec<u8>,
+    offset: usize,
+    zeros: usize,
+}
+
+impl ItmDecoder {
+    /// Create a new decoder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the next chunk of the ITM stream.
+    pub fn feed(&mut self, data: &[u8]) {
+        self.buffer.drain(..self.offset);
+        self.offset = 0;
+        self.buffer.extend_from_slice(data);
+    }
+}
+
+impl Iterator for ItmDecoder {
+    type Item = ItmPacket;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let data = &self.buffer[self.offset..];
+            let header = *data.first()?;
+
+            // The zero bytes of a synchronization packet are counted, as they can be split
+            // between chunks.
+            if header == 0 {
+                self.zeros += 1;
+                self.offset += 1;
+                continue;
+            }
+
+            if self.zeros > 0 {
+                let synchronization = header == SYNC_END && self.zeros >= SYNC_ZEROS;
+                self.zeros = 0;
+
+                if synchronization {
+                    self.offset += 1;
+                    return Some(ItmPacket::Synchronization);
+                }
+            }
+
+            let (length, packet) = decode(data)?;
+            self.offset += length;
+            return Some(packet);
+        }
+    }
+}
+
+/// Decodes the packet at the start of `data`, and returns its length.
+///
+/// Returns `None` if the packet is not complete yet.
+fn decode(data: &[u8]) -> Option<(usize, ItmPacket)> {
+    let header = data[0];
+
+    match header {
+        OVERFLOW => Some((1, ItmPacket::Overflow)),
+
+        // Local timestamp with a 3 bit delta in the header.
+        _ if header & 0x8F == 0 => Some((
+            1,
+            ItmPacket::LocalTimestamp {
+                delta: (header >> 4) as u32,
+                relation: 0,
+            },
+        )),
+
+        // Local timestamp with continuation bytes.
+        _ if header & 0xCF == 0xC0 => {
+            let payload = payload(data, 4)?;
+            Some((
+                payload.len() + 1,
+                ItmPacket::LocalTimestamp {
+                    delta: continued_value(payload) as u32,
+                    relation: (header >> 4) & 0b11,
+                },
+            ))
+        }
+
+        GLOBAL_TIMESTAMP_1 => {
+            let payload = payload(data, 4)?;
+            let mut value = continued_value(payload);
+            let (mut wrap, mut clock_change) = (false, false);
+
+            // The fourth byte carries bits [25:21], and the wrap and clock change flags.
+            if let [.., last] = payload {
+                if payload.len() == 4 {
+                    value &= (1 << 26) - 1;
+                    wrap = last & 0x40 != 0;
+                    clock_change = last & 0x20 != 0;
+                }
+            }
+
+            Some((
+                payload.len() + 1,
+                ItmPacket::GlobalTimestamp {
+                    value,
+                    high: false,
+                    wrap,
+                    clock_change,
+                },
+            ))
+        }
+
+        GLOBAL_TIMESTAMP_2 => {
+            let payload = payload(data, 6)?;
+            Some((
+                payload.len() + 1,
+                ItmPacket::GlobalTimestamp {
+                    value: continued_value(payload) << 26,
+                    high: true,
+                    wrap: false,
+                    clock_change: false,
+                },
+            ))
+        }
+
+        // Extension packet, with three bits of information in the header.
+        _ if header & 0x0B == 0x08 => {
+            let payload = if header & CONTINUATION != 0 {
+                payload(data, 4)?
+            } else {
+                &[]
+            };
+
+            Some((
+                payload.len() + 1,
+                ItmPacket::Extension {
+                    value: ((header >> 4) & 0b111) as u32 | (continued_value(payload) << 3) as u32,
+                    hardware: header & 0x04 != 0,
+                },
+            ))
+        }
+
+        // Source packets, with a payload of 1, 2 or 4 bytes.
+        _ if header & 0x03 != 0 => {
+            let length = 1 + (1 << ((header & 0x03) - 1));
+            let data = data.get(1..length)?.to_vec();
+            let address = header >> 3;
+
+            let packet = if header & 0x04 != 0 {
+                ItmPacket::Hardware {
+                    discriminator: address,
+                    data,
+                }
+            } else {
+                ItmPacket::Instrumentation {
+                    port: address,
+                    data,
+                }
+            };
+
+            Some((length, packet))
+        }
+
+        _ => Some((1, ItmPacket::Reserved(header))),
+    }
+}
+
+/// Returns the payload bytes of a packet with continuation bits, which has at most `max_length`
+/// payload bytes.
+///
+/// Returns `None` if the last payload byte was not received yet.
+fn payload(data: &[u8], max_length: usize) -> Option<&[u8]> {
+    let available = &data[1..data.len().min(max_length + 1)];
+
+    match available.iter().position(|byte| byte & CONTINUATION == 0) {
+        Some(index) => Some(&available[..=index]),
+        // Malformed packets are cut off at the maximum length.
+        None if available.len() == max_length => Some(available),
+        None => None,
+    }
+}
+
+/// Combines the 7 bit groups of the payload of a packet with continuation bits.
+fn continued_value(payload: &[u8]) -> u64 {
+    payload.iter().enumerate().fold(0, |value, (index, byte)| {
+        value | ((byte & !CONTINUATION) as u64) << (7 * index)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ItmDecoder, ItmPacket};
+
+    #[test]
+    fn decodes_packets() {
+        let mut decoder = ItmDecoder::new();
+
+        // Synchronization, "hi" on port 0, SysTick exception entry, a local timestamp of 3
+        // cycles and an overflow.
+        decoder.feed(&[
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x80, 0x02, b'h', b'i', 0x0E, 0x0F, 0x10, 0x30, 0x70,
+        ]);
+
+        assert_eq!(
+            decoder.collect::<Vec<_>>(),
+            [
+                ItmPacket::Synchronization,
+                ItmPacket::Instrumentation {
+                    port: 0,
+                    data: vec![b'h', b'i']
+                },
+                ItmPacket::Hardware {
+                    discriminator: 1,
+                    data: vec![0x0F, 0x10]
+                },
+                ItmPacket::LocalTimestamp {
+                    delta: 3,
+                    relation: 0
+                },
+                ItmPacket::Overflow,
+            ]
+        );
+    }
+
+    #[test]
+    fn resumes_split_packets() {
+        let mut decoder = ItmDecoder::new();
+
+        // A local timestamp with continuation, followed by a byte on port 1.
+        decoder.feed(&[0xC0, 0x81]);
+        assert_eq!(decoder.next(), None);
+
+        decoder.feed(&[0x01, 0x09]);
+        assert_eq!(
+            decoder.next(),
+            Some(ItmPacket::LocalTimestamp {
+                delta: 0x81,
+                relation: 0
+            })
+        );
+        assert_eq!(decoder.next(), None);
+
+        decoder.feed(&[b'x']);
+        assert_eq!(
+            decoder.next(),
+            Some(ItmPacket::Instrumentation {
+                port: 1,
+                data: vec![b'x']
+            })
+        );
+    }
+}
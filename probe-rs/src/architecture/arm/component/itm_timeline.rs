@@ -0,0 +1,169 @@
+//! Correlation of ITM instrumentation data with DWT PC samples.
+//!
+//! When both the ITM stimulus ports and periodic PC sampling are enabled, e.g. with a
+//! [`TraceConfig`](super::TraceConfig) which sets `pc_sampling`, the trace stream interleaves
+//! software log output with the sampled program counter. The [`ItmTimeline`] decodes this stream
+//! into a single sequence of [`TimedEvent`]s, with the time of each event reconstructed from the
+//! local timestamps.
+
+use super::{ExceptionEvent, ItmDecoder, ItmPacket};
+
+use std::collections::VecDeque;
+
+/// The discriminator of PC sample packets.
+const PC_SAMPLE_DISCRIMINATOR: u8 = 2;
+
+/// An event of the trace stream.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TraceEvent {
+    /// Data written by software to a stimulus port.
+    Instrumentation {
+        /// The stimulus port.
+        port: u8,
+        /// The written data, with a size of 1, 2 or 4 bytes.
+        data: Vec<u8>,
+    },
+    /// A periodic sample of the program counter, or `None` if the core was sleeping.
+    PcSample(Option<u32>),
+    /// An exception was entered, exited or returned to, if exception trace is enabled.
+    Exception(ExceptionEvent),
+    /// Trace data was lost, so the time of the following events is not exact anymore.
+    Overflow,
+}
+
+/// A [`TraceEvent`] with the time at which it occurred.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimedEvent {
+    /// The number of timestamp clock cycles since the start of the timeline.
+    ///
+    /// This is the sum of all local timestamps, so it is only accurate while local timestamps
+    /// are enabled.
+    pub timestamp: u64,
+    /// The event.
+    pub event: TraceEvent,
+}
+
+/// A decoder which turns an ITM byte stream into a timeline of instrumentation data, PC samples
+/// and exception trace.
+///
+/// The stream is passed in chunks of any size with [`ItmTimeline::feed`], e.g. as returned by
+/// [`Session::read_trace_data`](crate::Session::read_trace_data). The events are returned by
+/// iterating over the timeline, in the order they occurred.
+///
+/// A local timestamp refers to the packets preceding it, so events are held back until the
+/// following timestamp has been received. Other hardware packets, e.g. data trace, are
+/// skipped.
+#[derive(Debug, Clone, Default)]
+pub struct ItmTimeline {
+    decoder: ItmDecoder,
+    timestamp: u64,
+    pending: Vec<TraceEvent>,
+    ready: VecDeque<TimedEvent>,
+}
+
+impl ItmTimeline {
+    /// Create a new, empty timeline.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the next chunk of the ITM stream.
+    pub fn feed(&mut self, data: &[u8]) {
+        self.decoder.feed(data);
+
+        for packet in &mut self.decoder {
+            match packet {
+                ItmPacket::Instrumentation { port, data } => self
+                    .pending
+                    .push(TraceEvent::Instrumentation { port, data }),
+                ItmPacket::Hardware {
+                    discriminator: PC_SAMPLE_DISCRIMINATOR,
+                    data,
+                } => {
+                    // A sleeping core is reported with a single zero byte instead of the PC.
+                    let pc = <[u8; 4]>::try_from(data.as_slice())
+                        .ok()
+                        .map(u32::from_le_bytes);
+                    self.pending.push(TraceEvent::PcSample(pc));
+                }
+                ItmPacket::Hardware { .. } => {
+                    if let Some(event) = ExceptionEvent::from_packet(&packet) {
+                        self.pending.push(TraceEvent::Exception(event));
+                    }
+                }
+                ItmPacket::Overflow => self.pending.push(TraceEvent::Overflow),
+                ItmPacket::LocalTimestamp { delta, .. } => {
+                    self.timestamp += delta as u64;
+                    release(&mut self.pending, &mut self.ready, self.timestamp);
+                }
+                _ => (),
+            }
+        }
+    }
+
+    /// Returns all events which are still waiting for a timestamp, with the time of the last
+    /// timestamp.
+    ///
+    /// This should be called at the end of a capture, or if local timestamps are disabled.
+    pub fn flush(&mut self) -> impl Iterator<Item = TimedEvent> + '_ {
+        release(&mut self.pending, &mut self.ready, self.timestamp);
+        self.ready.drain(..)
+    }
+}
+
+/// Moves the `pending` events to the `ready` events, with the given timestamp.
+fn release(pending: &mut Vec<TraceEvent>, ready: &mut VecDeque<TimedEvent>, timestamp: u64) {
+    ready.extend(
+        pending
+            .drain(..)
+            .map(|event| TimedEvent { timestamp, event }),
+    );
+}
+
+impl Iterator for ItmTimeline {
+    type Item = TimedEvent;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.ready.pop_front()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ItmTimeline, TimedEvent, TraceEvent};
+
+    #[test]
+    fn correlates_log_output_with_pc_samples() {
+        let mut timeline = ItmTimeline::new();
+
+        // "x" on port 0 and a local timestamp of 5 cycles, a PC sample of 0x0800_0100 and a
+        // local timestamp of 3 cycles, then a sleep sample without a following timestamp.
+        timeline.feed(&[0x01, b'x', 0x50, 0x17, 0x00, 0x01, 0x00, 0x08, 0x30, 0x15]);
+        timeline.feed(&[0x00]);
+
+        assert_eq!(
+            timeline.by_ref().collect::<Vec<_>>(),
+            [
+                TimedEvent {
+                    timestamp: 5,
+                    event: TraceEvent::Instrumentation {
+                        port: 0,
+                        data: vec![b'x']
+                    }
+                },
+                TimedEvent {
+                    timestamp: 8,
+                    event: TraceEvent::PcSample(Some(0x0800_0100))
+                },
+            ]
+        );
+
+        assert_eq!(
+            timeline.flush().collect::<Vec<_>>(),
+            [TimedEvent {
+                timestamp: 8,
+                event: TraceEvent::PcSample(None)
+            }]
+        );
+    }
+}
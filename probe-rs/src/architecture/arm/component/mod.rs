@@ -1,26 +1,59 @@
 //! Types and functions for interacting with CoreSight Components
 
-mod dwt;
+mod data_sampler;
+pub(crate) mod dwt;
+mod exception_filter;
+mod exception_trace;
+mod fpb;
 mod itm;
+mod itm_decoder;
+mod itm_timeline;
 mod swo;
 mod tmc;
 mod tpiu;
+mod trace_capture;
+mod trace_config;
+mod trace_diagnosis;
 mod trace_funnel;
+mod tsgen;
 
+use super::memory::adi_v5_memory_interface::ArmProbe;
 use super::memory::romtable::{CoresightComponent, PeripheralType, RomTableError};
+use crate::architecture::arm::ap::MemoryAp;
 use crate::architecture::arm::core::armv6m::Demcr;
-use crate::architecture::arm::{ArmProbeInterface, SwoConfig, SwoMode};
-use crate::{Core, Error, MemoryInterface, MemoryMappedRegister};
+use crate::architecture::arm::core::cortex_m::{Afsr, Cpuid};
+use crate::architecture::arm::{ApAddress, ArmProbeInterface, SwoConfig, SwoMode};
+use crate::{Core, CoreType, Error, MemoryInterface, MemoryMappedRegister};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 pub use self::itm::Itm;
-pub use dwt::Dwt;
+pub use self::itm_decoder::{ItmDecoder, ItmPacket};
+pub use self::itm_timeline::{ItmTimeline, TimedEvent, TraceEvent};
+pub use data_sampler::DataSampler;
+pub use dwt::{
+    Dwt, DwtAccess, DwtComparatorMatch, DwtDataSize, PcSampleRate, ProfileCounterOverflow,
+    ProfileCounters,
+};
+pub use exception_filter::ExceptionTraceFilter;
+pub use exception_trace::{exception_name, ExceptionEvent, ExceptionTrace};
+pub use fpb::Fpb;
 pub use swo::Swo;
-pub use tmc::TraceMemoryController;
+pub use tmc::{TmcVariant, TraceMemoryController};
 pub use tpiu::Tpiu;
+pub use trace_capture::TraceCapture;
+pub(crate) use trace_config::configure_itm;
+pub use trace_config::{DataTraceConfig, TraceConfig, TraceConfigError};
+pub(crate) use trace_diagnosis::{
+    check_trace_authentication, check_trace_clock, find_marker, inject_marker,
+    restore_stimulus_ports, stage_of_check, MARKER_POLL_INTERVAL,
+};
+pub use trace_diagnosis::{TraceDiagnosis, TraceFailure, TraceStage};
 pub use trace_funnel::TraceFunnel;
+pub use tsgen::Tsgen;
 
 /// Specifies the data sink (destination) for trace data.
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
 pub enum TraceSink {
     /// Trace data should be sent to the SWO peripheral.
     ///
@@ -31,8 +64,65 @@ pub enum TraceSink {
     /// Trace data should be sent to the TPIU peripheral.
     Tpiu(SwoConfig),
 
+    /// Trace data should be sent to the parallel trace port of the TPIU, with a port width of
+    /// 1, 2 or 4 bits on the `TRACEDATA[3:0]` pins.
+    ///
+    /// The trace port allows a much higher bandwidth than SWO, e.g. for ETM trace, but requires a
+    /// probe which can capture it, e.g. through a MIPI-20 connector with trace pins. The TPIU
+    /// formatter is always enabled.
+    TracePort {
+        /// The number of data pins of the trace port.
+        port_width: u8,
+    },
+
     /// Trace data should be sent to the embedded trace buffer for software-based trace collection.
     TraceMemory,
+
+    /// Trace data should be captured continuously in the embedded trace buffer, which is used as a
+    /// circular buffer.
+    ///
+    /// Once the buffer is full, the oldest trace data is overwritten, so the trace stream is never
+    /// stalled. Every read of the trace data returns the whole buffer, i.e. the last trace data
+    /// before the read, and restarts the capture with an empty buffer. This is useful for a
+    /// post-mortem analysis of e.g. a crash, when the trace leading up to it is of interest.
+    CircularMemory,
+
+    /// Trace data should be captured continuously in the embedded trace buffer, until a trigger
+    /// stops the capture.
+    ///
+    /// The trigger is generated by a DWT comparator when the instruction at `trigger_address` is
+    /// executed. After the trigger, `post_count` more 32 bit words are captured. This allows
+    /// recording the trace leading up to a specific event.
+    ///
+    /// # Note
+    /// The trigger has to be routed from the DWT to the trigger input of the trace memory
+    /// controller, which is done by the cross trigger interface on most devices.
+    TriggeredMemory {
+        /// The instruction address which generates the trigger.
+        trigger_address: u32,
+        /// The number of 32 bit words captured after the trigger.
+        post_count: u32,
+    },
+
+    /// Trace data should be written into a circular buffer in system memory by an embedded trace
+    /// router (ETR), see [`TmcVariant::Etr`].
+    ///
+    /// This allows capturing sustained high-bandwidth trace, e.g. from an ETM, as the buffer can
+    /// be much larger than dedicated trace RAM. The whole buffer is read back on every read of
+    /// the trace data, and the capture is restarted with an empty buffer afterwards.
+    ///
+    /// The buffer is read through the memory AP `memory_ap`. The memory has to be reserved, so
+    /// it is not used by the firmware.
+    SystemMemory {
+        /// The address of the buffer, which has to be aligned to 4 kB.
+        address: u32,
+        /// The size of the buffer in bytes, which has to be a multiple of 16.
+        size: u32,
+        /// The index of the memory AP which accesses system memory, e.g. an AHB-AP or AXI-AP.
+        ///
+        /// This is usually not the AP of the ETR itself, which is an APB-AP on most SoCs.
+        memory_ap: u8,
+    },
 }
 
 /// An error when operating a core ROM table component occurred.
@@ -41,6 +131,233 @@ pub enum ComponentError {
     /// Nordic chips do not support setting all TPIU clocks. Try choosing another clock speed.
     #[error("Nordic does not support TPIU CLK value of {0}")]
     NordicUnsupportedTPUICLKValue(u32),
+    /// The requested SWO baud rate cannot be derived from the TPIU clock.
+    #[error("SWO baud rate {baud} cannot be derived from a TPIU clock of {tpiu_clk} Hz")]
+    UnsupportedSwoBaudRate {
+        /// The requested baud rate.
+        baud: u32,
+        /// The TPIU clock frequency in Hz.
+        tpiu_clk: u32,
+    },
+    /// The DWT does not implement any comparators, which are required for trace triggers.
+    #[error("The DWT does not implement any comparators")]
+    NoDwtComparator,
+    /// The divider for periodic DWT samples has to fit into the 4 bit `POSTPRESET` field.
+    #[error("The DWT sampling divider {0} is larger than 15")]
+    InvalidSamplingDivider(u8),
+    /// The FPB does not support remapping accesses to the Code region.
+    #[error("The FPB does not support remapping")]
+    FlashPatchNotSupported,
+    /// The remap table has to be aligned to 32 bytes and placed in the SRAM region.
+    #[error("The FPB remap table cannot be placed at {0:#010x}")]
+    InvalidRemapTableAddress(u32),
+    /// Words can only be remapped after the remap table has been set up.
+    #[error("The FPB remap table has not been set up")]
+    RemapTableNotConfigured,
+    /// Only word aligned addresses in the Code region can be remapped.
+    #[error("The address {0:#010x} cannot be remapped by the FPB")]
+    InvalidRemapAddress(u32),
+    /// All literal comparators of the FPB are already in use.
+    #[error("No free FPB literal comparator is available")]
+    NoFreeFlashPatchComparator,
+    /// No SWO baud rate close to the requested one can be both generated by the target and
+    /// received by the probe.
+    #[error(
+        "No SWO baud rate near {baud} can be generated by the target and received by the probe"
+    )]
+    SwoBaudRateNegotiationFailed {
+        /// The requested baud rate.
+        baud: u32,
+        /// The baud rate the probe was last configured to.
+        probe_baud: u32,
+    },
+    /// The data in trace memory does not look like formatted frames, which usually means that
+    /// the formatter does not match the trace configuration.
+    #[error("The trace data does not consist of valid formatted frames, check the formatter configuration")]
+    TraceFormatterMismatch,
+    /// The DWT does not implement the optional cycle counter.
+    #[error("The DWT does not implement a cycle counter")]
+    NoCycleCounter,
+    /// The DWT does not implement the optional profiling counters.
+    #[error("The DWT does not implement the profiling counters")]
+    NoProfileCounters,
+    /// The trace buffer in system memory has to be aligned to 4 kB, and its size has to be a
+    /// non-zero multiple of the 16 byte frame size.
+    #[error("The trace buffer at {address:#010x} with a size of {size} bytes is not valid")]
+    InvalidSystemTraceBuffer {
+        /// The address of the buffer.
+        address: u32,
+        /// The size of the buffer in bytes.
+        size: u32,
+    },
+    /// The `DEVID` register of the TMC contains a reserved configuration type.
+    #[error("The trace memory controller has the unknown configuration type {0:#04b}")]
+    UnknownTmcConfiguration(u8),
+    /// The configured trace sink is not supported by this TMC variant, e.g. an ETR requires a
+    /// buffer in system memory.
+    #[error(
+        "The trace memory controller is an {0:?}, which does not support the configured trace sink"
+    )]
+    UnsupportedTmcVariant(TmcVariant),
+    /// The DWT comparator does not support data value matching. On ARMv7-M, this is usually only
+    /// supported by comparator 1.
+    #[error("DWT comparator {0} does not support data value matching")]
+    NoDataValueMatch(usize),
+    /// The DWT comparator does not implement a mask of the given size. ARMv8-M comparators have
+    /// no mask at all.
+    #[error("DWT comparator {unit} does not support a mask of {mask} bits")]
+    UnsupportedComparatorMask {
+        /// The number of the comparator.
+        unit: usize,
+        /// The number of masked bits.
+        mask: u8,
+    },
+    /// On ARMv8-M, a data value comparator can only be linked to the preceding comparator.
+    #[error("DWT comparator {unit} cannot be linked to comparator {linked_unit}")]
+    InvalidLinkedComparator {
+        /// The number of the data value comparator.
+        unit: usize,
+        /// The number of the linked address comparator.
+        linked_unit: usize,
+    },
+    /// A trace funnel only has the input ports 0 to 7.
+    #[error("The trace funnel has no input port {0}")]
+    InvalidFunnelPort(u8),
+    /// The minimum hold time of a trace funnel has to be between 1 and 16 transactions.
+    #[error("The trace funnel hold time of {0} transactions is not supported")]
+    InvalidFunnelHoldTime(u8),
+    /// The priority of a trace funnel input has to be between 0 and 7.
+    #[error("The trace funnel priority {0} is not supported")]
+    InvalidFunnelPriority(u8),
+    /// The data value does not fit into the given access size.
+    #[error("The data value {value:#x} does not fit into a {size:?} access")]
+    InvalidDataValue {
+        /// The data value.
+        value: u32,
+        /// The access size.
+        size: DwtDataSize,
+    },
+    /// The ITM has 32 stimulus ports.
+    #[error("The ITM has no stimulus port {0}")]
+    InvalidStimulusPort(u8),
+    /// The FIFO of the ITM stimulus port cannot accept data, e.g. because the ITM is disabled.
+    #[error("The ITM stimulus port {0} is not ready")]
+    StimulusPortNotReady(u8),
+    /// The TPIU does not support the requested width of the parallel trace port.
+    #[error("The TPIU does not support a trace port width of {0} bits")]
+    UnsupportedTracePortWidth(u8),
+}
+
+/// The maximum value of the 13 bit SWO prescaler (`ACPR.SWOSCALER`).
+pub(crate) const MAX_SWO_PRESCALER: u32 = 0x1FFF;
+
+/// The required alignment of the trace buffer in system memory of an ETR.
+const SYSTEM_TRACE_BUFFER_ALIGNMENT: u32 = 4096;
+
+/// The number of FIFO sizes which is read at most in a single trace memory read, for cores with
+/// a high trace bandwidth.
+///
+/// A Cortex-M7 refills the trace memory while it is being read out, so stopping after a single
+/// FIFO size stalls the trace stream and causes the ITM FIFO to overflow.
+const HIGH_BANDWIDTH_FIFO_READS: usize = 4;
+
+/// Checks whether the core emits trace data at a high bandwidth, which is the case for the
+/// Cortex-M7 with its higher trace clock and deeper ITM FIFO.
+pub(crate) fn is_high_bandwidth_trace_core(core: &mut Core) -> Result<bool, Error> {
+    let cpuid = Cpuid::from(core.read_word_32(Cpuid::ADDRESS)?);
+    Ok(cpuid.is_cortex_m7())
+}
+
+/// Computes the SWO prescaler for the given configuration.
+///
+/// The prescaler is rounded to the nearest value, as the truncation error grows noticeably with
+/// the high trace clocks of cores like the Cortex-M7.
+fn swo_prescaler(config: &SwoConfig) -> Result<u32, Error> {
+    let (tpiu_clk, baud) = (config.tpiu_clk(), config.baud());
+
+    if baud == 0 || baud > tpiu_clk {
+        return Err(Error::architecture_specific(
+            ComponentError::UnsupportedSwoBaudRate { baud, tpiu_clk },
+        ));
+    }
+
+    let divisor = (tpiu_clk as u64 + baud as u64 / 2) / baud as u64;
+    let prescaler = divisor as u32 - 1;
+
+    if prescaler > MAX_SWO_PRESCALER {
+        return Err(Error::architecture_specific(
+            ComponentError::UnsupportedSwoBaudRate { baud, tpiu_clk },
+        ));
+    }
+
+    Ok(prescaler)
+}
+
+/// The maximum relative deviation between the baud rate generated by the target, and the baud
+/// rate received by the probe, for a negotiated baud rate. This is well within the tolerance of
+/// a UART receiver.
+const MAX_SWO_BAUD_DEVIATION: f64 = 0.01;
+
+/// The number of times the baud rate is requested from the probe while negotiating it.
+const SWO_BAUD_NEGOTIATION_ATTEMPTS: usize = 4;
+
+/// Configures SWO on the probe, and negotiates a baud rate which can both be generated by the
+/// target and received by the probe.
+///
+/// The configured baud rate is requested from the probe first. If the probe reports a different
+/// baud rate, the closest baud rate the TPIU clock can be divided to is requested next, until the
+/// probe and the target agree. Probes which don't report their baud rate are assumed to receive
+/// the requested baud rate exactly.
+///
+/// # Returns
+/// The configuration with the negotiated baud rate, which has to be used to configure the
+/// target.
+pub(crate) fn negotiate_swo_baud(
+    interface: &mut dyn ArmProbeInterface,
+    config: &SwoConfig,
+) -> Result<SwoConfig, Error> {
+    let mut request = *config;
+
+    for _ in 0..SWO_BAUD_NEGOTIATION_ATTEMPTS {
+        interface.enable_swo(&request)?;
+
+        let probe_baud = match interface.swo_baud_rate() {
+            Some(probe_baud) => probe_baud,
+            None => return Ok(request),
+        };
+
+        // The baud rate the target generates with the prescaler for the probe baud rate.
+        let negotiated = request.set_baud(probe_baud);
+        let target_baud = negotiated.tpiu_clk() / (swo_prescaler(&negotiated)? + 1);
+
+        let deviation = (target_baud as f64 - probe_baud as f64).abs() / probe_baud as f64;
+        if deviation <= MAX_SWO_BAUD_DEVIATION {
+            tracing::info!(
+                "Negotiated SWO baud rate {} (requested {})",
+                probe_baud,
+                config.baud()
+            );
+            return Ok(negotiated);
+        }
+
+        tracing::debug!(
+            "Probe SWO baud rate {} cannot be generated by the target, which would use {}",
+            probe_baud,
+            target_baud
+        );
+
+        if target_baud == request.baud() {
+            break;
+        }
+        request = request.set_baud(target_baud);
+    }
+
+    Err(Error::architecture_specific(
+        ComponentError::SwoBaudRateNegotiationFailed {
+            baud: config.baud(),
+            probe_baud: interface.swo_baud_rate().unwrap_or_default(),
+        },
+    ))
 }
 
 /// A trait to be implemented on debug register types for debug component interfaces.
@@ -94,7 +411,7 @@ pub trait DebugRegister: Clone + From<u32> + Into<u32> + Sized + std::fmt::Debug
 }
 
 /// Goes through every component in the vector and tries to find the first component with the given type
-fn find_component(
+pub(crate) fn find_component(
     components: &[CoresightComponent],
     peripheral_type: PeripheralType,
 ) -> Result<&CoresightComponent, Error> {
@@ -123,12 +440,9 @@ fn configure_tpiu(
     let mut tpiu = Tpiu::new(interface, component);
 
     tpiu.set_port_size(1)?;
-    let prescaler = (config.tpiu_clk() / config.baud()) - 1;
+    let prescaler = swo_prescaler(config)?;
     tpiu.set_prescaler(prescaler)?;
-    match config.mode() {
-        SwoMode::Manchester => tpiu.set_pin_protocol(1)?,
-        SwoMode::Uart => tpiu.set_pin_protocol(2)?,
-    }
+    tpiu.set_pin_protocol(swo_pin_protocol(config))?;
 
     // Formatter: TrigIn enabled, bypass optional
     if config.tpiu_continuous_formatting() {
@@ -142,6 +456,41 @@ fn configure_tpiu(
     Ok(())
 }
 
+/// The value of the CSPSR and SSPSR registers of the TPIU for a trace port width, which has a
+/// bit set for each supported width.
+fn trace_port_size(port_width: u8) -> Result<u32, Error> {
+    match port_width {
+        1 | 2 | 4 => Ok(1 << (port_width - 1)),
+        _ => Err(Error::architecture_specific(
+            ComponentError::UnsupportedTracePortWidth(port_width),
+        )),
+    }
+}
+
+/// Configure the Trace Port Interface Unit for the parallel trace port.
+fn configure_trace_port(
+    interface: &mut dyn ArmProbeInterface,
+    component: &CoresightComponent,
+    port_width: u8,
+) -> Result<(), Error> {
+    let mut tpiu = Tpiu::new(interface, component);
+
+    let port_size = trace_port_size(port_width)?;
+    if tpiu.supported_port_sizes()? & port_size == 0 {
+        return Err(Error::architecture_specific(
+            ComponentError::UnsupportedTracePortWidth(port_width),
+        ));
+    }
+
+    tpiu.set_port_size(port_size)?;
+    // Synchronous trace port mode.
+    tpiu.set_pin_protocol(0)?;
+    // The formatter is required to extract the trace data from the trace port.
+    tpiu.set_formatter(0x102)?;
+
+    Ok(())
+}
+
 /// Sets up all the SWV components.
 ///
 /// Expects to be given a list of all ROM table `components` as the second argument.
@@ -170,18 +519,23 @@ pub(crate) fn setup_tracing(
             )?;
         }
 
+        TraceSink::TracePort { port_width } => {
+            configure_trace_port(
+                interface,
+                find_component(components, PeripheralType::Tpiu)?,
+                *port_width,
+            )?;
+        }
+
         TraceSink::Swo(config) => {
             if let Ok(peripheral) = find_component(components, PeripheralType::Swo) {
                 let mut swo = Swo::new(interface, peripheral);
                 swo.unlock()?;
 
-                let prescaler = (config.tpiu_clk() / config.baud()) - 1;
+                let prescaler = swo_prescaler(config)?;
                 swo.set_prescaler(prescaler)?;
 
-                match config.mode() {
-                    SwoMode::Manchester => swo.set_pin_protocol(1)?,
-                    SwoMode::Uart => swo.set_pin_protocol(2)?,
-                }
+                swo.set_pin_protocol(swo_pin_protocol(config))?;
             } else {
                 // For Cortex-M4, the SWO and the TPIU are combined. If we don't find a SWO
                 // peripheral, use the TPIU instead.
@@ -193,7 +547,7 @@ pub(crate) fn setup_tracing(
             }
         }
 
-        TraceSink::TraceMemory => {
+        TraceSink::TraceMemory | TraceSink::CircularMemory => {
             let mut tmc = TraceMemoryController::new(
                 interface,
                 find_component(components, PeripheralType::Tmc)?,
@@ -203,9 +557,102 @@ pub(crate) fn setup_tracing(
             tmc.disable_capture()?;
             while !tmc.ready()? {}
 
-            // Configure the TMC for software-polled mode, as we will read out data using the debug
-            // interface.
-            tmc.set_mode(tmc::Mode::Software)?;
+            match tmc.variant()? {
+                // Configure the TMC for software-polled mode, as we will read out data using the
+                // debug interface.
+                TmcVariant::Etf if matches!(sink, TraceSink::TraceMemory) => {
+                    tmc.set_mode(tmc::Mode::Software)?
+                }
+                // An ETB only supports a circular buffer, which is read out while the capture is
+                // stopped, see `read_trace_buffer`.
+                TmcVariant::Etb | TmcVariant::Etf => {
+                    tmc.set_mode(tmc::Mode::Circular)?;
+                    tmc.set_write_pointer(0)?;
+                    tmc.set_read_pointer(0)?;
+                }
+                variant @ TmcVariant::Etr => {
+                    return Err(Error::architecture_specific(
+                        ComponentError::UnsupportedTmcVariant(variant),
+                    ))
+                }
+            }
+
+            tmc.enable_capture()?;
+        }
+
+        TraceSink::TriggeredMemory {
+            trigger_address,
+            post_count,
+        } => {
+            let mut tmc = TraceMemoryController::new(
+                interface,
+                find_component(components, PeripheralType::Tmc)?,
+            );
+
+            // The trace RAM of an ETR is in system memory, which has to be configured explicitly.
+            let variant = tmc.variant()?;
+            if variant == TmcVariant::Etr {
+                return Err(Error::architecture_specific(
+                    ComponentError::UnsupportedTmcVariant(variant),
+                ));
+            }
+
+            // Clear out the TMC FIFO before initiating the capture.
+            tmc.disable_capture()?;
+            while !tmc.ready()? {}
+
+            // Capture continuously into a circular buffer, which stops once the trigger counter
+            // expires after the trigger event.
+            tmc.set_mode(tmc::Mode::Circular)?;
+            tmc.set_write_pointer(0)?;
+            tmc.set_read_pointer(0)?;
+            tmc.stop_on_trigger(*post_count)?;
+
+            tmc.enable_capture()?;
+
+            // Use the last comparator for the trigger, the lower ones are used for data tracing.
+            let mut dwt = Dwt::new(interface, find_component(components, PeripheralType::Dwt)?);
+            let unit = dwt
+                .num_comparators()?
+                .checked_sub(1)
+                .ok_or_else(|| Error::architecture_specific(ComponentError::NoDwtComparator))?;
+            dwt.enable_trigger(unit as usize, *trigger_address)?;
+        }
+
+        TraceSink::SystemMemory { address, size, .. } => {
+            let mut tmc = TraceMemoryController::new(
+                interface,
+                find_component(components, PeripheralType::Tmc)?,
+            );
+
+            let variant = tmc.variant()?;
+            if variant != TmcVariant::Etr {
+                return Err(Error::architecture_specific(
+                    ComponentError::UnsupportedTmcVariant(variant),
+                ));
+            }
+
+            if address % SYSTEM_TRACE_BUFFER_ALIGNMENT != 0
+                || *size == 0
+                || size % 16 != 0
+                || address.checked_add(*size).is_none()
+            {
+                return Err(Error::architecture_specific(
+                    ComponentError::InvalidSystemTraceBuffer {
+                        address: *address,
+                        size: *size,
+                    },
+                ));
+            }
+
+            tmc.disable_capture()?;
+            while !tmc.ready()? {}
+
+            // The ETR writes into the buffer continuously, overwriting the oldest data.
+            tmc.set_mode(tmc::Mode::Circular)?;
+            tmc.configure_axi_writes()?;
+            tmc.enable_formatter()?;
+            tmc.set_system_buffer(*address, *size)?;
 
             tmc.enable_capture()?;
         }
@@ -214,11 +661,361 @@ pub(crate) fn setup_tracing(
     Ok(())
 }
 
-/// Read trace data from internal trace memory
+/// A trace configuration register, read back and compared to the value which
+/// [`Session::setup_tracing`](crate::Session::setup_tracing) configures for a trace sink.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceRegisterCheck {
+    /// The name of the register.
+    pub name: &'static str,
+    /// The bits of the register which are configured for tracing.
+    pub mask: u32,
+    /// The expected value of the configured bits.
+    pub expected: u32,
+    /// The value read from the register.
+    pub actual: u32,
+}
+
+impl TraceRegisterCheck {
+    fn new(name: &'static str, mask: u32, expected: u32, actual: u32) -> Self {
+        Self {
+            name,
+            mask,
+            expected: expected & mask,
+            actual,
+        }
+    }
+
+    /// Returns `true` if the configured bits of the register have the expected value.
+    pub fn matches(&self) -> bool {
+        self.actual & self.mask == self.expected
+    }
+}
+
+/// Check that exactly the configured `stimulus_ports` are enabled in the `actual` ITM_TER value.
+fn stimulus_port_check(stimulus_ports: u32, actual: u32) -> TraceRegisterCheck {
+    TraceRegisterCheck::new("ITM_TER", u32::MAX, stimulus_ports, actual)
+}
+
+/// The result of reading back the trace configuration, see
+/// [`Session::verify_trace_setup`](crate::Session::verify_trace_setup).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TraceSetupReport {
+    /// All registers which were checked.
+    pub checks: Vec<TraceRegisterCheck>,
+}
+
+impl TraceSetupReport {
+    /// Returns `true` if all registers have the expected configuration.
+    pub fn is_ok(&self) -> bool {
+        self.checks.iter().all(TraceRegisterCheck::matches)
+    }
+
+    /// All registers which do not have the expected configuration.
+    pub fn mismatches(&self) -> impl Iterator<Item = &TraceRegisterCheck> {
+        self.checks.iter().filter(|check| !check.matches())
+    }
+}
+
+/// Read back the configuration of the TPIU, and compare it to the one set by [`configure_tpiu`].
+fn verify_tpiu(
+    interface: &mut dyn ArmProbeInterface,
+    component: &CoresightComponent,
+    config: &SwoConfig,
+    checks: &mut Vec<TraceRegisterCheck>,
+) -> Result<(), Error> {
+    let mut tpiu = Tpiu::new(interface, component);
+
+    checks.push(TraceRegisterCheck::new(
+        "TPIU_CSPSR",
+        u32::MAX,
+        1,
+        tpiu.port_size()?,
+    ));
+    checks.push(TraceRegisterCheck::new(
+        "TPIU_ACPR",
+        MAX_SWO_PRESCALER,
+        swo_prescaler(config)?,
+        tpiu.prescaler()?,
+    ));
+    checks.push(TraceRegisterCheck::new(
+        "TPIU_SPPR",
+        0b11,
+        swo_pin_protocol(config),
+        tpiu.pin_protocol()?,
+    ));
+
+    let formatter = if config.tpiu_continuous_formatting() {
+        0x102
+    } else {
+        0x100
+    };
+    checks.push(TraceRegisterCheck::new(
+        "TPIU_FFCR",
+        0x102,
+        formatter,
+        tpiu.formatter()?,
+    ));
+
+    Ok(())
+}
+
+/// The value of the SPPR register of the TPIU or SWO for the SWO mode.
+fn swo_pin_protocol(config: &SwoConfig) -> u32 {
+    match config.mode() {
+        SwoMode::Manchester => 1,
+        SwoMode::Uart => 2,
+    }
+}
+
+/// Reads back the configuration of the SWV components, and compares it to the configuration
+/// done by [`setup_tracing`] for the given `sink`.
 ///
-/// # Args
-/// * `interface` - The interface with the debug probe.
-/// * `components` - The CoreSight debug components identified in the system.
+/// Expects to be given a list of all ROM table `components` as the second argument.
+pub(crate) fn verify_trace_setup(
+    interface: &mut dyn ArmProbeInterface,
+    components: &[CoresightComponent],
+    sink: &TraceSink,
+    stimulus_ports: u32,
+) -> Result<TraceSetupReport, Error> {
+    let mut checks = Vec::new();
+
+    // DWT
+    let mut dwt = Dwt::new(interface, find_component(components, PeripheralType::Dwt)?);
+
+    let mut mask = dwt::Ctrl::from(0);
+    mask.set_synctap(0b11);
+    mask.set_cyccntena(true);
+    mask.set_exctrcena(true);
+
+    let mut expected = dwt::Ctrl::from(0);
+    expected.set_synctap(0x01);
+    expected.set_cyccntena(true);
+    expected.set_exctrcena(true);
+
+    checks.push(TraceRegisterCheck::new(
+        "DWT_CTRL",
+        mask.into(),
+        expected.into(),
+        dwt.control()?,
+    ));
+
+    // ITM
+    let mut itm = Itm::new(interface, find_component(components, PeripheralType::Itm)?);
+    checks.push(TraceRegisterCheck::new(
+        "ITM_TCR",
+        itm::TCR_TX_ENABLE,
+        itm::TCR_TX_ENABLE,
+        itm.trace_control()?,
+    ));
+    checks.push(stimulus_port_check(stimulus_ports, itm.trace_enable()?));
+
+    // Trace destination
+    match sink {
+        TraceSink::Tpiu(config) => verify_tpiu(
+            interface,
+            find_component(components, PeripheralType::Tpiu)?,
+            config,
+            &mut checks,
+        )?,
+        TraceSink::TracePort { port_width } => {
+            let mut tpiu = Tpiu::new(interface, find_component(components, PeripheralType::Tpiu)?);
+
+            checks.push(TraceRegisterCheck::new(
+                "TPIU_CSPSR",
+                u32::MAX,
+                trace_port_size(*port_width)?,
+                tpiu.port_size()?,
+            ));
+            checks.push(TraceRegisterCheck::new(
+                "TPIU_SPPR",
+                0b11,
+                0,
+                tpiu.pin_protocol()?,
+            ));
+            checks.push(TraceRegisterCheck::new(
+                "TPIU_FFCR",
+                0x102,
+                0x102,
+                tpiu.formatter()?,
+            ));
+        }
+        TraceSink::Swo(config) => {
+            if let Ok(peripheral) = find_component(components, PeripheralType::Swo) {
+                let mut swo = Swo::new(interface, peripheral);
+
+                checks.push(TraceRegisterCheck::new(
+                    "SWO_CODR",
+                    MAX_SWO_PRESCALER,
+                    swo_prescaler(config)?,
+                    swo.prescaler()?,
+                ));
+                checks.push(TraceRegisterCheck::new(
+                    "SWO_SPPR",
+                    0b11,
+                    swo_pin_protocol(config),
+                    swo.pin_protocol()?,
+                ));
+            } else {
+                verify_tpiu(
+                    interface,
+                    find_component(components, PeripheralType::Tpiu)?,
+                    config,
+                    &mut checks,
+                )?;
+            }
+        }
+        // The trace memory controller has no static configuration which could be checked.
+        TraceSink::TraceMemory
+        | TraceSink::CircularMemory
+        | TraceSink::TriggeredMemory { .. }
+        | TraceSink::SystemMemory { .. } => {}
+    }
+
+    Ok(TraceSetupReport { checks })
+}
+
+/// The configuration of a single DWT comparator.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct DwtComparatorConfig {
+    comp: u32,
+    mask: u32,
+    function: u32,
+}
+
+/// The configuration of the TPIU.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct TpiuConfig {
+    port_size: u32,
+    prescaler: u32,
+    pin_protocol: u32,
+    formatter: u32,
+}
+
+/// The configuration of the SWO.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct SwoRegisterConfig {
+    prescaler: u32,
+    pin_protocol: u32,
+}
+
+/// A snapshot of the registers of the DWT, ITM, TPIU and SWO components, see
+/// [`Session::save_trace_config`](crate::Session::save_trace_config).
+///
+/// Components which are not present on the target are skipped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceConfiguration {
+    dwt_control: Option<u32>,
+    dwt_comparators: Vec<DwtComparatorConfig>,
+    itm: Option<(u32, u32)>,
+    tpiu: Option<TpiuConfig>,
+    swo: Option<SwoRegisterConfig>,
+}
+
+/// Reads the configuration of all trace components, so it can be restored later with
+/// [`restore_trace_config`].
+///
+/// Expects to be given a list of all ROM table `components` as the second argument.
+pub(crate) fn save_trace_config(
+    interface: &mut dyn ArmProbeInterface,
+    components: &[CoresightComponent],
+) -> Result<TraceConfiguration, Error> {
+    let mut config = TraceConfiguration {
+        dwt_control: None,
+        dwt_comparators: Vec::new(),
+        itm: None,
+        tpiu: None,
+        swo: None,
+    };
+
+    if let Ok(component) = find_component(components, PeripheralType::Dwt) {
+        let control = dwt::Ctrl::load(component, interface)?;
+
+        for unit in 0..control.numcomp() as usize {
+            config.dwt_comparators.push(DwtComparatorConfig {
+                comp: dwt::Comp::load_unit(component, interface, unit)?.into(),
+                mask: dwt::Mask::load_unit(component, interface, unit)?.into(),
+                function: dwt::Function::load_unit(component, interface, unit)?.into(),
+            });
+        }
+
+        config.dwt_control = Some(control.into());
+    }
+
+    if let Ok(component) = find_component(components, PeripheralType::Itm) {
+        let mut itm = Itm::new(interface, component);
+        config.itm = Some((itm.trace_control()?, itm.trace_enable()?));
+    }
+
+    if let Ok(component) = find_component(components, PeripheralType::Tpiu) {
+        let mut tpiu = Tpiu::new(interface, component);
+        config.tpiu = Some(TpiuConfig {
+            port_size: tpiu.port_size()?,
+            prescaler: tpiu.prescaler()?,
+            pin_protocol: tpiu.pin_protocol()?,
+            formatter: tpiu.formatter()?,
+        });
+    }
+
+    if let Ok(component) = find_component(components, PeripheralType::Swo) {
+        let mut swo = Swo::new(interface, component);
+        config.swo = Some(SwoRegisterConfig {
+            prescaler: swo.prescaler()?,
+            pin_protocol: swo.pin_protocol()?,
+        });
+    }
+
+    Ok(config)
+}
+
+/// Writes back a trace configuration read by [`save_trace_config`].
+///
+/// Expects to be given a list of all ROM table `components` as the second argument.
+pub(crate) fn restore_trace_config(
+    interface: &mut dyn ArmProbeInterface,
+    components: &[CoresightComponent],
+    config: &TraceConfiguration,
+) -> Result<(), Error> {
+    if let Some(control) = config.dwt_control {
+        let component = find_component(components, PeripheralType::Dwt)?;
+
+        for (unit, comparator) in config.dwt_comparators.iter().enumerate() {
+            // Disable the comparator first, so it does not fire with a partially restored
+            // configuration.
+            dwt::Function::from(0).store_unit(component, interface, unit)?;
+            dwt::Comp::from(comparator.comp).store_unit(component, interface, unit)?;
+            dwt::Mask::from(comparator.mask).store_unit(component, interface, unit)?;
+            dwt::Function::from(comparator.function).store_unit(component, interface, unit)?;
+        }
+
+        dwt::Ctrl::from(control).store(component, interface)?;
+    }
+
+    if let Some((trace_control, trace_enable)) = config.itm {
+        let mut itm = Itm::new(interface, find_component(components, PeripheralType::Itm)?);
+        itm.unlock()?;
+        itm.set_trace_control(trace_control)?;
+        itm.set_trace_enable(trace_enable)?;
+    }
+
+    if let Some(tpiu_config) = &config.tpiu {
+        let mut tpiu = Tpiu::new(interface, find_component(components, PeripheralType::Tpiu)?);
+        tpiu.set_port_size(tpiu_config.port_size)?;
+        tpiu.set_prescaler(tpiu_config.prescaler)?;
+        tpiu.set_pin_protocol(tpiu_config.pin_protocol)?;
+        tpiu.set_formatter(tpiu_config.formatter)?;
+    }
+
+    if let Some(swo_config) = &config.swo {
+        let mut swo = Swo::new(interface, find_component(components, PeripheralType::Swo)?);
+        swo.unlock()?;
+        swo.set_prescaler(swo_config.prescaler)?;
+        swo.set_pin_protocol(swo_config.pin_protocol)?;
+    }
+
+    Ok(())
+}
+
+/// Read the raw formatted frames from internal trace memory.
 ///
 /// # Note
 /// This function will read any available trace data in trace memory without blocking. At most,
@@ -226,23 +1023,69 @@ pub(crate) fn setup_tracing(
 /// filled while trace data is being extracted, this function can be called again to return that
 /// data.
 ///
+/// For cores with a high trace bandwidth, up to [`HIGH_BANDWIDTH_FIFO_READS`] times the size of
+/// the FIFO is read, as the FIFO is refilled while it is being read.
+///
+/// An ETB or ETR can't be read as a FIFO, so their whole buffer is read instead, see
+/// [`read_trace_buffer`]. The same applies to an ETF which is used as a circular buffer. The
+/// buffer of an ETR is read through the memory AP `system_memory_ap`, see
+/// [`TraceSink::SystemMemory`].
+///
 /// # Returns
-/// All data stored in trace memory, with an upper bound at the size of internal trace memory.
-pub(crate) fn read_trace_memory(
+/// A whole number of 16 byte formatter frames, with an upper bound at the size of internal trace
+/// memory, or a multiple of it for `high_bandwidth` cores.
+fn read_trace_memory_frames(
     interface: &mut dyn ArmProbeInterface,
     components: &[CoresightComponent],
+    high_bandwidth: bool,
+    system_memory_ap: Option<u8>,
 ) -> Result<Vec<u8>, Error> {
-    let mut tmc =
-        TraceMemoryController::new(interface, find_component(components, PeripheralType::Tmc)?);
+    let component = find_component(components, PeripheralType::Tmc)?;
 
-    let fifo_size = tmc.fifo_size()?;
+    let mut tmc = TraceMemoryController::new(interface, component);
+
+    match tmc.variant()? {
+        // An ETF which is used as a circular buffer is read like an ETB.
+        TmcVariant::Etf if !tmc.is_circular()? => {
+            read_trace_fifo(interface, component, high_bandwidth)
+        }
+        TmcVariant::Etr => match system_memory_ap {
+            Some(ap) => {
+                let memory_ap = MemoryAp::new(ApAddress {
+                    dp: component.ap.ap_address().dp,
+                    ap,
+                });
+                read_trace_buffer(interface, component, TmcVariant::Etr, memory_ap)
+            }
+            None => Err(Error::architecture_specific(
+                ComponentError::UnsupportedTmcVariant(TmcVariant::Etr),
+            )),
+        },
+        variant => read_trace_buffer(interface, component, variant, component.ap),
+    }
+}
+
+/// Read the formatted frames from the FIFO of an ETF, see [`read_trace_memory_frames`].
+fn read_trace_fifo(
+    interface: &mut dyn ArmProbeInterface,
+    component: &CoresightComponent,
+    high_bandwidth: bool,
+) -> Result<Vec<u8>, Error> {
+    let mut tmc = TraceMemoryController::new(interface, component);
+
+    let fifo_size = tmc.fifo_size()? as usize;
+    let max_size = if high_bandwidth {
+        fifo_size * HIGH_BANDWIDTH_FIFO_READS
+    } else {
+        fifo_size
+    };
 
     // This sequence is taken from "CoreSight Trace memory Controller Technical Reference Manual"
     // Section 2.2.2 "Software FIFO Mode". Without following this procedure, the trace data does
     // not properly stop even after disabling capture.
 
     // Read all of the data from the ETM into a vector for further processing.
-    let mut etf_trace: Vec<u8> = Vec::new();
+    let mut etf_trace: Vec<u8> = Vec::with_capacity(max_size);
     loop {
         match tmc.read()? {
             Some(data) => etf_trace.extend_from_slice(&data.to_le_bytes()),
@@ -259,32 +1102,226 @@ pub(crate) fn read_trace_memory(
         // maximum number of frames.
         let frame_boundary = (etf_trace.len() % 16) == 0;
 
-        if frame_boundary && etf_trace.len() >= fifo_size as usize {
+        if frame_boundary && etf_trace.len() >= max_size {
             break;
         }
     }
 
-    // The TMC formats data into frames, as it contains trace data from multiple data sources. We
-    // need to deserialize the frames and pull out only the data source of interest. For now, all
-    // we care about is the ITM data.
+    Ok(etf_trace)
+}
+
+/// Read the whole circular buffer of an ETB or ETR, starting with the oldest data.
+///
+/// The buffer can only be read while the capture is stopped, so the capture is stopped for the
+/// read, and restarted with an empty buffer afterwards. The system memory buffer of an ETR is
+/// read directly through `memory_ap`, which is much faster than reading it word by word through
+/// the TMC.
+fn read_trace_buffer(
+    interface: &mut dyn ArmProbeInterface,
+    component: &CoresightComponent,
+    variant: TmcVariant,
+    memory_ap: MemoryAp,
+) -> Result<Vec<u8>, Error> {
+    let mut tmc = TraceMemoryController::new(interface, component);
+
+    // Write out partially filled frames before stopping the capture.
+    tmc.manual_flush()?;
+    tmc.disable_capture()?;
+    while !tmc.ready()? {}
+
+    let size = tmc.fifo_size()?;
+    let base = match variant {
+        TmcVariant::Etr => tmc.system_buffer()?,
+        TmcVariant::Etb | TmcVariant::Etf => 0,
+    };
+    let write_pointer = tmc.write_pointer()?;
+
+    // Once the buffer wrapped around, the oldest data is at the write pointer.
+    let (start, len) = if tmc.full()? {
+        (write_pointer, size)
+    } else {
+        (base, write_pointer.wrapping_sub(base))
+    };
+    let len = (len - len % 16) as usize;
+
+    let trace = match variant {
+        TmcVariant::Etr => {
+            let mut trace = vec![0; len];
+            let (older, newer) = trace.split_at_mut(len.min((base + size - start) as usize));
+
+            let mut memory = interface.memory_interface(memory_ap)?;
+            memory.read(start as u64, older)?;
+            memory.read(base as u64, newer)?;
+            drop(memory);
+
+            trace
+        }
+        TmcVariant::Etb | TmcVariant::Etf => {
+            tmc.set_read_pointer(start)?;
+
+            let mut trace = Vec::with_capacity(len);
+            while trace.len() < len {
+                match tmc.read()? {
+                    Some(data) => trace.extend_from_slice(&data.to_le_bytes()),
+                    None => break,
+                }
+            }
+
+            trace
+        }
+    };
+
+    // Restart the capture with an empty buffer.
+    let mut tmc = TraceMemoryController::new(interface, component);
+    tmc.set_write_pointer(base)?;
+    tmc.set_read_pointer(base)?;
+    tmc.enable_capture()?;
+
+    Ok(trace)
+}
+
+/// Detects whether the TMC is an ETB, ETR or ETF, see [`TmcVariant`].
+///
+/// Expects to be given a list of all ROM table `components` as the second argument.
+pub(crate) fn trace_memory_variant(
+    interface: &mut dyn ArmProbeInterface,
+    components: &[CoresightComponent],
+) -> Result<TmcVariant, Error> {
+    TraceMemoryController::new(interface, find_component(components, PeripheralType::Tmc)?)
+        .variant()
+}
+
+/// Checks whether the TMC is full, i.e. whether trace data was lost at the sink.
+///
+/// This has to be checked before trace memory is read, as reading drains the FIFO.
+pub(crate) fn trace_memory_overflowed(
+    interface: &mut dyn ArmProbeInterface,
+    components: &[CoresightComponent],
+) -> Result<bool, Error> {
+    let mut tmc =
+        TraceMemoryController::new(interface, find_component(components, PeripheralType::Tmc)?);
+
+    let full = tmc.full()?;
+    if full {
+        tracing::warn!("The trace memory is full, trace data was lost at the TMC");
+    }
+
+    Ok(full)
+}
+
+/// Read all deformatted trace data from internal trace memory, without filtering by trace source.
+///
+/// Every byte is returned together with the ATID of the trace source it belongs to, in the order
+/// it was stored in trace memory. This includes bytes of the reserved ATID 0, which is useful
+/// for diagnosing the trace setup itself. For the same reason, data which doesn't look like
+/// formatted frames only results in a warning.
+///
+/// See [`read_trace_memory_frames`] for details on how much data is read.
+pub(crate) fn read_trace_memory_raw(
+    interface: &mut dyn ArmProbeInterface,
+    components: &[CoresightComponent],
+    high_bandwidth: bool,
+    system_memory_ap: Option<u8>,
+) -> Result<Vec<(u8, u8)>, Error> {
+    let etf_trace =
+        read_trace_memory_frames(interface, components, high_bandwidth, system_memory_ap)?;
+
+    if !tmc::is_plausibly_formatted(&etf_trace) {
+        tracing::warn!("The trace data does not look like formatted frames, is the formatter configured correctly?");
+    }
 
     let mut id = 0.into();
-    let mut itm_trace = Vec::new();
+    let mut trace: Vec<(u8, u8)> = Vec::with_capacity(etf_trace.len());
+
+    for frame_buffer in etf_trace.chunks_exact(16) {
+        let mut frame = tmc::Frame::new(frame_buffer, id);
+        trace.extend((&mut frame).map(|(id, data)| (id.into(), data)));
+        id = frame.id();
+    }
+
+    Ok(trace)
+}
+
+/// Read trace data from internal trace memory, separated by trace source.
+///
+/// The TMC formats data into frames, as it contains trace data from multiple data sources. The
+/// frames are deserialized and the data of every source is collected separately, keyed by the
+/// ATID of the source. The reserved ATID 0 is not included.
+///
+/// If the data doesn't look like formatted frames, e.g. because the formatter is bypassed,
+/// [`ComponentError::TraceFormatterMismatch`] is returned instead of garbage data.
+///
+/// See [`read_trace_memory_frames`] for details on how much data is read.
+pub(crate) fn read_trace_memory_sources(
+    interface: &mut dyn ArmProbeInterface,
+    components: &[CoresightComponent],
+    high_bandwidth: bool,
+    system_memory_ap: Option<u8>,
+) -> Result<BTreeMap<u8, Vec<u8>>, Error> {
+    let etf_trace =
+        read_trace_memory_frames(interface, components, high_bandwidth, system_memory_ap)?;
+
+    if !tmc::is_plausibly_formatted(&etf_trace) {
+        return Err(Error::architecture_specific(
+            ComponentError::TraceFormatterMismatch,
+        ));
+    }
+
+    let mut id = 0.into();
+    let mut sources: BTreeMap<u8, Vec<u8>> = BTreeMap::new();
 
     // Process each formatted frame and extract the multiplexed trace data.
     for frame_buffer in etf_trace.chunks_exact(16) {
         let mut frame = tmc::Frame::new(frame_buffer, id);
         for (id, data) in &mut frame {
             match id.into() {
-                // ITM ATID, see Itm::tx_enable()
-                13 => itm_trace.push(data),
                 0 => (),
-                id => tracing::warn!("Unexpected trace source ATID {id}: {data}, ignoring"),
+                id => sources.entry(id).or_default().push(data),
             }
         }
         id = frame.id();
     }
 
+    Ok(sources)
+}
+
+/// Read trace data from internal trace memory
+///
+/// # Args
+/// * `interface` - The interface with the debug probe.
+/// * `components` - The CoreSight debug components identified in the system.
+/// * `high_bandwidth` - Whether the traced core has a high trace bandwidth, see
+///   [`is_high_bandwidth_trace_core`].
+/// * `system_memory_ap` - The memory AP of [`TraceSink::SystemMemory`], if it is configured.
+///
+/// # Note
+/// This function will read any available trace data in trace memory without blocking. At most,
+/// this function will read as much data as can fit in the FIFO - if the FIFO continues to be
+/// filled while trace data is being extracted, this function can be called again to return that
+/// data.
+///
+/// # Returns
+/// All ITM data stored in trace memory, with an upper bound at the size of internal trace memory.
+pub(crate) fn read_trace_memory(
+    interface: &mut dyn ArmProbeInterface,
+    components: &[CoresightComponent],
+    high_bandwidth: bool,
+    system_memory_ap: Option<u8>,
+) -> Result<Vec<u8>, Error> {
+    // For now, all we care about is the ITM data.
+    let mut sources =
+        read_trace_memory_sources(interface, components, high_bandwidth, system_memory_ap)?;
+
+    // ITM ATID, see Itm::tx_enable()
+    let itm_trace = sources.remove(&13).unwrap_or_default();
+
+    for (id, data) in sources {
+        tracing::warn!(
+            "Unexpected trace source ATID {id}: {} bytes, ignoring",
+            data.len()
+        );
+    }
+
     Ok(itm_trace)
 }
 
@@ -315,6 +1352,106 @@ pub fn remove_swv_data_trace(
     dwt.disable_data_trace(unit)
 }
 
+/// Enables only the ITM stimulus ports set in `mask`, and sets the privilege `privilege_mask`
+/// for the groups of 8 ports, see [`Itm::set_privilege`].
+///
+/// Expects to be given a list of all ROM table `components` as the second argument.
+pub(crate) fn configure_itm_ports(
+    interface: &mut dyn ArmProbeInterface,
+    components: &[CoresightComponent],
+    mask: u32,
+    privilege_mask: u8,
+) -> Result<(), Error> {
+    let mut itm = Itm::new(interface, find_component(components, PeripheralType::Itm)?);
+    itm.set_privilege(privilege_mask)?;
+    itm.enable_ports(mask)
+}
+
+/// Enables periodic DWT PC samples at the given rate.
+///
+/// Expects to be given a list of all ROM table `components` as the second argument.
+pub(crate) fn enable_pc_sampling(
+    interface: &mut dyn ArmProbeInterface,
+    components: &[CoresightComponent],
+    rate: PcSampleRate,
+) -> Result<(), Error> {
+    Dwt::new(interface, find_component(components, PeripheralType::Dwt)?).enable_pc_sampling(rate)
+}
+
+/// Disables periodic DWT PC samples.
+///
+/// Expects to be given a list of all ROM table `components` as the second argument.
+pub(crate) fn disable_pc_sampling(
+    interface: &mut dyn ArmProbeInterface,
+    components: &[CoresightComponent],
+) -> Result<(), Error> {
+    Dwt::new(interface, find_component(components, PeripheralType::Dwt)?).disable_pc_sampling()
+}
+
+/// Enables the DWT cycle counter and resets it to zero.
+///
+/// Expects to be given a list of all ROM table `components` as the second argument.
+pub(crate) fn enable_cycle_counter(
+    interface: &mut dyn ArmProbeInterface,
+    components: &[CoresightComponent],
+) -> Result<(), Error> {
+    let mut dwt = Dwt::new(interface, find_component(components, PeripheralType::Dwt)?);
+    dwt.reset_cycle_counter()?;
+    dwt.enable_cycle_counter()
+}
+
+/// Reads the DWT cycle counter.
+///
+/// Expects to be given a list of all ROM table `components` as the second argument.
+pub(crate) fn cycle_count(
+    interface: &mut dyn ArmProbeInterface,
+    components: &[CoresightComponent],
+) -> Result<u32, Error> {
+    Dwt::new(interface, find_component(components, PeripheralType::Dwt)?).cycle_count()
+}
+
+/// Resets the DWT cycle counter to zero.
+///
+/// Expects to be given a list of all ROM table `components` as the second argument.
+pub(crate) fn reset_cycle_counter(
+    interface: &mut dyn ArmProbeInterface,
+    components: &[CoresightComponent],
+) -> Result<(), Error> {
+    Dwt::new(interface, find_component(components, PeripheralType::Dwt)?).reset_cycle_counter()
+}
+
+/// Details about the access which triggered a watchpoint, see
+/// [`Session::read_watchpoint_hit`](crate::Session::read_watchpoint_hit).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WatchpointHit {
+    /// The DWT comparators which matched, usually exactly one.
+    pub comparators: Vec<DwtComparatorMatch>,
+    /// The value of the implementation defined Auxiliary Fault Status Register (AFSR).
+    ///
+    /// This is `None` for ARMv6-M cores, which do not implement it.
+    pub auxiliary_fault_status: Option<u32>,
+}
+
+/// Reads the DWT comparators which matched since they were last read.
+///
+/// Expects to be given a list of all ROM table `components` as the second argument.
+pub(crate) fn read_dwt_matches(
+    interface: &mut dyn ArmProbeInterface,
+    components: &[CoresightComponent],
+) -> Result<Vec<DwtComparatorMatch>, Error> {
+    let mut dwt = Dwt::new(interface, find_component(components, PeripheralType::Dwt)?);
+    dwt.matched_comparators()
+}
+
+/// Reads the Auxiliary Fault Status Register, if the core implements it.
+pub(crate) fn read_auxiliary_fault_status(core: &mut Core) -> Result<Option<u32>, Error> {
+    if core.core_type() == CoreType::Armv6m {
+        return Ok(None);
+    }
+
+    Ok(Some(core.read_word_32(Afsr::ADDRESS)?))
+}
+
 /// Sets TRCENA in DEMCR to begin trace generation.
 pub fn enable_tracing(core: &mut Core) -> Result<(), Error> {
     let mut demcr = Demcr(core.read_word_32(Demcr::ADDRESS)?);
@@ -323,6 +1460,12 @@ pub fn enable_tracing(core: &mut Core) -> Result<(), Error> {
     Ok(())
 }
 
+/// Returns `true` if TRCENA is set in DEMCR, which enables the clock of the DWT and ITM.
+pub(crate) fn is_tracing_enabled(core: &mut Core) -> Result<bool, Error> {
+    let demcr = Demcr(core.read_word_32(Demcr::ADDRESS)?);
+    Ok(demcr.dwtena())
+}
+
 /// Disables TRCENA in DEMCR to disable trace generation.
 pub fn disable_swv(core: &mut Core) -> Result<(), Error> {
     let mut demcr = Demcr(core.read_word_32(Demcr::ADDRESS)?);
@@ -330,3 +1473,47 @@ pub fn disable_swv(core: &mut Core) -> Result<(), Error> {
     core.write_word_32(Demcr::ADDRESS, demcr.into())?;
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::{stimulus_port_check, swo_prescaler, MAX_SWO_PRESCALER};
+    use crate::architecture::arm::SwoConfig;
+
+    #[test]
+    fn stimulus_port_check_uses_configured_ports() {
+        assert!(stimulus_port_check(u32::MAX, u32::MAX).matches());
+        assert!(stimulus_port_check(0x8000_0001, 0x8000_0001).matches());
+    }
+
+    #[test]
+    fn stimulus_port_check_detects_wrong_ports() {
+        // A disabled port which should be enabled.
+        assert!(!stimulus_port_check(0x8000_0001, 0x0000_0001).matches());
+        // An enabled port which should be disabled.
+        assert!(!stimulus_port_check(0x0000_0001, u32::MAX).matches());
+    }
+
+    #[test]
+    fn swo_prescaler_is_rounded() {
+        let prescaler = |tpiu_clk, baud| swo_prescaler(&SwoConfig::new(tpiu_clk).set_baud(baud));
+
+        assert_eq!(prescaler(216_000_000, 2_000_000).unwrap(), 107);
+        // The divisor of 66.7 is rounded to 67, instead of being truncated to 66.
+        assert_eq!(prescaler(200_000_000, 3_000_000).unwrap(), 66);
+        assert_eq!(prescaler(1_000_000, 1_000_000).unwrap(), 0);
+    }
+
+    #[test]
+    fn swo_prescaler_rejects_unsupported_baud_rates() {
+        let prescaler = |tpiu_clk, baud| swo_prescaler(&SwoConfig::new(tpiu_clk).set_baud(baud));
+
+        assert!(prescaler(64_000_000, 0).is_err());
+        assert!(prescaler(1_000_000, 2_000_000).is_err());
+
+        assert_eq!(
+            prescaler((MAX_SWO_PRESCALER + 1) * 1000, 1000).unwrap(),
+            MAX_SWO_PRESCALER
+        );
+        assert!(prescaler((MAX_SWO_PRESCALER + 2) * 1000, 1000).is_err());
+    }
+}
@@ -58,4 +58,16 @@ impl<'a> Swo<'a> {
             .write_reg(self.interface, REGISTER_OFFSET_SWO_SPPR, value)?;
         Ok(())
     }
+
+    /// Read the prescaler of the SWO.
+    pub fn prescaler(&mut self) -> Result<u32, Error> {
+        self.component
+            .read_reg(self.interface, REGISTER_OFFSET_SWO_CODR)
+    }
+
+    /// Read the pin protocol of the SWO.
+    pub fn pin_protocol(&mut self) -> Result<u32, Error> {
+        self.component
+            .read_reg(self.interface, REGISTER_OFFSET_SWO_SPPR)
+    }
 }
@@ -5,7 +5,11 @@
 use core::iter::Iterator;
 
 use crate::{
-    architecture::arm::{component::DebugRegister, memory::CoresightComponent, ArmProbeInterface},
+    architecture::arm::{
+        component::{ComponentError, DebugRegister},
+        memory::CoresightComponent,
+        ArmProbeInterface,
+    },
     Error,
 };
 
@@ -13,8 +17,14 @@ use bitfield::bitfield;
 
 const REGISTER_OFFSET_RSZ: u32 = 0x04;
 const REGISTER_OFFSET_RRD: u32 = 0x10;
+const REGISTER_OFFSET_RRP: u32 = 0x14;
+const REGISTER_OFFSET_RWP: u32 = 0x18;
+const REGISTER_OFFSET_TRG: u32 = 0x1C;
 const REGISTER_OFFSET_CTL: u32 = 0x20;
 const REGISTER_OFFSET_CBUFLVL: u32 = 0x30;
+const REGISTER_OFFSET_AXICTL: u32 = 0x110;
+const REGISTER_OFFSET_DBALO: u32 = 0x118;
+const REGISTER_OFFSET_DBAHI: u32 = 0x11C;
 
 #[repr(u8)]
 pub enum Mode {
@@ -32,6 +42,21 @@ pub enum Mode {
     Hardware = 0b10,
 }
 
+/// The configuration of a TMC, which determines how trace data is stored and read out.
+///
+/// See "CoreSight Trace Memory Controller Technical Reference Manual", chapter 1.3 for details.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TmcVariant {
+    /// Embedded Trace Buffer: Trace data is captured into dedicated trace RAM, which can only be
+    /// used as a circular buffer. The buffer can only be read while the capture is stopped.
+    Etb,
+    /// Embedded Trace Router: Trace data is written into a buffer in system memory.
+    Etr,
+    /// Embedded Trace FIFO: Trace data is captured into dedicated trace RAM, which can also be
+    /// used as a FIFO that is read while the capture is running.
+    Etf,
+}
+
 /// The embedded trace memory controller.
 pub struct TraceMemoryController<'a> {
     component: &'a CoresightComponent,
@@ -50,6 +75,20 @@ impl<'a> TraceMemoryController<'a> {
         }
     }
 
+    /// Detect the configuration of the TMC from its `DEVID` register.
+    pub fn variant(&mut self) -> Result<TmcVariant, Error> {
+        let devid = DeviceId::load(self.component, self.interface)?;
+
+        match devid.configtype() {
+            0b00 => Ok(TmcVariant::Etb),
+            0b01 => Ok(TmcVariant::Etr),
+            0b10 => Ok(TmcVariant::Etf),
+            configtype => Err(Error::architecture_specific(
+                ComponentError::UnknownTmcConfiguration(configtype),
+            )),
+        }
+    }
+
     /// Configure the FIFO operational mode.
     ///
     /// # Args
@@ -61,6 +100,12 @@ impl<'a> TraceMemoryController<'a> {
         Ok(())
     }
 
+    /// Check if the trace memory is used as a circular buffer, see [`Mode::Circular`].
+    pub fn is_circular(&mut self) -> Result<bool, Error> {
+        let mode_reg = EtfMode::load(self.component, self.interface)?;
+        Ok(mode_reg.mode() == Mode::Circular as u8)
+    }
+
     /// Enable trace captures using the FIFO.
     pub fn enable_capture(&mut self) -> Result<(), Error> {
         self.component
@@ -147,6 +192,33 @@ impl<'a> TraceMemoryController<'a> {
         Ok(())
     }
 
+    /// Set the trigger counter, which is the number of 32 bit words captured after a trigger event
+    /// until the trigger is indicated in the trace data, and the capture stops if configured with
+    /// [`TraceMemoryController::stop_on_trigger`].
+    ///
+    /// This is only allowed while the capture is disabled.
+    pub fn set_trigger_counter(&mut self, count: u32) -> Result<(), Error> {
+        self.component
+            .write_reg(self.interface, REGISTER_OFFSET_TRG, count)
+    }
+
+    /// Configure the capture to stop after a trigger event.
+    ///
+    /// # Args
+    /// * `post_count` - The number of 32 bit words which are captured after the trigger, before
+    ///   the capture stops.
+    pub fn stop_on_trigger(&mut self, post_count: u32) -> Result<(), Error> {
+        self.set_trigger_counter(post_count)?;
+
+        let mut ffcr = FormatFlushControl::load(self.component, self.interface)?;
+        ffcr.set_enft(true);
+        ffcr.set_enti(true);
+        ffcr.set_trgontrgev(true);
+        ffcr.set_stpontrgev(true);
+        ffcr.store(self.component, self.interface)?;
+        Ok(())
+    }
+
     /// Get the size of the FIFO in bytes.
     pub fn fifo_size(&mut self) -> Result<u32, Error> {
         let size_words = self
@@ -154,6 +226,82 @@ impl<'a> TraceMemoryController<'a> {
             .read_reg(self.interface, REGISTER_OFFSET_RSZ)?;
         Ok(size_words * core::mem::size_of::<u32>() as u32)
     }
+
+    /// Configure the trace buffer in system memory, which is only supported by an ETR.
+    ///
+    /// The read and write pointers are reset to the start of the buffer.
+    ///
+    /// # Args
+    /// * `address` - The address of the buffer, which has to be aligned to 4 kB.
+    /// * `size` - The size of the buffer in bytes.
+    pub fn set_system_buffer(&mut self, address: u32, size: u32) -> Result<(), Error> {
+        self.component
+            .write_reg(self.interface, REGISTER_OFFSET_DBALO, address)?;
+        self.component
+            .write_reg(self.interface, REGISTER_OFFSET_DBAHI, 0)?;
+        self.component.write_reg(
+            self.interface,
+            REGISTER_OFFSET_RSZ,
+            size / core::mem::size_of::<u32>() as u32,
+        )?;
+        self.set_write_pointer(address)?;
+        self.set_read_pointer(address)
+    }
+
+    /// Configure the AXI master interface of an ETR to write the trace data into a contiguous
+    /// buffer, using bursts of up to 16 transfers.
+    ///
+    /// The protection and cache control settings are not changed.
+    pub fn configure_axi_writes(&mut self) -> Result<(), Error> {
+        let mut axictl = self
+            .component
+            .read_reg(self.interface, REGISTER_OFFSET_AXICTL)?;
+
+        // Clear ScatterGatherMode and set WrBurstLen to 16 transfers.
+        axictl &= !(1 << 7 | 0xF << 8);
+        axictl |= 0xF << 8;
+
+        self.component
+            .write_reg(self.interface, REGISTER_OFFSET_AXICTL, axictl)
+    }
+
+    /// Enable the formatter, which inserts the trace source IDs into the stored trace data.
+    ///
+    /// This is required to separate the data of multiple trace sources, e.g. the ITM and the ETM.
+    pub fn enable_formatter(&mut self) -> Result<(), Error> {
+        let mut ffcr = FormatFlushControl::load(self.component, self.interface)?;
+        ffcr.set_enft(true);
+        ffcr.set_enti(true);
+        ffcr.store(self.component, self.interface)?;
+        Ok(())
+    }
+
+    /// Get the address of the trace buffer in system memory of an ETR.
+    pub fn system_buffer(&mut self) -> Result<u32, Error> {
+        self.component
+            .read_reg(self.interface, REGISTER_OFFSET_DBALO)
+    }
+
+    /// Get the write pointer, where the next trace data is stored.
+    ///
+    /// For an ETB or ETF, this is an offset into trace RAM. For an ETR, this is an address in
+    /// system memory.
+    pub fn write_pointer(&mut self) -> Result<u32, Error> {
+        self.component.read_reg(self.interface, REGISTER_OFFSET_RWP)
+    }
+
+    /// Set the write pointer. This is only allowed while the capture is disabled.
+    pub fn set_write_pointer(&mut self, pointer: u32) -> Result<(), Error> {
+        self.component
+            .write_reg(self.interface, REGISTER_OFFSET_RWP, pointer)
+    }
+
+    /// Set the read pointer, where [`TraceMemoryController::read`] reads the next trace data.
+    /// This is only allowed while the capture is disabled.
+    pub fn set_read_pointer(&mut self, pointer: u32) -> Result<(), Error> {
+        self.component
+            .write_reg(self.interface, REGISTER_OFFSET_RRP, pointer)
+    }
 }
 
 bitfield! {
@@ -245,6 +393,32 @@ impl DebugRegister for EtfMode {
     const NAME: &'static str = "ETF_MODE";
 }
 
+bitfield! {
+    #[derive(Clone, Default)]
+    pub struct DeviceId(u32);
+    impl Debug;
+
+    pub u8, memwidth, _: 10, 8;
+    pub u8, configtype, _: 7, 6;
+}
+
+impl From<u32> for DeviceId {
+    fn from(raw: u32) -> DeviceId {
+        DeviceId(raw)
+    }
+}
+
+impl From<DeviceId> for u32 {
+    fn from(devid: DeviceId) -> u32 {
+        devid.0
+    }
+}
+
+impl DebugRegister for DeviceId {
+    const ADDRESS: u32 = 0xFC8;
+    const NAME: &'static str = "TMC_DEVID";
+}
+
 /// Trace ID (a.k.a. ATID or trace source ID)
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Id(u8);
@@ -320,3 +494,83 @@ impl<'a> Iterator for &mut Frame<'a> {
         ret
     }
 }
+
+/// The highest ATID which can be assigned to a trace source. The IDs above are reserved, except
+/// for [`TRIGGER_ID`].
+const MAX_SOURCE_ID: u8 = 0x6F;
+/// The ATID which marks a trigger event in the trace stream.
+const TRIGGER_ID: u8 = 0x7D;
+/// The number of different trace sources above which the data is considered garbage. This is
+/// plenty for the systems with internal trace memory, which have a few cores with an ITM and ETM.
+const MAX_PLAUSIBLE_SOURCES: usize = 8;
+
+/// Checks whether the given data plausibly consists of formatted frames.
+///
+/// If the formatter and the deformatting of the data don't match, e.g. because the formatter is
+/// bypassed, the frames are deserialized as garbage without any error. This is detected with a
+/// heuristic: The ID bytes of garbage frames regularly contain reserved ATIDs, and the deformatted
+/// data seems to originate from a large number of different trace sources.
+pub fn is_plausibly_formatted(frames: &[u8]) -> bool {
+    let mut sources = [false; MAX_SOURCE_ID as usize + 1];
+    let mut invalid_frames = 0;
+    let mut frame_count = 0;
+
+    for frame in frames.chunks_exact(16) {
+        frame_count += 1;
+
+        // Even bytes with a set LSbit contain an ID.
+        let ids = frame[..15]
+            .iter()
+            .step_by(2)
+            .filter(|byte| *byte & 1 != 0)
+            .map(|byte| byte >> 1);
+
+        let mut invalid = false;
+        for id in ids {
+            match id {
+                0..=MAX_SOURCE_ID => sources[id as usize] = true,
+                TRIGGER_ID => (),
+                _ => invalid = true,
+            }
+        }
+
+        if invalid {
+            invalid_frames += 1;
+        }
+    }
+
+    // The null ID 0 is used for padding, and is not a trace source.
+    let source_count = sources[1..].iter().filter(|source| **source).count();
+
+    invalid_frames * 8 <= frame_count && source_count <= MAX_PLAUSIBLE_SOURCES
+}
+
+#[cfg(test)]
+mod test {
+    use super::is_plausibly_formatted;
+
+    #[test]
+    fn formatted_frames_are_plausible() {
+        // A frame with data of ATID 13, followed by a padding frame of the null ID.
+        let mut frames = vec![0x1B, 0x48, 0x00, 0x65, 0x6C, 0x6C, 0x6E, 0x20];
+        frames.extend([0x76, 0x6F, 0x72, 0x6C, 0x64, 0x21, 0x0A, 0x00]);
+        frames.extend([0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        frames.extend([0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+
+        assert!(is_plausibly_formatted(&frames));
+    }
+
+    #[test]
+    fn unformatted_itm_data_is_detected() {
+        // ITM stimulus port 0 packets with two bytes of ASCII text each, as written without the
+        // formatter.
+        let text = b"The quick brown fox jumps over the lazy dog, 0123456789 times!!";
+        let mut frames: Vec<u8> = text
+            .chunks_exact(2)
+            .flat_map(|chunk| [0x02, chunk[0], chunk[1]])
+            .collect();
+        frames.truncate(frames.len() / 16 * 16);
+
+        assert!(!is_plausibly_formatted(&frames));
+    }
+}
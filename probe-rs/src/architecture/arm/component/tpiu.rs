@@ -4,7 +4,7 @@ use crate::Error;
 
 pub const _TPIU_PID: [u8; 8] = [0xA1, 0xB9, 0x0B, 0x0, 0x4, 0x0, 0x0, 0x0];
 
-const _REGISTER_OFFSET_TPIU_SSPSR: u32 = 0x0;
+const REGISTER_OFFSET_TPIU_SSPSR: u32 = 0x0;
 const REGISTER_OFFSET_TPIU_CSPSR: u32 = 0x4;
 const REGISTER_OFFSET_TPIU_ACPR: u32 = 0x10;
 const REGISTER_OFFSET_TPIU_SPPR: u32 = 0xF0;
@@ -61,4 +61,35 @@ impl<'a> Tpiu<'a> {
             .write_reg(self.interface, REGISTER_OFFSET_TPIU_FFCR, value)?;
         Ok(())
     }
+
+    /// Read the port sizes supported by the TPIU, with bit `N - 1` set if a port width of `N`
+    /// bits is supported.
+    pub fn supported_port_sizes(&mut self) -> Result<u32, Error> {
+        self.component
+            .read_reg(self.interface, REGISTER_OFFSET_TPIU_SSPSR)
+    }
+
+    /// Read the port size of the TPIU.
+    pub fn port_size(&mut self) -> Result<u32, Error> {
+        self.component
+            .read_reg(self.interface, REGISTER_OFFSET_TPIU_CSPSR)
+    }
+
+    /// Read the prescaler of the TPIU.
+    pub fn prescaler(&mut self) -> Result<u32, Error> {
+        self.component
+            .read_reg(self.interface, REGISTER_OFFSET_TPIU_ACPR)
+    }
+
+    /// Read the TPIU protocol, see [`Tpiu::set_pin_protocol`] for the possible values.
+    pub fn pin_protocol(&mut self) -> Result<u32, Error> {
+        self.component
+            .read_reg(self.interface, REGISTER_OFFSET_TPIU_SPPR)
+    }
+
+    /// Read the TPIU formatter.
+    pub fn formatter(&mut self) -> Result<u32, Error> {
+        self.component
+            .read_reg(self.interface, REGISTER_OFFSET_TPIU_FFCR)
+    }
 }
@@ -0,0 +1,270 @@
+//! Export of captured trace data for external trace decoders.
+//!
+//! probe-rs only decodes ITM data itself. To allow analysis of other trace sources, e.g. ETM
+//! instruction trace with tools like `ptm2human` or Trace Compass, the raw byte stream of every
+//! trace source can be exported in the container format described below.
+//!
+//! # Container format
+//!
+//! All multi-byte values are stored in little endian byte order.
+//!
+//! | Offset | Size | Description                                                       |
+//! |--------|------|-------------------------------------------------------------------|
+//! | 0      | 8    | Magic value, the ASCII string `PRSTRACE`                          |
+//! | 8      | 1    | Format version, currently `1`                                     |
+//! | 9      | 1    | Core type, see below                                              |
+//! | 10     | 2    | Number of streams                                                 |
+//! | 12     | 4    | Trace clock frequency in Hz, `0` if unknown                       |
+//!
+//! The header is followed by the streams, each of which starts with a stream header:
+//!
+//! | Offset | Size | Description                                                       |
+//! |--------|------|-------------------------------------------------------------------|
+//! | 0      | 1    | Trace source ID (ATID) of the stream                              |
+//! | 1      | 3    | Reserved, always zero                                             |
+//! | 4      | 4    | Length `N` of the stream data in bytes                            |
+//! | 8      | `N`  | Raw deformatted trace bytes of the source                         |
+//!
+//! The core type is encoded as `0` for ARMv6-M, `1` for ARMv7-A, `2` for ARMv7-M, `3` for
+//! ARMv7E-M, `4` for ARMv8-A, `5` for ARMv8-M and `6` for RISC-V.
+
+use crate::CoreType;
+use std::collections::BTreeMap;
+use std::io::{self, Read, Write};
+
+const MAGIC: [u8; 8] = *b"PRSTRACE";
+const VERSION: u8 = 1;
+
+/// Raw trace data captured from trace memory, separated by trace source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceCapture {
+    core_type: CoreType,
+    trace_clock: Option<u32>,
+    streams: BTreeMap<u8, Vec<u8>>,
+}
+
+impl TraceCapture {
+    /// Create a new capture from the deformatted byte streams of the trace sources, keyed by ATID.
+    pub fn new(
+        core_type: CoreType,
+        trace_clock: Option<u32>,
+        streams: BTreeMap<u8, Vec<u8>>,
+    ) -> Self {
+        Self {
+            core_type,
+            trace_clock,
+            streams,
+        }
+    }
+
+    /// The type of the core which generated the trace data.
+    pub fn core_type(&self) -> CoreType {
+        self.core_type
+    }
+
+    /// The frequency of the trace clock in Hz, if known.
+    pub fn trace_clock(&self) -> Option<u32> {
+        self.trace_clock
+    }
+
+    /// All captured streams, keyed by the ATID of their trace source.
+    pub fn streams(&self) -> &BTreeMap<u8, Vec<u8>> {
+        &self.streams
+    }
+
+    /// The raw byte stream of the trace source with the given ATID, if any data was captured.
+    pub fn stream(&self, atid: u8) -> Option<&[u8]> {
+        self.streams.get(&atid).map(|data| data.as_slice())
+    }
+
+    /// Write the capture in the container format described in the [module documentation](self).
+    pub fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        let stream_count = u16::try_from(self.streams.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "Too many trace streams"))?;
+
+        writer.write_all(&MAGIC)?;
+        writer.write_all(&[VERSION, encode_core_type(self.core_type)])?;
+        writer.write_all(&stream_count.to_le_bytes())?;
+        writer.write_all(&self.trace_clock.unwrap_or(0).to_le_bytes())?;
+
+        for (atid, data) in &self.streams {
+            let length = u32::try_from(data.len()).map_err(|_| {
+                io::Error::new(io::ErrorKind::InvalidInput, "Trace stream is too large")
+            })?;
+
+            writer.write_all(&[*atid, 0, 0, 0])?;
+            writer.write_all(&length.to_le_bytes())?;
+            writer.write_all(data)?;
+        }
+
+        Ok(())
+    }
+
+    /// Read a capture in the container format described in the [module documentation](self).
+    pub fn read_from(reader: &mut impl Read) -> io::Result<Self> {
+        let mut header = [0u8; 16];
+        reader.read_exact(&mut header)?;
+
+        if header[..8] != MAGIC {
+            return Err(invalid_data("Not a probe-rs trace capture"));
+        }
+
+        if header[8] != VERSION {
+            return Err(invalid_data("Unsupported trace capture version"));
+        }
+
+        let core_type = decode_core_type(header[9])
+            .ok_or_else(|| invalid_data("Unknown core type in trace capture"))?;
+        let stream_count = u16::from_le_bytes([header[10], header[11]]);
+        let trace_clock = match u32::from_le_bytes([header[12], header[13], header[14], header[15]])
+        {
+            0 => None,
+            clock => Some(clock),
+        };
+
+        let mut streams = BTreeMap::new();
+
+        for _ in 0..stream_count {
+            let mut stream_header = [0u8; 8];
+            reader.read_exact(&mut stream_header)?;
+
+            let length = u32::from_le_bytes([
+                stream_header[4],
+                stream_header[5],
+                stream_header[6],
+                stream_header[7],
+            ]);
+
+            // Don't allocate the length given in the header up front, it can't be trusted.
+            let mut data = Vec::new();
+            reader.by_ref().take(length as u64).read_to_end(&mut data)?;
+
+            if data.len() != length as usize {
+                return Err(invalid_data("Truncated stream in trace capture"));
+            }
+
+            if streams.insert(stream_header[0], data).is_some() {
+                return Err(invalid_data("Repeated trace source ID in trace capture"));
+            }
+        }
+
+        Ok(Self {
+            core_type,
+            trace_clock,
+            streams,
+        })
+    }
+}
+
+fn invalid_data(message: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn encode_core_type(core_type: CoreType) -> u8 {
+    match core_type {
+        CoreType::Armv6m => 0,
+        CoreType::Armv7a => 1,
+        CoreType::Armv7m => 2,
+        CoreType::Armv7em => 3,
+        CoreType::Armv8a => 4,
+        CoreType::Armv8m => 5,
+        CoreType::Riscv => 6,
+    }
+}
+
+fn decode_core_type(value: u8) -> Option<CoreType> {
+    Some(match value {
+        0 => CoreType::Armv6m,
+        1 => CoreType::Armv7a,
+        2 => CoreType::Armv7m,
+        3 => CoreType::Armv7em,
+        4 => CoreType::Armv8a,
+        5 => CoreType::Armv8m,
+        6 => CoreType::Riscv,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::TraceCapture;
+    use crate::CoreType;
+    use std::collections::BTreeMap;
+    use std::io;
+
+    #[test]
+    fn capture_round_trip() {
+        let mut streams = BTreeMap::new();
+        streams.insert(1, vec![0x00, 0x00, 0x00, 0x80, 0x08]);
+        streams.insert(13, vec![0x01, 0x41]);
+
+        let capture = TraceCapture::new(CoreType::Armv7em, Some(64_000_000), streams);
+
+        let mut buffer = Vec::new();
+        capture.write_to(&mut buffer).unwrap();
+
+        assert_eq!(&buffer[..8], b"PRSTRACE");
+        assert_eq!(buffer.len(), 16 + 8 + 5 + 8 + 2);
+
+        let parsed = TraceCapture::read_from(&mut buffer.as_slice()).unwrap();
+
+        assert_eq!(parsed, capture);
+        assert_eq!(parsed.stream(13), Some(&[0x01, 0x41][..]));
+        assert_eq!(parsed.stream(2), None);
+    }
+
+    #[test]
+    fn unknown_clock_is_none() {
+        let capture = TraceCapture::new(CoreType::Armv8m, None, BTreeMap::new());
+
+        let mut buffer = Vec::new();
+        capture.write_to(&mut buffer).unwrap();
+
+        let parsed = TraceCapture::read_from(&mut buffer.as_slice()).unwrap();
+
+        assert_eq!(parsed.trace_clock(), None);
+        assert!(parsed.streams().is_empty());
+    }
+
+    #[test]
+    fn reject_bad_magic() {
+        let buffer = [0u8; 16];
+
+        assert!(TraceCapture::read_from(&mut buffer.as_slice()).is_err());
+    }
+
+    #[test]
+    fn reject_truncated_stream() {
+        let mut streams = BTreeMap::new();
+        streams.insert(1, vec![0x00, 0x00, 0x00, 0x80, 0x08]);
+
+        let mut buffer = Vec::new();
+        TraceCapture::new(CoreType::Armv7em, None, streams)
+            .write_to(&mut buffer)
+            .unwrap();
+
+        // A stream length of 4 GiB, with only 5 bytes of data.
+        buffer[20..24].copy_from_slice(&u32::MAX.to_le_bytes());
+
+        let error = TraceCapture::read_from(&mut buffer.as_slice()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn reject_repeated_source_id() {
+        let mut streams = BTreeMap::new();
+        streams.insert(1, vec![0x01]);
+        streams.insert(2, vec![0x02]);
+
+        let mut buffer = Vec::new();
+        TraceCapture::new(CoreType::Armv7em, None, streams)
+            .write_to(&mut buffer)
+            .unwrap();
+
+        // Change the ATID of the second stream to the one of the first stream.
+        buffer[16 + 8 + 1] = 1;
+
+        let error = TraceCapture::read_from(&mut buffer.as_slice()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
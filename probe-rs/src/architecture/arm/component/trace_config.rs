@@ -0,0 +1,124 @@
+//! A serializable description of a trace capture setup.
+//!
+//! A [`TraceConfig`] describes which trace data is captured and where it is sent. It can be saved
+//! to a YAML file, so the same capture can be set up again later with
+//! [`Session::setup_tracing_from_config`](crate::Session::setup_tracing_from_config).
+
+use super::{find_component, Itm, PcSampleRate, TraceSink};
+use crate::architecture::arm::memory::romtable::{CoresightComponent, PeripheralType};
+use crate::architecture::arm::ArmProbeInterface;
+use crate::Error;
+
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::path::Path;
+
+/// `ITM_TCR.TSENA`, enables local timestamps.
+const TCR_TSENA: u32 = 1 << 1;
+/// Mask of `ITM_TCR.GTSFREQ`, the global timestamp frequency.
+const TCR_GTSFREQ_MASK: u32 = 0b11 << 10;
+/// `ITM_TCR.GTSFREQ` value to generate a global timestamp every 8192 cycles.
+const TCR_GTSFREQ_8192: u32 = 0b10 << 10;
+
+/// An error when loading or saving a [`TraceConfig`] occurred.
+#[derive(thiserror::Error, Debug)]
+pub enum TraceConfigError {
+    /// The configuration file could not be read or written.
+    #[error("An IO error was encountered")]
+    Io(#[from] std::io::Error),
+    /// The configuration file could not be (de)serialized.
+    #[error("Serializing the trace configuration as yaml encountered an error")]
+    Yaml(#[from] serde_yaml::Error),
+}
+
+/// A DWT data trace of a memory address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DataTraceConfig {
+    /// The DWT comparator used for the trace.
+    pub unit: usize,
+    /// The traced address.
+    pub address: u32,
+}
+
+/// The configuration of a trace capture.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TraceConfig {
+    /// The destination of the trace data.
+    pub sink: TraceSink,
+    /// The enabled ITM stimulus ports, with one bit per port.
+    #[serde(default = "all_stimulus_ports")]
+    pub stimulus_ports: u32,
+    /// The addresses traced by the DWT.
+    #[serde(default)]
+    pub data_traces: Vec<DataTraceConfig>,
+    /// Whether the ITM generates local timestamps.
+    #[serde(default = "enabled")]
+    pub local_timestamps: bool,
+    /// Whether the ITM generates global timestamps, every 8192 cycles.
+    #[serde(default = "enabled")]
+    pub global_timestamps: bool,
+    /// The rate of periodic PC samples, or `None` if PC sampling is disabled.
+    #[serde(default)]
+    pub pc_sampling: Option<PcSampleRate>,
+}
+
+fn all_stimulus_ports() -> u32 {
+    u32::MAX
+}
+
+fn enabled() -> bool {
+    true
+}
+
+impl TraceConfig {
+    /// Create a configuration for the given sink, with all stimulus ports and timestamps enabled,
+    /// and without data traces or PC sampling.
+    ///
+    /// This is the same configuration as set up by [`Session::setup_tracing`](crate::Session::setup_tracing).
+    pub fn new(sink: TraceSink) -> Self {
+        Self {
+            sink,
+            stimulus_ports: all_stimulus_ports(),
+            data_traces: Vec::new(),
+            local_timestamps: true,
+            global_timestamps: true,
+            pc_sampling: None,
+        }
+    }
+
+    /// Load a configuration from a YAML file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, TraceConfigError> {
+        let file = File::open(path)?;
+        Ok(serde_yaml::from_reader(file)?)
+    }
+
+    /// Save the configuration to a YAML file.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), TraceConfigError> {
+        let file = File::create(path)?;
+        Ok(serde_yaml::to_writer(file, self)?)
+    }
+}
+
+/// Applies the stimulus port and timestamp settings of `config` to the ITM.
+///
+/// The ITM has to be enabled before, which is done when setting up tracing.
+///
+/// Expects to be given a list of all ROM table `components` as the second argument.
+pub(crate) fn configure_itm(
+    interface: &mut dyn ArmProbeInterface,
+    components: &[CoresightComponent],
+    config: &TraceConfig,
+) -> Result<(), Error> {
+    let mut itm = Itm::new(interface, find_component(components, PeripheralType::Itm)?);
+
+    let mut control = itm.trace_control()? & !(TCR_TSENA | TCR_GTSFREQ_MASK);
+    if config.local_timestamps {
+        control |= TCR_TSENA;
+    }
+    if config.global_timestamps {
+        control |= TCR_GTSFREQ_8192;
+    }
+
+    itm.set_trace_control(control)?;
+    itm.set_trace_enable(config.stimulus_ports)
+}
@@ -0,0 +1,200 @@
+//! Diagnosis of the trace chain, see [`Session::diagnose_trace`](crate::Session::diagnose_trace).
+//!
+//! Trace data passes through several stages before it reaches the host, and each of them can
+//! silently drop it. The diagnosis checks the stages in the order the data passes through them,
+//! and reports the first one which doesn't work.
+
+use super::{
+    find_component, swo_prescaler, Itm, ItmDecoder, ItmPacket, TraceRegisterCheck, TraceSink,
+};
+use crate::architecture::arm::memory::romtable::{CoresightComponent, PeripheralType};
+use crate::architecture::arm::ArmProbeInterface;
+use crate::Error;
+use std::time::Duration;
+
+/// The offset of the authentication status register of a CoreSight component.
+const REGISTER_OFFSET_AUTHSTATUS: u32 = 0xFB8;
+
+/// The stimulus port used for the marker, chosen to not interfere with logging on the low ports.
+pub(crate) const MARKER_PORT: u8 = 31;
+/// The marker written by the host to check the trace path.
+pub(crate) const MARKER: u32 = u32::from_le_bytes(*b"PRS!");
+/// The time to wait between the reads of the trace sink, while waiting for the marker.
+pub(crate) const MARKER_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// A stage of the trace chain, in the order in which they are checked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TraceStage {
+    /// Non-invasive debug, which is required for trace, is allowed by the authentication
+    /// interface.
+    DebugAuthentication,
+    /// The trace components are clocked, and the SWO baud rate can be derived from the TPIU
+    /// clock.
+    TraceClock,
+    /// The DWT is configured.
+    Dwt,
+    /// The ITM is configured.
+    Itm,
+    /// The trace sink, i.e. the TPIU, SWO or trace memory, is configured.
+    Sink,
+    /// A marker written to an ITM stimulus port by the host was received in the trace data.
+    RoundTrip,
+}
+
+/// A stage of the trace chain which doesn't work.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceFailure {
+    /// The stage which failed.
+    pub stage: TraceStage,
+    /// A description of the failure.
+    pub reason: String,
+}
+
+/// The result of [`Session::diagnose_trace`](crate::Session::diagnose_trace).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TraceDiagnosis {
+    /// The stages which were checked successfully.
+    pub passed: Vec<TraceStage>,
+    /// The first stage which failed, the following stages were not checked.
+    pub failure: Option<TraceFailure>,
+}
+
+impl TraceDiagnosis {
+    /// Returns `true` if no stage failed.
+    pub fn is_ok(&self) -> bool {
+        self.failure.is_none()
+    }
+
+    /// The stage which failed, if any.
+    pub fn failed_stage(&self) -> Option<TraceStage> {
+        self.failure.as_ref().map(|failure| failure.stage)
+    }
+
+    pub(crate) fn pass(&mut self, stage: TraceStage) {
+        self.passed.push(stage);
+    }
+
+    pub(crate) fn fail(mut self, stage: TraceStage, reason: impl Into<String>) -> Self {
+        self.failure = Some(TraceFailure {
+            stage,
+            reason: reason.into(),
+        });
+        self
+    }
+}
+
+/// Checks the authentication status of the ITM, and returns the reason if non-invasive debug is
+/// disabled.
+///
+/// Components without an authentication status register read it as zero, which is accepted.
+pub(crate) fn check_trace_authentication(
+    interface: &mut dyn ArmProbeInterface,
+    components: &[CoresightComponent],
+) -> Result<Option<String>, Error> {
+    let itm = find_component(components, PeripheralType::Itm)?;
+    let status = itm.read_reg(interface, REGISTER_OFFSET_AUTHSTATUS)?;
+
+    // NSNID: 0b10 means non-secure non-invasive debug is implemented, but disabled.
+    if (status >> 2) & 0b11 == 0b10 {
+        return Ok(Some(format!(
+            "Non-invasive debug is disabled (AUTHSTATUS = {:#010x})",
+            status
+        )));
+    }
+
+    Ok(None)
+}
+
+/// Checks that the SWO baud rate of a serial trace sink can be derived from the TPIU clock, and
+/// returns the reason if not.
+pub(crate) fn check_trace_clock(sink: &TraceSink) -> Option<String> {
+    match sink {
+        // A negotiated baud rate is checked during the setup.
+        TraceSink::Swo(config) | TraceSink::Tpiu(config) if !config.auto_baud() => {
+            swo_prescaler(config).err().map(|error| error.to_string())
+        }
+        _ => None,
+    }
+}
+
+/// The stage a register checked by [`verify_trace_setup`](super::verify_trace_setup) belongs to.
+pub(crate) fn stage_of_check(check: &TraceRegisterCheck) -> TraceStage {
+    if check.name.starts_with("DWT_") {
+        TraceStage::Dwt
+    } else if check.name.starts_with("ITM_") {
+        TraceStage::Itm
+    } else {
+        TraceStage::Sink
+    }
+}
+
+/// Writes the marker to the marker stimulus port of the ITM.
+///
+/// The marker port is enabled in ITM_TER for the write, as writes to disabled ports are
+/// dropped. The previous value of ITM_TER is returned, which has to be restored with
+/// [`restore_stimulus_ports`] once the marker was received.
+pub(crate) fn inject_marker(
+    interface: &mut dyn ArmProbeInterface,
+    components: &[CoresightComponent],
+) -> Result<u32, Error> {
+    let mut itm = Itm::new(interface, find_component(components, PeripheralType::Itm)?);
+
+    let stimulus_ports = itm.trace_enable()?;
+    itm.set_trace_enable(with_marker_port(stimulus_ports))?;
+
+    if let Err(error) = itm.write_stimulus(MARKER_PORT, MARKER) {
+        itm.set_trace_enable(stimulus_ports)?;
+        return Err(error);
+    }
+
+    Ok(stimulus_ports)
+}
+
+/// Restores the `stimulus_ports` returned by [`inject_marker`].
+pub(crate) fn restore_stimulus_ports(
+    interface: &mut dyn ArmProbeInterface,
+    components: &[CoresightComponent],
+    stimulus_ports: u32,
+) -> Result<(), Error> {
+    let mut itm = Itm::new(interface, find_component(components, PeripheralType::Itm)?);
+    itm.set_trace_enable(stimulus_ports)
+}
+
+/// The ITM_TER value with the marker port enabled in addition to the `stimulus_ports`.
+fn with_marker_port(stimulus_ports: u32) -> u32 {
+    stimulus_ports | 1 << MARKER_PORT
+}
+
+/// Decodes the next chunk of trace data, and returns `true` if it contains the marker.
+pub(crate) fn find_marker(decoder: &mut ItmDecoder, data: &[u8]) -> bool {
+    decoder.feed(data);
+
+    decoder.any(|packet| {
+        packet
+            == ItmPacket::Instrumentation {
+                port: MARKER_PORT,
+                data: MARKER.to_le_bytes().to_vec(),
+            }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{find_marker, with_marker_port, ItmDecoder};
+
+    #[test]
+    fn marker_port_is_enabled_in_addition() {
+        assert_eq!(with_marker_port(0), 0x8000_0000);
+        assert_eq!(with_marker_port(0x0000_0001), 0x8000_0001);
+        assert_eq!(with_marker_port(u32::MAX), u32::MAX);
+    }
+
+    #[test]
+    fn finds_marker_split_between_chunks() {
+        let mut decoder = ItmDecoder::new();
+
+        // "x" on port 0, followed by the marker on port 31.
+        assert!(!find_marker(&mut decoder, &[0x01, b'x', 0xFB, b'P']));
+        assert!(find_marker(&mut decoder, b"RS!"));
+    }
+}
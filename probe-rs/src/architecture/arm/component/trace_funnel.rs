@@ -2,14 +2,22 @@
 //!
 //! # Description
 //! This module provides access and control of the trace funnel CoreSight component block.
-use super::DebugRegister;
+use super::{ComponentError, DebugRegister};
 use crate::architecture::arm::memory::romtable::CoresightComponent;
 use crate::architecture::arm::ArmProbeInterface;
 use crate::Error;
 use bitfield::bitfield;
 
+const REGISTER_OFFSET_PRIORITY: u32 = 0x04;
 const REGISTER_OFFSET_ACCESS: u32 = 0xFB0;
 
+/// The number of input ports of a trace funnel.
+const NUM_PORTS: u8 = 8;
+/// The maximum number of transactions the arbiter performs on an input before switching.
+const MAX_HOLD_TIME: u8 = 16;
+/// The maximum priority of an input port, which is the lowest priority.
+const MAX_PRIORITY: u8 = 7;
+
 /// Trace funnel unit
 pub struct TraceFunnel<'a> {
     component: &'a CoresightComponent,
@@ -46,6 +54,80 @@ impl<'a> TraceFunnel<'a> {
         control.set_slave_enable(mask);
         control.store(self.component, self.interface)
     }
+
+    /// Enable a single funnel input port, without changing the other ports.
+    ///
+    /// On devices with multiple trace sources, e.g. an ETM and the ITM, every source which should
+    /// reach the trace sink has to be enabled.
+    pub fn enable_input(&mut self, port: u8) -> Result<(), Error> {
+        check_port(port)?;
+
+        let mut control = Control::load(self.component, self.interface)?;
+        control.set_slave_enable(control.enable_slave_port() | (1 << port));
+        control.store(self.component, self.interface)
+    }
+
+    /// Disable a single funnel input port, without changing the other ports.
+    pub fn disable_input(&mut self, port: u8) -> Result<(), Error> {
+        check_port(port)?;
+
+        let mut control = Control::load(self.component, self.interface)?;
+        control.set_slave_enable(control.enable_slave_port() & !(1 << port));
+        control.store(self.component, self.interface)
+    }
+
+    /// Set the minimum number of transactions the arbiter performs on an input before switching
+    /// to the next one, from 1 to 16.
+    ///
+    /// A longer hold time reduces the overhead of switching between the inputs, as every switch
+    /// inserts a new trace source ID into the formatted trace data.
+    pub fn set_hold_time(&mut self, transactions: u8) -> Result<(), Error> {
+        if !(1..=MAX_HOLD_TIME).contains(&transactions) {
+            return Err(Error::architecture_specific(
+                ComponentError::InvalidFunnelHoldTime(transactions),
+            ));
+        }
+
+        let mut control = Control::load(self.component, self.interface)?;
+        control.set_min_hold_time(transactions - 1);
+        control.store(self.component, self.interface)
+    }
+
+    /// Set the priority of an input port, from 0 (highest) to 7 (lowest).
+    ///
+    /// If multiple inputs have trace data, the arbiter selects the input with the highest
+    /// priority, so a high bandwidth source like an ETM can be given a lower priority than the
+    /// ITM, which would otherwise overflow.
+    pub fn set_priority(&mut self, port: u8, priority: u8) -> Result<(), Error> {
+        check_port(port)?;
+        if priority > MAX_PRIORITY {
+            return Err(Error::architecture_specific(
+                ComponentError::InvalidFunnelPriority(priority),
+            ));
+        }
+
+        let mut priorities = self
+            .component
+            .read_reg(self.interface, REGISTER_OFFSET_PRIORITY)?;
+
+        // Every port has a 3 bit priority field.
+        let shift = 3 * port as u32;
+        priorities = (priorities & !(0b111 << shift)) | ((priority as u32) << shift);
+
+        self.component
+            .write_reg(self.interface, REGISTER_OFFSET_PRIORITY, priorities)
+    }
+}
+
+/// Checks that `port` is an input port of the funnel.
+fn check_port(port: u8) -> Result<(), Error> {
+    if port < NUM_PORTS {
+        Ok(())
+    } else {
+        Err(Error::architecture_specific(
+            ComponentError::InvalidFunnelPort(port),
+        ))
+    }
 }
 
 bitfield! {
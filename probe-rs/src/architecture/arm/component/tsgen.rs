@@ -0,0 +1,171 @@
+//! Arm timestamp generator (TSGEN) CoreSight Component
+//!
+//! # Description
+//! The timestamp generator provides a global time base, which is distributed to all trace
+//! sources. This allows aligning the global timestamps of different trace sources, e.g. ITM
+//! and ETM.
+//!
+//! See "CoreSight SoC-400 Technical Reference Manual", chapter 3.13 for details about the
+//! registers of this component.
+use super::DebugRegister;
+use crate::architecture::arm::memory::romtable::CoresightComponent;
+use crate::architecture::arm::ArmProbeInterface;
+use crate::Error;
+use bitfield::bitfield;
+
+/// Lower 32 bits of the counter value.
+const REGISTER_OFFSET_CNTCVL: u32 = 0x008;
+/// Upper 32 bits of the counter value.
+const REGISTER_OFFSET_CNTCVU: u32 = 0x00C;
+/// Base frequency of the counter.
+const REGISTER_OFFSET_CNTFID0: u32 = 0x020;
+
+/// Timestamp generator unit
+pub struct Tsgen<'a> {
+    component: &'a CoresightComponent,
+    interface: &'a mut dyn ArmProbeInterface,
+}
+
+impl<'a> Tsgen<'a> {
+    /// Construct a new timestamp generator component.
+    pub fn new(
+        interface: &'a mut dyn ArmProbeInterface,
+        component: &'a CoresightComponent,
+    ) -> Self {
+        Tsgen {
+            component,
+            interface,
+        }
+    }
+
+    /// Returns `true` if the counter is enabled.
+    pub fn is_enabled(&mut self) -> Result<bool, Error> {
+        let control = Cntcr::load(self.component, self.interface)?;
+        Ok(control.en())
+    }
+
+    /// Enable or disable the counter.
+    pub fn set_enabled(&mut self, enabled: bool) -> Result<(), Error> {
+        let mut control = Cntcr::load(self.component, self.interface)?;
+        control.set_en(enabled);
+        control.store(self.component, self.interface)
+    }
+
+    /// Configure whether the counter stops while a debugger has halted the system.
+    pub fn set_halt_on_debug(&mut self, halt_on_debug: bool) -> Result<(), Error> {
+        let mut control = Cntcr::load(self.component, self.interface)?;
+        control.set_hdbg(halt_on_debug);
+        control.store(self.component, self.interface)
+    }
+
+    /// Returns `true` if the counter is currently stopped because of a debug halt.
+    pub fn is_halted_on_debug(&mut self) -> Result<bool, Error> {
+        let status = Cntsr::load(self.component, self.interface)?;
+        Ok(status.dbgh())
+    }
+
+    /// Read the current 64 bit counter value.
+    pub fn counter(&mut self) -> Result<u64, Error> {
+        // The counter is read in two halves, so the upper half is read again to detect
+        // a carry from the lower half between the two reads.
+        loop {
+            let upper = self
+                .component
+                .read_reg(self.interface, REGISTER_OFFSET_CNTCVU)?;
+            let lower = self
+                .component
+                .read_reg(self.interface, REGISTER_OFFSET_CNTCVL)?;
+
+            if self
+                .component
+                .read_reg(self.interface, REGISTER_OFFSET_CNTCVU)?
+                == upper
+            {
+                return Ok(((upper as u64) << 32) | lower as u64);
+            }
+        }
+    }
+
+    /// Set the counter value.
+    ///
+    /// # Note
+    /// The counter value can only be written while the counter is disabled.
+    pub fn set_counter(&mut self, value: u64) -> Result<(), Error> {
+        self.component
+            .write_reg(self.interface, REGISTER_OFFSET_CNTCVL, value as u32)?;
+        self.component
+            .write_reg(self.interface, REGISTER_OFFSET_CNTCVU, (value >> 32) as u32)
+    }
+
+    /// The frequency of the counter in Hz, as configured in the base frequency register.
+    ///
+    /// This value is only informational and has to be set up by the firmware or debugger.
+    /// `0` means that the frequency was not configured.
+    pub fn frequency(&mut self) -> Result<u32, Error> {
+        self.component
+            .read_reg(self.interface, REGISTER_OFFSET_CNTFID0)
+    }
+
+    /// Set the frequency of the counter in Hz in the base frequency register.
+    pub fn set_frequency(&mut self, frequency: u32) -> Result<(), Error> {
+        self.component
+            .write_reg(self.interface, REGISTER_OFFSET_CNTFID0, frequency)
+    }
+}
+
+bitfield! {
+    /// The counter control register.
+    #[derive(Clone, Default)]
+    pub struct Cntcr(u32);
+    impl Debug;
+
+    /// Halt the counter while the debug halt signal is asserted.
+    pub hdbg, set_hdbg: 1;
+
+    /// Enable the counter.
+    pub en, set_en: 0;
+}
+
+impl DebugRegister for Cntcr {
+    const ADDRESS: u32 = 0x000;
+    const NAME: &'static str = "TSGEN/CNTCR";
+}
+
+impl From<u32> for Cntcr {
+    fn from(raw: u32) -> Cntcr {
+        Cntcr(raw)
+    }
+}
+
+impl From<Cntcr> for u32 {
+    fn from(control: Cntcr) -> u32 {
+        control.0
+    }
+}
+
+bitfield! {
+    /// The counter status register.
+    #[derive(Clone, Default)]
+    pub struct Cntsr(u32);
+    impl Debug;
+
+    /// The counter is halted because the debug halt signal is asserted.
+    pub dbgh, _: 1;
+}
+
+impl DebugRegister for Cntsr {
+    const ADDRESS: u32 = 0x004;
+    const NAME: &'static str = "TSGEN/CNTSR";
+}
+
+impl From<u32> for Cntsr {
+    fn from(raw: u32) -> Cntsr {
+        Cntsr(raw)
+    }
+}
+
+impl From<Cntsr> for u32 {
+    fn from(status: Cntsr) -> u32 {
+        status.0
+    }
+}
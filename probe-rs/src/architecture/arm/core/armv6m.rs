@@ -1,11 +1,14 @@
 //! Register types and the core interface for armv6-M
 
+use super::cortex_m::{check_reset_catch, prepare_step, StepAction};
+use super::watchpoints::DwtVersion;
 use super::{ArmError, CortexMState, Dfsr, CORTEX_M_COMMON_REGS};
 
 use crate::architecture::arm::memory::adi_v5_memory_interface::ArmProbe;
 use crate::architecture::arm::sequences::ArmDebugSequence;
 use crate::core::{
     RegisterDataType, RegisterDescription, RegisterFile, RegisterKind, RegisterValue,
+    WatchpointAccess,
 };
 use crate::error::Error;
 use crate::memory::valid_32bit_address;
@@ -544,6 +547,17 @@ impl<'probe> CoreInterface for Armv6m<'probe> {
         })
     }
 
+    fn request_halt(&mut self) -> Result<(), Error> {
+        let mut value = Dhcsr(0);
+        value.set_c_halt(true);
+        value.set_c_debugen(true);
+        value.enable_write();
+
+        self.memory.write_word_32(Dhcsr::ADDRESS, value.into())?;
+
+        Ok(())
+    }
+
     fn run(&mut self) -> Result<(), Error> {
         // Before we run, we always perform a single instruction step, to account for possible breakpoints that might get us stuck on the current instruction.
         self.step()?;
@@ -563,17 +577,18 @@ impl<'probe> CoreInterface for Armv6m<'probe> {
     }
 
     fn step(&mut self) -> Result<CoreInformation, Error> {
-        // First check if we stopped on a breakpoint, because this requires special handling before we can continue.
-        let pc_before_step = self.read_core_reg(self.registers().program_counter().id)?;
-        let was_breakpoint = if matches!(
-            self.state.current_state,
-            CoreStatus::Halted(HaltReason::Breakpoint(_))
-        ) {
-            self.enable_breakpoints(false)?;
-            true
-        } else {
-            false
+        let (hw_breakpoints_enabled, current_state) =
+            (self.state.hw_breakpoints_enabled, self.state.current_state);
+
+        let disable_breakpoints = match prepare_step(self, hw_breakpoints_enabled, current_state)? {
+            StepAction::Skipped(information) => return Ok(information),
+            StepAction::Step {
+                disable_breakpoints,
+            } => disable_breakpoints,
         };
+        if disable_breakpoints {
+            self.enable_breakpoints(false)?;
+        }
 
         let mut value = Dhcsr(0);
         // Leave halted state.
@@ -590,21 +605,10 @@ impl<'probe> CoreInterface for Armv6m<'probe> {
         self.wait_for_core_halted(Duration::from_millis(100))?;
 
         // Try to read the new program counter.
-        let mut pc_after_step = self.read_core_reg(self.registers().program_counter().id)?;
+        let pc_after_step = self.read_core_reg(self.registers().program_counter().id)?;
 
         // Re-enable breakpoints before we continue.
-        if was_breakpoint {
-            // If we were stopped on a software breakpoint, then we need to manually advance the PC, or else we will be stuck here forever.
-            if pc_before_step == pc_after_step
-                && !self
-                    .hw_breakpoints()?
-                    .contains(&pc_before_step.try_into().ok())
-            {
-                tracing::debug!("Encountered a breakpoint instruction @ {}. We need to manually advance the program counter to the next instruction.", pc_after_step);
-                // Advance the program counter by the architecture specific byte size of the BKPT instruction.
-                pc_after_step.incremenet_address(2)?;
-                self.write_core_reg(self.registers().program_counter().id, pc_after_step)?;
-            }
+        if disable_breakpoints {
             self.enable_breakpoints(true)?;
         }
 
@@ -618,14 +622,21 @@ impl<'probe> CoreInterface for Armv6m<'probe> {
             .reset_system(&mut *self.memory, crate::CoreType::Armv6m, None)
     }
 
-    fn reset_and_halt(&mut self, _timeout: Duration) -> Result<CoreInformation, Error> {
+    fn reset_and_halt(&mut self, timeout: Duration) -> Result<CoreInformation, Error> {
         self.sequence
             .reset_catch_set(&mut *self.memory, crate::CoreType::Armv6m, None)?;
         self.sequence
             .reset_system(&mut *self.memory, crate::CoreType::Armv6m, None)?;
 
-        // Update core status
-        let _ = self.status()?;
+        // The reset catch halts the core before it executes the first instruction.
+        let halted = self.wait_for_core_halted(timeout);
+        check_reset_catch(
+            &*self.sequence,
+            &mut *self.memory,
+            crate::CoreType::Armv6m,
+            timeout,
+            halted,
+        )?;
 
         const XPSR_THUMB: u32 = 1 << 24;
         let xpsr_value: u32 = self.read_core_reg(XPSR.id)?.try_into()?;
@@ -711,6 +722,35 @@ impl<'probe> CoreInterface for Armv6m<'probe> {
         Ok(())
     }
 
+    fn available_watchpoint_units(&mut self) -> Result<u32, Error> {
+        super::watchpoints::available_watchpoint_units(&mut *self.memory)
+    }
+
+    fn hw_watchpoints(&mut self) -> Result<Vec<Option<u64>>, Error> {
+        super::watchpoints::hw_watchpoints(&mut *self.memory)
+    }
+
+    fn set_hw_watchpoint(
+        &mut self,
+        unit_index: usize,
+        address: u64,
+        size: u64,
+        access: WatchpointAccess,
+    ) -> Result<(), Error> {
+        super::watchpoints::set_hw_watchpoint(
+            &mut *self.memory,
+            DwtVersion::Armv7m,
+            unit_index,
+            address,
+            size,
+            access,
+        )
+    }
+
+    fn clear_hw_watchpoint(&mut self, unit_index: usize) -> Result<(), Error> {
+        super::watchpoints::clear_hw_watchpoint(&mut *self.memory, unit_index)
+    }
+
     fn hw_breakpoints_enabled(&self) -> bool {
         self.state.hw_breakpoints_enabled
     }
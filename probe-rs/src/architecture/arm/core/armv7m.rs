@@ -4,12 +4,14 @@ use crate::architecture::arm::memory::adi_v5_memory_interface::ArmProbe;
 use crate::architecture::arm::sequences::ArmDebugSequence;
 use crate::core::{
     CoreInformation, CoreInterface, MemoryMappedRegister, RegisterFile, RegisterId, RegisterValue,
+    WatchpointAccess,
 };
 use crate::error::Error;
 use crate::memory::valid_32bit_address;
 use crate::{CoreType, DebugProbeError, InstructionSet};
 
-use super::cortex_m::Mvfr0;
+use super::cortex_m::{check_reset_catch, prepare_step, Mvfr0, StepAction};
+use super::watchpoints::DwtVersion;
 use super::{register, ArmError, CortexMState, Dfsr, CORTEX_M_COMMON_REGS, CORTEX_M_WITH_FP_REGS};
 use crate::{
     core::{Architecture, CoreStatus, HaltReason},
@@ -365,6 +367,38 @@ impl MemoryMappedRegister for Demcr {
     const NAME: &'static str = "DEMCR";
 }
 
+bitfield! {
+    /// System Handler Priority Register 3, SHPR3 (see armv7-M Architecture Reference Manual B3.2.12)
+    ///
+    /// Only the upper bits of each priority are implemented, the unimplemented bits read as zero.
+    #[derive(Copy, Clone)]
+    pub struct Shpr3(u32);
+    impl Debug;
+    /// Priority of the SysTick exception
+    pub u8, pri_15, set_pri_15: 31, 24;
+    /// Priority of the PendSV exception
+    pub u8, pri_14, set_pri_14: 23, 16;
+    /// Priority of the DebugMonitor exception
+    pub u8, pri_12, set_pri_12: 7, 0;
+}
+
+impl From<u32> for Shpr3 {
+    fn from(value: u32) -> Self {
+        Self(value)
+    }
+}
+
+impl From<Shpr3> for u32 {
+    fn from(value: Shpr3) -> Self {
+        value.0
+    }
+}
+
+impl MemoryMappedRegister for Shpr3 {
+    const ADDRESS: u64 = 0xe000_ed20;
+    const NAME: &'static str = "SHPR3";
+}
+
 bitfield! {
     /// Flash Patch Control Register, FP_CTRL (see armv7-M Architecture Reference Manual C1.11.3)
     #[derive(Copy,Clone)]
@@ -726,8 +760,7 @@ impl<'probe> CoreInterface for Armv7m<'probe> {
 
     fn read_core_reg(&mut self, address: RegisterId) -> Result<RegisterValue, Error> {
         if self.state.current_state.is_halted() {
-            let val = super::cortex_m::read_core_reg(&mut *self.memory, address)?;
-            Ok(val.into())
+            super::cortex_m::read_core_reg_value(&mut *self.memory, address)
         } else {
             Err(Error::architecture_specific(ArmError::CoreNotHalted))
         }
@@ -735,8 +768,7 @@ impl<'probe> CoreInterface for Armv7m<'probe> {
 
     fn write_core_reg(&mut self, address: RegisterId, value: RegisterValue) -> Result<(), Error> {
         if self.state.current_state.is_halted() {
-            super::cortex_m::write_core_reg(&mut *self.memory, address, value.try_into()?)?;
-            Ok(())
+            super::cortex_m::write_core_reg_value(&mut *self.memory, address, value)
         } else {
             Err(Error::architecture_specific(ArmError::CoreNotHalted))
         }
@@ -763,6 +795,17 @@ impl<'probe> CoreInterface for Armv7m<'probe> {
         })
     }
 
+    fn request_halt(&mut self) -> Result<(), Error> {
+        let mut value = Dhcsr(0);
+        value.set_c_halt(true);
+        value.set_c_debugen(true);
+        value.enable_write();
+
+        self.memory.write_word_32(Dhcsr::ADDRESS, value.into())?;
+
+        Ok(())
+    }
+
     fn run(&mut self) -> Result<(), Error> {
         // Before we run, we always perform a single instruction step, to account for possible breakpoints that might get us stuck on the current instruction.
         self.step()?;
@@ -791,17 +834,18 @@ impl<'probe> CoreInterface for Armv7m<'probe> {
     }
 
     fn step(&mut self) -> Result<CoreInformation, Error> {
-        // First check if we stopped on a breakpoint, because this requires special handling before we can continue.
-        let pc_before_step = self.read_core_reg(self.registers().program_counter().id)?;
-        let was_breakpoint = if matches!(
-            self.state.current_state,
-            CoreStatus::Halted(HaltReason::Breakpoint(_))
-        ) {
-            self.enable_breakpoints(false)?;
-            true
-        } else {
-            false
+        let (hw_breakpoints_enabled, current_state) =
+            (self.state.hw_breakpoints_enabled, self.state.current_state);
+
+        let disable_breakpoints = match prepare_step(self, hw_breakpoints_enabled, current_state)? {
+            StepAction::Skipped(information) => return Ok(information),
+            StepAction::Step {
+                disable_breakpoints,
+            } => disable_breakpoints,
         };
+        if disable_breakpoints {
+            self.enable_breakpoints(false)?;
+        }
 
         let mut dhcsr = Dhcsr(self.memory.read_word_32(Dhcsr::ADDRESS)?);
 
@@ -827,21 +871,10 @@ impl<'probe> CoreInterface for Armv7m<'probe> {
         self.wait_for_core_halted(Duration::from_millis(100))?;
 
         // Try to read the new program counter.
-        let mut pc_after_step = self.read_core_reg(self.registers().program_counter().id)?;
+        let pc_after_step = self.read_core_reg(self.registers().program_counter().id)?;
 
         // Re-enable breakpoints before we continue.
-        if was_breakpoint {
-            // If we were stopped on a software breakpoint, then we need to manually advance the PC, or else we will be stuck here forever.
-            if pc_before_step == pc_after_step
-                && !self
-                    .hw_breakpoints()?
-                    .contains(&pc_before_step.try_into().ok())
-            {
-                tracing::debug!("Encountered a breakpoint instruction @ {}. We need to manually advance the program counter to the next instruction.", pc_after_step);
-                // Advance the program counter by the architecture specific byte size of the BKPT instruction.
-                pc_after_step.incremenet_address(2)?;
-                self.write_core_reg(self.registers().program_counter().id, pc_after_step)?;
-            }
+        if disable_breakpoints {
             self.enable_breakpoints(true)?;
         }
 
@@ -855,7 +888,7 @@ impl<'probe> CoreInterface for Armv7m<'probe> {
             .reset_system(&mut *self.memory, crate::CoreType::Armv7m, None)
     }
 
-    fn reset_and_halt(&mut self, _timeout: Duration) -> Result<CoreInformation, Error> {
+    fn reset_and_halt(&mut self, timeout: Duration) -> Result<CoreInformation, Error> {
         // Set the vc_corereset bit in the DEMCR register.
         // This will halt the core after reset.
 
@@ -864,8 +897,15 @@ impl<'probe> CoreInterface for Armv7m<'probe> {
         self.sequence
             .reset_system(&mut *self.memory, crate::CoreType::Armv7m, None)?;
 
-        // Update core status
-        let _ = self.status()?;
+        // The reset catch halts the core before it executes the first instruction.
+        let halted = self.wait_for_core_halted(timeout);
+        check_reset_catch(
+            &*self.sequence,
+            &mut *self.memory,
+            crate::CoreType::Armv7m,
+            timeout,
+            halted,
+        )?;
 
         const XPSR_THUMB: u32 = 1 << 24;
         let xpsr_value: u32 = self.read_core_reg(register::XPSR.id)?.try_into()?;
@@ -966,6 +1006,35 @@ impl<'probe> CoreInterface for Armv7m<'probe> {
         Ok(())
     }
 
+    fn available_watchpoint_units(&mut self) -> Result<u32, Error> {
+        super::watchpoints::available_watchpoint_units(&mut *self.memory)
+    }
+
+    fn hw_watchpoints(&mut self) -> Result<Vec<Option<u64>>, Error> {
+        super::watchpoints::hw_watchpoints(&mut *self.memory)
+    }
+
+    fn set_hw_watchpoint(
+        &mut self,
+        unit_index: usize,
+        address: u64,
+        size: u64,
+        access: WatchpointAccess,
+    ) -> Result<(), Error> {
+        super::watchpoints::set_hw_watchpoint(
+            &mut *self.memory,
+            DwtVersion::Armv7m,
+            unit_index,
+            address,
+            size,
+            access,
+        )
+    }
+
+    fn clear_hw_watchpoint(&mut self, unit_index: usize) -> Result<(), Error> {
+        super::watchpoints::clear_hw_watchpoint(&mut *self.memory, unit_index)
+    }
+
     fn hw_breakpoints_enabled(&self) -> bool {
         self.state.hw_breakpoints_enabled
     }
@@ -603,4 +603,5 @@ pub static AARCH64_REGISTER_FILE: RegisterFile = RegisterFile {
         },
     ]),
     fp_status: Some(&FPSR),
+    fp_double_registers: None,
 };
@@ -10,13 +10,14 @@ use crate::{
 };
 use crate::{Architecture, CoreInformation};
 use crate::{CoreInterface, CoreType, InstructionSet, MemoryMappedRegister};
-use crate::{RegisterId, RegisterValue};
+use crate::{RegisterId, RegisterValue, WatchpointAccess};
 use anyhow::Result;
 
 use bitfield::bitfield;
 
-use super::cortex_m::Mvfr0;
-use super::{ArmError, CortexMState, Dfsr, CORTEX_M_COMMON_REGS, CORTEX_M_WITH_FP_REGS};
+use super::cortex_m::{check_reset_catch, prepare_step, Mvfr0, StepAction};
+use super::watchpoints::DwtVersion;
+use super::{ArmError, CortexMState, Dfsr, ARMV8M_COMMON_REGS, ARMV8M_WITH_FP_REGS};
 use std::sync::Arc;
 use std::{
     mem::size_of,
@@ -120,6 +121,17 @@ impl<'probe> CoreInterface for Armv8m<'probe> {
         })
     }
 
+    fn request_halt(&mut self) -> Result<(), Error> {
+        let mut value = Dhcsr(0);
+        value.set_c_halt(true);
+        value.set_c_debugen(true);
+        value.enable_write();
+
+        self.memory.write_word_32(Dhcsr::ADDRESS, value.into())?;
+
+        Ok(())
+    }
+
     fn run(&mut self) -> Result<(), Error> {
         // Before we run, we always perform a single instruction step, to account for possible breakpoints that might get us stuck on the current instruction.
         self.step()?;
@@ -143,7 +155,7 @@ impl<'probe> CoreInterface for Armv8m<'probe> {
             .reset_system(&mut *self.memory, crate::CoreType::Armv8m, None)
     }
 
-    fn reset_and_halt(&mut self, _timeout: Duration) -> Result<CoreInformation, Error> {
+    fn reset_and_halt(&mut self, timeout: Duration) -> Result<CoreInformation, Error> {
         // Set the vc_corereset bit in the DEMCR register.
         // This will halt the core after reset.
 
@@ -152,8 +164,15 @@ impl<'probe> CoreInterface for Armv8m<'probe> {
         self.sequence
             .reset_system(&mut *self.memory, crate::CoreType::Armv8m, None)?;
 
-        // Update core status
-        let _ = self.status()?;
+        // The reset catch halts the core before it executes the first instruction.
+        let halted = self.wait_for_core_halted(timeout);
+        check_reset_catch(
+            &*self.sequence,
+            &mut *self.memory,
+            crate::CoreType::Armv8m,
+            timeout,
+            halted,
+        )?;
 
         const XPSR_THUMB: u32 = 1 << 24;
         let xpsr_value: u32 = self.read_core_reg(register::XPSR.id)?.try_into()?;
@@ -174,17 +193,18 @@ impl<'probe> CoreInterface for Armv8m<'probe> {
     }
 
     fn step(&mut self) -> Result<CoreInformation, Error> {
-        // First check if we stopped on a breakpoint, because this requires special handling before we can continue.
-        let pc_before_step = self.read_core_reg(self.registers().program_counter().id)?;
-        let was_breakpoint = if matches!(
-            self.state.current_state,
-            CoreStatus::Halted(HaltReason::Breakpoint(_))
-        ) {
-            self.enable_breakpoints(false)?;
-            true
-        } else {
-            false
+        let (hw_breakpoints_enabled, current_state) =
+            (self.state.hw_breakpoints_enabled, self.state.current_state);
+
+        let disable_breakpoints = match prepare_step(self, hw_breakpoints_enabled, current_state)? {
+            StepAction::Skipped(information) => return Ok(information),
+            StepAction::Step {
+                disable_breakpoints,
+            } => disable_breakpoints,
         };
+        if disable_breakpoints {
+            self.enable_breakpoints(false)?;
+        }
 
         let mut value = Dhcsr(0);
         // Leave halted state.
@@ -201,21 +221,10 @@ impl<'probe> CoreInterface for Armv8m<'probe> {
         self.wait_for_core_halted(Duration::from_millis(100))?;
 
         // Try to read the new program counter.
-        let mut pc_after_step = self.read_core_reg(self.registers().program_counter().id)?;
+        let pc_after_step = self.read_core_reg(self.registers().program_counter().id)?;
 
         // Re-enable breakpoints before we continue.
-        if was_breakpoint {
-            // If we were stopped on a software breakpoint, then we need to manually advance the PC, or else we will be stuck here forever.
-            if pc_before_step == pc_after_step
-                && !self
-                    .hw_breakpoints()?
-                    .contains(&pc_before_step.try_into().ok())
-            {
-                tracing::debug!("Encountered a breakpoint instruction @ {}. We need to manually advance the program counter to the next instruction.", pc_after_step);
-                // Advance the program counter by the architecture specific byte size of the BKPT instruction.
-                pc_after_step.incremenet_address(2)?;
-                self.write_core_reg(self.registers().program_counter().id, pc_after_step)?;
-            }
+        if disable_breakpoints {
             self.enable_breakpoints(true)?;
         }
 
@@ -226,8 +235,7 @@ impl<'probe> CoreInterface for Armv8m<'probe> {
 
     fn read_core_reg(&mut self, address: RegisterId) -> Result<RegisterValue, Error> {
         if self.state.current_state.is_halted() {
-            let value = super::cortex_m::read_core_reg(&mut *self.memory, address)?;
-            Ok(value.into())
+            super::cortex_m::read_core_reg_value(&mut *self.memory, address)
         } else {
             Err(Error::architecture_specific(ArmError::CoreNotHalted))
         }
@@ -235,8 +243,7 @@ impl<'probe> CoreInterface for Armv8m<'probe> {
 
     fn write_core_reg(&mut self, address: RegisterId, value: RegisterValue) -> Result<(), Error> {
         if self.state.current_state.is_halted() {
-            super::cortex_m::write_core_reg(&mut *self.memory, address, value.try_into()?)?;
-            Ok(())
+            super::cortex_m::write_core_reg_value(&mut *self.memory, address, value)
         } else {
             Err(Error::architecture_specific(ArmError::CoreNotHalted))
         }
@@ -283,9 +290,9 @@ impl<'probe> CoreInterface for Armv8m<'probe> {
 
     fn registers(&self) -> &'static RegisterFile {
         if self.state.fp_present {
-            &CORTEX_M_WITH_FP_REGS
+            &ARMV8M_WITH_FP_REGS
         } else {
-            &CORTEX_M_COMMON_REGS
+            &ARMV8M_COMMON_REGS
         }
     }
 
@@ -301,6 +308,35 @@ impl<'probe> CoreInterface for Armv8m<'probe> {
         Ok(())
     }
 
+    fn available_watchpoint_units(&mut self) -> Result<u32, Error> {
+        super::watchpoints::available_watchpoint_units(&mut *self.memory)
+    }
+
+    fn hw_watchpoints(&mut self) -> Result<Vec<Option<u64>>, Error> {
+        super::watchpoints::hw_watchpoints(&mut *self.memory)
+    }
+
+    fn set_hw_watchpoint(
+        &mut self,
+        unit_index: usize,
+        address: u64,
+        size: u64,
+        access: WatchpointAccess,
+    ) -> Result<(), Error> {
+        super::watchpoints::set_hw_watchpoint(
+            &mut *self.memory,
+            DwtVersion::Armv8m,
+            unit_index,
+            address,
+            size,
+            access,
+        )
+    }
+
+    fn clear_hw_watchpoint(&mut self, unit_index: usize) -> Result<(), Error> {
+        super::watchpoints::clear_hw_watchpoint(&mut *self.memory, unit_index)
+    }
+
     fn hw_breakpoints_enabled(&self) -> bool {
         self.state.hw_breakpoints_enabled
     }
@@ -0,0 +1,390 @@
+//! Cache identification and maintenance for Cortex-M cores with caches, e.g. the Cortex-M7.
+//!
+//! The caches are maintained through the memory mapped cache maintenance operations of the
+//! system control block, so the functions in this module work while the core is running.
+//!
+//! See "ARMv7-M Architecture Reference Manual", chapters B2.2 and B4.8 for details.
+
+use crate::{
+    memory::valid_32bit_address, Core, CoreType, Error, MemoryInterface, MemoryMappedRegister,
+};
+
+use bitfield::bitfield;
+use std::ops::Range;
+
+bitfield! {
+    /// Cache Level ID Register
+    #[derive(Copy, Clone)]
+    pub struct Clidr(u32);
+    impl Debug;
+    pub louu, _: 29, 27;
+    pub loc, _: 26, 24;
+    pub louis, _: 23, 21;
+    pub ctype1, _: 2, 0;
+}
+
+impl Clidr {
+    /// Level 1 cache type: Only an instruction cache.
+    const CTYPE_INSTRUCTION: u32 = 0b001;
+    /// Level 1 cache type: Only a data cache.
+    const CTYPE_DATA: u32 = 0b010;
+    /// Level 1 cache type: Separate instruction and data caches.
+    const CTYPE_SEPARATE: u32 = 0b011;
+    /// Level 1 cache type: A unified cache.
+    const CTYPE_UNIFIED: u32 = 0b100;
+
+    /// Returns `true` if the core has a level 1 instruction cache.
+    pub fn has_instruction_cache(&self) -> bool {
+        matches!(
+            self.ctype1(),
+            Self::CTYPE_INSTRUCTION | Self::CTYPE_SEPARATE
+        )
+    }
+
+    /// Returns `true` if the core has a level 1 data or unified cache.
+    pub fn has_data_cache(&self) -> bool {
+        matches!(
+            self.ctype1(),
+            Self::CTYPE_DATA | Self::CTYPE_SEPARATE | Self::CTYPE_UNIFIED
+        )
+    }
+}
+
+impl From<u32> for Clidr {
+    fn from(value: u32) -> Self {
+        Self(value)
+    }
+}
+
+impl From<Clidr> for u32 {
+    fn from(value: Clidr) -> Self {
+        value.0
+    }
+}
+
+impl MemoryMappedRegister for Clidr {
+    const ADDRESS: u64 = 0xE000_ED78;
+    const NAME: &'static str = "CLIDR";
+}
+
+bitfield! {
+    /// Cache Type Register
+    #[derive(Copy, Clone)]
+    pub struct Ctr(u32);
+    impl Debug;
+    pub format, _: 31, 29;
+    pub cwg, _: 27, 24;
+    pub erg, _: 23, 20;
+    pub dminline, _: 19, 16;
+    pub iminline, _: 3, 0;
+}
+
+impl Ctr {
+    /// The smallest data cache line size in bytes.
+    pub fn data_line_size(&self) -> u32 {
+        // The line sizes are given as log2 of the number of words.
+        4 << self.dminline()
+    }
+
+    /// The smallest instruction cache line size in bytes.
+    pub fn instruction_line_size(&self) -> u32 {
+        4 << self.iminline()
+    }
+}
+
+impl From<u32> for Ctr {
+    fn from(value: u32) -> Self {
+        Self(value)
+    }
+}
+
+impl From<Ctr> for u32 {
+    fn from(value: Ctr) -> Self {
+        value.0
+    }
+}
+
+impl MemoryMappedRegister for Ctr {
+    const ADDRESS: u64 = 0xE000_ED7C;
+    const NAME: &'static str = "CTR";
+}
+
+bitfield! {
+    /// Cache Size ID Register, describes the cache selected in CSSELR.
+    #[derive(Copy, Clone)]
+    pub struct Ccsidr(u32);
+    impl Debug;
+    pub wt, _: 31;
+    pub wb, _: 30;
+    pub ra, _: 29;
+    pub wa, _: 28;
+    pub numsets, _: 27, 13;
+    pub associativity, _: 12, 3;
+    pub linesize, _: 2, 0;
+}
+
+impl From<u32> for Ccsidr {
+    fn from(value: u32) -> Self {
+        Self(value)
+    }
+}
+
+impl From<Ccsidr> for u32 {
+    fn from(value: Ccsidr) -> Self {
+        value.0
+    }
+}
+
+impl MemoryMappedRegister for Ccsidr {
+    const ADDRESS: u64 = 0xE000_ED80;
+    const NAME: &'static str = "CCSIDR";
+}
+
+bitfield! {
+    /// Cache Size Selection Register
+    #[derive(Copy, Clone)]
+    pub struct Csselr(u32);
+    impl Debug;
+    pub level, set_level: 3, 1;
+    pub ind, set_ind: 0;
+}
+
+impl From<u32> for Csselr {
+    fn from(value: u32) -> Self {
+        Self(value)
+    }
+}
+
+impl From<Csselr> for u32 {
+    fn from(value: Csselr) -> Self {
+        value.0
+    }
+}
+
+impl MemoryMappedRegister for Csselr {
+    const ADDRESS: u64 = 0xE000_ED84;
+    const NAME: &'static str = "CSSELR";
+}
+
+/// Instruction cache invalidate by address to the point of unification.
+const ICIMVAU: u64 = 0xE000_EF58;
+/// Data cache invalidate by address to the point of coherency.
+const DCIMVAC: u64 = 0xE000_EF5C;
+/// Data cache clean by address to the point of coherency.
+const DCCMVAC: u64 = 0xE000_EF68;
+/// Data cache clean and invalidate by address to the point of coherency.
+const DCCIMVAC: u64 = 0xE000_EF70;
+
+/// The geometry and properties of a cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheGeometry {
+    /// The size of a cache line in bytes.
+    pub line_size: u32,
+    /// The number of ways.
+    pub associativity: u32,
+    /// The number of sets.
+    pub sets: u32,
+    /// The cache supports write-through.
+    pub write_through: bool,
+    /// The cache supports write-back.
+    pub write_back: bool,
+    /// The cache supports read allocation.
+    pub read_allocate: bool,
+    /// The cache supports write allocation.
+    pub write_allocate: bool,
+}
+
+impl CacheGeometry {
+    /// The size of the cache in bytes.
+    pub fn size(&self) -> u32 {
+        self.line_size * self.associativity * self.sets
+    }
+
+    fn from_ccsidr(ccsidr: Ccsidr) -> Self {
+        Self {
+            // The line size is given as log2 of the number of words, minus 2.
+            line_size: 16 << ccsidr.linesize(),
+            associativity: ccsidr.associativity() + 1,
+            sets: ccsidr.numsets() + 1,
+            write_through: ccsidr.wt(),
+            write_back: ccsidr.wb(),
+            read_allocate: ccsidr.ra(),
+            write_allocate: ccsidr.wa(),
+        }
+    }
+}
+
+/// The level 1 cache configuration of a core.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheConfiguration {
+    /// The instruction cache, if implemented.
+    pub instruction_cache: Option<CacheGeometry>,
+    /// The data cache, if implemented. For a unified cache, this describes the unified cache.
+    pub data_cache: Option<CacheGeometry>,
+    /// The smallest data cache line size in bytes, as reported in the CTR.
+    pub data_line_size: u32,
+    /// The smallest instruction cache line size in bytes, as reported in the CTR.
+    pub instruction_line_size: u32,
+}
+
+/// Checks that the core implements the cache identification registers.
+fn check_core_type(core: &mut Core) -> Result<(), Error> {
+    match core.core_type() {
+        CoreType::Armv7m | CoreType::Armv7em | CoreType::Armv8m => Ok(()),
+        _ => Err(Error::ArchitectureRequired(&["ARMv7", "ARMv8"])),
+    }
+}
+
+/// Selects the level 1 instruction or data cache in the CSSELR, and reads its geometry.
+fn read_cache_geometry(core: &mut Core, instruction: bool) -> Result<CacheGeometry, Error> {
+    let mut csselr = Csselr(0);
+    csselr.set_ind(instruction);
+    core.write_word_32(Csselr::ADDRESS, csselr.into())?;
+
+    let ccsidr = Ccsidr(core.read_word_32(Ccsidr::ADDRESS)?);
+    Ok(CacheGeometry::from_ccsidr(ccsidr))
+}
+
+/// Reads the level 1 cache configuration of the core.
+///
+/// The CSSELR is restored afterwards, so the firmware is not disturbed.
+pub fn read_cache_configuration(core: &mut Core) -> Result<CacheConfiguration, Error> {
+    check_core_type(core)?;
+
+    let clidr = Clidr(core.read_word_32(Clidr::ADDRESS)?);
+    let ctr = Ctr(core.read_word_32(Ctr::ADDRESS)?);
+
+    let previous_csselr = core.read_word_32(Csselr::ADDRESS)?;
+
+    let instruction_cache = if clidr.has_instruction_cache() {
+        Some(read_cache_geometry(core, true)?)
+    } else {
+        None
+    };
+
+    let data_cache = if clidr.has_data_cache() {
+        Some(read_cache_geometry(core, false)?)
+    } else {
+        None
+    };
+
+    core.write_word_32(Csselr::ADDRESS, previous_csselr)?;
+
+    Ok(CacheConfiguration {
+        instruction_cache,
+        data_cache,
+        data_line_size: ctr.data_line_size(),
+        instruction_line_size: ctr.instruction_line_size(),
+    })
+}
+
+/// Performs the cache maintenance operation at `register` for every cache line in `range`.
+fn maintain_lines(
+    core: &mut Core,
+    register: u64,
+    line_size: u32,
+    range: Range<u64>,
+) -> Result<(), Error> {
+    if range.is_empty() {
+        return Ok(());
+    }
+
+    let start = valid_32bit_address(range.start)? & !(line_size - 1);
+    let end = valid_32bit_address(range.end - 1)?;
+
+    for address in (start..=end).step_by(line_size as usize) {
+        core.write_word_32(register, address)?;
+    }
+
+    Ok(())
+}
+
+/// Invalidates the data cache lines which contain the addresses in `range`.
+///
+/// Use this after modifying memory behind the data cache, so the core does not use stale cached
+/// data. Dirty lines are discarded, so data written by the core which was not written back to
+/// memory yet is lost. Use [`clean_invalidate_data_cache_lines`] to keep it.
+pub fn invalidate_data_cache_lines(core: &mut Core, range: Range<u64>) -> Result<(), Error> {
+    check_core_type(core)?;
+    let line_size = Ctr(core.read_word_32(Ctr::ADDRESS)?).data_line_size();
+    maintain_lines(core, DCIMVAC, line_size, range)
+}
+
+/// Cleans the data cache lines which contain the addresses in `range`.
+///
+/// This writes data written by the core, which is still held in the cache, back to memory.
+pub fn clean_data_cache_lines(core: &mut Core, range: Range<u64>) -> Result<(), Error> {
+    check_core_type(core)?;
+    let line_size = Ctr(core.read_word_32(Ctr::ADDRESS)?).data_line_size();
+    maintain_lines(core, DCCMVAC, line_size, range)
+}
+
+/// Cleans and invalidates the data cache lines which contain the addresses in `range`.
+pub fn clean_invalidate_data_cache_lines(core: &mut Core, range: Range<u64>) -> Result<(), Error> {
+    check_core_type(core)?;
+    let line_size = Ctr(core.read_word_32(Ctr::ADDRESS)?).data_line_size();
+    maintain_lines(core, DCCIMVAC, line_size, range)
+}
+
+/// Invalidates the instruction cache lines which contain the addresses in `range`.
+///
+/// Use this after modifying code, e.g. when downloading code into RAM.
+pub fn invalidate_instruction_cache_lines(core: &mut Core, range: Range<u64>) -> Result<(), Error> {
+    check_core_type(core)?;
+    let line_size = Ctr(core.read_word_32(Ctr::ADDRESS)?).instruction_line_size();
+    maintain_lines(core, ICIMVAU, line_size, range)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{CacheGeometry, Ccsidr, Clidr, Ctr};
+
+    #[test]
+    fn decode_cache_types() {
+        // Cortex-M7 with separate instruction and data caches.
+        let clidr = Clidr(0x0900_0003);
+        assert!(clidr.has_instruction_cache());
+        assert!(clidr.has_data_cache());
+
+        let clidr = Clidr(0x0000_0004);
+        assert!(!clidr.has_instruction_cache());
+        assert!(clidr.has_data_cache());
+
+        let clidr = Clidr(0);
+        assert!(!clidr.has_instruction_cache());
+        assert!(!clidr.has_data_cache());
+    }
+
+    #[test]
+    fn decode_line_sizes() {
+        let ctr = Ctr(0x8303_C003);
+        assert_eq!(ctr.data_line_size(), 32);
+        assert_eq!(ctr.instruction_line_size(), 32);
+    }
+
+    #[test]
+    fn decode_cache_geometry() {
+        // The 16 KiB 4-way data cache of a Cortex-M7.
+        let geometry = CacheGeometry::from_ccsidr(Ccsidr(0xF00F_E019));
+        assert_eq!(
+            geometry,
+            CacheGeometry {
+                line_size: 32,
+                associativity: 4,
+                sets: 128,
+                write_through: true,
+                write_back: true,
+                read_allocate: true,
+                write_allocate: true,
+            }
+        );
+        assert_eq!(geometry.size(), 16 * 1024);
+
+        // The 16 KiB 2-way instruction cache of a Cortex-M7.
+        let geometry = CacheGeometry::from_ccsidr(Ccsidr(0x001F_E009));
+        assert_eq!(geometry.associativity, 2);
+        assert_eq!(geometry.sets, 256);
+        assert!(!geometry.write_back);
+        assert_eq!(geometry.size(), 16 * 1024);
+    }
+}
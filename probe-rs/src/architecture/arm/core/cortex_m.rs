@@ -1,8 +1,9 @@
 //! Common functions and data types for Cortex-M core variants
 
 use crate::{
-    architecture::arm::memory::adi_v5_memory_interface::ArmProbe, DebugProbeError, Error,
-    MemoryMappedRegister, RegisterId,
+    architecture::arm::{memory::adi_v5_memory_interface::ArmProbe, sequences::ArmDebugSequence},
+    CoreInformation, CoreInterface, CoreStatus, CoreType, DebugProbeError, Error, HaltReason,
+    MemoryMappedRegister, RegisterId, RegisterValue,
 };
 
 use bitfield::bitfield;
@@ -163,6 +164,214 @@ impl MemoryMappedRegister for Mvfr0 {
     const NAME: &'static str = "MVFR0";
 }
 
+bitfield! {
+    /// CPUID Base Register
+    #[derive(Copy, Clone)]
+    pub struct Cpuid(u32);
+    impl Debug;
+    pub implementer, _: 31, 24;
+    pub variant, _: 23, 20;
+    pub partno, _: 15, 4;
+    pub revision, _: 3, 0;
+}
+
+impl Cpuid {
+    /// The part number of the Cortex-M7.
+    const PARTNO_CORTEX_M7: u32 = 0xC27;
+
+    /// The implementer code of Arm.
+    const IMPLEMENTER_ARM: u32 = 0x41;
+
+    pub fn is_cortex_m7(&self) -> bool {
+        self.implementer() == Self::IMPLEMENTER_ARM && self.partno() == Self::PARTNO_CORTEX_M7
+    }
+}
+
+impl From<u32> for Cpuid {
+    fn from(value: u32) -> Self {
+        Self(value)
+    }
+}
+
+impl From<Cpuid> for u32 {
+    fn from(value: Cpuid) -> Self {
+        value.0
+    }
+}
+
+impl MemoryMappedRegister for Cpuid {
+    const ADDRESS: u64 = 0xE000_ED00;
+    const NAME: &'static str = "CPUID";
+}
+
+/// Auxiliary Fault Status Register, with implementation defined contents.
+///
+/// This register is not implemented on ARMv6-M cores.
+#[derive(Debug, Copy, Clone)]
+pub struct Afsr(pub u32);
+
+impl From<u32> for Afsr {
+    fn from(value: u32) -> Self {
+        Self(value)
+    }
+}
+
+impl From<Afsr> for u32 {
+    fn from(value: Afsr) -> Self {
+        value.0
+    }
+}
+
+impl MemoryMappedRegister for Afsr {
+    const ADDRESS: u64 = 0xE000_ED3C;
+    const NAME: &'static str = "AFSR";
+}
+
+/// Checks that the reset catch halted the core after a reset, for `reset_and_halt`.
+///
+/// `halted` is the result of waiting up to `timeout` for the halt. If the core did not halt,
+/// the reset catch is cleared again and the error is returned, which is
+/// [`DebugProbeError::Timeout`] if the core did not halt in time.
+pub(crate) fn check_reset_catch(
+    sequence: &dyn ArmDebugSequence,
+    memory: &mut dyn ArmProbe,
+    core_type: CoreType,
+    timeout: Duration,
+    halted: Result<(), Error>,
+) -> Result<(), Error> {
+    if let Err(error) = halted {
+        sequence.reset_catch_clear(memory, core_type, None)?;
+
+        if matches!(error, Error::Probe(DebugProbeError::Timeout)) {
+            tracing::warn!("The core did not halt after reset within {:?}", timeout);
+        }
+
+        return Err(error);
+    }
+
+    Ok(())
+}
+
+/// Returns `true` if `instruction` is a Thumb `BKPT` instruction, with any immediate.
+pub(crate) fn is_bkpt_instruction(instruction: u16) -> bool {
+    instruction & 0xFF00 == 0xBE00
+}
+
+/// The encoding of the semihosting `BKPT 0xAB` instruction in Thumb state.
+const SEMIHOSTING_BKPT: u16 = 0xBEAB;
+
+/// How the instruction at the program counter of a halted core is stepped, see [`prepare_step`].
+pub(crate) enum StepAction {
+    /// The instruction was skipped, and the core is halted at the next instruction.
+    Skipped(CoreInformation),
+    /// The instruction has to be stepped, with the hardware breakpoints disabled if
+    /// `disable_breakpoints` is set.
+    Step { disable_breakpoints: bool },
+}
+
+/// Prepares stepping the instruction at the program counter of a halted core.
+///
+/// Stepping a `BKPT` instruction halts the core again without executing it, so it is skipped by
+/// advancing the program counter instead. The semihosting `BKPT 0xAB` is stepped, so the core
+/// halts on it again and the request can be serviced with
+/// [`Core::handle_semihosting`](crate::Core::handle_semihosting).
+///
+/// A hardware breakpoint at the instruction would also halt the core again before it is
+/// executed, so the breakpoints have to be disabled while stepping, and enabled again afterwards.
+pub(crate) fn prepare_step(
+    core: &mut impl CoreInterface,
+    hw_breakpoints_enabled: bool,
+    current_state: CoreStatus,
+) -> Result<StepAction, Error> {
+    let pc = core.read_core_reg(core.registers().program_counter().id)?;
+
+    let mut instruction = [0u8; 2];
+    core.read_8(pc.try_into()?, &mut instruction)?;
+    let instruction = u16::from_le_bytes(instruction);
+
+    if is_bkpt_instruction(instruction) && instruction != SEMIHOSTING_BKPT {
+        tracing::debug!(
+            "Encountered a breakpoint instruction @ {}. Advancing the program counter to the \
+             next instruction.",
+            pc
+        );
+
+        let mut next_pc = pc;
+        next_pc.incremenet_address(2)?;
+        core.write_core_reg(core.registers().program_counter().id, next_pc)?;
+
+        return Ok(StepAction::Skipped(CoreInformation {
+            pc: next_pc.try_into()?,
+        }));
+    }
+
+    let disable_breakpoints = hw_breakpoints_enabled
+        && (matches!(current_state, CoreStatus::Halted(HaltReason::Breakpoint(_)))
+            || core.hw_breakpoints()?.contains(&pc.try_into().ok()));
+
+    Ok(StepAction::Step {
+        disable_breakpoints,
+    })
+}
+
+/// The register selector of S0 in DCRSR, S1-S31 follow it.
+const FP_SINGLE_REGISTER_BASE: u16 = 0b100_0000;
+
+/// The ID of D0 in the register file, D1-D15 follow it.
+///
+/// The double precision registers can't be selected in DCRSR, so they use IDs outside of the
+/// range of register selectors.
+const FP_DOUBLE_REGISTER_BASE: u16 = 0x100;
+
+/// Returns the IDs of the two single precision registers which make up the double precision
+/// register with the given ID, or `None` if the ID isn't one of D0-D15.
+fn fp_double_register_halves(addr: RegisterId) -> Option<(RegisterId, RegisterId)> {
+    let index = addr.0.checked_sub(FP_DOUBLE_REGISTER_BASE)?;
+
+    (index < 16).then(|| {
+        let low = FP_SINGLE_REGISTER_BASE + 2 * index;
+        (RegisterId(low), RegisterId(low + 1))
+    })
+}
+
+/// Reads a core register, including the double precision registers D0-D15 of the FPU.
+///
+/// The FPU registers are always read from the register file of the core. If the core halted
+/// in an exception handler while the lazy preservation of the floating-point context is still
+/// pending (`FPCCR.LSPACT`), the registers haven't been stacked yet and still hold the values of
+/// the interrupted code, so they can be read and written like in any other context.
+pub(crate) fn read_core_reg_value(
+    memory: &mut dyn ArmProbe,
+    addr: RegisterId,
+) -> Result<RegisterValue, Error> {
+    match fp_double_register_halves(addr) {
+        Some((low, high)) => {
+            let low = read_core_reg(memory, low)?;
+            let high = read_core_reg(memory, high)?;
+
+            Ok(RegisterValue::U64(((high as u64) << 32) | low as u64))
+        }
+        None => Ok(read_core_reg(memory, addr)?.into()),
+    }
+}
+
+/// Writes a core register, including the double precision registers D0-D15 of the FPU.
+pub(crate) fn write_core_reg_value(
+    memory: &mut dyn ArmProbe,
+    addr: RegisterId,
+    value: RegisterValue,
+) -> Result<(), Error> {
+    match fp_double_register_halves(addr) {
+        Some((low, high)) => {
+            let value: u64 = value.try_into()?;
+
+            write_core_reg(memory, low, value as u32)?;
+            write_core_reg(memory, high, (value >> 32) as u32)
+        }
+        None => write_core_reg(memory, addr, value.try_into()?),
+    }
+}
+
 pub(crate) fn read_core_reg(memory: &mut dyn ArmProbe, addr: RegisterId) -> Result<u32, Error> {
     // Write the DCRSR value to select the register we want to read.
     let mut dcrsr_val = Dcrsr(0);
@@ -214,3 +423,41 @@ fn wait_for_core_register_transfer(
     }
     Err(Error::Probe(DebugProbeError::Timeout))
 }
+
+#[cfg(test)]
+mod test {
+    use super::{fp_double_register_halves, is_bkpt_instruction, Cpuid};
+    use crate::RegisterId;
+
+    #[test]
+    fn detect_bkpt_instructions() {
+        assert!(is_bkpt_instruction(0xBE00));
+        assert!(is_bkpt_instruction(0xBEAB));
+        // NOP
+        assert!(!is_bkpt_instruction(0xBF00));
+    }
+
+    #[test]
+    fn double_registers_map_to_single_registers() {
+        assert_eq!(
+            fp_double_register_halves(RegisterId(0x100)),
+            Some((RegisterId(64), RegisterId(65)))
+        );
+        assert_eq!(
+            fp_double_register_halves(RegisterId(0x10F)),
+            Some((RegisterId(94), RegisterId(95)))
+        );
+        assert_eq!(fp_double_register_halves(RegisterId(0x110)), None);
+        assert_eq!(fp_double_register_halves(RegisterId(64)), None);
+    }
+
+    #[test]
+    fn detect_cortex_m7() {
+        // Cortex-M7 r1p2
+        assert!(Cpuid::from(0x411F_C272).is_cortex_m7());
+        // Cortex-M4 r0p1
+        assert!(!Cpuid::from(0x410F_C241).is_cortex_m7());
+        // Same part number, but not implemented by Arm.
+        assert!(!Cpuid::from(0x511F_C272).is_cortex_m7());
+    }
+}
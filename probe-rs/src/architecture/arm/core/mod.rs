@@ -13,12 +13,17 @@ pub mod armv7a;
 pub mod armv7m;
 pub mod armv8a;
 pub mod armv8m;
+pub mod cache;
+pub mod nvic;
+pub mod semihosting;
+pub mod trace_counters;
 
 pub(crate) mod armv7a_debug_regs;
 pub(crate) mod armv8a_core_regs;
 pub(crate) mod armv8a_debug_regs;
 pub(crate) mod cortex_m;
 pub(crate) mod instructions;
+pub(crate) mod watchpoints;
 
 /// Core information data which is downloaded from the target, represents its state and can be used for debugging.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -112,6 +117,100 @@ pub(crate) mod register {
         size_in_bits: 32,
     };
 
+    // The registers below are banked between the security states on ARMv8-M cores. The secure
+    // registers are only accessible if the Security Extension is implemented, and the non-secure
+    // stack limit registers only on ARMv8-M Mainline cores.
+
+    // Non-secure main stack pointer.
+    pub const MSP_NS: RegisterDescription = RegisterDescription {
+        name: "MSP_NS",
+        _kind: RegisterKind::General,
+        id: RegisterId(0b0011000),
+        _type: RegisterDataType::UnsignedInteger,
+        size_in_bits: 32,
+    };
+
+    // Non-secure process stack pointer.
+    pub const PSP_NS: RegisterDescription = RegisterDescription {
+        name: "PSP_NS",
+        _kind: RegisterKind::General,
+        id: RegisterId(0b0011001),
+        _type: RegisterDataType::UnsignedInteger,
+        size_in_bits: 32,
+    };
+
+    // Secure main stack pointer.
+    pub const MSP_S: RegisterDescription = RegisterDescription {
+        name: "MSP_S",
+        _kind: RegisterKind::General,
+        id: RegisterId(0b0011010),
+        _type: RegisterDataType::UnsignedInteger,
+        size_in_bits: 32,
+    };
+
+    // Secure process stack pointer.
+    pub const PSP_S: RegisterDescription = RegisterDescription {
+        name: "PSP_S",
+        _kind: RegisterKind::General,
+        id: RegisterId(0b0011011),
+        _type: RegisterDataType::UnsignedInteger,
+        size_in_bits: 32,
+    };
+
+    // Secure main stack limit.
+    pub const MSPLIM_S: RegisterDescription = RegisterDescription {
+        name: "MSPLIM_S",
+        _kind: RegisterKind::General,
+        id: RegisterId(0b0011100),
+        _type: RegisterDataType::UnsignedInteger,
+        size_in_bits: 32,
+    };
+
+    // Secure process stack limit.
+    pub const PSPLIM_S: RegisterDescription = RegisterDescription {
+        name: "PSPLIM_S",
+        _kind: RegisterKind::General,
+        id: RegisterId(0b0011101),
+        _type: RegisterDataType::UnsignedInteger,
+        size_in_bits: 32,
+    };
+
+    // Non-secure main stack limit.
+    pub const MSPLIM_NS: RegisterDescription = RegisterDescription {
+        name: "MSPLIM_NS",
+        _kind: RegisterKind::General,
+        id: RegisterId(0b0011110),
+        _type: RegisterDataType::UnsignedInteger,
+        size_in_bits: 32,
+    };
+
+    // Non-secure process stack limit.
+    pub const PSPLIM_NS: RegisterDescription = RegisterDescription {
+        name: "PSPLIM_NS",
+        _kind: RegisterKind::General,
+        id: RegisterId(0b0011111),
+        _type: RegisterDataType::UnsignedInteger,
+        size_in_bits: 32,
+    };
+
+    // Secure CONTROL, FAULTMASK, BASEPRI and PRIMASK, packed like EXTRA.
+    pub const EXTRA_S: RegisterDescription = RegisterDescription {
+        name: "EXTRA_S",
+        _kind: RegisterKind::General,
+        id: RegisterId(0b0100010),
+        _type: RegisterDataType::UnsignedInteger,
+        size_in_bits: 32,
+    };
+
+    // Non-secure CONTROL, FAULTMASK, BASEPRI and PRIMASK, packed like EXTRA.
+    pub const EXTRA_NS: RegisterDescription = RegisterDescription {
+        name: "EXTRA_NS",
+        _kind: RegisterKind::General,
+        id: RegisterId(0b0100011),
+        _type: RegisterDataType::UnsignedInteger,
+        size_in_bits: 32,
+    };
+
     pub const FP: RegisterDescription = RegisterDescription {
         name: "FP",
         _kind: RegisterKind::General,
@@ -313,6 +412,7 @@ static ARM32_COMMON_REGS: RegisterFile = RegisterFile {
 
     fp_status: None,
     fp_registers: None,
+    fp_double_registers: None,
 };
 
 static AARCH32_COMMON_REGS: RegisterFile = RegisterFile {
@@ -910,10 +1010,156 @@ static CORTEX_M_WITH_FP_REGS: RegisterFile = RegisterFile {
             size_in_bits: 32,
         },
     ]),
+    // The IDs of D0-D15 are not valid register selectors for DCRSR, they are accessed through
+    // the two overlapping single precision registers, see `cortex_m::read_core_reg_value`.
+    fp_double_registers: Some(&[
+        RegisterDescription {
+            name: "D0",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(256),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 64,
+        },
+        RegisterDescription {
+            name: "D1",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(257),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 64,
+        },
+        RegisterDescription {
+            name: "D2",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(258),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 64,
+        },
+        RegisterDescription {
+            name: "D3",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(259),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 64,
+        },
+        RegisterDescription {
+            name: "D4",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(260),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 64,
+        },
+        RegisterDescription {
+            name: "D5",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(261),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 64,
+        },
+        RegisterDescription {
+            name: "D6",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(262),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 64,
+        },
+        RegisterDescription {
+            name: "D7",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(263),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 64,
+        },
+        RegisterDescription {
+            name: "D8",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(264),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 64,
+        },
+        RegisterDescription {
+            name: "D9",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(265),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 64,
+        },
+        RegisterDescription {
+            name: "D10",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(266),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 64,
+        },
+        RegisterDescription {
+            name: "D11",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(267),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 64,
+        },
+        RegisterDescription {
+            name: "D12",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(268),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 64,
+        },
+        RegisterDescription {
+            name: "D13",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(269),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 64,
+        },
+        RegisterDescription {
+            name: "D14",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(270),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 64,
+        },
+        RegisterDescription {
+            name: "D15",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(271),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 64,
+        },
+    ]),
 
     ..CORTEX_M_COMMON_REGS
 };
 
+/// Registers of ARMv8-M cores, which are not available on earlier Cortex-M cores.
+///
+/// Besides [`register::EXTRA`], these are the registers banked between the security states,
+/// which can be looked up with [`RegisterFile::other_by_name`].
+static ARMV8M_OTHER_REGS: &[RegisterDescription] = &[
+    register::EXTRA,
+    register::MSP_NS,
+    register::PSP_NS,
+    register::MSP_S,
+    register::PSP_S,
+    register::MSPLIM_S,
+    register::PSPLIM_S,
+    register::MSPLIM_NS,
+    register::PSPLIM_NS,
+    register::EXTRA_S,
+    register::EXTRA_NS,
+];
+
+static ARMV8M_COMMON_REGS: RegisterFile = RegisterFile {
+    other: ARMV8M_OTHER_REGS,
+
+    ..CORTEX_M_COMMON_REGS
+};
+
+static ARMV8M_WITH_FP_REGS: RegisterFile = RegisterFile {
+    other: ARMV8M_OTHER_REGS,
+
+    ..CORTEX_M_WITH_FP_REGS
+};
+
 bitfield! {
     #[derive(Copy, Clone)]
     pub struct Dfsr(u32);
@@ -0,0 +1,107 @@
+//! Reading the interrupt state of the Nested Vectored Interrupt Controller of Cortex-M cores.
+//!
+//! See "ARMv7-M Architecture Reference Manual", chapter B3.4 for details.
+
+use crate::{Core, CoreType, Error, MemoryInterface};
+
+/// Interrupt Controller Type Register, contains the number of implemented interrupt lines.
+const ICTR: u64 = 0xE000_E004;
+/// The first Interrupt Set-Enable Register.
+const ISER: u64 = 0xE000_E100;
+/// The first Interrupt Set-Pending Register.
+const ISPR: u64 = 0xE000_E200;
+/// The first Interrupt Active Bit Register.
+const IABR: u64 = 0xE000_E300;
+/// The first Interrupt Priority Register.
+const IPR: u64 = 0xE000_E400;
+
+/// The state of a single external interrupt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InterruptState {
+    /// The number of the interrupt, starting at 0 for the first external interrupt.
+    pub irq: u32,
+    /// The interrupt is enabled.
+    pub enabled: bool,
+    /// The interrupt is pending.
+    pub pending: bool,
+    /// The interrupt is active, or active and preempted by a higher priority interrupt.
+    ///
+    /// ARMv6-M cores don't report this, so it is always `false` for them.
+    pub active: bool,
+    /// The raw priority of the interrupt, a lower value is a higher priority.
+    ///
+    /// Only the upper bits are implemented, depending on the core. The unimplemented bits read as
+    /// zero.
+    pub priority: u8,
+}
+
+/// The state of all implemented external interrupts of a core.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NvicState {
+    /// The interrupts, ordered by their number.
+    ///
+    /// This contains all interrupt lines supported by the NVIC configuration, which can be more
+    /// than the interrupts actually used by the chip.
+    pub interrupts: Vec<InterruptState>,
+}
+
+impl NvicState {
+    /// Returns the state of the interrupt with number `irq`.
+    pub fn interrupt(&self, irq: u32) -> Option<&InterruptState> {
+        self.interrupts.get(irq as usize)
+    }
+
+    /// Returns the interrupts which are both enabled and pending.
+    pub fn pending(&self) -> impl Iterator<Item = &InterruptState> {
+        self.interrupts
+            .iter()
+            .filter(|interrupt| interrupt.enabled && interrupt.pending)
+    }
+
+    /// Returns the interrupts which are active.
+    pub fn active(&self) -> impl Iterator<Item = &InterruptState> {
+        self.interrupts.iter().filter(|interrupt| interrupt.active)
+    }
+}
+
+/// Reads and decodes the enable, pending, active and priority registers of the NVIC.
+///
+/// The registers are memory mapped, so this works while the core is running.
+pub(crate) fn read_nvic_state(core: &mut Core) -> Result<NvicState, Error> {
+    // ARMv6-M supports at most 32 interrupts, and has neither ICTR nor IABR.
+    let (words, has_active) = match core.core_type() {
+        CoreType::Armv6m => (1, false),
+        CoreType::Armv7m | CoreType::Armv7em | CoreType::Armv8m => {
+            let ictr = core.read_word_32(ICTR)?;
+            (((ictr & 0xF) + 1) as usize, true)
+        }
+        _ => return Err(Error::ArchitectureRequired(&["ARMv6", "ARMv7", "ARMv8"])),
+    };
+
+    let mut enabled = vec![0; words];
+    let mut pending = vec![0; words];
+    let mut active = vec![0; words];
+    let mut priorities = vec![0; words * 8];
+
+    core.read_32(ISER, &mut enabled)?;
+    core.read_32(ISPR, &mut pending)?;
+    if has_active {
+        core.read_32(IABR, &mut active)?;
+    }
+    // ARMv6-M only supports word accesses to the priority registers.
+    core.read_32(IPR, &mut priorities)?;
+
+    let bit = |words: &[u32], irq: usize| words[irq / 32] & (1 << (irq % 32)) != 0;
+
+    let interrupts = (0..words * 32)
+        .map(|irq| InterruptState {
+            irq: irq as u32,
+            enabled: bit(&enabled, irq),
+            pending: bit(&pending, irq),
+            active: bit(&active, irq),
+            priority: priorities[irq / 4].to_le_bytes()[irq % 4],
+        })
+        .collect();
+
+    Ok(NvicState { interrupts })
+}
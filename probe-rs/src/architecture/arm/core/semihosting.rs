@@ -0,0 +1,672 @@
+//! Servicing semihosting requests of Cortex-M cores.
+//!
+//! The firmware requests an operation with a `BKPT 0xAB` instruction, with the operation number
+//! in `r0` and a pointer to the parameters in `r1`. The debugger performs the operation on the
+//! host, writes the result to `r0` and resumes the core after the breakpoint instruction.
+//!
+//! See "Semihosting for AArch32 and AArch64", version 3.0, for details.
+
+use crate::memory::read_cstring_bytes;
+use crate::{Core, CoreStatus, Error, HaltReason, InstructionSet, MemoryInterface};
+
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Component, Path, PathBuf};
+
+/// The encoding of the `BKPT 0xAB` instruction in Thumb state.
+const SEMIHOSTING_BKPT: u16 = 0xBEAB;
+
+const SYS_OPEN: u32 = 0x01;
+const SYS_CLOSE: u32 = 0x02;
+const SYS_WRITEC: u32 = 0x03;
+const SYS_WRITE0: u32 = 0x04;
+const SYS_WRITE: u32 = 0x05;
+const SYS_READ: u32 = 0x06;
+const SYS_READC: u32 = 0x07;
+const SYS_EXIT: u32 = 0x18;
+
+/// `ADP_Stopped_ApplicationExit`, the reason of `SYS_EXIT` for a normal exit of the firmware.
+pub const ADP_STOPPED_APPLICATION_EXIT: u32 = 0x2_0026;
+
+/// The name of the console, which is opened by `SYS_OPEN` to access stdin and stdout.
+const CONSOLE_NAME: &[u8] = b":tt";
+
+/// The return value of failed operations.
+const FAILURE: u32 = u32::MAX;
+
+/// The maximum length of a file name passed to `SYS_OPEN`.
+const MAX_NAME_LENGTH: u32 = 1024;
+/// The maximum number of bytes transferred by a single `SYS_READ` or `SYS_WRITE`.
+///
+/// The lengths are controlled by the firmware, so they are limited to avoid allocating
+/// arbitrarily large buffers on the host.
+const MAX_TRANSFER_LENGTH: u32 = 64 * 1024;
+/// The maximum length of a string written with `SYS_WRITE0`, longer strings are truncated.
+const MAX_STRING_LENGTH: usize = 4096;
+
+/// The result of [`Core::handle_semihosting`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SemihostingOutcome {
+    /// The core is not halted on a semihosting breakpoint, e.g. it is running, or it hit a
+    /// regular breakpoint.
+    NotSemihosting,
+    /// The request was serviced, and the core was resumed.
+    Handled,
+    /// The firmware exited with `SYS_EXIT`. The core stays halted.
+    Exit {
+        /// The reason of the exit, [`ADP_STOPPED_APPLICATION_EXIT`] for a normal exit.
+        reason: u32,
+    },
+}
+
+/// The action to take after an operation was serviced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Response {
+    /// Return the value in `r0` and resume the core.
+    Return(u32),
+    /// The firmware exited with `SYS_EXIT`.
+    Exit { reason: u32 },
+}
+
+/// A file opened by the firmware.
+enum Handle {
+    ConsoleInput,
+    ConsoleOutput,
+    File(File),
+}
+
+/// The host side state of semihosting, e.g. the files opened by the firmware.
+///
+/// The console of the firmware is connected to the stdin and stdout of the host process by
+/// default, see [`SemihostingHandler::with_console`].
+///
+/// By default, the firmware can only access the console. Access to the files in a single
+/// directory of the host has to be granted with [`SemihostingHandler::with_file_access`].
+pub struct SemihostingHandler {
+    input: Box<dyn Read + Send>,
+    output: Box<dyn Write + Send>,
+    /// The directory the file names of the firmware are resolved against, if file access is
+    /// enabled.
+    file_root: Option<PathBuf>,
+    handles: HashMap<u32, Handle>,
+    next_handle: u32,
+}
+
+impl std::fmt::Debug for SemihostingHandler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SemihostingHandler")
+            .field("file_root", &self.file_root)
+            .field("open_handles", &self.handles.len())
+            .finish()
+    }
+}
+
+impl Default for SemihostingHandler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SemihostingHandler {
+    /// Creates a handler, which connects the console of the firmware to the stdin and stdout of
+    /// the host process.
+    pub fn new() -> Self {
+        Self::with_console(std::io::stdin(), std::io::stdout())
+    }
+
+    /// Creates a handler, which reads the console input of the firmware from `input`, and writes
+    /// its console output to `output`.
+    pub fn with_console(
+        input: impl Read + Send + 'static,
+        output: impl Write + Send + 'static,
+    ) -> Self {
+        Self {
+            input: Box::new(input),
+            output: Box::new(output),
+            file_root: None,
+            handles: HashMap::new(),
+            // Zero is not a valid handle for the firmware.
+            next_handle: 1,
+        }
+    }
+
+    /// Allows the firmware to open, read and write the files in the directory `root`.
+    ///
+    /// The file names of the firmware are resolved relative to `root`. Absolute names and names
+    /// containing `..` are rejected. Symbolic links in `root` are followed, so they must not
+    /// point to files the firmware should not access.
+    pub fn with_file_access(mut self, root: impl Into<PathBuf>) -> Self {
+        self.file_root = Some(root.into());
+        self
+    }
+
+    /// Returns the path of the file `name` of the firmware, or `None` if file access is disabled
+    /// or the name points outside of the root directory.
+    fn resolve_path(&self, name: &str) -> Option<PathBuf> {
+        let root = self.file_root.as_ref()?;
+        let path = Path::new(name);
+
+        path.components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+            .then(|| root.join(path))
+    }
+
+    /// Services `operation` with the parameter block or value `parameters`.
+    fn service(
+        &mut self,
+        memory: &mut dyn MemoryInterface,
+        operation: u32,
+        parameters: u32,
+    ) -> Result<Response, Error> {
+        let result = match operation {
+            SYS_OPEN => self.open(memory, parameters)?,
+            SYS_CLOSE => self.close(memory, parameters)?,
+            SYS_WRITEC => {
+                let mut character = [0];
+                memory.read_8(parameters as u64, &mut character)?;
+                self.write_console(&character);
+                // The return value is not defined, so it is left unchanged.
+                operation
+            }
+            SYS_WRITE0 => {
+                let string = read_string(memory, parameters)?;
+                self.write_console(&string);
+                operation
+            }
+            SYS_WRITE => self.write(memory, parameters)?,
+            SYS_READ => self.read(memory, parameters)?,
+            SYS_READC => self.read_console(),
+            // On AArch32, the reason is passed in r1 directly.
+            SYS_EXIT => return Ok(Response::Exit { reason: parameters }),
+            _ => {
+                tracing::warn!("Semihosting: Unsupported operation {:#x}", operation);
+                FAILURE
+            }
+        };
+
+        Ok(Response::Return(result))
+    }
+
+    fn open(&mut self, memory: &mut dyn MemoryInterface, parameters: u32) -> Result<u32, Error> {
+        let [name_address, mode, name_length] = read_parameters(memory, parameters)?;
+
+        if name_length > MAX_NAME_LENGTH {
+            tracing::warn!(
+                "Semihosting: File name of {} bytes is too long",
+                name_length
+            );
+            return Ok(FAILURE);
+        }
+
+        let mut name = vec![0; name_length as usize];
+        memory.read_8(name_address as u64, &mut name)?;
+
+        let mode = match OpenMode::from_raw(mode) {
+            Some(mode) => mode,
+            None => return Ok(FAILURE),
+        };
+
+        // Handles are not reused, and the last one would be the same as the failure value.
+        let next_handle = match self.next_handle.checked_add(1) {
+            Some(next_handle) => next_handle,
+            None => {
+                tracing::warn!("Semihosting: No more file handles are available");
+                return Ok(FAILURE);
+            }
+        };
+
+        let handle = if name == CONSOLE_NAME {
+            if mode.read {
+                Handle::ConsoleInput
+            } else {
+                Handle::ConsoleOutput
+            }
+        } else {
+            let name = String::from_utf8_lossy(&name).into_owned();
+
+            let path = match self.resolve_path(&name) {
+                Some(path) => path,
+                None => {
+                    tracing::warn!("Semihosting: Access to the file {} is not allowed", name);
+                    return Ok(FAILURE);
+                }
+            };
+
+            match mode.options().open(&path) {
+                Ok(file) => Handle::File(file),
+                Err(error) => {
+                    tracing::warn!("Semihosting: Failed to open {}: {}", path.display(), error);
+                    return Ok(FAILURE);
+                }
+            }
+        };
+
+        let number = self.next_handle;
+        self.next_handle = next_handle;
+        self.handles.insert(number, handle);
+
+        Ok(number)
+    }
+
+    fn close(&mut self, memory: &mut dyn MemoryInterface, parameters: u32) -> Result<u32, Error> {
+        let [handle] = read_parameters(memory, parameters)?;
+
+        Ok(match self.handles.remove(&handle) {
+            Some(_) => 0,
+            None => FAILURE,
+        })
+    }
+
+    /// Writes to the handle, and returns the number of bytes which were *not* written.
+    fn write(&mut self, memory: &mut dyn MemoryInterface, parameters: u32) -> Result<u32, Error> {
+        let [handle, address, length] = read_parameters(memory, parameters)?;
+
+        if length > MAX_TRANSFER_LENGTH {
+            tracing::warn!("Semihosting: Write of {} bytes is too large", length);
+            return Ok(FAILURE);
+        }
+
+        let mut data = vec![0; length as usize];
+        memory.read_8(address as u64, &mut data)?;
+
+        let result = match self.handles.get_mut(&handle) {
+            Some(Handle::ConsoleOutput) => self
+                .output
+                .write_all(&data)
+                .and_then(|_| self.output.flush()),
+            Some(Handle::File(file)) => file.write_all(&data),
+            Some(Handle::ConsoleInput) | None => return Ok(length),
+        };
+
+        Ok(match result {
+            Ok(()) => 0,
+            Err(error) => {
+                tracing::warn!(
+                    "Semihosting: Failed to write to handle {}: {}",
+                    handle,
+                    error
+                );
+                length
+            }
+        })
+    }
+
+    /// Reads from the handle, and returns the number of bytes which were *not* read.
+    fn read(&mut self, memory: &mut dyn MemoryInterface, parameters: u32) -> Result<u32, Error> {
+        let [handle, address, length] = read_parameters(memory, parameters)?;
+
+        if length > MAX_TRANSFER_LENGTH {
+            tracing::warn!("Semihosting: Read of {} bytes is too large", length);
+            return Ok(FAILURE);
+        }
+
+        let mut data = vec![0; length as usize];
+
+        let result = match self.handles.get_mut(&handle) {
+            Some(Handle::ConsoleInput) => self.input.read(&mut data),
+            Some(Handle::File(file)) => file.read(&mut data),
+            Some(Handle::ConsoleOutput) | None => return Ok(length),
+        };
+
+        match result {
+            Ok(read) => {
+                memory.write_8(address as u64, &data[..read])?;
+                Ok(length - read as u32)
+            }
+            Err(error) => {
+                tracing::warn!(
+                    "Semihosting: Failed to read from handle {}: {}",
+                    handle,
+                    error
+                );
+                Ok(length)
+            }
+        }
+    }
+
+    fn write_console(&mut self, data: &[u8]) {
+        if let Err(error) = self
+            .output
+            .write_all(data)
+            .and_then(|_| self.output.flush())
+        {
+            tracing::warn!("Semihosting: Failed to write to the console: {}", error);
+        }
+    }
+
+    fn read_console(&mut self) -> u32 {
+        let mut byte = [0];
+
+        match self.input.read(&mut byte) {
+            Ok(1) => byte[0] as u32,
+            Ok(_) => FAILURE,
+            Err(error) => {
+                tracing::warn!("Semihosting: Failed to read from the console: {}", error);
+                FAILURE
+            }
+        }
+    }
+}
+
+/// The access mode of a file opened with `SYS_OPEN`, which corresponds to a mode of `fopen`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct OpenMode {
+    read: bool,
+    write: bool,
+    append: bool,
+    truncate: bool,
+}
+
+impl OpenMode {
+    /// Decodes the mode, where 0 to 11 are `r`, `rb`, `r+`, `r+b`, `w`, `wb`, `w+`, `w+b`, `a`,
+    /// `ab`, `a+` and `a+b`. The binary modes are the same as the text modes.
+    fn from_raw(mode: u32) -> Option<Self> {
+        let update = mode & 0b10 != 0;
+
+        match mode >> 2 {
+            0 => Some(OpenMode {
+                read: true,
+                write: update,
+                append: false,
+                truncate: false,
+            }),
+            1 => Some(OpenMode {
+                read: update,
+                write: true,
+                append: false,
+                truncate: true,
+            }),
+            2 => Some(OpenMode {
+                read: update,
+                write: true,
+                append: true,
+                truncate: false,
+            }),
+            _ => None,
+        }
+    }
+
+    fn options(&self) -> OpenOptions {
+        let mut options = OpenOptions::new();
+        options
+            .read(self.read)
+            .write(self.write && !self.append)
+            .append(self.append)
+            .truncate(self.truncate)
+            .create(self.write);
+        options
+    }
+}
+
+/// Reads the parameter block of an operation.
+fn read_parameters<const N: usize>(
+    memory: &mut dyn MemoryInterface,
+    address: u32,
+) -> Result<[u32; N], Error> {
+    let mut parameters = [0; N];
+    memory.read_32(address as u64, &mut parameters)?;
+    Ok(parameters)
+}
+
+/// Reads the null terminated string at `address`.
+///
+/// Strings which are longer than [`MAX_STRING_LENGTH`], or which reach the end of the address
+/// space, are truncated.
+fn read_string(memory: &mut dyn MemoryInterface, address: u32) -> Result<Vec<u8>, Error> {
+    let end_of_memory = u32::MAX as u64 + 1;
+    let max_len = (end_of_memory - address as u64).min(MAX_STRING_LENGTH as u64) as usize;
+
+    let string = read_cstring_bytes(memory, address as u64, max_len, true)?;
+
+    if string.len() == max_len {
+        tracing::warn!(
+            "Semihosting: Truncated a string without terminator after {} bytes",
+            string.len()
+        );
+    }
+
+    Ok(string)
+}
+
+/// Services a semihosting request, if the core is halted on a semihosting breakpoint, see
+/// [`Core::handle_semihosting`].
+pub(crate) fn handle_semihosting(
+    core: &mut Core,
+    handler: &mut SemihostingHandler,
+) -> Result<SemihostingOutcome, Error> {
+    match core.status()? {
+        CoreStatus::Halted(HaltReason::Breakpoint(_) | HaltReason::Multiple) => {}
+        _ => return Ok(SemihostingOutcome::NotSemihosting),
+    }
+
+    if core.instruction_set()? != InstructionSet::Thumb2 {
+        return Ok(SemihostingOutcome::NotSemihosting);
+    }
+
+    let regs = core.registers();
+    let pc: u32 = core.read_core_reg(regs.program_counter())?;
+
+    // A regular breakpoint is either a hardware breakpoint or a BKPT with another immediate, so
+    // only the instruction at the PC tells them apart.
+    let mut instruction = [0; 2];
+    core.read_8(pc as u64, &mut instruction)?;
+    if u16::from_le_bytes(instruction) != SEMIHOSTING_BKPT {
+        return Ok(SemihostingOutcome::NotSemihosting);
+    }
+
+    let operation: u32 = core.read_core_reg(regs.argument_register(0))?;
+    let parameters: u32 = core.read_core_reg(regs.argument_register(1))?;
+
+    tracing::debug!(
+        "Semihosting: Operation {:#x} with parameters at {:#010x}",
+        operation,
+        parameters
+    );
+
+    let result = match handler.service(core, operation, parameters)? {
+        Response::Return(result) => result,
+        Response::Exit { reason } => return Ok(SemihostingOutcome::Exit { reason }),
+    };
+
+    core.write_core_reg(regs.result_register(0).into(), result)?;
+    core.write_core_reg(regs.program_counter().into(), pc + 2)?;
+    core.run()?;
+
+    Ok(SemihostingOutcome::Handled)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        read_string, OpenMode, Response, SemihostingHandler, FAILURE, MAX_STRING_LENGTH, SYS_OPEN,
+        SYS_WRITE, SYS_WRITE0,
+    };
+    use crate::memory::mock::MockMemory;
+    use crate::MemoryInterface;
+    use std::io::Write;
+    use std::path::PathBuf;
+    use std::sync::{Arc, Mutex};
+
+    const RAM: u64 = 0x2000_0000;
+
+    /// A console output which can be inspected while it is owned by the handler.
+    #[derive(Clone, Default)]
+    struct SharedOutput(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedOutput {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn console_handler() -> (SemihostingHandler, SharedOutput) {
+        let output = SharedOutput::default();
+        let handler = SemihostingHandler::with_console(std::io::empty(), output.clone());
+        (handler, output)
+    }
+
+    #[test]
+    fn write_strings_and_buffers_to_the_console() {
+        let (mut handler, output) = console_handler();
+        let mut memory = MockMemory::new(RAM, 0x100);
+
+        memory.write_8(RAM, b"hello\0").unwrap();
+        assert_eq!(
+            handler
+                .service(&mut memory, SYS_WRITE0, RAM as u32)
+                .unwrap(),
+            Response::Return(SYS_WRITE0)
+        );
+
+        // Open ":tt" with mode "w", which is the console output.
+        memory.write_8(RAM + 0x10, b":tt").unwrap();
+        memory
+            .write_32(RAM + 0x20, &[RAM as u32 + 0x10, 4, 3])
+            .unwrap();
+        let handle = match handler.service(&mut memory, SYS_OPEN, RAM as u32 + 0x20) {
+            Ok(Response::Return(handle)) => handle,
+            other => panic!("Unexpected response {:?}", other),
+        };
+
+        memory.write_8(RAM + 0x30, b" world").unwrap();
+        memory
+            .write_32(RAM + 0x40, &[handle, RAM as u32 + 0x30, 6])
+            .unwrap();
+        assert_eq!(
+            handler
+                .service(&mut memory, SYS_WRITE, RAM as u32 + 0x40)
+                .unwrap(),
+            Response::Return(0)
+        );
+
+        assert_eq!(output.0.lock().unwrap().as_slice(), b"hello world");
+
+        // A write which is larger than the limit fails without reading the memory.
+        memory
+            .write_32(RAM + 0x40, &[handle, RAM as u32 + 0x30, u32::MAX])
+            .unwrap();
+        assert_eq!(
+            handler
+                .service(&mut memory, SYS_WRITE, RAM as u32 + 0x40)
+                .unwrap(),
+            Response::Return(FAILURE)
+        );
+    }
+
+    #[test]
+    fn open_fails_when_the_handles_are_exhausted() {
+        let (mut handler, _) = console_handler();
+        let mut memory = MockMemory::new(RAM, 0x100);
+
+        // Open ":tt" with mode "w", which is the console output.
+        memory.write_8(RAM + 0x10, b":tt").unwrap();
+        memory
+            .write_32(RAM + 0x20, &[RAM as u32 + 0x10, 4, 3])
+            .unwrap();
+
+        handler.next_handle = u32::MAX - 1;
+        assert_eq!(
+            handler
+                .service(&mut memory, SYS_OPEN, RAM as u32 + 0x20)
+                .unwrap(),
+            Response::Return(u32::MAX - 1)
+        );
+        assert_eq!(
+            handler
+                .service(&mut memory, SYS_OPEN, RAM as u32 + 0x20)
+                .unwrap(),
+            Response::Return(FAILURE)
+        );
+    }
+
+    #[test]
+    fn files_are_not_accessible_by_default() {
+        let (mut handler, _) = console_handler();
+        let mut memory = MockMemory::new(RAM, 0x100);
+
+        // Open "test.txt" with mode "r".
+        memory.write_8(RAM + 0x10, b"test.txt").unwrap();
+        memory
+            .write_32(RAM + 0x20, &[RAM as u32 + 0x10, 0, 8])
+            .unwrap();
+        assert_eq!(
+            handler
+                .service(&mut memory, SYS_OPEN, RAM as u32 + 0x20)
+                .unwrap(),
+            Response::Return(FAILURE)
+        );
+    }
+
+    #[test]
+    fn resolve_paths_in_the_root_directory() {
+        let (handler, _) = console_handler();
+        assert_eq!(handler.resolve_path("test.txt"), None);
+
+        let handler = handler.with_file_access("/tmp/firmware");
+        assert_eq!(
+            handler.resolve_path("logs/test.txt"),
+            Some(PathBuf::from("/tmp/firmware/logs/test.txt"))
+        );
+        assert_eq!(handler.resolve_path("../test.txt"), None);
+        assert_eq!(handler.resolve_path("/etc/passwd"), None);
+    }
+
+    #[test]
+    fn strings_without_terminator_are_truncated() {
+        let mut memory = MockMemory::new(RAM, 2 * MAX_STRING_LENGTH);
+        memory.memory.fill(b'a');
+
+        assert_eq!(
+            read_string(&mut memory, RAM as u32 + 1).unwrap().len(),
+            MAX_STRING_LENGTH
+        );
+    }
+
+    #[test]
+    fn strings_end_at_the_end_of_the_address_space() {
+        let mut memory = MockMemory::new(0xFFFF_FF00, 0x100);
+        memory.memory.fill(b'a');
+
+        assert_eq!(read_string(&mut memory, 0xFFFF_FFF0).unwrap(), [b'a'; 16]);
+    }
+
+    #[test]
+    fn decode_open_modes() {
+        // "rb"
+        assert_eq!(
+            OpenMode::from_raw(1),
+            Some(OpenMode {
+                read: true,
+                write: false,
+                append: false,
+                truncate: false,
+            })
+        );
+        // "w+"
+        assert_eq!(
+            OpenMode::from_raw(6),
+            Some(OpenMode {
+                read: true,
+                write: true,
+                append: false,
+                truncate: true,
+            })
+        );
+        // "ab"
+        assert_eq!(
+            OpenMode::from_raw(9),
+            Some(OpenMode {
+                read: false,
+                write: true,
+                append: true,
+                truncate: false,
+            })
+        );
+        assert_eq!(OpenMode::from_raw(12), None);
+    }
+}
@@ -0,0 +1,203 @@
+//! Sampling the profiling counters of the DWT of Cortex-M cores.
+//!
+//! See "ARMv7-M Architecture Reference Manual", chapter C1.8 for details.
+
+use super::armv6m::Demcr;
+use crate::architecture::arm::component::{find_component, Dwt};
+use crate::architecture::arm::memory::PeripheralType;
+use crate::core::MemoryMappedRegister;
+use crate::{CoreType, Error, MemoryInterface, Session};
+
+use std::time::{Duration, Instant};
+
+/// The time between two samples of the counters.
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(1);
+
+/// The profiling counters of a core, accumulated over a sampling period, see
+/// [`Session::trace_counters`].
+///
+/// All counts are in core clock cycles, except for `fold`, which counts instructions.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TraceCounters {
+    /// The number of cycles.
+    pub cycles: u64,
+    /// The additional cycles required to execute multi-cycle instructions, including instruction
+    /// fetch stalls.
+    pub cpi: u64,
+    /// The cycles spent in exception entry and exit.
+    pub exception: u64,
+    /// The cycles spent sleeping.
+    pub sleep: u64,
+    /// The additional cycles required by load and store instructions.
+    pub lsu: u64,
+    /// The number of folded instructions, which took zero cycles.
+    pub fold: u64,
+}
+
+impl TraceCounters {
+    /// The estimated number of executed instructions.
+    pub fn instructions(&self) -> u64 {
+        (self.cycles + self.fold).saturating_sub(self.cpi + self.exception + self.sleep + self.lsu)
+    }
+
+    /// The average number of cycles per executed instruction, or `None` if no instruction was
+    /// executed.
+    pub fn cycles_per_instruction(&self) -> Option<f64> {
+        match self.instructions() {
+            0 => None,
+            instructions => Some(self.cycles as f64 / instructions as f64),
+        }
+    }
+
+    /// The fraction of the cycles spent on multi-cycle instructions.
+    pub fn cpi_overhead(&self) -> f64 {
+        self.ratio(self.cpi)
+    }
+
+    /// The fraction of the cycles spent in exception entry and exit.
+    pub fn exception_overhead(&self) -> f64 {
+        self.ratio(self.exception)
+    }
+
+    /// The fraction of the cycles spent sleeping.
+    pub fn sleep_ratio(&self) -> f64 {
+        self.ratio(self.sleep)
+    }
+
+    /// The fraction of the cycles spent on load and store instructions.
+    pub fn lsu_overhead(&self) -> f64 {
+        self.ratio(self.lsu)
+    }
+
+    fn ratio(&self, count: u64) -> f64 {
+        match self.cycles {
+            0 => 0.0,
+            cycles => count as f64 / cycles as f64,
+        }
+    }
+
+    /// Adds the difference between two raw readings of the counter registers.
+    fn accumulate(&mut self, previous: &[u32; 6], current: &[u32; 6]) {
+        // The profiling counters are 8 bit wide.
+        let delta = |index: usize| (current[index].wrapping_sub(previous[index]) & 0xFF) as u64;
+
+        self.cycles += current[0].wrapping_sub(previous[0]) as u64;
+        self.cpi += delta(1);
+        self.exception += delta(2);
+        self.sleep += delta(3);
+        self.lsu += delta(4);
+        self.fold += delta(5);
+    }
+}
+
+/// Samples the profiling counters of the DWT for `duration`, while the core with the given
+/// index is running.
+///
+/// The DWT is enabled for the sampling, and DEMCR and DWT_CTRL are restored afterwards, also if
+/// the sampling fails.
+///
+/// The 8 bit profiling counters are polled, so events are lost if a counter wraps more than once
+/// between two polls, e.g. on a fast core with a slow probe. Use the overflow packets of the
+/// counters, see [`ProfileCounterOverflow`](crate::architecture::arm::component::ProfileCounterOverflow),
+/// if exact counts are required.
+pub(crate) fn sample_trace_counters(
+    session: &mut Session,
+    core_index: usize,
+    duration: Duration,
+) -> Result<TraceCounters, Error> {
+    let demcr = {
+        let mut core = session.core(core_index)?;
+
+        match core.core_type() {
+            CoreType::Armv7m | CoreType::Armv7em | CoreType::Armv8m => {}
+            _ => return Err(Error::ArchitectureRequired(&["ARMv7", "ARMv8"])),
+        }
+
+        let demcr = core.read_word_32(Demcr::ADDRESS)?;
+        let mut enabled = Demcr(demcr);
+        enabled.set_dwtena(true);
+        core.write_word_32(Demcr::ADDRESS, enabled.into())?;
+        demcr
+    };
+
+    let result = session.get_arm_components().and_then(|components| {
+        let interface = session.get_arm_interface()?;
+        let mut dwt = Dwt::new(interface, find_component(&components, PeripheralType::Dwt)?);
+
+        let ctrl = dwt.control()?;
+        let result = sample(&mut dwt, duration);
+        let restored = dwt.set_control(ctrl);
+
+        let counters = result?;
+        restored?;
+
+        Ok(counters)
+    });
+
+    let restored = session
+        .core(core_index)
+        .and_then(|mut core| core.write_word_32(Demcr::ADDRESS, demcr));
+
+    let counters = result?;
+    restored?;
+
+    Ok(counters)
+}
+
+/// Enables the counters, and accumulates them until `duration` has elapsed.
+fn sample(dwt: &mut Dwt, duration: Duration) -> Result<TraceCounters, Error> {
+    dwt.enable_cycle_counter()?;
+    dwt.enable_profile_counters()?;
+
+    let mut counters = TraceCounters::default();
+    let mut previous = read_counters(dwt)?;
+
+    let start = Instant::now();
+    loop {
+        let elapsed = start.elapsed();
+        if elapsed >= duration {
+            return Ok(counters);
+        }
+
+        std::thread::sleep(SAMPLE_INTERVAL.min(duration - elapsed));
+
+        let current = read_counters(dwt)?;
+        counters.accumulate(&previous, &current);
+        previous = current;
+    }
+}
+
+/// Reads the cycle counter, followed by the CPI, exception, sleep, LSU and folded-instruction
+/// counters.
+fn read_counters(dwt: &mut Dwt) -> Result<[u32; 6], Error> {
+    let cycles = dwt.cycle_count()?;
+    let profile = dwt.read_profile_counters()?;
+
+    Ok([
+        cycles,
+        profile.cpi as u32,
+        profile.exception as u32,
+        profile.sleep as u32,
+        profile.lsu as u32,
+        profile.fold as u32,
+    ])
+}
+
+#[cfg(test)]
+mod test {
+    use super::TraceCounters;
+
+    #[test]
+    fn accumulate_wrapping_counters() {
+        let mut counters = TraceCounters::default();
+
+        counters.accumulate(
+            &[0xFFFF_FF00, 0xF0, 0, 0, 0, 0xFF],
+            &[0x0000_0100, 0x10, 0, 0, 0, 0x01],
+        );
+
+        assert_eq!(counters.cycles, 0x200);
+        assert_eq!(counters.cpi, 0x20);
+        assert_eq!(counters.fold, 2);
+    }
+}
@@ -0,0 +1,485 @@
+//! Hardware watchpoints of Cortex-M cores, which use the comparators of the DWT.
+//!
+//! See "ARMv7-M Architecture Reference Manual", chapter C1.8, and "ARMv8-M Architecture
+//! Reference Manual", chapter B13.4, for details.
+
+use super::armv6m::Demcr;
+use crate::architecture::arm::component::dwt::{register_address, Comp, Ctrl, Function, Mask};
+use crate::architecture::arm::memory::adi_v5_memory_interface::ArmProbe;
+use crate::core::{MemoryMappedRegister, WatchpointAccess};
+use crate::memory::valid_32bit_address;
+use crate::Error;
+use anyhow::anyhow;
+
+/// The comparator is disabled, if `DWT_FUNCTION[3:0]` is zero.
+const FUNCTION_DISABLED: u32 = 0b0000;
+/// `DWT_FUNCTION.ACTION` of ARMv8-M, which generates a debug event on a match.
+const ACTION_DEBUG_EVENT: u8 = 0b01;
+
+/// The register layout of the DWT comparators.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum DwtVersion {
+    /// ARMv6-M and ARMv7-M, which match naturally aligned ranges selected with `DWT_MASK`.
+    Armv7m,
+    /// ARMv8-M, which matches accesses to up to a word, selected with `DWT_FUNCTION.DATAVSIZE`.
+    Armv8m,
+}
+
+/// Returns the number of comparators of the DWT.
+pub(crate) fn available_watchpoint_units(memory: &mut dyn ArmProbe) -> Result<u32, Error> {
+    let ctrl = Ctrl::from(memory.read_word_32(register_address::<Ctrl>(0))?);
+    Ok(ctrl.numcomp() as u32)
+}
+
+/// Returns the address compared by every comparator, or `None` if the comparator is disabled.
+///
+/// Comparators which are used for data tracing are reported as well, so that they are not
+/// overwritten by a watchpoint.
+pub(crate) fn hw_watchpoints(memory: &mut dyn ArmProbe) -> Result<Vec<Option<u64>>, Error> {
+    let num_units = available_watchpoint_units(memory)? as usize;
+
+    let mut watchpoints = Vec::with_capacity(num_units);
+    for unit_index in 0..num_units {
+        let function =
+            Function::from(memory.read_word_32(register_address::<Function>(unit_index))?);
+
+        if function.function() == FUNCTION_DISABLED {
+            watchpoints.push(None);
+        } else {
+            let comp = Comp::from(memory.read_word_32(register_address::<Comp>(unit_index))?);
+            watchpoints.push(Some(comp.comp() as u64));
+        }
+    }
+
+    Ok(watchpoints)
+}
+
+/// Configures the comparator `unit_index` to halt the core on an `access` to the `size` bytes
+/// at `address`.
+///
+/// The size has to be a power of two, and the address has to be aligned to it. ARMv8-M cores
+/// only support watchpoints of up to 4 bytes.
+pub(crate) fn set_hw_watchpoint(
+    memory: &mut dyn ArmProbe,
+    version: DwtVersion,
+    unit_index: usize,
+    address: u64,
+    size: u64,
+    access: WatchpointAccess,
+) -> Result<(), Error> {
+    let address = valid_32bit_address(address)?;
+
+    if !size.is_power_of_two() || address as u64 % size != 0 {
+        return Err(Error::Other(anyhow!(
+            "The watchpoint size {} is not a power of two, or the address {:#010x} is not aligned to it.",
+            size,
+            address
+        )));
+    }
+
+    // The DWT is only enabled if DEMCR.TRCENA is set.
+    let mut demcr = Demcr(memory.read_word_32(Demcr::ADDRESS)?);
+    if !demcr.dwtena() {
+        demcr.set_dwtena(true);
+        memory.write_word_32(Demcr::ADDRESS, demcr.into())?;
+    }
+
+    let function_register = register_address::<Function>(unit_index);
+
+    // Disable the comparator while it is reconfigured.
+    memory.write_word_32(function_register, FUNCTION_DISABLED)?;
+
+    let mut comp = Comp::default();
+    comp.set_comp(address);
+    memory.write_word_32(register_address::<Comp>(unit_index), comp.into())?;
+
+    let mut function = Function::default();
+    match version {
+        DwtVersion::Armv7m => {
+            let mask_register = register_address::<Mask>(unit_index);
+            let mut mask = Mask::default();
+            mask.set_mask(size.trailing_zeros());
+
+            // The mask field only implements as many bits as the largest supported mask needs.
+            memory.write_word_32(mask_register, mask.clone().into())?;
+            let supported_mask = Mask::from(memory.read_word_32(mask_register)?);
+            if supported_mask.mask() != mask.mask() {
+                return Err(Error::Other(anyhow!(
+                    "The watchpoint size {} is larger than the maximum size of {} bytes.",
+                    size,
+                    1u64 << supported_mask.mask()
+                )));
+            }
+
+            function.set_function(match access {
+                WatchpointAccess::Read => 0b0101,
+                WatchpointAccess::Write => 0b0110,
+                WatchpointAccess::ReadWrite => 0b0111,
+            });
+        }
+        DwtVersion::Armv8m => {
+            if size > 4 {
+                return Err(Error::Other(anyhow!(
+                    "The watchpoint size {} is larger than the maximum size of 4 bytes.",
+                    size
+                )));
+            }
+
+            function.set_function(match access {
+                WatchpointAccess::ReadWrite => 0b0100,
+                WatchpointAccess::Write => 0b0101,
+                WatchpointAccess::Read => 0b0110,
+            });
+            function.set_action(ACTION_DEBUG_EVENT);
+            function.set_datavsize(size.trailing_zeros() as u8);
+        }
+    }
+
+    memory.write_word_32(function_register, function.clone().into())?;
+
+    // Not every comparator supports data address matching, in which case the match type reads
+    // back differently.
+    let readback = Function::from(memory.read_word_32(function_register)?);
+    if readback.function() != function.function() {
+        memory.write_word_32(function_register, FUNCTION_DISABLED)?;
+        return Err(Error::Other(anyhow!(
+            "The DWT comparator {} does not support data address matching.",
+            unit_index
+        )));
+    }
+
+    Ok(())
+}
+
+/// Disables the comparator `unit_index`.
+pub(crate) fn clear_hw_watchpoint(
+    memory: &mut dyn ArmProbe,
+    unit_index: usize,
+) -> Result<(), Error> {
+    memory.write_word_32(register_address::<Function>(unit_index), FUNCTION_DISABLED)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::architecture::arm::{ap::MemoryAp, communication_interface::SwdSequence};
+
+    pub struct ExpectedMemoryOp {
+        read: bool,
+        address: u64,
+        value: u32,
+    }
+
+    pub struct MockProbe {
+        expected_ops: Vec<ExpectedMemoryOp>,
+    }
+
+    impl MockProbe {
+        pub fn new() -> Self {
+            MockProbe {
+                expected_ops: vec![],
+            }
+        }
+
+        pub fn expected_read(&mut self, addr: u64, value: u32) {
+            self.expected_ops.push(ExpectedMemoryOp {
+                read: true,
+                address: addr,
+                value,
+            });
+        }
+
+        pub fn expected_write(&mut self, addr: u64, value: u32) {
+            self.expected_ops.push(ExpectedMemoryOp {
+                read: false,
+                address: addr,
+                value,
+            });
+        }
+
+        fn assert_done(&self) {
+            assert!(
+                self.expected_ops.is_empty(),
+                "{} expected operations were not performed",
+                self.expected_ops.len()
+            );
+        }
+    }
+
+    impl ArmProbe for MockProbe {
+        fn read_8(&mut self, _address: u64, _data: &mut [u8]) -> Result<(), Error> {
+            todo!()
+        }
+
+        fn read_32(&mut self, address: u64, data: &mut [u32]) -> Result<(), Error> {
+            if self.expected_ops.is_empty() {
+                panic!("Received unexpected read_32 op: address {:#010x}", address);
+            }
+
+            assert_eq!(data.len(), 1);
+
+            let expected_op = self.expected_ops.remove(0);
+
+            assert!(
+                expected_op.read,
+                "R/W mismatch for address: Expected {:#010x} Actual: {:#010x}",
+                expected_op.address, address
+            );
+            assert_eq!(
+                expected_op.address, address,
+                "Read from unexpected address: Expected {:#010x} Actual: {:#010x}",
+                expected_op.address, address
+            );
+
+            data[0] = expected_op.value;
+
+            Ok(())
+        }
+
+        fn read(&mut self, address: u64, data: &mut [u8]) -> Result<(), Error> {
+            self.read_8(address, data)
+        }
+
+        fn write_8(&mut self, _address: u64, _data: &[u8]) -> Result<(), Error> {
+            todo!()
+        }
+
+        fn write_32(&mut self, address: u64, data: &[u32]) -> Result<(), Error> {
+            if self.expected_ops.is_empty() {
+                panic!("Received unexpected write_32 op: address {:#010x}", address);
+            }
+
+            assert_eq!(data.len(), 1);
+
+            let expected_op = self.expected_ops.remove(0);
+
+            assert!(
+                !expected_op.read,
+                "Read/write mismatch on address: {:#010x}",
+                address
+            );
+            assert_eq!(
+                expected_op.address, address,
+                "Write to unexpected address: Expected {:#010x} Actual: {:#010x}",
+                expected_op.address, address
+            );
+
+            assert_eq!(
+                expected_op.value, data[0],
+                "Write value mismatch Expected {:#X} Actual: {:#X}",
+                expected_op.value, data[0]
+            );
+
+            Ok(())
+        }
+
+        fn write(&mut self, address: u64, data: &[u8]) -> Result<(), Error> {
+            self.write_8(address, data)
+        }
+
+        fn flush(&mut self) -> Result<(), Error> {
+            todo!()
+        }
+
+        fn supports_8bit_transfers(&self) -> Result<bool, Error> {
+            Ok(false)
+        }
+
+        fn get_arm_communication_interface(
+            &mut self,
+        ) -> Result<
+            &mut crate::architecture::arm::ArmCommunicationInterface<
+                crate::architecture::arm::communication_interface::Initialized,
+            >,
+            Error,
+        > {
+            todo!()
+        }
+
+        fn read_64(&mut self, _address: u64, _data: &mut [u64]) -> Result<(), Error> {
+            todo!()
+        }
+
+        fn write_64(&mut self, _address: u64, _data: &[u64]) -> Result<(), Error> {
+            todo!()
+        }
+
+        fn ap(&mut self) -> MemoryAp {
+            todo!()
+        }
+
+        fn supports_native_64bit_access(&mut self) -> bool {
+            false
+        }
+    }
+
+    impl SwdSequence for MockProbe {
+        fn swj_sequence(&mut self, _bit_len: u8, _bits: u64) -> Result<(), Error> {
+            todo!()
+        }
+
+        fn swj_pins(
+            &mut self,
+            _pin_out: u32,
+            _pin_select: u32,
+            _pin_wait: u32,
+        ) -> Result<u32, Error> {
+            todo!()
+        }
+    }
+
+    const DWT_CTRL: u64 = 0xE000_1000;
+    const DWT_COMP: u64 = 0xE000_1020;
+    const DWT_MASK: u64 = 0xE000_1024;
+    const DWT_FUNCTION: u64 = 0xE000_1028;
+
+    /// The address of the register at `base` of comparator `unit`.
+    fn unit(base: u64, unit: u64) -> u64 {
+        base + 0x10 * unit
+    }
+
+    /// Expects the comparator `unit_index` to be disabled and to be set to `address`.
+    fn add_comparator_expectations(probe: &mut MockProbe, unit_index: u64, address: u32) {
+        let mut demcr = Demcr(0);
+        demcr.set_dwtena(true);
+        probe.expected_read(Demcr::ADDRESS, demcr.into());
+
+        probe.expected_write(unit(DWT_FUNCTION, unit_index), 0);
+        probe.expected_write(unit(DWT_COMP, unit_index), address);
+    }
+
+    #[test]
+    fn available_units() {
+        let mut probe = MockProbe::new();
+        probe.expected_read(DWT_CTRL, 0x4000_0000);
+
+        assert_eq!(available_watchpoint_units(&mut probe).unwrap(), 4);
+        probe.assert_done();
+    }
+
+    #[test]
+    fn list_watchpoints() {
+        let mut probe = MockProbe::new();
+        probe.expected_read(DWT_CTRL, 0x2000_0000);
+        probe.expected_read(unit(DWT_FUNCTION, 0), 0);
+        probe.expected_read(unit(DWT_FUNCTION, 1), 0b0111);
+        probe.expected_read(unit(DWT_COMP, 1), 0x2000_0100);
+
+        assert_eq!(
+            hw_watchpoints(&mut probe).unwrap(),
+            [None, Some(0x2000_0100)]
+        );
+        probe.assert_done();
+    }
+
+    #[test]
+    fn armv7m_encoding() {
+        for (access, function) in [
+            (WatchpointAccess::Read, 0b0101),
+            (WatchpointAccess::Write, 0b0110),
+            (WatchpointAccess::ReadWrite, 0b0111),
+        ] {
+            let mut probe = MockProbe::new();
+            add_comparator_expectations(&mut probe, 1, 0x2000_0040);
+            probe.expected_write(unit(DWT_MASK, 1), 6);
+            probe.expected_read(unit(DWT_MASK, 1), 6);
+            probe.expected_write(unit(DWT_FUNCTION, 1), function);
+            probe.expected_read(unit(DWT_FUNCTION, 1), function);
+
+            set_hw_watchpoint(&mut probe, DwtVersion::Armv7m, 1, 0x2000_0040, 64, access).unwrap();
+            probe.assert_done();
+        }
+    }
+
+    #[test]
+    fn armv7m_mask_too_large() {
+        let mut probe = MockProbe::new();
+        add_comparator_expectations(&mut probe, 0, 0x2001_0000);
+        probe.expected_write(unit(DWT_MASK, 0), 16);
+        // Only the lower four bits of the mask are implemented.
+        probe.expected_read(unit(DWT_MASK, 0), 0);
+
+        assert!(set_hw_watchpoint(
+            &mut probe,
+            DwtVersion::Armv7m,
+            0,
+            0x2001_0000,
+            0x1_0000,
+            WatchpointAccess::Write,
+        )
+        .is_err());
+        probe.assert_done();
+    }
+
+    #[test]
+    fn armv8m_encoding() {
+        // MATCH, ACTION is a debug event, and DATAVSIZE is a halfword.
+        for (access, function) in [
+            (WatchpointAccess::ReadWrite, 0b0100_0001_0100),
+            (WatchpointAccess::Write, 0b0100_0001_0101),
+            (WatchpointAccess::Read, 0b0100_0001_0110),
+        ] {
+            let mut probe = MockProbe::new();
+            add_comparator_expectations(&mut probe, 2, 0x2000_0002);
+            probe.expected_write(unit(DWT_FUNCTION, 2), function);
+            probe.expected_read(unit(DWT_FUNCTION, 2), function);
+
+            set_hw_watchpoint(&mut probe, DwtVersion::Armv8m, 2, 0x2000_0002, 2, access).unwrap();
+            probe.assert_done();
+        }
+    }
+
+    #[test]
+    fn armv8m_size_too_large() {
+        let mut probe = MockProbe::new();
+        add_comparator_expectations(&mut probe, 0, 0x2000_0000);
+
+        assert!(set_hw_watchpoint(
+            &mut probe,
+            DwtVersion::Armv8m,
+            0,
+            0x2000_0000,
+            8,
+            WatchpointAccess::Read,
+        )
+        .is_err());
+        probe.assert_done();
+    }
+
+    #[test]
+    fn unsupported_comparator_is_disabled() {
+        let mut probe = MockProbe::new();
+        add_comparator_expectations(&mut probe, 3, 0x2000_0000);
+        probe.expected_write(unit(DWT_FUNCTION, 3), 0b1000_0001_0101);
+        // The comparator only supports instruction address matching.
+        probe.expected_read(unit(DWT_FUNCTION, 3), 0);
+        probe.expected_write(unit(DWT_FUNCTION, 3), 0);
+
+        assert!(set_hw_watchpoint(
+            &mut probe,
+            DwtVersion::Armv8m,
+            3,
+            0x2000_0000,
+            4,
+            WatchpointAccess::Write,
+        )
+        .is_err());
+        probe.assert_done();
+    }
+
+    #[test]
+    fn unaligned_address() {
+        let mut probe = MockProbe::new();
+
+        assert!(set_hw_watchpoint(
+            &mut probe,
+            DwtVersion::Armv7m,
+            0,
+            0x2000_0002,
+            4,
+            WatchpointAccess::Read,
+        )
+        .is_err());
+        probe.assert_done();
+    }
+}
@@ -7,6 +7,7 @@ use crate::architecture::arm::ArmCommunicationInterface;
 use crate::architecture::arm::{
     communication_interface::Initialized, dp::DpAccess, MemoryApInformation,
 };
+use crate::memory::split_at_words;
 use crate::{CommunicationInterface, Error};
 use std::convert::TryInto;
 use std::ops::Range;
@@ -54,15 +55,15 @@ pub trait ArmProbe: SwdSequence {
                 bytes.copy_from_slice(&u32::to_le_bytes(*value));
             }
         } else {
+            // Read the whole words containing the data, so no 8 bit transfers are needed.
             let start_extra_count = (address % 4) as usize;
             let mut buffer = vec![0u32; (start_extra_count + len + 3) / 4];
             let read_address = address - start_extra_count as u64;
             self.read_32(read_address, &mut buffer)?;
-            for (bytes, value) in data
-                .chunks_exact_mut(4)
-                .zip(buffer[start_extra_count..start_extra_count + len].iter())
-            {
-                bytes.copy_from_slice(&u32::to_le_bytes(*value));
+
+            let bytes = buffer.iter().flat_map(|value| value.to_le_bytes());
+            for (byte, value) in data.iter_mut().zip(bytes.skip(start_extra_count)) {
+                *byte = value;
             }
         }
         Ok(())
@@ -93,38 +94,30 @@ pub trait ArmProbe: SwdSequence {
     /// so it should only be used if writing memory locations that don't have side
     /// effects. Generally faster than [`MemoryInterface::write_8`].
     fn write(&mut self, address: u64, data: &[u8]) -> Result<(), Error> {
-        let len = data.len();
-        let start_extra_count = 4 - (address % 4) as usize;
-        let end_extra_count = (len - start_extra_count) % 4;
-        let inbetween_count = len - start_extra_count - end_extra_count;
-        assert!(start_extra_count < 4);
-        assert!(end_extra_count < 4);
-        assert!(inbetween_count % 4 == 0);
-
-        // If we do not have 32 bit aligned access we first check that we can do 8 bit aligned access on this platform.
-        // If we cannot we throw an error.
-        // If we can we read the first n < 4 bytes up until the word aligned address that comes next.
-        if address % 4 != 0 || len % 4 != 0 {
-            // If we do not support 8 bit transfers we have to bail because we can only do 32 bit word aligned transers.
-            if !self.supports_8bit_transfers()? {
-                return Err(Error::ArchitectureSpecific(Box::new(
-                    AccessPortError::alignment_error(address, 4),
-                )));
-            }
+        let (head, body, tail) = split_at_words(address, data);
+
+        // The bytes before the first and after the last full word have to be written with 8 bit
+        // transfers, so they don't overwrite the neighbouring bytes.
+        if !(head.is_empty() && tail.is_empty()) && !self.supports_8bit_transfers()? {
+            return Err(Error::ArchitectureSpecific(Box::new(
+                AccessPortError::alignment_error(address, 4),
+            )));
+        }
 
-            // We first do an 8 bit write of the first < 4 bytes up until the 4 byte aligned boundary.
-            self.write_8(address, &data[..start_extra_count])?;
+        if !head.is_empty() {
+            self.write_8(address, head)?;
         }
 
-        let mut buffer = vec![0u32; inbetween_count / 4];
-        for (bytes, value) in data.chunks_exact(4).zip(buffer.iter_mut()) {
-            *value = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        if !body.is_empty() {
+            let buffer: Vec<u32> = body
+                .chunks_exact(4)
+                .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+                .collect();
+            self.write_32(address + head.len() as u64, &buffer)?;
         }
-        self.write_32(address, &buffer)?;
 
-        // We read the remaining bytes that we did not read yet which is always n < 4.
-        if end_extra_count > 0 {
-            self.write_8(address, &data[..start_extra_count])?;
+        if !tail.is_empty() {
+            self.write_8(address + (head.len() + body.len()) as u64, tail)?;
         }
 
         Ok(())
@@ -160,6 +153,12 @@ where
     /// cached on a lower level, where the other Memory AP information is
     /// stored.
     cached_csw_value: Option<CSW>,
+
+    /// The address the TAR register was auto-incremented to by the last single word access.
+    ///
+    /// A sequential word access at this address can skip writing the TAR register, so reading
+    /// a block of registers word by word only needs a single TAR write.
+    cached_tar_value: Option<u64>,
 }
 
 impl<'interface, AP> ADIMemoryInterface<'interface, AP>
@@ -177,6 +176,7 @@ where
             ap_information,
             memory_ap: MemoryAp::new(address),
             cached_csw_value: None,
+            cached_tar_value: None,
         })
     }
 }
@@ -187,8 +187,21 @@ where
 {
     /// Build the correct CSW register for a memory access
     ///
-    /// Currently, only AMBA AHB Access is supported.
+    /// AMBA AHB and AXI access ports use the AHB settings below,
+    /// AMBA APB access ports only use the fields defined for APB.
     fn build_csw_register(&self, data_size: DataSize) -> CSW {
+        if self.ap_information.ap_type.is_apb() {
+            // APB access ports don't have the HPROT/HNONSEC signals of an AHB bus,
+            // setting them would select a different access on some implementations.
+            // The debug software access is always enabled.
+            return CSW {
+                DbgSwEnable: 0b1,
+                AddrInc: AddressIncrement::Single,
+                SIZE: data_size,
+                ..Default::default()
+            };
+        }
+
         // The CSW Register is set for an AMBA AHB Acccess, according to
         // the ARM Debug Interface Architecture Specification.
         //
@@ -236,6 +249,11 @@ where
         access_port: MemoryAp,
         address: u64,
     ) -> Result<(), AccessPortError> {
+        // The following DRW access increments the TAR, so the cached value is only valid once.
+        if self.cached_tar_value.take() == Some(address) {
+            return Ok(());
+        }
+
         let address_lower = address as u32;
         let address_upper = (address >> 32) as u32;
 
@@ -256,6 +274,24 @@ where
         Ok(())
     }
 
+    /// Updates the cached TAR value after a single word access of `size` bytes at `address`.
+    ///
+    /// The auto-increment is only guaranteed in the lowest 10 bits of the TAR, so the TAR is
+    /// unknown after an access which crosses a 1 KiB boundary, or a failed access.
+    fn update_cached_tar<T>(
+        &mut self,
+        address: u64,
+        size: u64,
+        result: &Result<T, AccessPortError>,
+    ) {
+        let next = address + size;
+
+        self.cached_tar_value = match result {
+            Ok(_) if (address & !0x3FF) == (next & !0x3FF) => Some(next),
+            _ => None,
+        };
+    }
+
     /// Read a 32 bit register on the given AP.
     fn read_ap_register<R>(&mut self, access_port: MemoryAp) -> Result<R, AccessPortError>
     where
@@ -318,7 +354,7 @@ where
 
     /// Read a 64bit word at `address`.
     ///
-    /// The address where the read should be performed at has to be word aligned.
+    /// The address where the read should be performed at has to be 8 byte aligned.
     /// Returns `AccessPortError::MemoryNotAligned` if this does not hold true.
     pub fn read_word_64(
         &mut self,
@@ -326,7 +362,7 @@ where
         address: u64,
     ) -> Result<u64, AccessPortError> {
         if (address % 8) != 0 {
-            return Err(AccessPortError::alignment_error(address, 4));
+            return Err(AccessPortError::alignment_error(address, 8));
         }
 
         if !self.ap_information.has_large_data_extension {
@@ -367,9 +403,10 @@ where
 
         self.write_csw_register(access_port, csw)?;
         self.write_tar_register(access_port, address)?;
-        let result: DRW = self.read_ap_register(access_port)?;
+        let result: Result<DRW, _> = self.read_ap_register(access_port);
+        self.update_cached_tar(address, 4, &result);
 
-        Ok(result.data)
+        Ok(result?.data)
     }
 
     /// Read an 8 bit word at `address`.
@@ -397,6 +434,62 @@ where
         Ok(((result.data >> bit_offset) & 0xFF) as u8)
     }
 
+    /// Read a block of 64 bit words at `address`.
+    ///
+    /// The number of words read is `data.len()`.
+    /// The address where the read should be performed at has to be 8 byte aligned.
+    /// Returns `AccessPortError::MemoryNotAligned` if this does not hold true.
+    ///
+    /// If the AP doesn't support 64 bit accesses, every word is read as two 32 bit words, with
+    /// the lower word first.
+    pub fn read_64(
+        &mut self,
+        access_port: MemoryAp,
+        address: u64,
+        data: &mut [u64],
+    ) -> Result<(), AccessPortError> {
+        if data.is_empty() {
+            return Ok(());
+        }
+
+        if (address % 8) != 0 {
+            return Err(AccessPortError::alignment_error(address, 8));
+        }
+
+        let mut words = vec![0u32; data.len() * 2];
+
+        if self.ap_information.has_large_data_extension {
+            let csw = self.build_csw_register(DataSize::U64);
+            self.write_csw_register(access_port, csw)?;
+
+            let mut offset = 0;
+            while offset < words.len() {
+                // The autoincrement is limited to the 10 lowest bits, so the TAR has to be
+                // written again at every 1 KiB boundary.
+                let chunk_address = address + offset as u64 * 4;
+                let chunk_len =
+                    ((0x400 - (chunk_address as usize % 0x400)) / 4).min(words.len() - offset);
+
+                self.write_tar_register(access_port, chunk_address)?;
+                self.read_ap_register_repeated(
+                    access_port,
+                    DRW { data: 0 },
+                    &mut words[offset..offset + chunk_len],
+                )?;
+
+                offset += chunk_len;
+            }
+        } else {
+            self.read_32(access_port, address, &mut words)?;
+        }
+
+        for (word, halves) in data.iter_mut().zip(words.chunks_exact(2)) {
+            *word = halves[0] as u64 | (halves[1] as u64) << 32;
+        }
+
+        Ok(())
+    }
+
     /// Read a block of 32 bit words at `address`.
     ///
     /// The number of words read is `data.len()`.
@@ -588,7 +681,7 @@ where
 
     /// Write a 64bit word at `addr`.
     ///
-    /// The address where the write should be performed at has to be word aligned.
+    /// The address where the write should be performed at has to be 8 byte aligned.
     /// Returns `AccessPortError::MemoryNotAligned` if this does not hold true.
     pub fn write_word_64(
         &mut self,
@@ -597,7 +690,7 @@ where
         data: u64,
     ) -> Result<(), AccessPortError> {
         if (address % 8) != 0 {
-            return Err(AccessPortError::alignment_error(address, 4));
+            return Err(AccessPortError::alignment_error(address, 8));
         }
 
         let low_word = data as u32;
@@ -642,9 +735,10 @@ where
         self.write_csw_register(access_port, csw)?;
 
         self.write_tar_register(access_port, address)?;
-        self.write_ap_register(access_port, drw)?;
+        let result = self.write_ap_register(access_port, drw);
+        self.update_cached_tar(address, 4, &result);
 
-        Ok(())
+        result
     }
 
     /// Write an 8 bit word at `address`.
@@ -674,6 +768,61 @@ where
         Ok(())
     }
 
+    /// Write a block of 64 bit words at `address`.
+    ///
+    /// The number of words written is `data.len()`.
+    /// The address where the write should be performed at has to be 8 byte aligned.
+    /// Returns `AccessPortError::MemoryNotAligned` if this does not hold true.
+    ///
+    /// If the AP doesn't support 64 bit accesses, every word is written as two 32 bit words,
+    /// with the lower word first.
+    pub fn write_64(
+        &mut self,
+        access_port: MemoryAp,
+        address: u64,
+        data: &[u64],
+    ) -> Result<(), AccessPortError> {
+        if (address % 8) != 0 {
+            return Err(AccessPortError::alignment_error(address, 8));
+        }
+
+        if data.is_empty() {
+            return Ok(());
+        }
+
+        let words: Vec<u32> = data
+            .iter()
+            .flat_map(|word| [*word as u32, (*word >> 32) as u32])
+            .collect();
+
+        if !self.ap_information.has_large_data_extension {
+            return self.write_32(access_port, address, &words);
+        }
+
+        let csw = self.build_csw_register(DataSize::U64);
+        self.write_csw_register(access_port, csw)?;
+
+        let mut offset = 0;
+        while offset < words.len() {
+            // The autoincrement is limited to the 10 lowest bits, so the TAR has to be
+            // written again at every 1 KiB boundary.
+            let chunk_address = address + offset as u64 * 4;
+            let chunk_len =
+                ((0x400 - (chunk_address as usize % 0x400)) / 4).min(words.len() - offset);
+
+            self.write_tar_register(access_port, chunk_address)?;
+            self.write_ap_register_repeated(
+                access_port,
+                DRW { data: 0 },
+                &words[offset..offset + chunk_len],
+            )?;
+
+            offset += chunk_len;
+        }
+
+        Ok(())
+    }
+
     /// Write a block of 32 bit words at `address`.
     ///
     /// The number of words written is `data.len()`.
@@ -922,8 +1071,10 @@ where
     }
 
     fn read_64(&mut self, address: u64, data: &mut [u64]) -> Result<(), Error> {
-        for (i, d) in data.iter_mut().enumerate() {
-            *d = self.read_word_64(self.memory_ap, address + (i as u64 * 8))?;
+        if data.len() == 1 {
+            data[0] = self.read_word_64(self.memory_ap, address)?;
+        } else {
+            self.read_64(self.memory_ap, address, data)?;
         }
 
         Ok(())
@@ -950,8 +1101,10 @@ where
     }
 
     fn write_64(&mut self, address: u64, data: &[u64]) -> Result<(), Error> {
-        for (i, d) in data.iter().enumerate() {
-            self.write_word_64(self.memory_ap, address + (i as u64 * 8), *d)?;
+        if data.len() == 1 {
+            self.write_word_64(self.memory_ap, address, data[0])?;
+        } else {
+            self.write_64(self.memory_ap, address, data)?;
         }
 
         Ok(())
@@ -962,7 +1115,12 @@ where
     }
 
     fn flush(&mut self) -> Result<(), Error> {
-        self.interface.flush()?;
+        // A failed batched write doesn't increment the TAR.
+        let result = self.interface.flush();
+        if result.is_err() {
+            self.cached_tar_value = None;
+        }
+        result?;
 
         Ok(())
     }
@@ -975,6 +1133,8 @@ where
     fn get_arm_communication_interface(
         &mut self,
     ) -> Result<&mut ArmCommunicationInterface<Initialized>, Error> {
+        // The TAR can be modified through the raw interface.
+        self.cached_tar_value = None;
         CommunicationInterface::get_arm_communication_interface(self.interface)
     }
 }
@@ -1002,11 +1162,14 @@ fn aligned_range(address: u64, len: usize) -> Result<Range<u64>, AccessPortError
 mod tests {
     use scroll::Pread;
 
-    use crate::architecture::arm::{ap::AccessPort, ApAddress, DpAddress, MemoryApInformation};
+    use crate::architecture::arm::{
+        ap::{AccessPort, ApType},
+        ApAddress, DpAddress, MemoryApInformation,
+    };
 
     use super::super::super::ap::memory_ap::mock::MockMemoryAp;
     use super::super::super::ap::memory_ap::MemoryAp;
-    use super::ADIMemoryInterface;
+    use super::{ADIMemoryInterface, ArmProbe};
 
     const DUMMY_AP: MemoryAp = MemoryAp::new(ApAddress {
         dp: DpAddress::Default,
@@ -1020,6 +1183,7 @@ mod tests {
         ) -> ADIMemoryInterface<'interface, MockMemoryAp> {
             let ap_information = MemoryApInformation {
                 address: DUMMY_AP.ap_address(),
+                ap_type: ApType::AmbaAhb3,
                 supports_only_32bit_data_size: false,
                 supports_hnonsec: false,
                 debug_base_address: 0xf000_0000,
@@ -1058,6 +1222,23 @@ mod tests {
         }
     }
 
+    #[test]
+    fn sequential_read_word_32_writes_tar_once() {
+        let mut mock = MockMemoryAp::with_pattern();
+        mock.memory[..8].copy_from_slice(&DATA8[..8]);
+        let mut mi = ADIMemoryInterface::new_mock(&mut mock);
+
+        for &address in &[0, 4, 0] {
+            let value = mi
+                .read_word_32(DUMMY_AP, address)
+                .expect("read_word_32 failed");
+            assert_eq!(value, DATA32[address as usize / 4]);
+        }
+
+        // The read at 4 uses the auto-incremented TAR, going back to 0 needs a new TAR write.
+        assert_eq!(mock.tar_writes, 2);
+    }
+
     #[test]
     fn read_word_8() {
         let mut mock = MockMemoryAp::with_pattern();
@@ -1137,6 +1318,52 @@ mod tests {
         }
     }
 
+    #[test]
+    fn read_64_without_large_data_extension() {
+        let mut mock = MockMemoryAp::with_pattern();
+        let expected: Vec<u64> = mock
+            .memory
+            .chunks(8)
+            .map(|b| b.pread_with(0, scroll::LE).unwrap())
+            .skip(1)
+            .take(3)
+            .collect();
+        let mut mi = ADIMemoryInterface::new_mock(&mut mock);
+
+        let mut data = vec![0u64; 3];
+        mi.read_64(DUMMY_AP, 8, &mut data).unwrap();
+        assert_eq!(data, expected);
+
+        assert!(mi.read_64(DUMMY_AP, 4, &mut data).is_err());
+    }
+
+    #[test]
+    fn read_and_write_unaligned_bytes() {
+        for address in 0..4 {
+            for len in [0, 1, 3, 9] {
+                let mut mock = MockMemoryAp::with_pattern();
+                let mut mi = ADIMemoryInterface::new_mock(&mut mock);
+
+                let data: Vec<u8> = (0..len).map(|i| 0xA0 + i as u8).collect();
+                let mut expected = Vec::from(mi.mock_memory());
+                expected[address..address + len].copy_from_slice(&data);
+
+                ArmProbe::write(&mut mi, address as u64, &data).unwrap();
+                assert_eq!(
+                    mi.mock_memory(),
+                    expected.as_slice(),
+                    "address = {}, len = {}",
+                    address,
+                    len
+                );
+
+                let mut read_back = vec![0; len];
+                ArmProbe::read(&mut mi, address as u64, &mut read_back).unwrap();
+                assert_eq!(read_back, data, "address = {}, len = {}", address, len);
+            }
+        }
+    }
+
     #[test]
     fn read_32_big_chunk() {
         let mut mock = MockMemoryAp::with_pattern();
@@ -13,7 +13,10 @@ mod traits;
 pub use communication_interface::{
     ApInformation, ArmChipInfo, ArmCommunicationInterface, DapError, MemoryApInformation, Register,
 };
-pub use swo::{SwoAccess, SwoConfig, SwoMode, SwoReader};
+pub use swo::{
+    RotatingFileWriter, RotationPolicy, SwoAccess, SwoChunk, SwoConfig, SwoErrors, SwoMode,
+    SwoReader, SwoStream, SwoStreamSource,
+};
 pub use traits::*;
 
 pub use self::core::armv6m;
@@ -21,6 +24,9 @@ pub use self::core::armv7a;
 pub use self::core::armv7m;
 pub use self::core::armv8a;
 pub use self::core::armv8m;
+pub use self::core::cache;
+pub use self::core::nvic;
+pub use self::core::trace_counters;
 pub use self::core::Dump;
 
 pub use communication_interface::ArmProbeInterface;
@@ -47,6 +47,20 @@ pub enum ArmDebugSequenceError {
     /// CTI base address is required but not specified
     #[error("Core access requries cti_base to be specified, but it is not")]
     CtiBaseNotSpecified,
+
+    /// The sequence of the target has no model of the clock tree, so the core clock can't be
+    /// determined.
+    #[error("The clock configuration of this target is not known")]
+    NoClockModel,
+
+    /// The core clock is derived from an external oscillator, whose frequency has to be
+    /// specified.
+    #[error("The core clock is derived from an external oscillator with an unknown frequency")]
+    ExternalClockRequired,
+
+    /// The clock configuration registers contain an unsupported configuration.
+    #[error("The clock configuration of the target is not supported: {0}")]
+    UnsupportedClockConfiguration(&'static str),
 }
 
 /// The default sequences that is used for ARM chips that do not specify a specific sequence.
@@ -645,6 +659,25 @@ pub trait ArmDebugSequence: Send + Sync {
         }
     }
 
+    /// Prepare the target for tracing, before any trace component or the probe is configured.
+    ///
+    /// # Note
+    /// This function is responsible for the board and device specific setup outside of the
+    /// CoreSight components, such as enabling the clocks of the trace port and routing the trace
+    /// signals, e.g. SWO, to the pins. It is called before the probe starts to capture trace
+    /// data, so no garbage is captured while the pins are being configured.
+    ///
+    /// The default implementation does nothing, and assumes the trace pins and clocks are
+    /// already set up.
+    fn trace_prepare(
+        &self,
+        _interface: &mut dyn ArmProbeInterface,
+        _components: &[CoresightComponent],
+        _sink: &TraceSink,
+    ) -> Result<(), crate::Error> {
+        Ok(())
+    }
+
     /// Enable target trace capture.
     ///
     /// # Note
@@ -738,6 +771,24 @@ pub trait ArmDebugSequence: Send + Sync {
         Ok(())
     }
 
+    /// Determine the current frequency of the core clock in Hz, from the clock configuration
+    /// registers of the target.
+    ///
+    /// The frequency of an external oscillator can't be read from the target, so it has to be
+    /// passed as `external_clock_hz` if the core clock is derived from it.
+    ///
+    /// The default implementation returns [`ArmDebugSequenceError::NoClockModel`], as the clock
+    /// tree is specific to each device family.
+    fn core_clock_hz(
+        &self,
+        _interface: &mut dyn ArmProbeInterface,
+        _external_clock_hz: Option<u32>,
+    ) -> Result<u32, crate::Error> {
+        Err(crate::Error::architecture_specific(
+            ArmDebugSequenceError::NoClockModel,
+        ))
+    }
+
     /// Return the Debug Erase Sequence implementation if it exists
     fn debug_erase_sequence(&self) -> Option<Arc<dyn DebugEraseSequence>> {
         None
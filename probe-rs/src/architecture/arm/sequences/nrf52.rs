@@ -73,7 +73,10 @@ impl ArmDebugSequence for Nrf52 {
         sink: &TraceSink,
     ) -> Result<(), crate::Error> {
         let tpiu_clock = match sink {
-            TraceSink::TraceMemory => {
+            TraceSink::TraceMemory
+            | TraceSink::CircularMemory
+            | TraceSink::TriggeredMemory { .. }
+            | TraceSink::SystemMemory { .. } => {
                 tracing::error!("nRF52 does not have a trace buffer");
                 return Err(Error::architecture_specific(
                     ComponentError::NordicNoTraceMem,
@@ -82,6 +85,8 @@ impl ArmDebugSequence for Nrf52 {
 
             TraceSink::Tpiu(config) => config.tpiu_clk(),
             TraceSink::Swo(config) => config.tpiu_clk(),
+            // Clock the trace port at the highest speed.
+            TraceSink::TracePort { .. } => 32_000_000,
         };
 
         let portspeed = match tpiu_clock {
@@ -99,7 +104,7 @@ impl ArmDebugSequence for Nrf52 {
         let mut memory = interface.memory_interface(components[0].ap)?;
         let mut config = clock::TraceConfig::read(&mut *memory)?;
         config.set_traceportspeed(portspeed);
-        if matches!(sink, TraceSink::Tpiu(_)) {
+        if matches!(sink, TraceSink::Tpiu(_) | TraceSink::TracePort { .. }) {
             config.set_tracemux(2);
         } else {
             config.set_tracemux(1);
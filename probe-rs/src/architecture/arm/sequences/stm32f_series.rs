@@ -2,7 +2,7 @@
 
 use std::sync::Arc;
 
-use super::ArmDebugSequence;
+use super::{ArmDebugSequence, ArmDebugSequenceError};
 use crate::architecture::arm::{
     ap::MemoryAp, component::TraceSink, memory::CoresightComponent, ApAddress, ArmProbeInterface,
     DpAddress,
@@ -53,6 +53,122 @@ mod dbgmcu {
             memory.write_word_32(DBGMCU + Self::ADDRESS, self.0)
         }
     }
+
+    /// Read the device ID from the IDCODE register, which identifies the product line.
+    pub fn device_id(memory: &mut dyn ArmProbe) -> Result<u16, crate::Error> {
+        Ok((memory.read_word_32(DBGMCU)? & 0xFFF) as u16)
+    }
+}
+
+mod rcc {
+    use super::ArmDebugSequenceError;
+    use crate::architecture::arm::memory::adi_v5_memory_interface::ArmProbe;
+
+    /// The base address of the RCC of the STM32F1 series.
+    const RCC_F1: u64 = 0x4002_1000;
+    /// The base address of the RCC of the STM32F2, F4 and F7 series.
+    const RCC_F2: u64 = 0x4002_3800;
+
+    /// The frequency of the internal oscillator of the STM32F1 series.
+    const HSI_F1: u32 = 8_000_000;
+    /// The frequency of the internal oscillator of the STM32F2, F4 and F7 series.
+    const HSI_F2: u32 = 16_000_000;
+
+    /// The device IDs of the STM32F1 series, see "RM0008: STM32F101xx, STM32F102xx, STM32F103xx,
+    /// STM32F105xx and STM32F107xx reference manual" section 31.6.1.
+    const F1_DEVICE_IDS: [u16; 7] = [0x410, 0x412, 0x414, 0x418, 0x420, 0x428, 0x430];
+    /// The device ID of the STM32F1 connectivity line, which has an additional PLL prescaler.
+    const F1_CONNECTIVITY_LINE: u16 = 0x418;
+
+    fn unsupported(reason: &'static str) -> crate::Error {
+        crate::Error::architecture_specific(ArmDebugSequenceError::UnsupportedClockConfiguration(
+            reason,
+        ))
+    }
+
+    fn external_clock(external_clock_hz: Option<u32>) -> Result<u32, crate::Error> {
+        external_clock_hz.ok_or_else(|| {
+            crate::Error::architecture_specific(ArmDebugSequenceError::ExternalClockRequired)
+        })
+    }
+
+    /// The AHB prescaler selected by the `HPRE` field of `RCC_CFGR`, which divides the system
+    /// clock into the core clock.
+    fn ahb_divider(hpre: u32) -> u32 {
+        match hpre {
+            0b1000..=0b1011 => 2 << (hpre - 0b1000),
+            // A division by 32 is skipped.
+            0b1100..=0b1111 => 64 << (hpre - 0b1100),
+            _ => 1,
+        }
+    }
+
+    /// Compute the core clock from the RCC registers.
+    ///
+    /// See "RM0008" section 7.3 for the STM32F1 series, and "RM0090: STM32F405/415, STM32F407/417,
+    /// STM32F427/437 and STM32F429/439 reference manual" section 6.3 for the other series.
+    pub fn core_clock_hz(
+        memory: &mut dyn ArmProbe,
+        device_id: u16,
+        external_clock_hz: Option<u32>,
+    ) -> Result<u32, crate::Error> {
+        let (cfgr, system_clock) = if F1_DEVICE_IDS.contains(&device_id) {
+            let cfgr = memory.read_word_32(RCC_F1 + 0x04)?;
+
+            let system_clock = match (cfgr >> 2) & 0b11 {
+                0b00 => HSI_F1,
+                0b01 => external_clock(external_clock_hz)?,
+                0b10 => {
+                    if device_id == F1_CONNECTIVITY_LINE {
+                        return Err(unsupported("PLL of the STM32F105/107"));
+                    }
+
+                    let source = if cfgr & (1 << 16) == 0 {
+                        HSI_F1 / 2
+                    } else if cfgr & (1 << 17) == 0 {
+                        external_clock(external_clock_hz)?
+                    } else {
+                        external_clock(external_clock_hz)? / 2
+                    };
+                    let multiplier = (((cfgr >> 18) & 0xF) + 2).min(16);
+
+                    source * multiplier
+                }
+                _ => return Err(unsupported("reserved system clock source")),
+            };
+
+            (cfgr, system_clock)
+        } else {
+            let pllcfgr = memory.read_word_32(RCC_F2 + 0x04)?;
+            let cfgr = memory.read_word_32(RCC_F2 + 0x08)?;
+
+            let system_clock = match (cfgr >> 2) & 0b11 {
+                0b00 => HSI_F2,
+                0b01 => external_clock(external_clock_hz)?,
+                0b10 => {
+                    let source = if pllcfgr & (1 << 22) == 0 {
+                        HSI_F2
+                    } else {
+                        external_clock(external_clock_hz)?
+                    };
+                    let m = pllcfgr & 0x3F;
+                    let n = (pllcfgr >> 6) & 0x1FF;
+                    let p = 2 * (((pllcfgr >> 16) & 0b11) + 1);
+
+                    if m == 0 {
+                        return Err(unsupported("PLL input divider of 0"));
+                    }
+
+                    (source as u64 / m as u64 * n as u64 / p as u64) as u32
+                }
+                _ => return Err(unsupported("PLLR as system clock source")),
+            };
+
+            (cfgr, system_clock)
+        };
+
+        Ok(system_clock / ahb_divider((cfgr >> 4) & 0xF))
+    }
 }
 
 impl ArmDebugSequence for Stm32fSeries {
@@ -91,7 +207,23 @@ impl ArmDebugSequence for Stm32fSeries {
         Ok(())
     }
 
-    fn trace_start(
+    fn core_clock_hz(
+        &self,
+        interface: &mut dyn ArmProbeInterface,
+        external_clock_hz: Option<u32>,
+    ) -> Result<u32, crate::Error> {
+        let ap = MemoryAp::new(ApAddress {
+            dp: DpAddress::Default,
+            ap: 0,
+        });
+
+        let mut memory = interface.memory_interface(ap)?;
+
+        let device_id = dbgmcu::device_id(&mut *memory)?;
+        rcc::core_clock_hz(&mut *memory, device_id, external_clock_hz)
+    }
+
+    fn trace_prepare(
         &self,
         interface: &mut dyn ArmProbeInterface,
         components: &[CoresightComponent],
@@ -100,7 +232,15 @@ impl ArmDebugSequence for Stm32fSeries {
         let mut memory = interface.memory_interface(components[0].ap)?;
         let mut cr = dbgmcu::Control::read(&mut *memory)?;
 
-        if matches!(sink, TraceSink::Tpiu(_) | TraceSink::Swo(_)) {
+        if let TraceSink::TracePort { port_width } = sink {
+            // The synchronous trace modes with a port width of 1, 2 and 4 bits.
+            cr.set_traceioen(true);
+            cr.set_tracemode(match port_width {
+                1 => 1,
+                2 => 2,
+                _ => 3,
+            });
+        } else if matches!(sink, TraceSink::Tpiu(_) | TraceSink::Swo(_)) {
             cr.set_traceioen(true);
             cr.set_tracemode(0);
         } else {
@@ -194,7 +194,12 @@ impl ArmDebugSequence for Stm32h7 {
         cstf.unlock()?;
         match sink {
             TraceSink::Swo(_) => cstf.enable_port(0b00)?,
-            TraceSink::Tpiu(_) | TraceSink::TraceMemory => cstf.enable_port(0b10)?,
+            TraceSink::Tpiu(_)
+            | TraceSink::TracePort { .. }
+            | TraceSink::TraceMemory
+            | TraceSink::CircularMemory
+            | TraceSink::TriggeredMemory { .. }
+            | TraceSink::SystemMemory { .. } => cstf.enable_port(0b10)?,
         }
 
         // The SWTF needs to be configured to route traffic to SWO. When not in use, it needs to be
@@ -0,0 +1,265 @@
+//! Software decoding of Manchester encoded SWO data.
+//!
+//! Some probes can't decode the Manchester encoded SWO signal in hardware, and only deliver the
+//! sampled level of the SWO pin. The [`ManchesterDecoder`] recovers the transmitted bytes from
+//! these samples.
+//!
+//! Every bit is transmitted with a transition in the middle of its bit period: A `1` is a high
+//! half bit followed by a low half bit, and a `0` is the inverse. A packet starts with a `1` start
+//! bit, followed by the data bits with the least significant bit first. The line is low while
+//! idle, and a packet ends when the line stays low for more than a bit period.
+
+use super::SwoErrors;
+use crate::Error;
+use anyhow::anyhow;
+
+/// The state of the decoder within a packet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Waiting for the start bit.
+    Idle,
+    /// At the transition in the middle of a bit with the given value.
+    MidBit(bool),
+    /// At the boundary to the next bit, which has the same value as the previous bit.
+    Boundary(bool),
+}
+
+/// The length of a run of samples with the same level, relative to the bit period.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RunLength {
+    /// About half a bit period.
+    Short,
+    /// About a whole bit period.
+    Long,
+    /// Too short or too long for a valid Manchester signal.
+    Invalid,
+}
+
+/// A decoder for Manchester encoded SWO data, which was sampled by the probe.
+///
+/// The samples are passed as bytes, with 8 samples per byte and the first sample in the least
+/// significant bit. The sample rate has to be a multiple of the SWO baud rate, with at least 4
+/// samples per bit.
+///
+/// The clock is recovered at every transition in the middle of a bit, so the decoder tolerates
+/// deviations of up to 25% of a bit period between the sample rate and the baud rate. Samples can
+/// be passed in chunks of any size, packets split between two chunks are decoded once they are
+/// complete.
+#[derive(Debug, Clone)]
+pub struct ManchesterDecoder {
+    samples_per_bit: u32,
+    level: bool,
+    run: u32,
+    state: State,
+    byte: u8,
+    bit_count: u8,
+    errors: SwoErrors,
+}
+
+impl ManchesterDecoder {
+    /// Create a decoder for the given number of samples per bit period.
+    ///
+    /// Returns an error if `samples_per_bit` is less than 4, as the half bits can't be
+    /// distinguished from whole bits then.
+    pub fn new(samples_per_bit: u32) -> Result<Self, Error> {
+        if samples_per_bit < 4 {
+            return Err(Error::Other(anyhow!(
+                "At least 4 samples per bit are required for Manchester decoding, got {}",
+                samples_per_bit
+            )));
+        }
+
+        Ok(Self {
+            samples_per_bit,
+            level: false,
+            run: 0,
+            state: State::Idle,
+            byte: 0,
+            bit_count: 0,
+            errors: SwoErrors::default(),
+        })
+    }
+
+    /// Decode the next chunk of samples, and return the bytes of all packets which were
+    /// completed.
+    pub fn decode(&mut self, samples: &[u8]) -> Vec<u8> {
+        let mut output = Vec::new();
+
+        for sample in samples
+            .iter()
+            .flat_map(|byte| (0..8).map(move |bit| byte & (1 << bit) != 0))
+        {
+            if sample == self.level {
+                self.run = self.run.saturating_add(1);
+
+                // The line has to be low at the end of a packet, long high levels are errors.
+                if self.state != State::Idle && self.run_length(self.run) == RunLength::Invalid {
+                    if self.level {
+                        self.errors.decode_error = true;
+                        self.reset();
+                    } else {
+                        self.end_packet();
+                    }
+                }
+            } else {
+                self.complete_run(&mut output);
+                self.level = sample;
+                self.run = 1;
+            }
+        }
+
+        output
+    }
+
+    /// Returns the errors which occurred since the last call, e.g. framing errors.
+    pub fn take_errors(&mut self) -> SwoErrors {
+        std::mem::take(&mut self.errors)
+    }
+
+    /// Classifies the length of a run of samples.
+    fn run_length(&self, run: u32) -> RunLength {
+        let quarters = run.saturating_mul(4);
+        let bit = self.samples_per_bit;
+
+        if quarters < bit {
+            RunLength::Invalid
+        } else if quarters < 3 * bit {
+            RunLength::Short
+        } else if quarters < 5 * bit {
+            RunLength::Long
+        } else {
+            RunLength::Invalid
+        }
+    }
+
+    /// Processes the run of samples which was ended by a transition.
+    fn complete_run(&mut self, output: &mut Vec<u8>) {
+        let length = self.run_length(self.run);
+
+        self.state = match (self.state, length) {
+            // The high half of the start bit.
+            (State::Idle, RunLength::Short) if self.level => State::MidBit(true),
+            (State::Idle, _) => State::Idle,
+
+            // The second half of the bit and the first half of a following bit with the same
+            // value.
+            (State::MidBit(value), RunLength::Short) => State::Boundary(value),
+            // The second half of the bit and the first half of a following bit with the opposite
+            // value.
+            (State::MidBit(value), RunLength::Long) => {
+                self.push_bit(!value, output);
+                State::MidBit(!value)
+            }
+            (State::Boundary(value), RunLength::Short) => {
+                self.push_bit(value, output);
+                State::MidBit(value)
+            }
+
+            (State::MidBit(_) | State::Boundary(_), _) => {
+                self.errors.decode_error = true;
+                self.reset();
+                State::Idle
+            }
+        };
+    }
+
+    /// Adds a data bit to the current byte.
+    fn push_bit(&mut self, value: bool, output: &mut Vec<u8>) {
+        self.byte |= (value as u8) << self.bit_count;
+        self.bit_count += 1;
+
+        if self.bit_count == 8 {
+            output.push(self.byte);
+            self.byte = 0;
+            self.bit_count = 0;
+        }
+    }
+
+    /// Ends the current packet, which is a framing error if it contains an incomplete byte.
+    fn end_packet(&mut self) {
+        if self.bit_count != 0 {
+            self.errors.decode_error = true;
+        }
+
+        self.reset();
+    }
+
+    /// Discards the current byte, and waits for the next start bit.
+    fn reset(&mut self) {
+        self.state = State::Idle;
+        self.byte = 0;
+        self.bit_count = 0;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ManchesterDecoder;
+
+    /// Encodes `bits` as a packet with `samples_per_bit` samples per bit, followed by two idle
+    /// bit periods, packed into bytes.
+    fn encode(bits: &[bool], samples_per_bit: usize) -> Vec<u8> {
+        let mut samples = Vec::new();
+
+        for &bit in [true].iter().chain(bits) {
+            let first_half = samples_per_bit / 2;
+            samples.extend(std::iter::repeat(bit).take(first_half));
+            samples.extend(std::iter::repeat(!bit).take(samples_per_bit - first_half));
+        }
+        samples.extend(std::iter::repeat(false).take(2 * samples_per_bit));
+
+        samples
+            .chunks(8)
+            .map(|chunk| {
+                chunk
+                    .iter()
+                    .enumerate()
+                    .fold(0, |byte, (index, &sample)| byte | ((sample as u8) << index))
+            })
+            .collect()
+    }
+
+    fn bits(bytes: &[u8]) -> Vec<bool> {
+        bytes
+            .iter()
+            .flat_map(|byte| (0..8).map(move |bit| byte & (1 << bit) != 0))
+            .collect()
+    }
+
+    #[test]
+    fn decodes_split_packets() {
+        let samples = encode(&bits(&[0x41, 0xFF, 0x00]), 8);
+        let mut decoder = ManchesterDecoder::new(8).unwrap();
+
+        let (first, second) = samples.split_at(5);
+        let mut output = decoder.decode(first);
+        output.extend(decoder.decode(second));
+
+        assert_eq!(output, [0x41, 0xFF, 0x00]);
+        assert!(!decoder.take_errors().any());
+    }
+
+    #[test]
+    fn tolerates_clock_deviation() {
+        // The target sends slightly slower than expected by the decoder.
+        let samples = encode(&bits(&[0xA5]), 9);
+        let mut decoder = ManchesterDecoder::new(8).unwrap();
+
+        assert_eq!(decoder.decode(&samples), [0xA5]);
+    }
+
+    #[test]
+    fn reports_incomplete_bytes() {
+        let samples = encode(&bits(&[0x12])[..5], 8);
+        let mut decoder = ManchesterDecoder::new(8).unwrap();
+
+        assert!(decoder.decode(&samples).is_empty());
+        assert!(decoder.take_errors().decode_error);
+    }
+
+    #[test]
+    fn rejects_too_few_samples_per_bit() {
+        assert!(ManchesterDecoder::new(3).is_err());
+        assert!(ManchesterDecoder::new(4).is_ok());
+    }
+}
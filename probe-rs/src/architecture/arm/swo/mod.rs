@@ -1,10 +1,20 @@
 //! SWO tracing related functions.
 
+mod manchester;
+mod rotating_file;
+mod stream;
+
+pub use manchester::ManchesterDecoder;
+pub use rotating_file::{RotatingFileWriter, RotationPolicy};
+pub use stream::SwoStream;
+
 use crate::architecture::arm::communication_interface::ArmProbeInterface;
-use crate::Error;
+use crate::architecture::arm::component::{ComponentError, MAX_SWO_PRESCALER};
+use crate::{DebugProbeError, Error};
+use serde::{Deserialize, Serialize};
 
 /// The protocol the SWO pin should use for data transmission.
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
 pub enum SwoMode {
     /// UART
     Uart,
@@ -13,7 +23,7 @@ pub enum SwoMode {
 }
 
 /// The config for the SWO pin.
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
 pub struct SwoConfig {
     /// SWO mode: either UART or Manchester.
     mode: SwoMode,
@@ -32,6 +42,11 @@ pub struct SwoConfig {
     /// This is required to use ETM over SWO, but otherwise
     /// adds overhead if only DWT/ITM data is used.
     tpiu_continuous_formatting: bool,
+
+    /// Whether to negotiate the baud rate with the probe, instead of requiring the
+    /// exact baud rate from both the target and the probe.
+    #[serde(default)]
+    auto_baud: bool,
 }
 
 impl SwoConfig {
@@ -45,7 +60,39 @@ impl SwoConfig {
             baud: 1_000_000,
             tpiu_clk,
             tpiu_continuous_formatting: false,
+            auto_baud: false,
+        }
+    }
+
+    /// Create a new SwoConfig with the highest baud rate which can be generated from a TPIU clock
+    /// of `core_clock_hz` Hz, without exceeding `max_probe_swo_hz`.
+    ///
+    /// The baud rate is the core clock divided by the smallest integral prescaler, rounded down
+    /// to whole Hz. The core clock can e.g. be determined with
+    /// [`Session::core_clock_hz`](crate::Session::core_clock_hz).
+    ///
+    /// Returns an error if the SWO prescaler of the target can't divide the core clock down to
+    /// `max_probe_swo_hz`.
+    pub fn auto(core_clock_hz: u32, max_probe_swo_hz: u32) -> Result<Self, Error> {
+        let unsupported = || {
+            Error::architecture_specific(ComponentError::UnsupportedSwoBaudRate {
+                baud: max_probe_swo_hz,
+                tpiu_clk: core_clock_hz,
+            })
+        };
+
+        if max_probe_swo_hz == 0 {
+            return Err(unsupported());
+        }
+
+        let max_probe_swo_hz = max_probe_swo_hz as u64;
+        let divisor = ((core_clock_hz as u64 + max_probe_swo_hz - 1) / max_probe_swo_hz).max(1);
+
+        if divisor - 1 > MAX_SWO_PRESCALER as u64 {
+            return Err(unsupported());
         }
+
+        Ok(Self::new(core_clock_hz).set_baud((core_clock_hz as u64 / divisor) as u32))
     }
 
     /// Set the baud rate in Hz.
@@ -78,6 +125,17 @@ impl SwoConfig {
         self
     }
 
+    /// Set whether the baud rate is negotiated with the probe.
+    ///
+    /// Probes can often only receive a baud rate close to the requested one. If enabled, the
+    /// baud rate is adjusted to a rate which can be received by the probe and generated from the
+    /// TPIU clock, starting at the configured baud rate. The negotiated baud rate is then
+    /// available from [`Session::swo_baud_rate`](crate::Session::swo_baud_rate).
+    pub fn set_auto_baud(mut self, enabled: bool) -> Self {
+        self.auto_baud = enabled;
+        self
+    }
+
     /// The SWO mode.
     pub fn mode(&self) -> SwoMode {
         self.mode
@@ -102,6 +160,39 @@ impl SwoConfig {
     pub fn tpiu_continuous_formatting(&self) -> bool {
         self.tpiu_continuous_formatting
     }
+
+    /// Whether the baud rate is negotiated with the probe.
+    pub fn auto_baud(&self) -> bool {
+        self.auto_baud
+    }
+}
+
+/// A chunk of SWO data, together with the probe-side timestamp at which it was received.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SwoChunk {
+    /// The received SWO bytes.
+    pub data: Vec<u8>,
+    /// The value of the probe timestamp timer when the first byte of `data` was received.
+    ///
+    /// This is `None` if the probe does not timestamp SWO data. The timer frequency is returned
+    /// by [`SwoAccess::swo_timestamp_frequency`].
+    pub timestamp: Option<u32>,
+}
+
+/// Errors reported by the probe while receiving SWO data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SwoErrors {
+    /// The SWO buffer of the probe overflowed, and received data was lost.
+    pub overrun: bool,
+    /// The probe failed to decode the SWO signal, e.g. because of a framing or Manchester bit error.
+    pub decode_error: bool,
+}
+
+impl SwoErrors {
+    /// Returns `true` if any error was reported.
+    pub fn any(&self) -> bool {
+        self.overrun || self.decode_error
+    }
 }
 
 /// An interface to operate SWO to be implemented on drivers that support SWO.
@@ -112,6 +203,15 @@ pub trait SwoAccess {
     /// Disable SWO reading on this SwoAccess interface.
     fn disable_swo(&mut self) -> Result<(), Error>;
 
+    /// Configure the probe for capturing the parallel trace port, with a port width of
+    /// `port_width` bits.
+    ///
+    /// The default implementation returns an error, for probes which cannot capture parallel
+    /// trace.
+    fn enable_trace_port(&mut self, _port_width: u8) -> Result<(), Error> {
+        Err(DebugProbeError::CommandNotSupportedByProbe("parallel trace capture").into())
+    }
+
     /// Read any available SWO data without waiting.
     ///
     /// Returns a Vec<u8> of received SWO bytes since the last `read_swo()` call.
@@ -126,6 +226,42 @@ pub trait SwoAccess {
     /// May return earlier than `timeout` if the receive buffer fills up.
     fn read_swo_timeout(&mut self, timeout: std::time::Duration) -> Result<Vec<u8>, Error>;
 
+    /// Read SWO data for up to `timeout` duration, together with the probe-side timestamp of
+    /// the first received byte.
+    ///
+    /// This allows reconstructing the timing of the trace data without on-chip timestamps.
+    /// The default implementation returns the data of [`read_swo_timeout`](Self::read_swo_timeout)
+    /// without a timestamp.
+    fn read_swo_timestamped(&mut self, timeout: std::time::Duration) -> Result<SwoChunk, Error> {
+        Ok(SwoChunk {
+            data: self.read_swo_timeout(timeout)?,
+            timestamp: None,
+        })
+    }
+
+    /// The number of bytes of SWO data currently buffered in the probe.
+    ///
+    /// This allows only reading when data is available. The default implementation returns `0`,
+    /// for probes which do not report how much data they have buffered.
+    fn swo_buffered_bytes(&mut self) -> Result<usize, Error> {
+        Ok(0)
+    }
+
+    /// The baud rate in Hz which the probe receives SWO data with, as configured by the last call
+    /// to [`enable_swo`](Self::enable_swo).
+    ///
+    /// This can differ from the requested baud rate, if the probe cannot receive it exactly. The
+    /// default implementation returns `None`, for probes which do not report the baud rate.
+    fn swo_baud_rate(&mut self) -> Option<u32> {
+        None
+    }
+
+    /// The frequency in Hz of the timer used for the timestamps returned by
+    /// [`read_swo_timestamped`](Self::read_swo_timestamped), if the probe timestamps SWO data.
+    fn swo_timestamp_frequency(&mut self) -> Option<u32> {
+        None
+    }
+
     /// Request an estimated best time to wait between polls of `read_swo`.
     ///
     /// A probe can implement this if it can work out a sensible time to
@@ -146,6 +282,25 @@ pub trait SwoAccess {
     fn swo_buffer_size(&mut self) -> Option<usize> {
         None
     }
+
+    /// Returns a source of the SWO data which can be read from another thread, while other
+    /// commands are sent to the probe.
+    ///
+    /// This is used by [`SwoStream`] to receive SWO data in the background. The default
+    /// implementation returns `None`, for probes which receive SWO data through the same channel
+    /// as their other commands.
+    fn swo_stream_source(&mut self) -> Option<Box<dyn SwoStreamSource>> {
+        None
+    }
+}
+
+/// A source of SWO data which is independent of the other communication with the probe, e.g. a
+/// dedicated USB endpoint.
+pub trait SwoStreamSource: Send {
+    /// Read SWO data for up to `timeout` duration.
+    ///
+    /// If no data is received before the timeout, returns an empty Vec.
+    fn read_swo_stream(&mut self, timeout: std::time::Duration) -> Result<Vec<u8>, Error>;
 }
 
 /// Helper function to compute a poll interval from a SwoConfig and SWO buffer size.
@@ -170,6 +325,7 @@ pub(crate) fn poll_interval_from_buf_size(
 pub struct SwoReader<'a> {
     interface: &'a mut dyn ArmProbeInterface,
     buf: Vec<u8>,
+    idle_timeout: Option<std::time::Duration>,
 }
 
 impl<'a> SwoReader<'a> {
@@ -177,28 +333,102 @@ impl<'a> SwoReader<'a> {
         Self {
             interface,
             buf: Vec::new(),
+            idle_timeout: None,
         }
     }
+
+    /// Wait for SWO data in [`read`](std::io::Read::read), and deliver the received bytes after
+    /// the target did not send any data for `idle_timeout`.
+    ///
+    /// By default, `read` returns the data received so far immediately, which might be no data at
+    /// all. With an idle timeout, `read` waits until either the buffer passed to it is full, or no
+    /// more data arrived for `idle_timeout`. This way, trailing bytes are delivered even if the
+    /// target stops sending, e.g. the last line of an ITM console without a trailing newline.
+    /// If no data arrives at all within `idle_timeout`, `read` returns no data.
+    pub fn with_idle_timeout(mut self, idle_timeout: std::time::Duration) -> Self {
+        self.idle_timeout = Some(idle_timeout);
+        self
+    }
+
+    /// Read SWO data for `duration`, and write it to `writer`.
+    ///
+    /// Combined with a [`RotatingFileWriter`], this allows long captures to be split into files
+    /// of a manageable size. Returns the number of captured bytes.
+    pub fn capture_to(
+        &mut self,
+        writer: &mut impl std::io::Write,
+        duration: std::time::Duration,
+    ) -> std::io::Result<u64> {
+        use std::io::Read;
+
+        let start = std::time::Instant::now();
+        let mut buf = [0u8; 4096];
+        let mut captured = 0;
+
+        while start.elapsed() < duration {
+            let len = self.read(&mut buf)?;
+
+            if len == 0 {
+                std::thread::sleep(std::time::Duration::from_millis(1));
+                continue;
+            }
+
+            writer.write_all(&buf[..len])?;
+            captured += len as u64;
+        }
+
+        writer.flush()?;
+
+        Ok(captured)
+    }
 }
 
+/// The time [`SwoReader`] waits between polls for SWO data when an idle timeout is set.
+const SWO_IDLE_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(1);
+
 impl<'a> std::io::Read for SwoReader<'a> {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
         use core::cmp;
         use std::{
             io::{Error, ErrorKind},
             mem,
+            time::Instant,
         };
 
-        // Always buffer: this pulls data as quickly as possible from
-        // the target to clear it's embedded trace buffer, minimizing
-        // the chance of an overflow event during which packets are
-        // lost.
-        self.buf.append(
-            &mut self
+        // The time after which the target is considered to have stopped sending.
+        let mut deadline = self
+            .idle_timeout
+            .map(|idle_timeout| Instant::now() + idle_timeout);
+
+        loop {
+            // Always buffer: this pulls data as quickly as possible from
+            // the target to clear it's embedded trace buffer, minimizing
+            // the chance of an overflow event during which packets are
+            // lost.
+            let mut data = self
                 .interface
                 .read_swo()
-                .map_err(|e| Error::new(ErrorKind::Other, e))?,
-        );
+                .map_err(|e| Error::new(ErrorKind::Other, e))?;
+
+            if !data.is_empty() {
+                deadline = self
+                    .idle_timeout
+                    .map(|idle_timeout| Instant::now() + idle_timeout);
+                self.buf.append(&mut data);
+            }
+
+            let deadline = match deadline {
+                Some(deadline) => deadline,
+                None => break,
+            };
+
+            let now = Instant::now();
+            if self.buf.len() >= buf.len() || now >= deadline {
+                break;
+            }
+
+            std::thread::sleep(cmp::min(SWO_IDLE_POLL_INTERVAL, deadline - now));
+        }
 
         let swo = {
             let next_buf = self.buf.split_off(cmp::min(self.buf.len(), buf.len()));
@@ -209,3 +439,36 @@ impl<'a> std::io::Read for SwoReader<'a> {
         Ok(swo.len())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::SwoConfig;
+
+    #[test]
+    fn auto_baud_rate() {
+        let config = SwoConfig::auto(64_000_000, 2_000_000).unwrap();
+        assert_eq!(config.baud(), 2_000_000);
+        assert_eq!(config.tpiu_clk(), 64_000_000);
+
+        // 64 MHz / 3
+        assert_eq!(
+            SwoConfig::auto(64_000_000, 30_000_000).unwrap().baud(),
+            21_333_333
+        );
+
+        // The probe is faster than the core clock.
+        assert_eq!(
+            SwoConfig::auto(8_000_000, 30_000_000).unwrap().baud(),
+            8_000_000
+        );
+    }
+
+    #[test]
+    fn auto_prescaler_too_large() {
+        // A divisor of 0x2000 is the largest one the SWO prescaler supports.
+        assert_eq!(SwoConfig::auto(0x2000 * 1000, 1000).unwrap().baud(), 1000);
+
+        assert!(SwoConfig::auto(0x2000 * 1000 + 1, 1000).is_err());
+        assert!(SwoConfig::auto(64_000_000, 0).is_err());
+    }
+}
@@ -0,0 +1,179 @@
+//! Writing of long trace captures into a sequence of bounded files.
+
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// When a [`RotatingFileWriter`] starts a new file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RotationPolicy {
+    /// Start a new file once the current file contains the given number of bytes.
+    ///
+    /// Writes are split, so no file is larger than this.
+    Size(u64),
+    /// Start a new file for the first write after the current file was open for the given time.
+    Interval(Duration),
+}
+
+/// A writer which distributes the written data over multiple files, according to a
+/// [`RotationPolicy`].
+///
+/// The path of every file is determined by a naming function, which is called with the index of
+/// the file, starting at `0`. Existing files are overwritten. Files are only created once data is
+/// written to them.
+pub struct RotatingFileWriter {
+    policy: RotationPolicy,
+    file_name: Box<dyn FnMut(usize) -> PathBuf + Send>,
+    current: Option<File>,
+    file_count: usize,
+    written: u64,
+    opened_at: Instant,
+}
+
+impl RotatingFileWriter {
+    /// Create a writer which names the files with the given function.
+    pub fn new(
+        policy: RotationPolicy,
+        file_name: impl FnMut(usize) -> PathBuf + Send + 'static,
+    ) -> Self {
+        Self {
+            policy,
+            file_name: Box::new(file_name),
+            current: None,
+            file_count: 0,
+            written: 0,
+            opened_at: Instant::now(),
+        }
+    }
+
+    /// Create a writer which numbers the files based on `path`.
+    ///
+    /// The index of a file is inserted before the extension of `path` with four digits, so
+    /// `trace.bin` results in `trace-0000.bin`, `trace-0001.bin` and so on.
+    pub fn numbered(path: impl AsRef<Path>, policy: RotationPolicy) -> Self {
+        let path = path.as_ref().to_path_buf();
+        Self::new(policy, move |index| numbered_path(&path, index))
+    }
+
+    /// The number of files which were created so far.
+    pub fn file_count(&self) -> usize {
+        self.file_count
+    }
+
+    /// Returns `true` if the current file is full and the next write has to go to a new file.
+    fn needs_rotation(&self) -> bool {
+        match self.policy {
+            RotationPolicy::Size(max_size) => self.written >= max_size,
+            RotationPolicy::Interval(interval) => self.opened_at.elapsed() >= interval,
+        }
+    }
+
+    /// Open the next file.
+    fn rotate(&mut self) -> io::Result<()> {
+        if let Some(mut file) = self.current.take() {
+            file.flush()?;
+        }
+
+        let path = (self.file_name)(self.file_count);
+        tracing::debug!("Writing trace data to {}", path.display());
+
+        self.current = Some(File::create(path)?);
+        self.file_count += 1;
+        self.written = 0;
+        self.opened_at = Instant::now();
+
+        Ok(())
+    }
+}
+
+impl Write for RotatingFileWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        if self.current.is_none() || self.needs_rotation() {
+            self.rotate()?;
+        }
+
+        let len = match self.policy {
+            RotationPolicy::Size(max_size) => {
+                let remaining = max_size.saturating_sub(self.written).max(1);
+                usize::try_from(remaining).map_or(buf.len(), |remaining| remaining.min(buf.len()))
+            }
+            RotationPolicy::Interval(_) => buf.len(),
+        };
+
+        // The file was opened above.
+        let written = self.current.as_mut().unwrap().write(&buf[..len])?;
+        self.written += written as u64;
+
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.current {
+            Some(file) => file.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Inserts `-{index:04}` between the file stem and the extension of `path`.
+fn numbered_path(path: &Path, index: usize) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    let file_name = match path.extension() {
+        Some(extension) => format!("{stem}-{index:04}.{}", extension.to_string_lossy()),
+        None => format!("{stem}-{index:04}"),
+    };
+
+    path.with_file_name(file_name)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{numbered_path, RotatingFileWriter, RotationPolicy};
+    use std::io::Write;
+    use std::path::Path;
+
+    #[test]
+    fn numbered_names() {
+        assert_eq!(
+            numbered_path(Path::new("out/trace.bin"), 3),
+            Path::new("out/trace-0003.bin")
+        );
+        assert_eq!(
+            numbered_path(Path::new("trace"), 12),
+            Path::new("trace-0012")
+        );
+    }
+
+    #[test]
+    fn size_rotation_splits_writes() {
+        let directory =
+            std::env::temp_dir().join(format!("probe-rs-rotating-file-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+
+        let mut writer =
+            RotatingFileWriter::numbered(directory.join("trace.bin"), RotationPolicy::Size(4));
+        writer.write_all(&[0, 1, 2, 3, 4, 5, 6, 7, 8, 9]).unwrap();
+        writer.flush().unwrap();
+
+        assert_eq!(writer.file_count(), 3);
+        assert_eq!(
+            std::fs::read(directory.join("trace-0001.bin")).unwrap(),
+            [4, 5, 6, 7]
+        );
+        assert_eq!(
+            std::fs::read(directory.join("trace-0002.bin")).unwrap(),
+            [8, 9]
+        );
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
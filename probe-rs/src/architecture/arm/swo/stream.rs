@@ -0,0 +1,136 @@
+//! Streaming of decoded SWO data, while the session is used for other debug operations.
+
+use super::SwoStreamSource;
+use crate::architecture::arm::component::{ItmDecoder, ItmPacket};
+use crate::{Error, Session};
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender, TryRecvError};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// The number of chunks of SWO data buffered between the background thread and the stream.
+///
+/// Once the buffer is full, the background thread stops reading from the probe, so the probe
+/// buffers the data until it overflows and reports an overrun.
+const STREAM_CAPACITY: usize = 256;
+
+/// The timeout of a single read of the background thread, which bounds how long it takes to
+/// notice that the stream was dropped.
+const STREAM_READ_TIMEOUT: Duration = Duration::from_millis(10);
+
+/// A stream of decoded ITM packets received over SWO, see
+/// [`Session::swo_stream`](crate::Session::swo_stream).
+///
+/// If the probe has a dedicated SWO channel, e.g. the SWO streaming endpoint of a CMSIS-DAP v2
+/// probe, the SWO data is received by a background thread. Otherwise, the SWO data is polled
+/// from the probe on every call to [`SwoStream::read_packets`]. In both cases, the stream does
+/// not borrow the session, so the core can be halted and stepped while trace flows.
+///
+/// In the background mode, the SWO data must not be read through the session at the same time,
+/// e.g. with [`Session::read_trace_data`](crate::Session::read_trace_data), as the data would be
+/// split between both readers.
+pub struct SwoStream {
+    background: Option<Background>,
+    decoder: ItmDecoder,
+}
+
+/// The background thread which reads a [`SwoStreamSource`].
+struct Background {
+    receiver: Option<Receiver<Result<Vec<u8>, Error>>>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl SwoStream {
+    pub(crate) fn new(source: Option<Box<dyn SwoStreamSource>>) -> Self {
+        let background = source.map(|source| {
+            let (sender, receiver) = mpsc::sync_channel(STREAM_CAPACITY);
+            let stop = Arc::new(AtomicBool::new(false));
+
+            let thread = {
+                let stop = stop.clone();
+                std::thread::spawn(move || stream(source, sender, stop))
+            };
+
+            Background {
+                receiver: Some(receiver),
+                stop,
+                thread: Some(thread),
+            }
+        });
+
+        Self {
+            background,
+            decoder: ItmDecoder::new(),
+        }
+    }
+
+    /// Returns `true` if the SWO data is received by a background thread, instead of being
+    /// polled by [`SwoStream::read_packets`].
+    pub fn is_background(&self) -> bool {
+        self.background.is_some()
+    }
+
+    /// Returns the ITM packets decoded from all SWO data received since the last call, without
+    /// waiting for more data.
+    ///
+    /// The `session` is only used to poll the probe if the stream is not received in the
+    /// background. Packets which are split between two reads are returned by the next read.
+    pub fn read_packets(&mut self, session: &mut Session) -> Result<Vec<ItmPacket>, Error> {
+        match self
+            .background
+            .as_ref()
+            .and_then(|background| background.receiver.as_ref())
+        {
+            Some(receiver) => loop {
+                match receiver.try_recv() {
+                    Ok(data) => self.decoder.feed(&data?),
+                    // The thread stopped after reporting an error.
+                    Err(TryRecvError::Empty | TryRecvError::Disconnected) => break,
+                }
+            },
+            None => {
+                let data = session.get_arm_interface()?.read_swo()?;
+                self.decoder.feed(&data);
+            }
+        }
+
+        Ok(self.decoder.by_ref().collect())
+    }
+}
+
+impl Drop for Background {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+
+        // Dropping the receiver unblocks the thread, if it waits for buffer space.
+        self.receiver.take();
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Reads the `source` until `stop` is set or the receiver is dropped, and sends the data and
+/// the first error to the receiver.
+fn stream(
+    mut source: Box<dyn SwoStreamSource>,
+    sender: SyncSender<Result<Vec<u8>, Error>>,
+    stop: Arc<AtomicBool>,
+) {
+    while !stop.load(Ordering::Relaxed) {
+        let result = source.read_swo_stream(STREAM_READ_TIMEOUT);
+        let failed = result.is_err();
+
+        if matches!(&result, Ok(data) if data.is_empty()) {
+            continue;
+        }
+
+        if sender.send(result).is_err() || failed {
+            return;
+        }
+    }
+}
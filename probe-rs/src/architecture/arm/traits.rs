@@ -48,6 +48,26 @@ pub struct ApAddress {
     pub ap: u8,
 }
 
+/// Settings for the SWD wire protocol.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct SwdSettings {
+    /// The number of turnaround clock cycles, between 1 and 4.
+    ///
+    /// Targets with marginal signal integrity can require a longer turnaround period.
+    pub turnaround: u8,
+    /// Always generate a data phase, also for WAIT and FAULT responses.
+    pub data_phase: bool,
+}
+
+impl Default for SwdSettings {
+    fn default() -> Self {
+        Self {
+            turnaround: 1,
+            data_phase: false,
+        }
+    }
+}
+
 /// Low-level DAP register access.
 ///
 /// Operations on this trait closely match the transactions on the wire. Implementors
@@ -143,6 +163,36 @@ pub trait RawDapAccess {
         pin_wait: u32,
     ) -> Result<u32, DebugProbeError>;
 
+    /// Configure the SWD turnaround period and data phase behaviour.
+    ///
+    /// The settings are applied when attaching to the target, and immediately if the probe is
+    /// already attached.
+    fn set_swd_settings(&mut self, settings: SwdSettings) -> Result<(), DebugProbeError> {
+        let _ = settings;
+        Err(DebugProbeError::CommandNotSupportedByProbe(
+            "set_swd_settings",
+        ))
+    }
+
+    /// Configure the number of extra idle clock cycles the probe inserts after each transfer.
+    ///
+    /// Some targets need the idle cycles to complete internal operations, e.g. on slow internal
+    /// buses. The setting is applied when attaching to the target, and immediately if the probe
+    /// is already attached.
+    fn set_transfer_idle_cycles(&mut self, idle_cycles: u8) -> Result<(), DebugProbeError> {
+        let _ = idle_cycles;
+        Err(DebugProbeError::CommandNotSupportedByProbe(
+            "set_transfer_idle_cycles",
+        ))
+    }
+
+    /// The number of extra idle clock cycles the probe inserts after each transfer.
+    fn transfer_idle_cycles(&self) -> Result<u8, DebugProbeError> {
+        Err(DebugProbeError::CommandNotSupportedByProbe(
+            "transfer_idle_cycles",
+        ))
+    }
+
     /// Cast this interface into a generic [`DebugProbe`].
     fn into_probe(self: Box<Self>) -> Box<dyn DebugProbe>;
 }
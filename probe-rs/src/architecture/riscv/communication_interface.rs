@@ -6,7 +6,7 @@
 
 use super::{
     dtm::{DmiOperation, DmiOperationStatus, Dtm},
-    register, Dmcontrol, Dmstatus,
+    register, Dmcontrol, Dmstatus, Hawindow, Hawindowsel,
 };
 use crate::DebugProbeError;
 use crate::{
@@ -48,7 +48,7 @@ pub enum RiscvError {
     UnsupportedDebugTransportModuleVersion(u8),
     /// This version of the debug module is not supported.
     #[error("The version '{0:?}' of the debug module is currently not supported.")]
-    UnsupportedDebugModuleVersion(DebugModuleVersion),
+    UnsupportedDebugModuleVersion(RiscvDebugVersion),
     /// The provided csr address was invalid/unsupported
     #[error("CSR at address '{0:x}' is unsupported.")]
     UnsupportedCsrAddress(u16),
@@ -67,6 +67,17 @@ pub enum RiscvError {
     /// The given trigger type is not available for the address breakpoint.
     #[error("Unexpected trigger type {0} for address breakpoint.")]
     UnexpectedTriggerType(u32),
+    /// The debug module does not implement the data register with the given index.
+    #[error("The debug module does not implement data register {0}.")]
+    MissingDataRegister(u8),
+    /// The requested hart does not exist.
+    #[error("Hart {hart} does not exist, the debug module only has {num_harts} hart(s).")]
+    HartNotFound {
+        /// The requested hart.
+        hart: u32,
+        /// The number of harts available.
+        num_harts: u32,
+    },
 }
 
 impl From<RiscvError> for ProbeRsError {
@@ -129,31 +140,59 @@ impl AbstractCommandErrorKind {
 /// The version of the debug module can be read from the version field of the `dmstatus`
 /// register.
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
-pub enum DebugModuleVersion {
+pub enum RiscvDebugVersion {
     /// There is no debug module present.
     NoModule,
     /// The debug module conforms to the version 0.11 of the RISCV Debug Specification.
     Version0_11,
     /// The debug module conforms to the version 0.13 of the RISCV Debug Specification.
     Version0_13,
+    /// The debug module conforms to the version 1.0 of the RISCV Debug Specification.
+    Version1_0,
     /// The debug module is present, but does not conform to any available version of the RISCV Debug Specification.
     NonConforming,
     /// Unknown debug module version.
     Unknown(u8),
 }
 
-impl From<u8> for DebugModuleVersion {
+impl From<u8> for RiscvDebugVersion {
     fn from(raw: u8) -> Self {
         match raw {
-            0 => DebugModuleVersion::NoModule,
-            1 => DebugModuleVersion::Version0_11,
-            2 => DebugModuleVersion::Version0_13,
-            15 => DebugModuleVersion::NonConforming,
-            other => DebugModuleVersion::Unknown(other),
+            0 => RiscvDebugVersion::NoModule,
+            1 => RiscvDebugVersion::Version0_11,
+            2 => RiscvDebugVersion::Version0_13,
+            3 => RiscvDebugVersion::Version1_0,
+            15 => RiscvDebugVersion::NonConforming,
+            other => RiscvDebugVersion::Unknown(other),
         }
     }
 }
 
+/// The previous name of [`RiscvDebugVersion`].
+#[deprecated(note = "Use `RiscvDebugVersion` instead")]
+pub type DebugModuleVersion = RiscvDebugVersion;
+
+/// Capabilities of a RISC-V debug module, as determined when the interface is initialized.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DebugModuleCapabilities {
+    /// The version of the debug specification the debug module conforms to.
+    pub version: RiscvDebugVersion,
+    /// The number of harts connected to the debug module.
+    pub num_harts: u32,
+    /// The number of implemented bits of the `hartsel` field.
+    pub hartsellen: u8,
+    /// The size of the program buffer, in 32-bit words.
+    pub progbuf_size: u8,
+    /// An implicit `ebreak` instruction follows the program buffer.
+    pub implicit_ebreak: bool,
+    /// The number of data registers used for abstract commands.
+    pub data_register_count: u8,
+    /// Abstract commands can be executed automatically on data register accesses.
+    pub supports_autoexec: bool,
+    /// The system bus can be accessed directly through the debug module.
+    pub supports_system_bus_access: bool,
+}
+
 #[derive(Copy, Clone, Debug)]
 struct CoreRegisterAbstractCmdSupport(u8);
 
@@ -175,7 +214,7 @@ impl CoreRegisterAbstractCmdSupport {
 #[derive(Debug)]
 pub struct RiscvCommunicationInterfaceState {
     /// Debug specification version
-    debug_version: DebugModuleVersion,
+    debug_version: RiscvDebugVersion,
 
     /// Size of the program buffer, in 32-bit words
     progbuf_size: u8,
@@ -203,8 +242,15 @@ pub struct RiscvCommunicationInterfaceState {
     /// Number of harts
     num_harts: u32,
 
+    /// The hart selected with `hartsel` for all operations
+    selected_hart: u32,
+
     memory_access_info: HashMap<RiscvBusAccess, MemoryAccessMethod>,
 
+    /// Use abstract commands instead of the program buffer for memory
+    /// accesses without system bus access
+    abstract_memory_access: bool,
+
     /// describes, if the given register can be read / written with an
     /// abstract command
     abstract_cmd_register_info: HashMap<RegisterId, CoreRegisterAbstractCmdSupport>,
@@ -225,7 +271,7 @@ impl RiscvCommunicationInterfaceState {
             progbuf_size: 0,
             progbuf_cache: [0u32; 16],
 
-            debug_version: DebugModuleVersion::NonConforming,
+            debug_version: RiscvDebugVersion::NonConforming,
 
             // Assume the implicit ebreak is not present
             implicit_ebreak: false,
@@ -245,19 +291,33 @@ impl RiscvCommunicationInterfaceState {
             // We assume only a singe hart exisits initially
             num_harts: 1,
 
+            selected_hart: 0,
+
             memory_access_info: HashMap::new(),
 
+            abstract_memory_access: false,
+
             abstract_cmd_register_info: HashMap::new(),
         }
     }
 
     /// Get the memory access method which should be used for an
     /// access with the specified width.
+    ///
+    /// If system bus access is not available, memory is accessed using the program
+    /// buffer. If abstract memory access is enabled, abstract commands are used
+    /// instead, until the debug module reports that these are not supported.
     fn memory_access_method(&mut self, access_width: RiscvBusAccess) -> MemoryAccessMethod {
+        let fallback = if self.abstract_memory_access {
+            MemoryAccessMethod::AbstractCommand
+        } else {
+            MemoryAccessMethod::ProgramBuffer
+        };
+
         *self
             .memory_access_info
             .entry(access_width)
-            .or_insert(MemoryAccessMethod::ProgramBuffer)
+            .or_insert(fallback)
     }
 }
 
@@ -318,10 +378,14 @@ impl RiscvCommunicationInterface {
         // read the  version of the debug module
         let status: Dmstatus = self.read_dm_register()?;
 
-        self.state.debug_version = DebugModuleVersion::from(status.version() as u8);
+        self.state.debug_version = RiscvDebugVersion::from(status.version() as u8);
 
-        // Only version of 0.13 of the debug specification is currently supported.
-        if self.state.debug_version != DebugModuleVersion::Version0_13 {
+        // Only versions 0.13 and 1.0 of the debug specification are currently supported.
+        // The registers used by probe-rs are compatible between these versions.
+        if !matches!(
+            self.state.debug_version,
+            RiscvDebugVersion::Version0_13 | RiscvDebugVersion::Version1_0
+        ) {
             return Err(RiscvError::UnsupportedDebugModuleVersion(
                 self.state.debug_version,
             ));
@@ -391,9 +455,8 @@ impl RiscvCommunicationInterface {
 
         self.state.num_harts = num_harts;
 
-        // Select hart 0 again
-        let mut control = Dmcontrol(0);
-        control.set_hartsel(0);
+        // Select the previously selected hart again
+        let mut control = self.dmcontrol();
         control.set_dmactive(true);
 
         self.write_dm_register(control)?;
@@ -481,6 +544,233 @@ impl RiscvCommunicationInterface {
         Ok(())
     }
 
+    /// The version of the debug specification the debug module conforms to.
+    pub fn debug_version(&self) -> RiscvDebugVersion {
+        self.state.debug_version
+    }
+
+    /// The capabilities of the debug module.
+    pub fn capabilities(&self) -> DebugModuleCapabilities {
+        DebugModuleCapabilities {
+            version: self.state.debug_version,
+            num_harts: self.state.num_harts,
+            hartsellen: self.state.hartsellen,
+            progbuf_size: self.state.progbuf_size,
+            implicit_ebreak: self.state.implicit_ebreak,
+            data_register_count: self.state.data_register_count,
+            supports_autoexec: self.state.supports_autoexec,
+            supports_system_bus_access: self
+                .state
+                .memory_access_info
+                .values()
+                .any(|method| matches!(method, MemoryAccessMethod::SystemBus)),
+        }
+    }
+
+    /// The number of harts connected to the debug module.
+    pub fn num_harts(&self) -> u32 {
+        self.state.num_harts
+    }
+
+    /// The hart which is currently selected.
+    pub fn selected_hart(&self) -> u32 {
+        self.state.selected_hart
+    }
+
+    /// Select the hart which is accessed by all following operations.
+    pub fn select_hart(&mut self, hart: u32) -> Result<(), RiscvError> {
+        if hart >= self.state.num_harts {
+            return Err(RiscvError::HartNotFound {
+                hart,
+                num_harts: self.state.num_harts,
+            });
+        }
+
+        if hart == self.state.selected_hart {
+            return Ok(());
+        }
+
+        tracing::debug!("Selecting hart {}", hart);
+
+        self.state.selected_hart = hart;
+
+        let mut control = self.dmcontrol();
+        control.set_dmactive(true);
+        self.write_dm_register(control)
+    }
+
+    /// Request all harts to halt.
+    ///
+    /// If the debug module supports a hart array mask, all harts are halted with a single
+    /// request. Otherwise, the harts are halted one after another.
+    pub(crate) fn request_halt_all_harts(&mut self) -> Result<(), RiscvError> {
+        if self.select_all_harts()? {
+            let mut control = self.dmcontrol();
+            control.set_dmactive(true);
+            control.set_hasel(true);
+            control.set_haltreq(true);
+            return self.write_dm_register(control);
+        }
+
+        for hart in 0..self.state.num_harts {
+            let mut control = Dmcontrol(0);
+            control.set_dmactive(true);
+            control.set_hartsel(hart);
+            control.set_haltreq(true);
+            self.write_dm_register(control)?;
+        }
+
+        Ok(())
+    }
+
+    /// Wait until all harts halted after [`RiscvCommunicationInterface::request_halt_all_harts`].
+    ///
+    /// Returns whether each hart halted within `timeout`. Selecting a hart with `haltreq` cleared
+    /// would also clear its halt request, so the request is set again for every hart which is
+    /// polled. It has to be cleared with
+    /// [`RiscvCommunicationInterface::clear_halt_request_all_harts`] afterwards.
+    pub(crate) fn wait_for_all_harts_halted(
+        &mut self,
+        timeout: Duration,
+    ) -> Result<Vec<bool>, RiscvError> {
+        let start = Instant::now();
+        let mut halted = vec![false; self.state.num_harts as usize];
+
+        loop {
+            for (hart, halted) in halted.iter_mut().enumerate() {
+                if *halted {
+                    continue;
+                }
+
+                let mut control = Dmcontrol(0);
+                control.set_dmactive(true);
+                control.set_hartsel(hart as u32);
+                control.set_haltreq(true);
+                self.write_dm_register(control)?;
+
+                let status: Dmstatus = self.read_dm_register()?;
+                *halted = status.allhalted();
+            }
+
+            if halted.iter().all(|&halted| halted) || start.elapsed() >= timeout {
+                return Ok(halted);
+            }
+        }
+    }
+
+    /// Clear the halt requests issued by [`RiscvCommunicationInterface::request_halt_all_harts`].
+    pub(crate) fn clear_halt_request_all_harts(&mut self) -> Result<(), RiscvError> {
+        for hart in 0..self.state.num_harts {
+            let mut control = Dmcontrol(0);
+            control.set_dmactive(true);
+            control.set_hartsel(hart);
+            self.write_dm_register(control)?;
+        }
+
+        // Restore the selection of the current hart.
+        let mut control = self.dmcontrol();
+        control.set_dmactive(true);
+        self.write_dm_register(control)
+    }
+
+    /// Request the given `harts` to resume, using a single request if the debug module supports
+    /// hart arrays.
+    ///
+    /// Returns whether the resume request was acknowledged, for each of the `harts`.
+    pub(crate) fn resume_harts(&mut self, harts: &[u32]) -> Result<Vec<bool>, RiscvError> {
+        if let Some(&hart) = harts.iter().find(|&&hart| hart >= self.state.num_harts) {
+            return Err(RiscvError::HartNotFound {
+                hart,
+                num_harts: self.state.num_harts,
+            });
+        }
+
+        if harts.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        if self.select_harts(harts)? {
+            let mut control = self.dmcontrol();
+            control.set_dmactive(true);
+            control.set_hasel(true);
+            control.set_resumereq(true);
+            self.write_dm_register(control)?;
+        } else {
+            for &hart in harts {
+                let mut control = Dmcontrol(0);
+                control.set_dmactive(true);
+                control.set_hartsel(hart);
+                control.set_resumereq(true);
+                self.write_dm_register(control)?;
+            }
+        }
+
+        // Selecting each hart on its own also clears the resume request.
+        let mut acknowledged = Vec::with_capacity(harts.len());
+
+        for &hart in harts {
+            let mut control = Dmcontrol(0);
+            control.set_dmactive(true);
+            control.set_hartsel(hart);
+            self.write_dm_register(control)?;
+
+            let status: Dmstatus = self.read_dm_register()?;
+            acknowledged.push(status.allresumeack());
+        }
+
+        // Restore the selection of the current hart.
+        let mut control = self.dmcontrol();
+        control.set_dmactive(true);
+        self.write_dm_register(control)?;
+
+        Ok(acknowledged)
+    }
+
+    /// Add all harts to the hart array mask.
+    ///
+    /// Returns `false` if the debug module does not support selecting multiple harts,
+    /// or has more harts than fit into a single hart array window.
+    fn select_all_harts(&mut self) -> Result<bool, RiscvError> {
+        let harts: Vec<u32> = (0..self.state.num_harts).collect();
+        self.select_harts(&harts)
+    }
+
+    /// Set the hart array mask to the given `harts`.
+    ///
+    /// Returns `false` if the debug module does not support selecting multiple harts,
+    /// or one of the harts is outside of the first hart array window.
+    fn select_harts(&mut self, harts: &[u32]) -> Result<bool, RiscvError> {
+        if harts.iter().any(|&hart| hart >= 32) {
+            return Ok(false);
+        }
+
+        let mut control = self.dmcontrol();
+        control.set_dmactive(true);
+        control.set_hasel(true);
+        self.write_dm_register(control)?;
+
+        let readback: Dmcontrol = self.read_dm_register()?;
+
+        if !readback.hasel() {
+            tracing::debug!("Debug module does not support hart arrays");
+            return Ok(false);
+        }
+
+        let mask = harts.iter().fold(0u32, |mask, &hart| mask | 1 << hart);
+
+        self.write_dm_register(Hawindowsel(0))?;
+        self.write_dm_register(Hawindow(mask))?;
+
+        Ok(true)
+    }
+
+    /// A `dmcontrol` value with only the currently selected hart set.
+    pub(super) fn dmcontrol(&self) -> Dmcontrol {
+        let mut control = Dmcontrol(0);
+        control.set_hartsel(self.state.selected_hart);
+        control
+    }
+
     pub(super) fn read_dm_register<R: DebugRegister>(&mut self) -> Result<R, RiscvError> {
         tracing::debug!("Reading DM register '{}' at {:#010x}", R::NAME, R::ADDRESS);
 
@@ -680,6 +970,109 @@ impl RiscvCommunicationInterface {
         }
     }
 
+    /// Perform memory read from a single location using an abstract command.
+    /// Only reads up to a width of 32 bits are currently supported.
+    fn perform_memory_read_abstract<V: RiscvValue32>(
+        &mut self,
+        address: u32,
+    ) -> Result<V, RiscvError> {
+        // The address is stored in arg1, which is data1 for 32 bit accesses.
+        self.write_dm_register(Data1(address))?;
+
+        let mut command = AccessMemoryCommand(0);
+        command.set_aamsize(V::WIDTH as u32);
+
+        self.run_abstract_command(command.into())?;
+
+        let value: Data0 = self.read_dm_register()?;
+
+        Ok(V::from_register_value(value.into()))
+    }
+
+    /// Perform memory reads from consecutive locations using abstract commands.
+    fn perform_memory_read_multiple_abstract<V: RiscvValue32>(
+        &mut self,
+        address: u32,
+        data: &mut [V],
+    ) -> Result<(), RiscvError> {
+        self.write_dm_register(Data1(address))?;
+
+        let mut command = AccessMemoryCommand(0);
+        command.set_aamsize(V::WIDTH as u32);
+        command.set_aampostincrement(true);
+
+        let command: u32 = command.into();
+
+        for value in data.iter_mut() {
+            self.run_abstract_command(command)?;
+
+            let data0: Data0 = self.read_dm_register()?;
+            *value = V::from_register_value(data0.into());
+        }
+
+        Ok(())
+    }
+
+    /// Perform memory writes to consecutive locations using abstract commands.
+    fn perform_memory_write_abstract<V: RiscvValue32>(
+        &mut self,
+        address: u32,
+        data: &[V],
+    ) -> Result<(), RiscvError> {
+        self.write_dm_register(Data1(address))?;
+
+        let mut command = AccessMemoryCommand(0);
+        command.set_aamsize(V::WIDTH as u32);
+        command.set_aampostincrement(true);
+        command.set_write(true);
+
+        let command: u32 = command.into();
+
+        for value in data {
+            V::write_to_register::<Arg0>(self, *value)?;
+            self.run_abstract_command(command)?;
+        }
+
+        Ok(())
+    }
+
+    /// Remember that memory accesses with the given width are not possible
+    /// using abstract commands, if the error indicates this.
+    ///
+    /// Returns `true` if the access should be retried using the program buffer.
+    fn abstract_memory_access_failed(
+        &mut self,
+        access_width: RiscvBusAccess,
+        error: &RiscvError,
+    ) -> bool {
+        if let RiscvError::AbstractCommand(AbstractCommandErrorKind::NotSupported) = error {
+            tracing::debug!(
+                "Memory access with width {:?} using abstract commands is not supported, falling back to program buffer",
+                access_width
+            );
+            self.state
+                .memory_access_info
+                .insert(access_width, MemoryAccessMethod::ProgramBuffer);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Enable or disable memory accesses using abstract commands.
+    ///
+    /// By default, memory which can't be accessed using system bus access is accessed
+    /// using the program buffer. When enabled, abstract commands are used instead, falling
+    /// back to the program buffer for access widths the debug module does not support.
+    pub fn set_abstract_memory_access(&mut self, enabled: bool) {
+        self.state.abstract_memory_access = enabled;
+
+        // Select the method for accesses without system bus access again.
+        self.state
+            .memory_access_info
+            .retain(|_, method| matches!(method, MemoryAccessMethod::SystemBus));
+    }
+
     /// Perform memory read from a single location using the program buffer.
     /// Only reads up to a width of 32 bits are currently supported.
     fn perform_memory_read_progbuf<V: RiscvValue32>(
@@ -977,13 +1370,43 @@ impl RiscvCommunicationInterface {
         Ok(())
     }
 
-    pub(crate) fn execute_abstract_command(&mut self, command: u32) -> Result<(), RiscvError> {
+    /// Execute an arbitrary abstract command, e.g. a vendor specific command.
+    ///
+    /// `cmdtype` is written to bits 31:24 of the `command` register, `control` to bits 23:0.
+    /// Arguments can be passed with [`RiscvCommunicationInterface::write_abstract_data`].
+    ///
+    /// On success, the contents of all data registers are returned. If the debug module reports
+    /// an error in `abstractcs.cmderr`, a [`RiscvError::AbstractCommand`] error is returned.
+    pub fn execute_abstract_command(
+        &mut self,
+        cmdtype: u8,
+        control: u32,
+    ) -> Result<Vec<u32>, RiscvError> {
+        let command = (u32::from(cmdtype) << 24) | (control & 0x00ff_ffff);
+
+        self.run_abstract_command(command)?;
+
+        (0..self.state.data_register_count)
+            .map(|index| self.read_dm_register_untyped(Data0::ADDRESS as u64 + index as u64))
+            .collect()
+    }
+
+    /// Write the data register with the given index, which is used for the arguments of abstract commands.
+    pub fn write_abstract_data(&mut self, index: u8, value: u32) -> Result<(), RiscvError> {
+        if index >= self.state.data_register_count {
+            return Err(RiscvError::MissingDataRegister(index));
+        }
+
+        self.write_dm_register_untyped(Data0::ADDRESS as u64 + index as u64, value)
+    }
+
+    pub(crate) fn run_abstract_command(&mut self, command: u32) -> Result<(), RiscvError> {
         // ensure that preconditions are fullfileld
         // haltreq      = 0
         // resumereq    = 0
         // ackhavereset = 0
 
-        let mut dmcontrol = Dmcontrol(0);
+        let mut dmcontrol = self.dmcontrol();
         dmcontrol.set_haltreq(false);
         dmcontrol.set_resumereq(false);
         dmcontrol.set_ackhavereset(false);
@@ -1086,7 +1509,7 @@ impl RiscvCommunicationInterface {
 
         command.set_regno(regno.0 as u32);
 
-        match self.execute_abstract_command(command.0) {
+        match self.run_abstract_command(command.0) {
             Ok(_) => (),
             err @ Err(RiscvError::AbstractCommand(AbstractCommandErrorKind::NotSupported)) => {
                 // Remember, that this register is unsupported
@@ -1129,7 +1552,7 @@ impl RiscvCommunicationInterface {
 
         self.write_large_dtm_register::<V, Arg0>(value)?;
 
-        match self.execute_abstract_command(command.0) {
+        match self.run_abstract_command(command.0) {
             Ok(_) => Ok(()),
             err @ Err(RiscvError::AbstractCommand(AbstractCommandErrorKind::NotSupported)) => {
                 // Remember, that this register is unsupported
@@ -1163,7 +1586,7 @@ impl RiscvCommunicationInterface {
         let mut postexec_cmd = AccessRegisterCommand(0);
         postexec_cmd.set_postexec(true);
 
-        self.execute_abstract_command(postexec_cmd.0)?;
+        self.run_abstract_command(postexec_cmd.0)?;
 
         // read the s0 value
         let reg_value = self.abstract_cmd_register_read(&register::S0)?;
@@ -1197,7 +1620,7 @@ impl RiscvCommunicationInterface {
         let mut postexec_cmd = AccessRegisterCommand(0);
         postexec_cmd.set_postexec(true);
 
-        self.execute_abstract_command(postexec_cmd.0)?;
+        self.run_abstract_command(postexec_cmd.0)?;
 
         // command: transfer, regno = 0x1008
         // restore original value in s0
@@ -1227,7 +1650,12 @@ impl RiscvCommunicationInterface {
             MemoryAccessMethod::ProgramBuffer => self.perform_memory_read_progbuf(address)?,
             MemoryAccessMethod::SystemBus => self.perform_memory_read_sysbus(address)?,
             MemoryAccessMethod::AbstractCommand => {
-                unimplemented!("Memory access using abstract commands is not implemted")
+                match self.perform_memory_read_abstract(address) {
+                    Err(e) if self.abstract_memory_access_failed(V::WIDTH, &e) => {
+                        self.perform_memory_read_progbuf(address)?
+                    }
+                    other => other?,
+                }
             }
         };
 
@@ -1249,7 +1677,12 @@ impl RiscvCommunicationInterface {
                 self.perform_memory_read_multiple_sysbus(address, data)?;
             }
             MemoryAccessMethod::AbstractCommand => {
-                unimplemented!("Memory access using abstract commands is not implemted")
+                match self.perform_memory_read_multiple_abstract(address, data) {
+                    Err(e) if self.abstract_memory_access_failed(RiscvBusAccess::A32, &e) => {
+                        self.perform_memory_read_multiple_progbuf(address, data)?
+                    }
+                    other => other?,
+                }
             }
         };
 
@@ -1263,7 +1696,12 @@ impl RiscvCommunicationInterface {
             }
             MemoryAccessMethod::SystemBus => self.perform_memory_write_sysbus(address, &[data])?,
             MemoryAccessMethod::AbstractCommand => {
-                unimplemented!("Memory access using abstract commands is not implemted")
+                match self.perform_memory_write_abstract(address, &[data]) {
+                    Err(e) if self.abstract_memory_access_failed(V::WIDTH, &e) => {
+                        self.perform_memory_write_progbuf(address, data)?
+                    }
+                    other => other?,
+                }
             }
         };
 
@@ -1281,7 +1719,12 @@ impl RiscvCommunicationInterface {
                 self.perform_memory_write_multiple_progbuf(address, data)?
             }
             MemoryAccessMethod::AbstractCommand => {
-                unimplemented!("Memory access using abstract commands is not implemted")
+                match self.perform_memory_write_abstract(address, data) {
+                    Err(e) if self.abstract_memory_access_failed(V::WIDTH, &e) => {
+                        self.perform_memory_write_multiple_progbuf(address, data)?
+                    }
+                    other => other?,
+                }
             }
         }
 
@@ -1864,10 +2307,7 @@ impl From<RiscvBusAccess> for u8 {
 
 /// Different methods of memory access,
 /// which can be supported by a debug module.
-///
-/// The `AbstractCommand` method for memory access is not implemented.
 #[derive(Debug, Copy, Clone)]
-#[allow(dead_code)]
 enum MemoryAccessMethod {
     /// Memory access using the program buffer is supported
     ProgramBuffer,
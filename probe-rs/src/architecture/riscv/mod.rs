@@ -2,7 +2,7 @@
 
 #![allow(clippy::inconsistent_digit_grouping)]
 
-use crate::core::{Architecture, BreakpointCause};
+use crate::core::{Architecture, BreakpointCause, WatchpointAccess};
 use crate::{CoreInterface, CoreType, DebugProbeError, InstructionSet};
 use anyhow::{anyhow, Result};
 use communication_interface::{
@@ -16,15 +16,19 @@ use crate::{CoreStatus, Error, HaltReason, MemoryInterface, RegisterId};
 use bitfield::bitfield;
 use register::RISCV_REGISTERS;
 use std::time::{Duration, Instant};
+use trigger::{napot_tdata2, Mcontrol, TDATA1, TDATA2, TINFO, TRIGGER_TYPE_MCONTROL, TSELECT};
 
 #[macro_use]
 mod register;
 pub(crate) mod assembly;
 mod dtm;
+mod trigger;
 
 pub mod communication_interface;
 pub mod sequences;
 
+pub use trigger::TriggerKind;
+
 /// A interface to operate RISC-V cores.
 pub struct Riscv32<'probe> {
     interface: &'probe mut RiscvCommunicationInterface,
@@ -72,10 +76,96 @@ impl<'probe> Riscv32<'probe> {
         }
     }
 
+    /// Program the trigger with index `unit_index` to halt the core on an access of the given
+    /// `kind` to `address`.
+    ///
+    /// Execution triggers are the hardware breakpoints used by [`CoreInterface::set_hw_breakpoint`],
+    /// load and store triggers are the watchpoints used by [`CoreInterface::set_hw_watchpoint`].
+    /// A trigger is cleared with [`CoreInterface::clear_hw_breakpoint`].
+    pub fn set_trigger(
+        &mut self,
+        unit_index: usize,
+        address: u64,
+        kind: TriggerKind,
+    ) -> Result<(), crate::Error> {
+        let address = valid_32bit_address(address)?;
+
+        if !self.hw_breakpoints_enabled() {
+            self.enable_breakpoints(true)?;
+        }
+
+        tracing::debug!("Setting {:?} trigger {}", kind, unit_index);
+
+        self.write_csr(TSELECT, unit_index as u32)?;
+
+        // verify the trigger has the correct type
+        let tdata_value = Mcontrol(self.read_csr(TDATA1)?);
+
+        let trigger_type = tdata_value.type_();
+        if trigger_type != TRIGGER_TYPE_MCONTROL {
+            return Err(RiscvError::UnexpectedTriggerType(trigger_type).into());
+        }
+
+        self.write_csr(TDATA1, Mcontrol::address_match(kind).0)?;
+        self.write_csr(TDATA2, address)?;
+
+        Ok(())
+    }
+
+    /// Read the reason for the last halt and the PC the hart halted at from `dcsr` and `dpc`.
+    ///
+    /// The hart has to be halted, otherwise the debug CSRs can not be read. This is also
+    /// available as [`Core::riscv_halt_status`](crate::Core::riscv_halt_status).
+    pub fn halt_status(&mut self) -> Result<RiscvHaltStatus, crate::Error> {
+        let dcsr = Dcsr(self.read_csr(0x7b0)?);
+        let dpc = self.read_csr(0x7b1)?;
+
+        Ok(RiscvHaltStatus {
+            cause: RiscvHaltCause::from(dcsr.cause() as u8),
+            pc: dpc,
+            privilege: dcsr.prv() as u8,
+            nmi_pending: dcsr.nmip(),
+        })
+    }
+
+    /// Returns the address matched by every trigger which enters debug mode and is of the kind
+    /// selected by `is_kind`, or `None` for all other triggers.
+    fn address_triggers(
+        &mut self,
+        is_kind: impl Fn(&Mcontrol) -> bool,
+    ) -> Result<Vec<Option<u64>>, crate::Error> {
+        let mut triggers = vec![];
+        let num_triggers = self.available_breakpoint_units()? as usize;
+        for unit_index in 0..num_triggers {
+            // Select the trigger.
+            self.write_csr(TSELECT, unit_index as u32)?;
+
+            // Read the trigger "configuration" data.
+            let tdata_value = Mcontrol(self.read_csr(TDATA1)?);
+
+            tracing::debug!("Trigger {}: {:?}", unit_index, tdata_value);
+
+            // The trigger must be active in at least a single mode
+            let trigger_any_mode_active = tdata_value.m() || tdata_value.s() || tdata_value.u();
+
+            if tdata_value.is_debug_address_match()
+                && trigger_any_mode_active
+                && is_kind(&tdata_value)
+            {
+                let address = tdata_value.matched_address(self.read_csr(TDATA2)?);
+                triggers.push(Some(address as u64));
+            } else {
+                triggers.push(None);
+            }
+        }
+
+        Ok(triggers)
+    }
+
     // Resume the core.
     fn resume_core(&mut self) -> Result<(), crate::Error> {
         // set resume request.
-        let mut dmcontrol = Dmcontrol(0);
+        let mut dmcontrol = self.interface.dmcontrol();
         dmcontrol.set_resumereq(true);
         dmcontrol.set_dmactive(true);
         self.interface.write_dm_register(dmcontrol)?;
@@ -87,7 +177,7 @@ impl<'probe> Riscv32<'probe> {
         };
 
         // clear resume request.
-        let mut dmcontrol = Dmcontrol(0);
+        let mut dmcontrol = self.interface.dmcontrol();
         dmcontrol.set_dmactive(true);
         self.interface.write_dm_register(dmcontrol)?;
 
@@ -127,7 +217,7 @@ impl<'probe> CoreInterface for Riscv32<'probe> {
             self.interface.read_dm_register::<Dmcontrol>()?
         );
 
-        let mut dmcontrol = Dmcontrol(0);
+        let mut dmcontrol = self.interface.dmcontrol();
 
         dmcontrol.set_haltreq(true);
         dmcontrol.set_dmactive(true);
@@ -137,7 +227,7 @@ impl<'probe> CoreInterface for Riscv32<'probe> {
         self.wait_for_core_halted(timeout)?;
 
         // clear the halt request
-        let mut dmcontrol = Dmcontrol(0);
+        let mut dmcontrol = self.interface.dmcontrol();
 
         dmcontrol.set_dmactive(true);
 
@@ -177,7 +267,7 @@ impl<'probe> CoreInterface for Riscv32<'probe> {
     ) -> Result<crate::core::CoreInformation, crate::Error> {
         tracing::debug!("Resetting core, setting hartreset bit");
 
-        let mut dmcontrol = Dmcontrol(0);
+        let mut dmcontrol = self.interface.dmcontrol();
         dmcontrol.set_dmactive(true);
         dmcontrol.set_hartreset(true);
         dmcontrol.set_haltreq(true);
@@ -190,7 +280,7 @@ impl<'probe> CoreInterface for Riscv32<'probe> {
         if readback.hartreset() {
             tracing::debug!("Clearing hartreset bit");
             // Reset is performed by setting the bit high, and then low again
-            let mut dmcontrol = Dmcontrol(0);
+            let mut dmcontrol = self.interface.dmcontrol();
             dmcontrol.set_dmactive(true);
             dmcontrol.set_haltreq(true);
             dmcontrol.set_hartreset(false);
@@ -201,7 +291,7 @@ impl<'probe> CoreInterface for Riscv32<'probe> {
             //
             // TODO: Cache this
             tracing::debug!("Hartreset bit not supported, using ndmreset");
-            let mut dmcontrol = Dmcontrol(0);
+            let mut dmcontrol = self.interface.dmcontrol();
             dmcontrol.set_dmactive(true);
             dmcontrol.set_ndmreset(true);
             dmcontrol.set_haltreq(true);
@@ -209,7 +299,7 @@ impl<'probe> CoreInterface for Riscv32<'probe> {
             self.interface.write_dm_register(dmcontrol)?;
 
             tracing::debug!("Clearing ndmreset bit");
-            let mut dmcontrol = Dmcontrol(0);
+            let mut dmcontrol = self.interface.dmcontrol();
             dmcontrol.set_dmactive(true);
             dmcontrol.set_ndmreset(false);
             dmcontrol.set_haltreq(true);
@@ -225,7 +315,7 @@ impl<'probe> CoreInterface for Riscv32<'probe> {
         }
 
         // acknowledge the reset, clear the halt request
-        let mut dmcontrol = Dmcontrol(0);
+        let mut dmcontrol = self.interface.dmcontrol();
         dmcontrol.set_dmactive(true);
         dmcontrol.set_ackhavereset(true);
 
@@ -237,65 +327,9 @@ impl<'probe> CoreInterface for Riscv32<'probe> {
     }
 
     fn step(&mut self) -> Result<crate::core::CoreInformation, crate::Error> {
-        let halt_reason = self.status()?;
-        if matches!(
-            halt_reason,
-            CoreStatus::Halted(HaltReason::Breakpoint(BreakpointCause::Software))
-        ) && self.state.hw_breakpoints_enabled
-        {
-            // If we are halted on a software breakpoint AND we have passed the flashing operation, we can skip the single step and manually advance the dpc.
-            let mut debug_pc = self.read_core_reg(RegisterId(0x7b1))?;
-            // Advance the dpc by the size of the EBREAK (ebreak or c.ebreak) instruction.
-            if matches!(self.instruction_set()?, InstructionSet::RV32C) {
-                debug_pc.incremenet_address(2)?;
-            } else {
-                debug_pc.incremenet_address(4)?;
-            }
+        let pc = single_step(self)?;
 
-            self.write_core_reg(RegisterId(0x7b1), debug_pc)?;
-            return Ok(CoreInformation {
-                pc: debug_pc.try_into()?,
-            });
-        } else if matches!(
-            halt_reason,
-            CoreStatus::Halted(HaltReason::Breakpoint(BreakpointCause::Hardware))
-        ) {
-            // If we are halted on a hardware breakpoint.
-            self.enable_breakpoints(false)?;
-        }
-
-        let mut dcsr = Dcsr(self.read_core_reg(RegisterId(0x7b0))?.try_into()?);
-        // Set it up, so that the next `self.run()` will only do a single step
-        dcsr.set_step(true);
-        // Disable any interrupts during single step.
-        dcsr.set_stepie(false);
-        dcsr.set_stopcount(true);
-        self.write_csr(0x7b0, dcsr.0)?;
-
-        // Now we can resume the core for the single step.
-        self.resume_core()?;
-        self.wait_for_core_halted(Duration::from_millis(100))?;
-
-        let pc = self.read_core_reg(RegisterId(0x7b1))?;
-
-        // clear step request
-        let mut dcsr = Dcsr(self.read_core_reg(RegisterId(0x7b0))?.try_into()?);
-        dcsr.set_step(false);
-        //Re-enable interrupts for single step.
-        dcsr.set_stepie(true);
-        dcsr.set_stopcount(false);
-        self.write_csr(0x7b0, dcsr.0)?;
-
-        // Re-enable breakpoints before we continue.
-        if matches!(
-            halt_reason,
-            CoreStatus::Halted(HaltReason::Breakpoint(BreakpointCause::Hardware))
-        ) {
-            // If we are halted on a hardware breakpoint.
-            self.enable_breakpoints(true)?;
-        }
-
-        Ok(CoreInformation { pc: pc.try_into()? })
+        Ok(CoreInformation { pc: pc.into() })
     }
 
     fn read_core_reg(&mut self, address: crate::RegisterId) -> Result<RegisterValue, crate::Error> {
@@ -314,33 +348,31 @@ impl<'probe> CoreInterface for Riscv32<'probe> {
     }
 
     fn available_breakpoint_units(&mut self) -> Result<u32, crate::Error> {
-        // TODO: This should probably only be done once, when initialising
+        if let Some(count) = self.state.trigger_count {
+            return Ok(count);
+        }
 
         tracing::debug!("Determining number of HW breakpoints supported");
 
-        let tselect = 0x7a0;
-        let tdata1 = 0x7a1;
-        let tinfo = 0x7a4;
-
         let mut tselect_index = 0;
 
         // These steps follow the debug specification 0.13, section 5.1 Enumeration
         loop {
             tracing::debug!("Trying tselect={}", tselect_index);
-            if let Err(e) = self.write_csr(tselect, tselect_index) {
+            if let Err(e) = self.write_csr(TSELECT, tselect_index) {
                 match e {
                     RiscvError::AbstractCommand(AbstractCommandErrorKind::Exception) => break,
                     other_error => return Err(other_error.into()),
                 }
             }
 
-            let readback = self.read_csr(tselect)?;
+            let readback = self.read_csr(TSELECT)?;
 
             if readback != tselect_index {
                 break;
             }
 
-            match self.read_csr(tinfo) {
+            match self.read_csr(TINFO) {
                 Ok(tinfo_val) => {
                     if tinfo_val & 0xffff == 1 {
                         // Trigger doesn't exist, break the loop
@@ -355,7 +387,7 @@ impl<'probe> CoreInterface for Riscv32<'probe> {
                 }
                 Err(RiscvError::AbstractCommand(AbstractCommandErrorKind::Exception)) => {
                     // An exception means we have to read tdata1 to discover the type
-                    let tdata_val = self.read_csr(tdata1)?;
+                    let tdata_val = self.read_csr(TDATA1)?;
 
                     // Read the mxl field from the misa register (see RISC-V Privileged Spec, 3.1.1)
                     let misa_value = Misa(self.read_csr(0x301)?);
@@ -381,26 +413,22 @@ impl<'probe> CoreInterface for Riscv32<'probe> {
 
         tracing::debug!("Target supports {} breakpoints.", tselect_index);
 
+        self.state.trigger_count = Some(tselect_index);
+
         Ok(tselect_index)
     }
 
     fn enable_breakpoints(&mut self, state: bool) -> Result<(), crate::Error> {
         // Loop through all triggers, and enable/disable them.
-        let tselect = 0x7a0;
-        let tdata1 = 0x7a1;
-
         for bp_unit_index in 0..self.available_breakpoint_units()? as usize {
             // Select the trigger.
-            self.write_csr(tselect, bp_unit_index as u32)?;
+            self.write_csr(TSELECT, bp_unit_index as u32)?;
 
             // Read the trigger "configuration" data.
-            let mut tdata_value = Mcontrol(self.read_csr(tdata1)?);
+            let mut tdata_value = Mcontrol(self.read_csr(TDATA1)?);
 
-            // Only modify the trigger if it is for an execution debug action in all modes(probe-rs enabled it) or no modes (we previously disabled it).
-            if tdata_value.type_() == 0b10
-                && tdata_value.action() == 1
-                && tdata_value.match_() == 0
-                && tdata_value.execute()
+            // Only modify the trigger if it is for a debug action in all modes (probe-rs enabled it) or no modes (we previously disabled it).
+            if tdata_value.is_debug_address_match()
                 && ((tdata_value.m() && tdata_value.u()) || (!tdata_value.m() && !tdata_value.u()))
             {
                 tracing::debug!(
@@ -411,7 +439,7 @@ impl<'probe> CoreInterface for Riscv32<'probe> {
                 );
                 tdata_value.set_m(state);
                 tdata_value.set_u(state);
-                self.write_csr(tdata1, tdata_value.0)?;
+                self.write_csr(TDATA1, tdata_value.0)?;
             }
         }
 
@@ -420,69 +448,127 @@ impl<'probe> CoreInterface for Riscv32<'probe> {
     }
 
     fn set_hw_breakpoint(&mut self, bp_unit_index: usize, addr: u64) -> Result<(), crate::Error> {
-        let addr = valid_32bit_address(addr)?;
+        self.set_trigger(bp_unit_index, addr, TriggerKind::Execute)
+    }
 
-        if !self.hw_breakpoints_enabled() {
-            self.enable_breakpoints(true)?;
-        }
+    fn clear_hw_breakpoint(&mut self, unit_index: usize) -> Result<(), crate::Error> {
+        self.write_csr(TSELECT, unit_index as u32)?;
+        self.write_csr(TDATA1, 0)?;
+        self.write_csr(TDATA2, 0)?;
 
-        // select requested trigger
-        let tselect = 0x7a0;
-        let tdata1 = 0x7a1;
-        let tdata2 = 0x7a2;
+        Ok(())
+    }
 
-        tracing::warn!("Setting breakpoint {}", bp_unit_index);
+    /// The watchpoints share the triggers with the hardware breakpoints.
+    fn available_watchpoint_units(&mut self) -> Result<u32, crate::Error> {
+        self.available_breakpoint_units()
+    }
 
-        self.write_csr(tselect, bp_unit_index as u32)?;
+    /// NOTE: For riscv, only the load and store triggers are reported, triggers used by hardware
+    /// breakpoints are `None`.
+    fn hw_watchpoints(&mut self) -> Result<Vec<Option<u64>>, crate::Error> {
+        self.address_triggers(Mcontrol::is_watchpoint)
+    }
 
-        // verify the trigger has the correct type
+    /// Watchpoints of a single byte use an exact address match, larger watchpoints a naturally
+    /// aligned power of two (NAPOT) range, which is only supported by some triggers.
+    fn set_hw_watchpoint(
+        &mut self,
+        unit_index: usize,
+        address: u64,
+        size: u64,
+        access: WatchpointAccess,
+    ) -> Result<(), crate::Error> {
+        let address = valid_32bit_address(address)?;
+
+        if !size.is_power_of_two() || address as u64 % size != 0 {
+            return Err(Error::Other(anyhow!(
+                "The watchpoint size {} is not a power of two, or the address {:#010x} is not aligned to it.",
+                size,
+                address
+            )));
+        }
+
+        // The NAPOT mask of a range covering the whole 32 bit address space can't be encoded.
+        let napot_size = u32::try_from(size).map_err(|_| {
+            Error::Other(anyhow!(
+                "The watchpoint size {} is larger than the 32 bit address space.",
+                size
+            ))
+        })?;
+
+        self.write_csr(TSELECT, unit_index as u32)?;
+
+        let tdata_value = Mcontrol(self.read_csr(TDATA1)?);
+        if tdata_value.is_debug_address_match() && !tdata_value.is_watchpoint() {
+            return Err(Error::Other(anyhow!(
+                "Trigger {} is used by a hardware breakpoint.",
+                unit_index
+            )));
+        }
 
-        let tdata_value = Mcontrol(self.read_csr(tdata1)?);
+        if size == 1 {
+            return self.set_trigger(unit_index, address as u64, access.into());
+        }
 
-        // This should not happen
         let trigger_type = tdata_value.type_();
-        if trigger_type != 0b10 {
+        if trigger_type != TRIGGER_TYPE_MCONTROL {
             return Err(RiscvError::UnexpectedTriggerType(trigger_type).into());
         }
 
-        // Setup the trigger
-
-        let mut instruction_breakpoint = Mcontrol(0);
-
-        // Enter debug mode
-        instruction_breakpoint.set_action(1);
-
-        // Match exactly the value in tdata2
-        instruction_breakpoint.set_match(0);
-
-        instruction_breakpoint.set_m(true);
-
-        instruction_breakpoint.set_u(true);
-
-        // Trigger when instruction is executed
-        instruction_breakpoint.set_execute(true);
+        // `maskmax` is the logarithm of the largest range the trigger can match.
+        if size.trailing_zeros() > tdata_value.maskmax() {
+            return Err(Error::Other(anyhow!(
+                "The watchpoint size {} is larger than the maximum size of {} bytes of trigger {}.",
+                size,
+                if tdata_value.maskmax() == 0 {
+                    1
+                } else {
+                    1u64 << tdata_value.maskmax()
+                },
+                unit_index
+            )));
+        }
 
-        instruction_breakpoint.set_dmode(true);
+        if !self.hw_breakpoints_enabled() {
+            self.enable_breakpoints(true)?;
+        }
 
-        // Match address
-        instruction_breakpoint.set_select(false);
+        tracing::debug!(
+            "Setting {:?} trigger {} on {} bytes",
+            access,
+            unit_index,
+            size
+        );
 
-        self.write_csr(tdata1, instruction_breakpoint.0)?;
-        self.write_csr(tdata2, addr)?;
+        let trigger = Mcontrol::napot_match(access.into());
+        self.write_csr(TDATA1, trigger.0)?;
+        self.write_csr(TDATA2, napot_tdata2(address, napot_size))?;
+
+        // The match field is WARL, triggers without NAPOT support read back a different value.
+        if Mcontrol(self.read_csr(TDATA1)?).match_() != trigger.match_() {
+            self.clear_hw_watchpoint(unit_index)?;
+            return Err(Error::Other(anyhow!(
+                "Trigger {} does not support matching address ranges.",
+                unit_index
+            )));
+        }
 
         Ok(())
     }
 
-    fn clear_hw_breakpoint(&mut self, unit_index: usize) -> Result<(), crate::Error> {
-        let tselect = 0x7a0;
-        let tdata1 = 0x7a1;
-        let tdata2 = 0x7a2;
+    fn clear_hw_watchpoint(&mut self, unit_index: usize) -> Result<(), crate::Error> {
+        self.write_csr(TSELECT, unit_index as u32)?;
 
-        self.write_csr(tselect, unit_index as u32)?;
-        self.write_csr(tdata1, 0)?;
-        self.write_csr(tdata2, 0)?;
+        let tdata_value = Mcontrol(self.read_csr(TDATA1)?);
+        if tdata_value.is_debug_address_match() && !tdata_value.is_watchpoint() {
+            return Err(Error::Other(anyhow!(
+                "Trigger {} is used by a hardware breakpoint.",
+                unit_index
+            )));
+        }
 
-        Ok(())
+        self.clear_hw_breakpoint(unit_index)
     }
 
     fn registers(&self) -> &'static RegisterFile {
@@ -493,6 +579,11 @@ impl<'probe> CoreInterface for Riscv32<'probe> {
         self.state.hw_breakpoints_enabled
     }
 
+    /// The watchpoints share the triggers with the hardware breakpoints.
+    fn watchpoints_share_breakpoint_units(&self) -> bool {
+        true
+    }
+
     fn architecture(&self) -> Architecture {
         Architecture::Riscv
     }
@@ -522,20 +613,7 @@ impl<'probe> CoreInterface for Riscv32<'probe> {
             // determine reason for halt
             let dcsr = Dcsr(self.read_core_reg(RegisterId::from(0x7b0))?.try_into()?);
 
-            let reason = match dcsr.cause() {
-                // An ebreak instruction was hit
-                1 => HaltReason::Breakpoint(BreakpointCause::Software),
-                // Trigger module caused halt
-                2 => HaltReason::Breakpoint(BreakpointCause::Hardware),
-                // Debugger requested a halt
-                3 => HaltReason::Request,
-                // Core halted after single step
-                4 => HaltReason::Step,
-                // Core halted directly after reset
-                5 => HaltReason::Exception,
-                // Reserved for future use in specification
-                _ => HaltReason::Unknown,
-            };
+            let reason = RiscvHaltCause::from(dcsr.cause() as u8).into();
 
             Ok(CoreStatus::Halted(reason))
         } else if status.allrunning() {
@@ -549,44 +627,10 @@ impl<'probe> CoreInterface for Riscv32<'probe> {
     }
 
     /// See docs on the [`CoreInterface::hw_breakpoints`] trait
-    /// NOTE: For riscv, this assumes that only execution breakpoints are used.
+    /// NOTE: For riscv, only the execute triggers are reported, data access triggers set with
+    /// [`Riscv32::set_trigger`] or used by watchpoints are `None`.
     fn hw_breakpoints(&mut self) -> Result<Vec<Option<u64>>, Error> {
-        let tselect = 0x7a0;
-        let tdata1 = 0x7a1;
-        let tdata2 = 0x7a2;
-
-        let mut breakpoints = vec![];
-        let num_hw_breakpoints = self.available_breakpoint_units()? as usize;
-        for bp_unit_index in 0..num_hw_breakpoints {
-            // Select the trigger.
-            self.write_csr(tselect, bp_unit_index as u32)?;
-
-            // Read the trigger "configuration" data.
-            let tdata_value = Mcontrol(self.read_csr(tdata1)?);
-
-            tracing::warn!("Breakpoint {}: {:?}", bp_unit_index, tdata_value);
-
-            // The trigger must be active in at least a single mode
-            let trigger_any_mode_active = tdata_value.m() || tdata_value.s() || tdata_value.u();
-
-            let trigger_any_action_enabled =
-                tdata_value.execute() || tdata_value.store() || tdata_value.load();
-
-            // Only return if the trigger if it is for an execution debug action in all modes.
-            if tdata_value.type_() == 0b10
-                && tdata_value.action() == 1
-                && tdata_value.match_() == 0
-                && trigger_any_mode_active
-                && trigger_any_action_enabled
-            {
-                let breakpoint = self.read_csr(tdata2)?;
-                breakpoints.push(Some(breakpoint as u64));
-            } else {
-                breakpoints.push(None);
-            }
-        }
-
-        Ok(breakpoints)
+        self.address_triggers(Mcontrol::execute)
     }
 
     fn fpu_support(&mut self) -> Result<bool, crate::error::Error> {
@@ -604,6 +648,10 @@ impl<'probe> CoreInterface for Riscv32<'probe> {
 
         self.write_csr(0x7b0, dcsr.0).map_err(|e| e.into())
     }
+
+    fn riscv_halt_status(&mut self) -> Result<RiscvHaltStatus, crate::error::Error> {
+        self.halt_status()
+    }
 }
 
 impl<'probe> MemoryInterface for Riscv32<'probe> {
@@ -672,17 +720,77 @@ impl<'probe> MemoryInterface for Riscv32<'probe> {
     }
 }
 
+/// The reason for a halt of a RISC-V hart, decoded from `dcsr.cause`
+/// (see RISC-V External Debug Support, 4.9.1).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RiscvHaltCause {
+    /// An `ebreak` instruction was executed.
+    Ebreak,
+    /// A trigger of the trigger module fired.
+    Trigger,
+    /// The debugger requested a halt.
+    HaltRequest,
+    /// The hart halted after a single step.
+    Step,
+    /// The hart halted directly after a reset, because of `resethaltreq`.
+    ResetHaltRequest,
+    /// A cause which is reserved in the specification.
+    Reserved(u8),
+}
+
+impl From<u8> for RiscvHaltCause {
+    fn from(cause: u8) -> Self {
+        match cause {
+            1 => RiscvHaltCause::Ebreak,
+            2 => RiscvHaltCause::Trigger,
+            3 => RiscvHaltCause::HaltRequest,
+            4 => RiscvHaltCause::Step,
+            5 => RiscvHaltCause::ResetHaltRequest,
+            other => RiscvHaltCause::Reserved(other),
+        }
+    }
+}
+
+impl From<RiscvHaltCause> for HaltReason {
+    fn from(cause: RiscvHaltCause) -> Self {
+        match cause {
+            RiscvHaltCause::Ebreak => HaltReason::Breakpoint(BreakpointCause::Software),
+            RiscvHaltCause::Trigger => HaltReason::Breakpoint(BreakpointCause::Hardware),
+            RiscvHaltCause::HaltRequest => HaltReason::Request,
+            RiscvHaltCause::Step => HaltReason::Step,
+            RiscvHaltCause::ResetHaltRequest => HaltReason::Exception,
+            RiscvHaltCause::Reserved(_) => HaltReason::Unknown,
+        }
+    }
+}
+
+/// The halt status of a RISC-V hart, as returned by [`Riscv32::halt_status`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RiscvHaltStatus {
+    /// The reason for the halt.
+    pub cause: RiscvHaltCause,
+    /// The PC at which the hart halted, read from `dpc`.
+    pub pc: u32,
+    /// The privilege level the hart was running in before the halt.
+    pub privilege: u8,
+    /// A non-maskable interrupt is pending for the hart.
+    pub nmi_pending: bool,
+}
+
 #[derive(Debug)]
 /// Flags used to control the [`SpecificCoreState`] for RiscV architecture
 pub struct RiscVState {
     /// A flag to remember whether we want to use hw_breakpoints during stepping of the core.
     hw_breakpoints_enabled: bool,
+    /// The number of triggers, once they have been enumerated.
+    trigger_count: Option<u32>,
 }
 
 impl RiscVState {
     pub(crate) fn new() -> Self {
         Self {
             hw_breakpoints_enabled: false,
+            trigger_count: None,
         }
     }
 }
@@ -803,6 +911,175 @@ bitfield! {
         prv, set_prv: 1,0;
 }
 
+impl Dcsr {
+    /// Returns `true` if an `ebreak` in the current privilege mode enters debug mode.
+    fn ebreak_enters_debug_mode(&self) -> bool {
+        match self.prv() {
+            0 => self.ebreaku(),
+            1 => self.ebreaks(),
+            3 => self.ebreakm(),
+            _ => false,
+        }
+    }
+}
+
+/// The `dcsr` value to use for a single step, based on the current value.
+///
+/// Interrupts are masked and counters and timers are stopped during the step.
+fn step_dcsr(dcsr: &Dcsr) -> Dcsr {
+    let mut step = Dcsr(dcsr.0);
+    step.set_step(true);
+    step.set_stepie(false);
+    step.set_stopcount(true);
+    step.set_stoptime(true);
+    step
+}
+
+/// The accesses to a halted hart which are used by [`single_step`].
+trait SteppableHart {
+    fn read_debug_csr(&mut self, address: u16) -> Result<u32, crate::Error>;
+
+    fn write_debug_csr(&mut self, address: u16, value: u32) -> Result<(), crate::Error>;
+
+    fn read_instruction(&mut self, address: u32) -> Result<u32, crate::Error>;
+
+    fn triggers_enabled(&self) -> bool;
+
+    fn enable_triggers(&mut self, enabled: bool) -> Result<(), crate::Error>;
+
+    fn resume_hart(&mut self) -> Result<(), crate::Error>;
+
+    fn wait_for_step(&mut self) -> Result<(), crate::Error>;
+
+    fn halt_hart(&mut self) -> Result<(), crate::Error>;
+}
+
+impl SteppableHart for Riscv32<'_> {
+    fn read_debug_csr(&mut self, address: u16) -> Result<u32, crate::Error> {
+        Ok(self.read_csr(address)?)
+    }
+
+    fn write_debug_csr(&mut self, address: u16, value: u32) -> Result<(), crate::Error> {
+        Ok(self.write_csr(address, value)?)
+    }
+
+    fn read_instruction(&mut self, address: u32) -> Result<u32, crate::Error> {
+        let mut instruction = [0u8; 4];
+        self.read_8(address.into(), &mut instruction)?;
+        Ok(u32::from_le_bytes(instruction))
+    }
+
+    fn triggers_enabled(&self) -> bool {
+        self.state.hw_breakpoints_enabled
+    }
+
+    fn enable_triggers(&mut self, enabled: bool) -> Result<(), crate::Error> {
+        self.enable_breakpoints(enabled)
+    }
+
+    fn resume_hart(&mut self) -> Result<(), crate::Error> {
+        self.resume_core()
+    }
+
+    fn wait_for_step(&mut self) -> Result<(), crate::Error> {
+        self.wait_for_core_halted(Duration::from_millis(100))
+    }
+
+    fn halt_hart(&mut self) -> Result<(), crate::Error> {
+        self.halt(Duration::from_millis(100)).map(|_| ())
+    }
+}
+
+/// Execute a single instruction on the halted `hart`, and return the new PC.
+///
+/// Interrupts are masked and the triggers are disabled during the step. The original `dcsr`
+/// configuration and the triggers are restored afterwards, also if the step fails.
+fn single_step(hart: &mut impl SteppableHart) -> Result<u32, crate::Error> {
+    let pc = hart.read_debug_csr(0x7b1)?;
+    let dcsr = Dcsr(hart.read_debug_csr(0x7b0)?);
+
+    // Stepping an ebreak which enters debug mode would halt again at the same
+    // instruction, so we skip it by advancing the dpc instead.
+    if let Some(length) = ebreak_length(hart.read_instruction(pc)?) {
+        if dcsr.ebreak_enters_debug_mode() {
+            let next_pc = pc + length;
+            hart.write_debug_csr(0x7b1, next_pc)?;
+
+            return Ok(next_pc);
+        }
+    }
+
+    // A trigger on the current instruction would fire before it is executed,
+    // so all triggers are disabled while stepping.
+    let triggers_enabled = hart.triggers_enabled();
+    if triggers_enabled {
+        if let Err(error) = hart.enable_triggers(false) {
+            // Some of the triggers may already be disabled.
+            if let Err(restore_error) = hart.enable_triggers(true) {
+                tracing::warn!("Failed to re-enable triggers: {}", restore_error);
+            }
+            return Err(error);
+        }
+    }
+
+    let result = resume_for_step(hart, &dcsr);
+
+    // Restore the original step configuration, and re-enable the triggers before we continue.
+    let restored = restore_step_dcsr(hart, &dcsr);
+    let reenabled = if triggers_enabled {
+        hart.enable_triggers(true)
+    } else {
+        Ok(())
+    };
+
+    result?;
+    restored?;
+    reenabled?;
+
+    hart.read_debug_csr(0x7b1)
+}
+
+/// Resume `hart` for a single step, and wait until it halted again.
+fn resume_for_step(hart: &mut impl SteppableHart, dcsr: &Dcsr) -> Result<(), crate::Error> {
+    // Set it up, so that the next resume will only do a single step,
+    // and disable any interrupts and counters during single step.
+    hart.write_debug_csr(0x7b0, step_dcsr(dcsr).0)?;
+
+    hart.resume_hart()?;
+
+    if let Err(error) = hart.wait_for_step() {
+        // The step did not complete, e.g. because the hart is waiting for an
+        // interrupt. Halt it, so the original state can be restored.
+        hart.halt_hart()?;
+        return Err(error);
+    }
+
+    Ok(())
+}
+
+/// Clear the step bit, and restore the step configuration of `dcsr`.
+fn restore_step_dcsr(hart: &mut impl SteppableHart, dcsr: &Dcsr) -> Result<(), crate::Error> {
+    // The cause of the halt was updated by the step, so the current value is modified.
+    let mut restored = Dcsr(hart.read_debug_csr(0x7b0)?);
+    restored.set_step(false);
+    restored.set_stepie(dcsr.stepie());
+    restored.set_stopcount(dcsr.stopcount());
+    restored.set_stoptime(dcsr.stoptime());
+    hart.write_debug_csr(0x7b0, restored.0)
+}
+
+/// Returns the length of the instruction in bytes, if `instruction` is an
+/// `ebreak` or `c.ebreak` instruction.
+fn ebreak_length(instruction: u32) -> Option<u32> {
+    if instruction & 0xffff == 0x9002 {
+        Some(2)
+    } else if instruction == 0x0010_0073 {
+        Some(4)
+    } else {
+        None
+    }
+}
+
 bitfield! {
     /// Abstract Control and Status (see 3.12.6)
     pub struct Abstractcs(u32);
@@ -872,6 +1149,8 @@ data_register! { pub Data9, 0x0D, "data9" }
 data_register! { pub Data10, 0x0E, "data10" }
 data_register! { pub Data11, 0x0f, "data11" }
 
+data_register! { Hawindowsel, 0x14, "hawindowsel" }
+data_register! { Hawindow, 0x15, "hawindow" }
 data_register! { Command, 0x17, "command" }
 
 data_register! { pub Progbuf0, 0x20, "progbuf0" }
@@ -891,28 +1170,6 @@ data_register! { pub Progbuf13, 0x2D, "progbuf13" }
 data_register! { pub Progbuf14, 0x2E, "progbuf14" }
 data_register! { pub Progbuf15, 0x2F, "progbuf15" }
 
-bitfield! {
-    struct Mcontrol(u32);
-    impl Debug;
-
-    type_, set_type: 31, 28;
-    dmode, set_dmode: 27;
-    maskmax, _: 26, 21;
-    hit, set_hit: 20;
-    select, set_select: 19;
-    timing, set_timing: 18;
-    sizelo, set_sizelo: 17, 16;
-    action, set_action: 15, 12;
-    chain, set_chain: 11;
-    match_, set_match: 10, 7;
-    m, set_m: 6;
-    s, set_s: 4;
-    u, set_u: 3;
-    execute, set_execute: 2;
-    store, set_store: 1;
-    load, set_load: 0;
-}
-
 bitfield! {
     /// Isa and Extensions (see RISC-V Privileged Spec, 3.1.1)
     pub struct Misa(u32);
@@ -923,3 +1180,221 @@ bitfield! {
     /// Standard RISC-V extensions
     extensions, _: 25, 0;
 }
+
+#[cfg(test)]
+mod test {
+    use super::{ebreak_length, single_step, step_dcsr, Dcsr, SteppableHart};
+    use crate::{DebugProbeError, Error};
+    use std::collections::HashMap;
+
+    /// A hart which executes a single instruction of `program` when it is resumed with
+    /// `dcsr.step` set.
+    struct MockHart {
+        csrs: HashMap<u16, u32>,
+        memory: HashMap<u32, u32>,
+        /// Returns the PC after executing the instruction at the given PC.
+        program: fn(u32) -> u32,
+        triggers_enabled: bool,
+        /// The `dcsr` value and trigger state for each resume.
+        resumed: Vec<(Dcsr, bool)>,
+        halted: bool,
+        fail_step: bool,
+        halt_requests: usize,
+    }
+
+    impl MockHart {
+        fn new(pc: u32, dcsr: Dcsr, program: fn(u32) -> u32) -> Self {
+            Self {
+                csrs: HashMap::from([(0x7b0, dcsr.0), (0x7b1, pc)]),
+                memory: HashMap::new(),
+                program,
+                triggers_enabled: true,
+                resumed: Vec::new(),
+                halted: true,
+                fail_step: false,
+                halt_requests: 0,
+            }
+        }
+
+        fn dcsr(&self) -> Dcsr {
+            Dcsr(self.csrs[&0x7b0])
+        }
+    }
+
+    impl SteppableHart for MockHart {
+        fn read_debug_csr(&mut self, address: u16) -> Result<u32, Error> {
+            assert!(self.halted, "CSR read of a running hart");
+            Ok(self.csrs[&address])
+        }
+
+        fn write_debug_csr(&mut self, address: u16, value: u32) -> Result<(), Error> {
+            assert!(self.halted, "CSR write of a running hart");
+            self.csrs.insert(address, value);
+            Ok(())
+        }
+
+        fn read_instruction(&mut self, address: u32) -> Result<u32, Error> {
+            // An unknown instruction is a `nop`.
+            Ok(self.memory.get(&address).copied().unwrap_or(0x0000_0013))
+        }
+
+        fn triggers_enabled(&self) -> bool {
+            self.triggers_enabled
+        }
+
+        fn enable_triggers(&mut self, enabled: bool) -> Result<(), Error> {
+            self.triggers_enabled = enabled;
+            Ok(())
+        }
+
+        fn resume_hart(&mut self) -> Result<(), Error> {
+            let mut dcsr = self.dcsr();
+            self.resumed.push((Dcsr(dcsr.0), self.triggers_enabled));
+            self.halted = false;
+
+            if dcsr.step() && !self.fail_step {
+                let pc = (self.program)(self.csrs[&0x7b1]);
+                self.csrs.insert(0x7b1, pc);
+                // Halted by a step.
+                dcsr.set_cause(4);
+                self.csrs.insert(0x7b0, dcsr.0);
+                self.halted = true;
+            }
+
+            Ok(())
+        }
+
+        fn wait_for_step(&mut self) -> Result<(), Error> {
+            if self.halted {
+                Ok(())
+            } else {
+                Err(Error::Probe(DebugProbeError::Timeout))
+            }
+        }
+
+        fn halt_hart(&mut self) -> Result<(), Error> {
+            self.halt_requests += 1;
+            self.halted = true;
+            Ok(())
+        }
+    }
+
+    fn machine_mode_dcsr() -> Dcsr {
+        let mut dcsr = Dcsr(0);
+        dcsr.set_ebreakm(true);
+        dcsr.set_stepie(true);
+        dcsr.set_prv(3);
+        dcsr
+    }
+
+    #[test]
+    fn step_across_taken_branch() {
+        // beq a0, a1, 0x40
+        let mut hart = MockHart::new(0x100, machine_mode_dcsr(), |pc| pc + 0x40);
+        hart.memory.insert(0x100, 0x04b5_0063);
+
+        assert_eq!(single_step(&mut hart).unwrap(), 0x140);
+
+        // Interrupts and triggers are disabled during the step.
+        let (step_dcsr, triggers_enabled) = &hart.resumed[0];
+        assert!(step_dcsr.step());
+        assert!(!step_dcsr.stepie());
+        assert!(!triggers_enabled);
+
+        // The configuration is restored afterwards.
+        let dcsr = hart.dcsr();
+        assert!(!dcsr.step());
+        assert!(dcsr.stepie());
+        assert!(!dcsr.stopcount());
+        assert_eq!(dcsr.cause(), 4);
+        assert!(hart.triggers_enabled);
+        assert_eq!(hart.resumed.len(), 1);
+    }
+
+    #[test]
+    fn step_into_trap_handler() {
+        const TRAP_VECTOR: u32 = 0x2000_0000;
+
+        // ecall, which traps to the handler at mtvec.
+        let mut hart = MockHart::new(0x100, machine_mode_dcsr(), |_| TRAP_VECTOR);
+        hart.memory.insert(0x100, 0x0000_0073);
+
+        // The step halts at the first instruction of the trap handler.
+        assert_eq!(single_step(&mut hart).unwrap(), TRAP_VECTOR);
+        assert!(!hart.dcsr().step());
+        assert!(hart.triggers_enabled);
+    }
+
+    #[test]
+    fn step_over_ebreak_advances_pc() {
+        let mut hart = MockHart::new(0x100, machine_mode_dcsr(), |pc| pc);
+        // c.ebreak
+        hart.memory.insert(0x100, 0x0000_9002);
+
+        assert_eq!(single_step(&mut hart).unwrap(), 0x102);
+        assert!(hart.resumed.is_empty());
+    }
+
+    #[test]
+    fn failed_step_restores_configuration() {
+        let mut hart = MockHart::new(0x100, machine_mode_dcsr(), |pc| pc + 4);
+        hart.fail_step = true;
+
+        assert!(single_step(&mut hart).is_err());
+
+        // The hart is halted again, and the step configuration and triggers are restored.
+        assert_eq!(hart.halt_requests, 1);
+        assert!(!hart.dcsr().step());
+        assert!(hart.dcsr().stepie());
+        assert!(hart.triggers_enabled);
+    }
+
+    #[test]
+    fn step_keeps_triggers_disabled() {
+        let mut hart = MockHart::new(0x100, machine_mode_dcsr(), |pc| pc + 4);
+        hart.triggers_enabled = false;
+
+        assert_eq!(single_step(&mut hart).unwrap(), 0x104);
+        assert!(!hart.triggers_enabled);
+    }
+
+    #[test]
+    fn step_masks_interrupts_and_keeps_configuration() {
+        let mut dcsr = Dcsr(0);
+        dcsr.set_ebreakm(true);
+        dcsr.set_stepie(true);
+        dcsr.set_prv(3);
+
+        let step = step_dcsr(&dcsr);
+
+        assert!(step.step());
+        assert!(!step.stepie());
+        assert!(step.stopcount());
+        assert!(step.ebreakm());
+        assert_eq!(step.prv(), 3);
+    }
+
+    #[test]
+    fn ebreak_enters_debug_mode_depends_on_privilege() {
+        let mut dcsr = Dcsr(0);
+        dcsr.set_ebreakm(true);
+
+        dcsr.set_prv(3);
+        assert!(dcsr.ebreak_enters_debug_mode());
+
+        // An ebreak in user mode traps to the exception handler instead.
+        dcsr.set_prv(0);
+        assert!(!dcsr.ebreak_enters_debug_mode());
+    }
+
+    #[test]
+    fn detect_ebreak() {
+        assert_eq!(ebreak_length(0x0010_0073), Some(4));
+        // c.ebreak, followed by another instruction
+        assert_eq!(ebreak_length(0x4505_9002), Some(2));
+        // beq a0, a1, 8
+        assert_eq!(ebreak_length(0x00b5_0463), None);
+        // ecall
+        assert_eq!(ebreak_length(0x0000_0073), None);
+    }
+}
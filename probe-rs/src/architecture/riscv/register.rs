@@ -425,4 +425,5 @@ pub(super) static RISCV_REGISTERS: RegisterFile = RegisterFile {
     // TODO: Add FPU registers
     fp_registers: None,
     fp_status: None,
+    fp_double_registers: None,
 };
@@ -0,0 +1,192 @@
+//! Access to the RISC-V trigger module, used for hardware breakpoints and watchpoints.
+//!
+//! See chapter 5 of the RISC-V External Debug Support specification 0.13.
+
+use crate::core::WatchpointAccess;
+use bitfield::bitfield;
+
+/// Trigger select register.
+pub(crate) const TSELECT: u16 = 0x7a0;
+/// First trigger data register, contains the configuration of the selected trigger.
+pub(crate) const TDATA1: u16 = 0x7a1;
+/// Second trigger data register, contains the address to match for address triggers.
+pub(crate) const TDATA2: u16 = 0x7a2;
+/// Trigger info register, lists the supported types of the selected trigger.
+pub(crate) const TINFO: u16 = 0x7a4;
+
+/// The `type` of an address / data match trigger in `tdata1`.
+pub(crate) const TRIGGER_TYPE_MCONTROL: u32 = 0b10;
+
+/// The kind of access which causes a trigger to fire.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TriggerKind {
+    /// Fire when an instruction at the address is executed, i.e. a hardware breakpoint.
+    Execute,
+    /// Fire when the address is read.
+    Load,
+    /// Fire when the address is written.
+    Store,
+    /// Fire when the address is read or written.
+    Access,
+}
+
+impl From<WatchpointAccess> for TriggerKind {
+    fn from(access: WatchpointAccess) -> Self {
+        match access {
+            WatchpointAccess::Read => TriggerKind::Load,
+            WatchpointAccess::Write => TriggerKind::Store,
+            WatchpointAccess::ReadWrite => TriggerKind::Access,
+        }
+    }
+}
+
+bitfield! {
+    /// Match control trigger (see RISC-V External Debug Support, 5.2.2)
+    pub(crate) struct Mcontrol(u32);
+    impl Debug;
+
+    pub type_, set_type: 31, 28;
+    pub dmode, set_dmode: 27;
+    pub maskmax, _: 26, 21;
+    pub hit, set_hit: 20;
+    pub select, set_select: 19;
+    pub timing, set_timing: 18;
+    pub sizelo, set_sizelo: 17, 16;
+    pub action, set_action: 15, 12;
+    pub chain, set_chain: 11;
+    pub match_, set_match: 10, 7;
+    pub m, set_m: 6;
+    pub s, set_s: 4;
+    pub u, set_u: 3;
+    pub execute, set_execute: 2;
+    pub store, set_store: 1;
+    pub load, set_load: 0;
+}
+
+impl Mcontrol {
+    /// Configuration for a trigger which enters debug mode on an exact address match
+    /// of the given kind, in machine and user mode.
+    pub(crate) fn address_match(kind: TriggerKind) -> Self {
+        let mut trigger = Mcontrol(0);
+
+        // Enter debug mode
+        trigger.set_action(1);
+
+        // Match exactly the value in tdata2
+        trigger.set_match(0);
+
+        trigger.set_m(true);
+        trigger.set_u(true);
+
+        // Only the debugger may modify this trigger
+        trigger.set_dmode(true);
+
+        // Match address, not data
+        trigger.set_select(false);
+
+        match kind {
+            TriggerKind::Execute => trigger.set_execute(true),
+            TriggerKind::Load => trigger.set_load(true),
+            TriggerKind::Store => trigger.set_store(true),
+            TriggerKind::Access => {
+                trigger.set_load(true);
+                trigger.set_store(true);
+            }
+        }
+
+        trigger
+    }
+
+    /// Configuration for a trigger which enters debug mode on an access of the given kind to
+    /// a naturally aligned power of two range, encoded in `tdata2` (see [`napot_tdata2`]).
+    pub(crate) fn napot_match(kind: TriggerKind) -> Self {
+        let mut trigger = Self::address_match(kind);
+
+        // Match the upper bits of the value in tdata2
+        trigger.set_match(1);
+
+        trigger
+    }
+
+    /// Returns `true` if this is an address match trigger which enters debug mode,
+    /// as set up by [`Mcontrol::address_match`] or [`Mcontrol::napot_match`].
+    pub(crate) fn is_debug_address_match(&self) -> bool {
+        self.type_() == TRIGGER_TYPE_MCONTROL
+            && self.action() == 1
+            && (self.match_() == 0 || self.match_() == 1)
+            && (self.execute() || self.store() || self.load())
+    }
+
+    /// Returns `true` if the trigger fires on loads or stores, but not on execution, i.e. it is
+    /// used by a watchpoint.
+    pub(crate) fn is_watchpoint(&self) -> bool {
+        !self.execute() && (self.load() || self.store())
+    }
+
+    /// Returns the first address matched by the trigger, for the value of `tdata2`.
+    pub(crate) fn matched_address(&self, tdata2: u32) -> u32 {
+        if self.match_() == 1 {
+            // Clear the trailing ones, which select the size of the range.
+            tdata2 & tdata2.wrapping_add(1)
+        } else {
+            tdata2
+        }
+    }
+}
+
+/// Returns the value of `tdata2` for a NAPOT trigger on the `size` bytes at `address`.
+///
+/// The size has to be a power of two of at least 2 bytes, and the address has to be aligned to it.
+pub(crate) fn napot_tdata2(address: u32, size: u32) -> u32 {
+    address | ((size >> 1) - 1)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{napot_tdata2, Mcontrol, TriggerKind};
+
+    #[test]
+    fn access_trigger_matches_loads_and_stores() {
+        let trigger = Mcontrol::address_match(TriggerKind::Access);
+
+        assert!(trigger.load());
+        assert!(trigger.store());
+        assert!(!trigger.execute());
+        assert_eq!(trigger.action(), 1);
+    }
+
+    #[test]
+    fn written_trigger_is_recognized() {
+        let mut trigger = Mcontrol::address_match(TriggerKind::Store);
+
+        // The type field is read-only and reported by the hardware.
+        trigger.set_type(0b10);
+
+        assert!(trigger.is_debug_address_match());
+        assert!(!Mcontrol(0).is_debug_address_match());
+    }
+
+    #[test]
+    fn watchpoints_are_distinguished_from_breakpoints() {
+        assert!(Mcontrol::address_match(TriggerKind::Load).is_watchpoint());
+        assert!(Mcontrol::address_match(TriggerKind::Store).is_watchpoint());
+        assert!(Mcontrol::napot_match(TriggerKind::Access).is_watchpoint());
+
+        let breakpoint = Mcontrol::address_match(TriggerKind::Execute);
+        assert!(breakpoint.execute());
+        assert!(!breakpoint.is_watchpoint());
+    }
+
+    #[test]
+    fn napot_range_is_encoded_in_tdata2() {
+        let trigger = Mcontrol::napot_match(TriggerKind::Load);
+
+        assert_eq!(napot_tdata2(0x2000_0010, 2), 0x2000_0010);
+        assert_eq!(napot_tdata2(0x2000_0010, 16), 0x2000_0017);
+        assert_eq!(trigger.matched_address(0x2000_0017), 0x2000_0010);
+        assert_eq!(
+            Mcontrol::address_match(TriggerKind::Load).matched_address(0x2000_0017),
+            0x2000_0017
+        );
+    }
+}
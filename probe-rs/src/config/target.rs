@@ -168,7 +168,7 @@ impl Target {
     /// Create a [FlashLoader] for this target, which can be used
     /// to program its non-volatile memory.
     pub fn flash_loader(&self) -> FlashLoader {
-        FlashLoader::new(self.memory_map.clone(), self.source.clone())
+        FlashLoader::for_target(self.memory_map.clone(), self.source.clone(), &self.name)
     }
 
     /// Gets a [RawFlashAlgorithm] by name.
@@ -1,22 +1,30 @@
 pub(crate) mod communication_interface;
 
+use crate::architecture::arm::core::armv7m::Shpr3;
+use crate::architecture::arm::core::nvic::NvicState;
+use crate::architecture::arm::core::semihosting::{SemihostingHandler, SemihostingOutcome};
 use crate::architecture::arm::memory::adi_v5_memory_interface::ArmProbe;
-use crate::architecture::riscv::RiscVState;
+use crate::architecture::riscv::{RiscVState, RiscvHaltStatus};
 use crate::{CoreType, InstructionSet};
 pub use communication_interface::CommunicationInterface;
 use num_traits::Zero;
 pub use probe_rs_target::{Architecture, CoreAccessOptions};
 
 use crate::architecture::{
-    arm::core::CortexAState, arm::core::CortexMState,
+    arm::core::register, arm::core::CortexAState, arm::core::CortexMState,
     riscv::communication_interface::RiscvCommunicationInterface,
 };
+use crate::config::MemoryRegion;
+use crate::debug::{DebugInfo, StackFrame};
 use crate::error;
 use crate::Target;
 use crate::{Error, MemoryInterface};
 use anyhow::{anyhow, Result};
 use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet};
 use std::convert::Infallible;
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 /// A memory mapped register, for instance ARM debug registers (DHCSR, etc).
@@ -34,6 +42,55 @@ pub struct CoreInformation {
     pub pc: u64,
 }
 
+/// The execution context of a halted Cortex-M core, as returned by [`Core::current_context`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CoreContext {
+    /// The current Program Counter.
+    pub pc: u64,
+    /// The number of the active exception, decoded from IPSR.
+    ///
+    /// This is `None` if the core is in thread mode.
+    pub exception_number: Option<u16>,
+    /// The stack pointer which is currently in use.
+    pub stack_pointer: ActiveStackPointer,
+}
+
+impl CoreContext {
+    /// Decode the context from the values of the PC, XPSR and the combined
+    /// CONTROL/FAULTMASK/BASEPRI/PRIMASK register.
+    fn from_registers(pc: u32, xpsr: u32, extra: u32) -> Self {
+        let exception_number = (xpsr & 0x1FF) as u16;
+        let spsel = (extra >> 24) & 0b10 != 0;
+
+        // In handler mode the main stack is always used, regardless of CONTROL.SPSEL.
+        let stack_pointer = if exception_number == 0 && spsel {
+            ActiveStackPointer::Process
+        } else {
+            ActiveStackPointer::Main
+        };
+
+        Self {
+            pc: pc.into(),
+            exception_number: (exception_number != 0).then_some(exception_number),
+            stack_pointer,
+        }
+    }
+
+    /// Returns `true` if the core is currently executing an exception handler.
+    pub fn is_in_handler(&self) -> bool {
+        self.exception_number.is_some()
+    }
+}
+
+/// The stack pointer used by a Cortex-M core.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActiveStackPointer {
+    /// The main stack pointer (MSP) is in use.
+    Main,
+    /// The process stack pointer (PSP) is in use.
+    Process,
+}
+
 /// The type of data stored in a register
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum RegisterDataType {
@@ -352,6 +409,8 @@ pub struct RegisterFile {
 
     pub(crate) fp_registers: Option<&'static [RegisterDescription]>,
 
+    pub(crate) fp_double_registers: Option<&'static [RegisterDescription]>,
+
     pub(crate) other: &'static [RegisterDescription],
 }
 
@@ -471,6 +530,15 @@ impl RegisterFile {
     pub fn get_fpu_register(&self, index: usize) -> Option<&RegisterDescription> {
         self.fp_registers.and_then(|r| r.get(index))
     }
+
+    /// Returns an iterator over the double precision views of the fpu registers, if the core
+    /// exposes them separately from [`RegisterFile::fpu_registers`].
+    ///
+    /// On Cortex-M cores, each of the registers D0-D15 is made up of two of the single precision
+    /// registers S0-S31.
+    pub fn fpu_double_registers(&self) -> Option<impl Iterator<Item = &RegisterDescription>> {
+        self.fp_double_registers.map(|r| r.iter())
+    }
 }
 
 /// A generic interface to control a MCU core.
@@ -490,6 +558,14 @@ pub trait CoreInterface: MemoryInterface {
     /// returns a [`DebugProbeError::Timeout`](crate::DebugProbeError::Timeout) otherwise.
     fn halt(&mut self, timeout: Duration) -> Result<CoreInformation, error::Error>;
 
+    /// Request the core to halt, without waiting for it to actually halt.
+    ///
+    /// This is used to halt multiple cores as close to simultaneously as possible.
+    /// The default implementation halts the core and waits for it.
+    fn request_halt(&mut self) -> Result<(), error::Error> {
+        self.halt(Duration::from_millis(100)).map(|_| ())
+    }
+
     /// Continue to execute instructions.
     fn run(&mut self) -> Result<(), error::Error>;
 
@@ -535,6 +611,48 @@ pub trait CoreInterface: MemoryInterface {
     /// Clears the breakpoint configured in unit `unit_index`.
     fn clear_hw_breakpoint(&mut self, unit_index: usize) -> Result<(), error::Error>;
 
+    /// Returns the number of hardware watchpoint units of the core.
+    fn available_watchpoint_units(&mut self) -> Result<u32, error::Error> {
+        Ok(0)
+    }
+
+    /// Reads the addresses watched by the hardware watchpoint units.
+    /// A value of None in any position of the Vector indicates that the unit is unused.
+    fn hw_watchpoints(&mut self) -> Result<Vec<Option<u64>>, error::Error> {
+        Ok(Vec::new())
+    }
+
+    /// Sets a watchpoint on the `size` bytes at `address`, using unit `unit_index`. The core
+    /// halts on an `access` of the watched bytes.
+    fn set_hw_watchpoint(
+        &mut self,
+        _unit_index: usize,
+        _address: u64,
+        _size: u64,
+        _access: WatchpointAccess,
+    ) -> Result<(), error::Error> {
+        Err(Error::Other(anyhow!(
+            "Hardware watchpoints are not supported on {:?} cores.",
+            self.core_type()
+        )))
+    }
+
+    /// Clears the watchpoint configured in unit `unit_index`.
+    fn clear_hw_watchpoint(&mut self, _unit_index: usize) -> Result<(), error::Error> {
+        Err(Error::Other(anyhow!(
+            "Hardware watchpoints are not supported on {:?} cores.",
+            self.core_type()
+        )))
+    }
+
+    /// Returns `true` if the hardware breakpoints and watchpoints use the same units.
+    ///
+    /// A unit which is listed by [`CoreInterface::hw_watchpoints`] is then not free for a
+    /// breakpoint, and a unit listed by [`CoreInterface::hw_breakpoints`] not for a watchpoint.
+    fn watchpoints_share_breakpoint_units(&self) -> bool {
+        false
+    }
+
     /// Returns a list of all the registers of this core.
     fn registers(&self) -> &'static RegisterFile;
 
@@ -567,6 +685,14 @@ pub trait CoreInterface: MemoryInterface {
     fn on_session_stop(&mut self) -> Result<(), Error> {
         Ok(())
     }
+
+    /// Read the reason for the last halt of a RISC-V hart, see [`Core::riscv_halt_status`].
+    fn riscv_halt_status(&mut self) -> Result<RiscvHaltStatus, error::Error> {
+        Err(Error::Other(anyhow!(
+            "Reading the RISC-V halt status is not supported on {:?} cores.",
+            self.core_type()
+        )))
+    }
 }
 
 impl<'probe> MemoryInterface for Core<'probe> {
@@ -646,6 +772,10 @@ pub struct CoreState {
 
     /// Information needed to access the core
     core_access_options: CoreAccessOptions,
+
+    /// The breakpoints set by each core, if the hardware breakpoint units are shared with other
+    /// cores.
+    shared_breakpoints: Option<Arc<Mutex<SharedBreakpointUnits>>>,
 }
 
 impl CoreState {
@@ -654,6 +784,7 @@ impl CoreState {
         Self {
             id,
             core_access_options,
+            shared_breakpoints: None,
         }
     }
 
@@ -662,6 +793,112 @@ impl CoreState {
     pub fn id(&self) -> usize {
         self.id
     }
+
+    /// Returns `true` if the hardware breakpoint units used for this core are also used for the
+    /// `other` core.
+    ///
+    /// Cortex-M cores access their FPB at a fixed address on the private peripheral bus, so cores
+    /// which are accessed through the same AP share a single FPB. The breakpoint units of
+    /// Cortex-A cores are located at their `debug_base`, and RISC-V triggers are per hart.
+    pub(crate) fn shares_breakpoints_with(
+        &self,
+        core_type: CoreType,
+        other: &CoreState,
+        other_type: CoreType,
+    ) -> bool {
+        match (&self.core_access_options, &other.core_access_options) {
+            (CoreAccessOptions::Arm(options), CoreAccessOptions::Arm(other_options)) => {
+                let same_ap = options.ap == other_options.ap && options.psel == other_options.psel;
+
+                if core_type.is_cortex_m() && other_type.is_cortex_m() {
+                    same_ap
+                } else {
+                    same_ap
+                        && options.debug_base.is_some()
+                        && options.debug_base == other_options.debug_base
+                }
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Gives all cores which share their hardware breakpoint units a common record of the
+/// breakpoints set by each of them, see [`SharedBreakpointUnits`].
+pub(crate) fn share_breakpoint_units(cores: &mut [(SpecificCoreState, CoreState)]) {
+    for index in 0..cores.len() {
+        if cores[index].1.shared_breakpoints.is_some() {
+            continue;
+        }
+
+        let (core, state) = &cores[index];
+        let sharing: Vec<usize> = (index + 1..cores.len())
+            .filter(|&other| {
+                let (other_core, other_state) = &cores[other];
+                state.shares_breakpoints_with(core.core_type(), other_state, other_core.core_type())
+            })
+            .collect();
+
+        if sharing.is_empty() {
+            continue;
+        }
+
+        let units = Arc::new(Mutex::new(SharedBreakpointUnits::default()));
+        for index in std::iter::once(index).chain(sharing) {
+            cores[index].1.shared_breakpoints = Some(units.clone());
+        }
+    }
+}
+
+/// The breakpoints in hardware breakpoint units which are shared by multiple cores.
+///
+/// A breakpoint in a shared unit applies to all cores which share it. To keep the breakpoints
+/// of the cores apart, each core only clears the breakpoints it set itself, and a unit is only
+/// cleared once no core uses it anymore.
+#[derive(Debug, Default)]
+pub(crate) struct SharedBreakpointUnits {
+    /// The IDs of the cores which set a breakpoint, for every used unit.
+    owners: BTreeMap<usize, BTreeSet<usize>>,
+}
+
+/// What to do with a shared breakpoint unit, when a core removes its breakpoint from it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SharedBreakpointRemoval {
+    /// No other core uses the unit, so it has to be cleared.
+    Clear,
+    /// Another core set a breakpoint at the same address, so the unit has to be kept.
+    Keep,
+    /// The core did not set the breakpoint, it belongs to other cores.
+    NotOwned,
+}
+
+impl SharedBreakpointUnits {
+    /// Records that the core `core_id` set a breakpoint in the unit `unit_index`.
+    pub(crate) fn insert(&mut self, unit_index: usize, core_id: usize) {
+        self.owners.entry(unit_index).or_default().insert(core_id);
+    }
+
+    /// Removes the breakpoint of the core `core_id` from the unit `unit_index`.
+    ///
+    /// A breakpoint which was not set by any core of the session, e.g. in a previous session, is
+    /// cleared by any core.
+    pub(crate) fn remove(&mut self, unit_index: usize, core_id: usize) -> SharedBreakpointRemoval {
+        let owners = match self.owners.get_mut(&unit_index) {
+            Some(owners) => owners,
+            None => return SharedBreakpointRemoval::Clear,
+        };
+
+        if !owners.remove(&core_id) {
+            return SharedBreakpointRemoval::NotOwned;
+        }
+
+        if owners.is_empty() {
+            self.owners.remove(&unit_index);
+            SharedBreakpointRemoval::Clear
+        } else {
+            SharedBreakpointRemoval::Keep
+        }
+    }
 }
 
 /// The architecture specific core state.
@@ -841,6 +1078,11 @@ impl<'probe> Core<'probe> {
         self.inner.halt(timeout)
     }
 
+    /// Request the core to halt, without waiting until it is halted.
+    pub(crate) fn request_halt(&mut self) -> Result<(), error::Error> {
+        self.inner.request_halt()
+    }
+
     /// Continue to execute instructions.
     #[tracing::instrument(skip(self))]
     pub fn run(&mut self) -> Result<(), error::Error> {
@@ -859,6 +1101,10 @@ impl<'probe> Core<'probe> {
     /// Reset the core, and then immediately halt. To continue execution after
     /// reset, use the [`reset`] function.
     ///
+    /// On Cortex-M cores, the core is caught while coming out of reset, so it halts
+    /// before executing any instruction. If the core does not halt within `timeout`,
+    /// [`DebugProbeError::Timeout`](crate::DebugProbeError::Timeout) is returned.
+    ///
     /// [`reset`]: Core::reset
     #[tracing::instrument(skip(self))]
     pub fn reset_and_halt(&mut self, timeout: Duration) -> Result<CoreInformation, error::Error> {
@@ -866,6 +1112,10 @@ impl<'probe> Core<'probe> {
     }
 
     /// Steps one instruction and then enters halted state again.
+    ///
+    /// A hardware breakpoint at the current instruction is disabled during the step, so that it
+    /// doesn't halt the core again before the instruction is executed. On Cortex-M cores, a `BKPT`
+    /// instruction is skipped without executing it.
     #[tracing::instrument(skip(self))]
     pub fn step(&mut self) -> Result<CoreInformation, error::Error> {
         self.inner.step()
@@ -940,11 +1190,80 @@ impl<'probe> Core<'probe> {
         self.inner.registers()
     }
 
+    /// Returns which of the hardware breakpoint units are used by a watchpoint.
+    ///
+    /// The list is empty if the watchpoints use separate units.
+    fn units_used_by_watchpoints(&mut self) -> Result<Vec<bool>, error::Error> {
+        if !self.inner.watchpoints_share_breakpoint_units() {
+            return Ok(Vec::new());
+        }
+
+        Ok(self
+            .inner
+            .hw_watchpoints()?
+            .iter()
+            .map(Option::is_some)
+            .collect())
+    }
+
+    /// Returns which of the hardware watchpoint units are used by a breakpoint.
+    ///
+    /// The list is empty if the breakpoints use separate units.
+    fn units_used_by_breakpoints(&mut self) -> Result<Vec<bool>, error::Error> {
+        if !self.inner.watchpoints_share_breakpoint_units() {
+            return Ok(Vec::new());
+        }
+
+        Ok(self
+            .inner
+            .hw_breakpoints()?
+            .iter()
+            .map(Option::is_some)
+            .collect())
+    }
+
+    /// Records that this core set a breakpoint in the unit `unit_index`, if the unit is shared
+    /// with other cores.
+    fn record_hw_breakpoint(&mut self, unit_index: usize) {
+        if let Some(units) = &self.state.shared_breakpoints {
+            units
+                .lock()
+                .expect("The shared breakpoint record is poisoned")
+                .insert(unit_index, self.state.id);
+        }
+    }
+
+    /// Removes the breakpoint of this core from the unit `unit_index`, and clears the unit unless
+    /// another core which shares it still uses it.
+    ///
+    /// Returns `false` if the breakpoint in the unit was only set by other cores.
+    fn release_hw_breakpoint(&mut self, unit_index: usize) -> Result<bool, error::Error> {
+        let removal = match &self.state.shared_breakpoints {
+            Some(units) => units
+                .lock()
+                .expect("The shared breakpoint record is poisoned")
+                .remove(unit_index, self.state.id),
+            None => SharedBreakpointRemoval::Clear,
+        };
+
+        match removal {
+            SharedBreakpointRemoval::Clear => {
+                self.inner.clear_hw_breakpoint(unit_index)?;
+                Ok(true)
+            }
+            SharedBreakpointRemoval::Keep => Ok(true),
+            SharedBreakpointRemoval::NotOwned => Ok(false),
+        }
+    }
+
     /// Find the index of the next available HW breakpoint comparator.
     fn find_free_breakpoint_comparator_index(&mut self) -> Result<usize, error::Error> {
+        let used_by_watchpoints = self.units_used_by_watchpoints()?;
+
         let mut next_available_hw_breakpoint = 0;
         for breakpoint in self.inner.hw_breakpoints()? {
-            if breakpoint.is_none() {
+            if breakpoint.is_none() && !is_used(&used_by_watchpoints, next_available_hw_breakpoint)
+            {
                 return Ok(next_available_hw_breakpoint);
             } else {
                 next_available_hw_breakpoint += 1;
@@ -987,6 +1306,7 @@ impl<'probe> Core<'probe> {
         // Actually set the breakpoint. Even if it has been set, set it again so it will be active.
         self.inner
             .set_hw_breakpoint(breakpoint_comparator_index, address)?;
+        self.record_hw_breakpoint(breakpoint_comparator_index);
         Ok(())
     }
 
@@ -1008,30 +1328,182 @@ impl<'probe> Core<'probe> {
         );
 
         match bp_position {
-            Some(bp_position) => {
-                self.inner.clear_hw_breakpoint(bp_position)?;
-                Ok(())
-            }
-            None => Err(error::Error::Other(anyhow!(
+            Some(bp_position) if self.release_hw_breakpoint(bp_position)? => Ok(()),
+            _ => Err(error::Error::Other(anyhow!(
                 "No breakpoint found at address {:#010x}",
                 address
             ))),
         }
     }
 
+    /// Set hardware breakpoints at all given addresses.
+    ///
+    /// The breakpoint units are only read once, so this is faster than calling
+    /// [`Core::set_hw_breakpoint`] for every address. Before any breakpoint is written,
+    /// the addresses are assigned to the free breakpoint units.
+    ///
+    /// Returns the addresses for which no breakpoint could be set because all breakpoint
+    /// units are in use.
+    #[tracing::instrument(skip(self))]
+    pub fn set_hw_breakpoints(&mut self, addresses: &[u64]) -> Result<Vec<u64>, error::Error> {
+        let used_by_watchpoints = self.units_used_by_watchpoints()?;
+        let mut breakpoints = self.inner.hw_breakpoints()?;
+
+        let mut assignments = Vec::with_capacity(addresses.len());
+        let mut unassigned = Vec::new();
+
+        for &address in addresses {
+            // Reuse a unit which is already set to the address, else use the next free one.
+            let unit_index = breakpoints
+                .iter()
+                .position(|&bp| bp == Some(address))
+                .or_else(|| {
+                    (0..breakpoints.len()).find(|&unit_index| {
+                        breakpoints[unit_index].is_none()
+                            && !is_used(&used_by_watchpoints, unit_index)
+                    })
+                });
+
+            match unit_index {
+                Some(unit_index) => {
+                    breakpoints[unit_index] = Some(address);
+                    assignments.push((unit_index, address));
+                }
+                None => unassigned.push(address),
+            }
+        }
+
+        if !unassigned.is_empty() {
+            tracing::warn!(
+                "No available hardware breakpoints for addresses {:#010x?}",
+                unassigned
+            );
+        }
+
+        if !assignments.is_empty() && !self.inner.hw_breakpoints_enabled() {
+            self.enable_breakpoints(true)?;
+        }
+
+        for (unit_index, address) in assignments {
+            tracing::debug!(
+                "Setting HW breakpoint #{} with comparator address {:#08x}",
+                unit_index,
+                address
+            );
+
+            self.inner.set_hw_breakpoint(unit_index, address)?;
+            self.record_hw_breakpoint(unit_index);
+        }
+
+        Ok(unassigned)
+    }
+
+    /// Clear the hardware breakpoints at all given addresses.
+    ///
+    /// The breakpoint units are only read once, so this is faster than calling
+    /// [`Core::clear_hw_breakpoint`] for every address.
+    ///
+    /// Returns the addresses at which no breakpoint was set.
+    #[tracing::instrument(skip(self))]
+    pub fn clear_hw_breakpoints(&mut self, addresses: &[u64]) -> Result<Vec<u64>, error::Error> {
+        let mut breakpoints = self.inner.hw_breakpoints()?;
+
+        let mut not_found = Vec::new();
+
+        for &address in addresses {
+            match breakpoints.iter().position(|&bp| bp == Some(address)) {
+                Some(unit_index) if self.release_hw_breakpoint(unit_index)? => {
+                    breakpoints[unit_index] = None;
+                }
+                _ => not_found.push(address),
+            }
+        }
+
+        Ok(not_found)
+    }
+
     /// Clear all hardware breakpoints
     ///
     /// This function will clear all HW breakpoints which are configured on the target,
     /// regardless if they are set by probe-rs, AND regardless if they are enabled or not.
+    /// Only the breakpoints set on other cores, which share the breakpoint units with this core,
+    /// are kept, see [`Session::cores_sharing_breakpoints`](crate::Session::cores_sharing_breakpoints).
     /// Also used as a helper function in [`Session::drop`](crate::session::Session).
     #[tracing::instrument(skip(self))]
     pub fn clear_all_hw_breakpoints(&mut self) -> Result<(), error::Error> {
-        for breakpoint in (self.inner.hw_breakpoints()?).into_iter().flatten() {
-            self.clear_hw_breakpoint(breakpoint)?
+        let breakpoints = self.inner.hw_breakpoints()?;
+        for (unit_index, breakpoint) in breakpoints.into_iter().enumerate() {
+            if breakpoint.is_some() {
+                self.release_hw_breakpoint(unit_index)?;
+            }
         }
         Ok(())
     }
 
+    /// Returns the number of hardware watchpoint units of the core.
+    pub fn available_watchpoint_units(&mut self) -> Result<u32, error::Error> {
+        self.inner.available_watchpoint_units()
+    }
+
+    /// Set a hardware watchpoint
+    ///
+    /// This function will try to set a watchpoint on the `size` bytes at `address`, which halts
+    /// the core with [`HaltReason::Watchpoint`] on an `access` of any of these bytes.
+    ///
+    /// The size has to be a power of two, and the address has to be aligned to it. Which sizes
+    /// are supported, and the amount of watchpoints, is chip specific. The amount can be queried
+    /// using the `available_watchpoint_units` function.
+    #[tracing::instrument(skip(self))]
+    pub fn set_watchpoint(
+        &mut self,
+        address: u64,
+        size: u64,
+        access: WatchpointAccess,
+    ) -> Result<(), error::Error> {
+        let used_by_breakpoints = self.units_used_by_breakpoints()?;
+        let watchpoints = self.inner.hw_watchpoints()?;
+
+        // If there is a watchpoint set already, reconfigure its unit, else use the next free one.
+        let unit_index = watchpoints
+            .iter()
+            .position(|&wp| wp == Some(address))
+            .or_else(|| {
+                (0..watchpoints.len()).find(|&unit_index| {
+                    watchpoints[unit_index].is_none() && !is_used(&used_by_breakpoints, unit_index)
+                })
+            })
+            .ok_or_else(|| error::Error::Other(anyhow!("No available hardware watchpoints")))?;
+
+        tracing::debug!(
+            "Trying to set watchpoint #{} on {} bytes at address {:#08x}",
+            unit_index,
+            size,
+            address
+        );
+
+        self.inner
+            .set_hw_watchpoint(unit_index, address, size, access)
+    }
+
+    /// Clear a hardware watchpoint
+    ///
+    /// This function will try to clear the watchpoint at `address` if there exists a watchpoint at that address.
+    #[tracing::instrument(skip(self))]
+    pub fn clear_watchpoint(&mut self, address: u64) -> Result<(), error::Error> {
+        match self
+            .inner
+            .hw_watchpoints()?
+            .iter()
+            .position(|&wp| wp == Some(address))
+        {
+            Some(unit_index) => self.inner.clear_hw_watchpoint(unit_index),
+            None => Err(error::Error::Other(anyhow!(
+                "No watchpoint found at address {:#010x}",
+                address
+            ))),
+        }
+    }
+
     /// Returns the architecture of the core.
     pub fn architecture(&self) -> Architecture {
         self.inner.architecture()
@@ -1056,6 +1528,131 @@ impl<'probe> Core<'probe> {
         self.inner.fpu_support()
     }
 
+    /// Read the current execution context of the core.
+    ///
+    /// This decodes whether an exception handler is active and which stack is in use.
+    /// It is only supported on Cortex-M cores, which must be halted.
+    pub fn current_context(&mut self) -> Result<CoreContext, error::Error> {
+        if !self.core_type().is_cortex_m() {
+            return Err(Error::Other(anyhow!(
+                "Reading the current context is only supported on Cortex-M cores, not on {:?}.",
+                self.core_type()
+            )));
+        }
+
+        let pc: u32 = self.read_core_reg(self.registers().program_counter())?;
+        let xpsr: u32 = self.read_core_reg(register::XPSR.id)?;
+        let extra: u32 = self.read_core_reg(register::EXTRA.id)?;
+
+        Ok(CoreContext::from_registers(pc, xpsr, extra))
+    }
+
+    /// Read the reason for the last halt, and the PC the hart halted at, from the `dcsr` and
+    /// `dpc` CSRs.
+    ///
+    /// It is only supported on RISC-V cores, which must be halted.
+    pub fn riscv_halt_status(&mut self) -> Result<RiscvHaltStatus, error::Error> {
+        self.inner.riscv_halt_status()
+    }
+
+    /// Read the enable, pending, active and priority state of all external interrupts from the
+    /// NVIC.
+    ///
+    /// It is only supported on Cortex-M cores.
+    pub fn nvic_state(&mut self) -> Result<NvicState, error::Error> {
+        crate::architecture::arm::core::nvic::read_nvic_state(self)
+    }
+
+    /// Unwind the call stack of the halted core, using the call frame information in
+    /// `debug_info` and the current register values.
+    ///
+    /// The first [`StackFrame`] is the frame of the current program counter, followed by the
+    /// frames of the calling functions. On Cortex-M cores, the frame of an exception handler is
+    /// followed by the frame of the interrupted code. The unwind ends at the first function for
+    /// which the call frame information is missing or incomplete.
+    pub fn unwind(&mut self, debug_info: &DebugInfo) -> Result<Vec<StackFrame>, error::Error> {
+        let program_counter: u64 = self.read_core_reg(self.registers().program_counter())?;
+
+        debug_info.unwind(self, program_counter)
+    }
+
+    /// Service a semihosting request of the firmware, if the core is halted on a semihosting
+    /// breakpoint (`BKPT 0xAB`).
+    ///
+    /// The operation is performed on the host with the state in `handler`, then the result is
+    /// written to `r0`, and the core is resumed after the breakpoint. If the core is running, or
+    /// halted for any other reason, it is left untouched. This can be called whenever the core
+    /// halted, to get the console output of the firmware without SWO or RTT.
+    ///
+    /// It is only supported on Cortex-M cores.
+    pub fn handle_semihosting(
+        &mut self,
+        handler: &mut SemihostingHandler,
+    ) -> Result<SemihostingOutcome, error::Error> {
+        crate::architecture::arm::core::semihosting::handle_semihosting(self, handler)
+    }
+
+    /// Read the raw priority of the DebugMonitor exception from SHPR3.
+    ///
+    /// A lower value is a higher priority. Only interrupts with a higher priority can preempt the
+    /// debug monitor when debugging in monitor mode. This is only supported on ARMv7-M and
+    /// ARMv8-M cores, as ARMv6-M has no DebugMonitor exception.
+    pub fn debug_monitor_priority(&mut self) -> Result<u8, error::Error> {
+        self.check_debug_monitor_support()?;
+
+        let shpr3 = Shpr3(self.read_word_32(Shpr3::ADDRESS)?);
+        Ok(shpr3.pri_12())
+    }
+
+    /// Set the raw priority of the DebugMonitor exception in SHPR3, see
+    /// [`Core::debug_monitor_priority`].
+    ///
+    /// Only the upper bits of the priority are implemented, depending on the core, so the lower
+    /// bits of `priority` are ignored.
+    pub fn set_debug_monitor_priority(&mut self, priority: u8) -> Result<(), error::Error> {
+        self.check_debug_monitor_support()?;
+
+        let mut shpr3 = Shpr3(self.read_word_32(Shpr3::ADDRESS)?);
+        shpr3.set_pri_12(priority);
+        self.write_word_32(Shpr3::ADDRESS, shpr3.into())
+    }
+
+    /// Checks that the core implements the DebugMonitor exception.
+    fn check_debug_monitor_support(&self) -> Result<(), error::Error> {
+        match self.core_type() {
+            CoreType::Armv7m | CoreType::Armv7em | CoreType::Armv8m => Ok(()),
+            _ => Err(Error::ArchitectureRequired(&["ARMv7", "ARMv8"])),
+        }
+    }
+
+    /// Read memory starting at `address`, without reading past the end of `region`.
+    ///
+    /// If the read would cross the end of the region, only the part inside the region is read.
+    /// Returns the number of bytes which were read into `data`.
+    ///
+    /// Returns [`Error::AddressNotInRegion`] if `address` is not contained in the region.
+    pub fn read_memory_region(
+        &mut self,
+        region: &MemoryRegion,
+        address: u64,
+        data: &mut [u8],
+    ) -> Result<usize, error::Error> {
+        let range = region.address_range();
+
+        if !range.contains(&address) {
+            return Err(Error::AddressNotInRegion {
+                address,
+                region: range,
+            });
+        }
+
+        let readable = bytes_in_region(&range, address, data.len());
+
+        self.read(address, &mut data[..readable])?;
+
+        Ok(readable)
+    }
+
     /// Called during session tear down to do any pending cleanup
     #[tracing::instrument(skip(self))]
     pub(crate) fn on_session_stop(&mut self) -> Result<(), Error> {
@@ -1063,6 +1660,19 @@ impl<'probe> Core<'probe> {
     }
 }
 
+/// The number of bytes of a `len` bytes long access at `address` which lie inside `range`.
+fn bytes_in_region(range: &Range<u64>, address: u64, len: usize) -> usize {
+    let remaining = range.end.saturating_sub(address);
+
+    usize::try_from(remaining).map_or(len, |remaining| remaining.min(len))
+}
+
+/// Returns `true` if the unit `unit_index` is marked in a list returned by
+/// [`Core::units_used_by_watchpoints`] or [`Core::units_used_by_breakpoints`].
+fn is_used(used_units: &[bool], unit_index: usize) -> bool {
+    used_units.get(unit_index).copied().unwrap_or(false)
+}
+
 /// The id of a breakpoint.
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub struct BreakpointId(usize);
@@ -1107,6 +1717,17 @@ pub enum BreakpointCause {
     Unknown,
 }
 
+/// The type of memory accesses which trigger a watchpoint, see [`Core::set_watchpoint`].
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum WatchpointAccess {
+    /// Reads of the watched memory.
+    Read,
+    /// Writes to the watched memory.
+    Write,
+    /// Reads of and writes to the watched memory.
+    ReadWrite,
+}
+
 /// The reason why a core was halted.
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
 pub enum HaltReason {
@@ -1134,3 +1755,134 @@ pub enum HaltReason {
     /// This can happen for example when the core is already halted when we connect.
     Unknown,
 }
+
+#[cfg(test)]
+mod test {
+    use super::{
+        bytes_in_region, share_breakpoint_units, ActiveStackPointer, CoreAccessOptions,
+        CoreContext, CoreState, SharedBreakpointRemoval, SharedBreakpointUnits, SpecificCoreState,
+    };
+    use crate::CoreType;
+    use probe_rs_target::ArmCoreAccessOptions;
+    use std::sync::Arc;
+
+    fn arm_core(
+        id: usize,
+        core_type: CoreType,
+        ap: u8,
+        debug_base: Option<u64>,
+    ) -> (SpecificCoreState, CoreState) {
+        (
+            SpecificCoreState::from_core_type(core_type),
+            CoreState::new(
+                id,
+                CoreAccessOptions::Arm(ArmCoreAccessOptions {
+                    ap,
+                    debug_base,
+                    ..Default::default()
+                }),
+            ),
+        )
+    }
+
+    #[test]
+    fn cortex_m_cores_behind_one_ap_share_breakpoints() {
+        let mut cores = vec![
+            arm_core(0, CoreType::Armv7m, 0, None),
+            arm_core(1, CoreType::Armv7m, 0, None),
+            arm_core(2, CoreType::Armv7m, 1, None),
+        ];
+
+        share_breakpoint_units(&mut cores);
+
+        let first = cores[0].1.shared_breakpoints.as_ref().unwrap();
+        let second = cores[1].1.shared_breakpoints.as_ref().unwrap();
+        assert!(Arc::ptr_eq(first, second));
+        assert!(cores[2].1.shared_breakpoints.is_none());
+    }
+
+    #[test]
+    fn cortex_a_cores_use_their_own_breakpoints() {
+        let mut cores = vec![
+            arm_core(0, CoreType::Armv7a, 0, Some(0x8001_0000)),
+            arm_core(1, CoreType::Armv7a, 0, Some(0x8001_2000)),
+        ];
+
+        share_breakpoint_units(&mut cores);
+
+        assert!(cores[0].1.shared_breakpoints.is_none());
+        assert!(cores[1].1.shared_breakpoints.is_none());
+    }
+
+    #[test]
+    fn shared_unit_is_cleared_by_the_last_core() {
+        let mut units = SharedBreakpointUnits::default();
+        units.insert(0, 0);
+        units.insert(0, 1);
+        units.insert(1, 1);
+
+        assert_eq!(units.remove(0, 2), SharedBreakpointRemoval::NotOwned);
+        assert_eq!(units.remove(0, 1), SharedBreakpointRemoval::Keep);
+        assert_eq!(units.remove(0, 1), SharedBreakpointRemoval::NotOwned);
+        assert_eq!(units.remove(0, 0), SharedBreakpointRemoval::Clear);
+
+        assert_eq!(units.remove(1, 0), SharedBreakpointRemoval::NotOwned);
+        assert_eq!(units.remove(1, 1), SharedBreakpointRemoval::Clear);
+    }
+
+    #[test]
+    fn unrecorded_breakpoint_is_cleared_by_any_core() {
+        let mut units = SharedBreakpointUnits::default();
+
+        assert_eq!(units.remove(3, 0), SharedBreakpointRemoval::Clear);
+    }
+
+    #[test]
+    fn context_in_thread_mode() {
+        // Thumb state, CONTROL.SPSEL set.
+        let context = CoreContext::from_registers(0x0000_1234, 0x0100_0000, 0x0200_0000);
+
+        assert_eq!(
+            context,
+            CoreContext {
+                pc: 0x1234,
+                exception_number: None,
+                stack_pointer: ActiveStackPointer::Process,
+            }
+        );
+        assert!(!context.is_in_handler());
+
+        let context = CoreContext::from_registers(0x0000_1234, 0x0100_0000, 0);
+        assert_eq!(context.stack_pointer, ActiveStackPointer::Main);
+    }
+
+    #[test]
+    fn context_in_handler_mode() {
+        // The SysTick handler is active, CONTROL.SPSEL is ignored in handler mode.
+        let context = CoreContext::from_registers(0x0000_2000, 0x0100_000F, 0x0200_0000);
+
+        assert_eq!(context.exception_number, Some(15));
+        assert_eq!(context.stack_pointer, ActiveStackPointer::Main);
+        assert!(context.is_in_handler());
+
+        // IRQ 240 is exception 256, which needs all 9 bits of IPSR.
+        let context = CoreContext::from_registers(0, 0x0000_0100, 0);
+        assert_eq!(context.exception_number, Some(256));
+    }
+
+    #[test]
+    fn access_inside_region() {
+        assert_eq!(bytes_in_region(&(0x1000..0x2000), 0x1000, 0x100), 0x100);
+        assert_eq!(bytes_in_region(&(0x1000..0x2000), 0x1F00, 0x100), 0x100);
+    }
+
+    #[test]
+    fn access_crossing_end_of_region() {
+        assert_eq!(bytes_in_region(&(0x1000..0x2000), 0x1FF0, 0x100), 0x10);
+        assert_eq!(bytes_in_region(&(0x1000..0x2000), 0x2000, 0x100), 0);
+        assert_eq!(
+            bytes_in_region(&(0..u64::MAX), u64::MAX - 4, usize::MAX),
+            4usize
+        );
+    }
+}
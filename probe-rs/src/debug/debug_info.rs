@@ -10,6 +10,7 @@ use crate::{
 use ::gimli::{FileEntry, LineProgramHeader, UnwindContext};
 use gimli::{BaseAddresses, ColumnType, DebugFrame, UnwindSection};
 use object::read::{Object, ObjectSection};
+use once_cell::unsync::OnceCell;
 use probe_rs_target::InstructionSet;
 use registers::RegisterGroup;
 use std::{
@@ -832,7 +833,11 @@ impl DebugInfo {
                                 }
                             }
                         }
-                        gimli::CfaRule::Expression(_) => unimplemented!(),
+                        gimli::CfaRule::Expression(_) => {
+                            tracing::error!("UNWIND: `StackFrameIterator` does not support CFA expressions yet, so the unwind ends here.");
+                            stack_frames.push(return_frame);
+                            break;
+                        }
                     };
 
                     // PART 2-c: Unwind registers for the "previous/calling" frame.
@@ -861,6 +866,27 @@ impl DebugInfo {
                             break 'unwind;
                         };
                     }
+
+                    // PART 2-d: If the function was an exception handler, continue with the interrupted code.
+                    match unwind_exception_frame(
+                        core,
+                        &mut unwind_registers,
+                        unwound_return_address,
+                    ) {
+                        Ok(true) => tracing::trace!(
+                            "UNWIND: Unwound the exception frame of {}",
+                            return_frame.function_name
+                        ),
+                        Ok(false) => {}
+                        Err(error) => {
+                            tracing::error!(
+                                "UNWIND: Failed to read the exception frame: {}",
+                                error
+                            );
+                            stack_frames.push(return_frame);
+                            break;
+                        }
+                    }
                 }
                 Err(error) => {
                     // We cannot do stack unwinding if we do not have debug info. However, there is one case where we can continue. When the following conditions are met:
@@ -1121,6 +1147,45 @@ impl DebugInfo {
     }
 }
 
+/// Debug information of an ELF file, which is only parsed when it is first accessed.
+///
+/// Parsing the DWARF information of a large binary takes a significant amount of time,
+/// which can be avoided with this if the debug information is not always needed,
+/// e.g. when a debug session only flashes the target.
+pub struct LazyDebugInfo {
+    path: PathBuf,
+    debug_info: OnceCell<DebugInfo>,
+}
+
+impl LazyDebugInfo {
+    /// Create a handle to the debug information in the ELF file at `path`, without reading the file.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        LazyDebugInfo {
+            path: path.into(),
+            debug_info: OnceCell::new(),
+        }
+    }
+
+    /// The path of the ELF file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns `true` if the debug information was already parsed.
+    pub fn is_loaded(&self) -> bool {
+        self.debug_info.get().is_some()
+    }
+
+    /// Get the debug information, parsing it from the ELF file on the first call.
+    ///
+    /// The parsed debug information is cached. If parsing fails, the error is returned,
+    /// and parsing is attempted again on the next call.
+    pub fn get(&self) -> Result<&DebugInfo, DebugError> {
+        self.debug_info
+            .get_or_try_init(|| DebugInfo::from_file(&self.path))
+    }
+}
+
 /// Get a handle to the [`gimli::UnwindTableRow`] for this call frame, so that we can reference it to unwind register values.
 fn get_unwind_info<'a>(
     unwind_context: &'a mut Box<UnwindContext<DwarfReader>>,
@@ -1300,7 +1365,14 @@ fn unwind_register(
             }
         }
         //TODO: Implement the remainder of these `RegisterRule`s
-        _ => unimplemented!(),
+        _ => {
+            tracing::error!(
+                "UNWIND: Register rule {:?} for register {} is not supported yet, so the unwind ends here.",
+                register_rule,
+                debug_register.name
+            );
+            return ControlFlow::Break(());
+        }
     };
     debug_register.value = new_value;
 
@@ -1317,6 +1389,84 @@ fn unwind_register(
     ControlFlow::Continue(())
 }
 
+/// The upper byte of the EXC_RETURN values, which the LR of Cortex-M cores holds in an exception handler.
+const EXC_RETURN_MARKER: u32 = 0xFF00_0000;
+
+/// If the `return_address` is an EXC_RETURN value of a Cortex-M core, the unwound function is an exception handler, and the registers of the interrupted code were stacked by the core on exception entry.
+/// In that case, the stacked registers replace the `unwind_registers`, and `Ok(true)` is returned.
+///
+/// NOTE: [ARMv7-M Architecture Reference Manual](https://developer.arm.com/documentation/ddi0403/ee), Section B1.5.7 and B1.5.8, and [ARMv8-M Architecture Reference Manual](https://developer.arm.com/documentation/ddi0553/latest), Section B3.19 describe the layout of the exception frame.
+fn unwind_exception_frame(
+    core: &mut Core,
+    unwind_registers: &mut DebugRegisters,
+    return_address: Option<RegisterValue>,
+) -> Result<bool, crate::Error> {
+    let exc_return = match return_address {
+        Some(RegisterValue::U32(return_address))
+            if core.core_type().is_cortex_m()
+                && return_address & EXC_RETURN_MARKER == EXC_RETURN_MARKER =>
+        {
+            return_address
+        }
+        _ => return Ok(false),
+    };
+
+    let register_file = core.registers();
+
+    // Bit 2 selects the stack the frame was pushed to. On the main stack, the frame starts at the SP before the exception handler was entered, which is the unwound SP.
+    let mut frame_address: u32 = if exc_return & (1 << 2) != 0 {
+        match register_file.psp() {
+            Some(psp) => core.read_core_reg(psp)?,
+            None => return Ok(false),
+        }
+    } else {
+        match unwind_registers.get_stack_pointer().and_then(|sp| sp.value) {
+            Some(sp) => sp.try_into()?,
+            None => return Ok(false),
+        }
+    };
+
+    // Bit 5 is only clear on ARMv8-M cores, which stacked the additional state context before the frame.
+    if exc_return & (1 << 5) == 0 {
+        frame_address += 0x28;
+    }
+
+    let mut frame = [0u32; 8];
+    core.read_32(frame_address as u64, &mut frame)?;
+    let [r0, r1, r2, r3, r12, lr, pc, xpsr] = frame;
+
+    // Bit 4 is clear if the frame contains the floating-point context, i.e. S0-S15, FPSCR and a reserved word.
+    let mut frame_size = if exc_return & (1 << 4) == 0 {
+        0x68
+    } else {
+        0x20
+    };
+    // xPSR bit 9 is set if the core aligned the stack to 8 bytes by adding a padding word.
+    if xpsr & (1 << 9) != 0 {
+        frame_size += 4;
+    }
+
+    for (register_index, value) in [
+        (0, r0),
+        (1, r1),
+        (2, r2),
+        (3, r3),
+        (12, r12),
+        (13, frame_address + frame_size),
+        (14, lr),
+        (15, pc),
+    ] {
+        if let Some(register) = register_file
+            .get_platform_register(register_index)
+            .and_then(|register| unwind_registers.get_register_mut(register.id))
+        {
+            register.value = Some(RegisterValue::U32(value));
+        }
+    }
+
+    Ok(true)
+}
+
 /// Helper function to handle adding a signed offset to a u64 address.
 /// The result wraps, which matches previous behavior of using i64 operations and
 /// casting to u32
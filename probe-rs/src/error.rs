@@ -2,6 +2,7 @@
 
 use crate::DebugProbeError;
 use crate::{architecture::arm::ap::AccessPortError, config::RegistryError};
+use std::ops::Range;
 
 /// The overarching error type which contains all possible errors as variants.
 #[derive(thiserror::Error, Debug)]
@@ -30,6 +31,17 @@ pub enum Error {
     /// Then the correct permission needs to be given to automatically unlock the core to prevent accidental erases.
     #[error("An operation could not be performed because it lacked the permission to do so: {0}")]
     MissingPermissions(String),
+    /// The target does not define a mass erase sequence.
+    #[error("The target {0} does not define a mass erase sequence")]
+    MassEraseNotSupported(String),
+    /// A memory access started outside of the memory region it was restricted to.
+    #[error("Address {address:#010x} is outside of the memory region {region:#010x?}")]
+    AddressNotInRegion {
+        /// The start address of the memory access.
+        address: u64,
+        /// The address range of the memory region.
+        region: Range<u64>,
+    },
     /// Any other error occurred.
     #[error(transparent)]
     Other(#[from] anyhow::Error),
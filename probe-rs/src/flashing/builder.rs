@@ -132,6 +132,54 @@ impl FlashLayout {
         &self.data_blocks
     }
 
+    /// Returns the contents of `sector` after flashing, which are the data of the pages in the
+    /// sector, and the erased byte value everywhere else.
+    pub(super) fn sector_contents(&self, sector: &FlashSector, erased_byte_value: u8) -> Vec<u8> {
+        let mut contents = vec![erased_byte_value; sector.size() as usize];
+        let range = sector.address()..sector.address() + sector.size();
+
+        for page in self
+            .pages
+            .iter()
+            .filter(|page| range.contains(&page.address()))
+        {
+            let offset = (page.address() - sector.address()) as usize;
+            let size = page.data().len().min(contents.len() - offset);
+            contents[offset..offset + size].copy_from_slice(&page.data()[..size]);
+        }
+
+        contents
+    }
+
+    /// Removes `sector`, and the pages and fills in it, from the layout.
+    ///
+    /// Returns the total size of the removed pages.
+    pub(super) fn remove_sector(&mut self, sector: &FlashSector) -> u64 {
+        let range = sector.address()..sector.address() + sector.size();
+
+        self.sectors.retain(|s| s != sector);
+
+        let page_size = self
+            .pages
+            .iter()
+            .filter(|page| range.contains(&page.address()))
+            .map(|page| page.size() as u64)
+            .sum();
+        self.pages.retain(|page| !range.contains(&page.address()));
+
+        // The page indices of the remaining fills have to be updated, as pages were removed.
+        self.fills.retain(|fill| !range.contains(&fill.address));
+        for fill in &mut self.fills {
+            if let Some(index) = self.pages.iter().position(|page| {
+                (page.address()..page.address() + page.size() as u64).contains(&fill.address)
+            }) {
+                fill.page_index = index;
+            }
+        }
+
+        page_size
+    }
+
     pub fn visualize(&self) -> FlashVisualizer {
         FlashVisualizer::new(self)
     }
@@ -1139,4 +1187,38 @@ mod tests {
             }
         )
     }
+
+    #[test]
+    fn remove_sector_updates_fills() {
+        let (region, flash_algorithm) = assemble_demo_flash1();
+        let mut flash_builder = FlashBuilder::new();
+        flash_builder.add_data(0, &[42; 5024]).unwrap();
+        let mut flash_layout = flash_builder
+            .build_sectors_and_pages(&region, &flash_algorithm, true)
+            .unwrap();
+
+        let erased_byte_value = flash_algorithm.flash_properties.erased_byte_value;
+        let sectors = flash_layout.sectors().to_vec();
+
+        let contents = flash_layout.sector_contents(&sectors[1], erased_byte_value);
+        assert_eq!(contents.len(), 0x1000);
+        assert!(contents[..928].iter().all(|byte| *byte == 42));
+        assert!(contents[928..]
+            .iter()
+            .all(|byte| *byte == erased_byte_value));
+
+        assert_eq!(flash_layout.remove_sector(&sectors[0]), 0x1000);
+
+        assert_eq!(flash_layout.sectors(), &sectors[1..]);
+        assert_eq!(flash_layout.pages().len(), 4);
+        assert_eq!(flash_layout.pages()[0].address(), 0x1000);
+        assert_eq!(
+            flash_layout
+                .fills()
+                .iter()
+                .map(|fill| fill.page_index())
+                .collect::<Vec<_>>(),
+            [0, 1, 2, 3]
+        );
+    }
 }
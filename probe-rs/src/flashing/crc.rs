@@ -0,0 +1,137 @@
+//! Verification of the flash contents with a CRC, which is calculated on the target.
+//!
+//! Reading back the whole flash over the debug interface is slow for large images, so a small
+//! routine is loaded into RAM which calculates the CRC of a flash range, and only the CRC is
+//! read back and compared with the CRC of the data.
+
+use crate::{Core, Error, InstructionSet, MemoryInterface, RegisterId};
+use std::ops::Range;
+use std::time::Duration;
+
+/// The time a single CRC calculation may take.
+const CRC_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The reversed polynomial of the CRC-32 used by zlib and Ethernet.
+const CRC32_POLYNOMIAL: u32 = 0xEDB8_8320;
+
+/// A Thumb routine which calculates the CRC-32 of `r1` bytes starting at the address in `r0`,
+/// returns it in `r0` and stops with a breakpoint.
+///
+/// It only uses Thumb-1 instructions, so it runs on all Cortex-M cores.
+const CRC32_ROUTINE: [u32; 10] = [
+    // movs r2, #0; mvns r2, r2
+    0x43D2_2200,
+    // ldr r3, [pc, #28]; loop_byte: cmp r1, #0
+    0x2900_4B07,
+    // beq done; ldrb r4, [r0]
+    0x7804_D00A,
+    // adds r0, #1; eors r2, r4
+    0x4062_3001,
+    // movs r5, #8; loop_bit: lsrs r2, r2, #1
+    0x0852_2508,
+    // bcc skip; eors r2, r3
+    0x405A_D300,
+    // skip: subs r5, #1; bne loop_bit
+    0xD1FA_3D01,
+    // subs r1, #1; b loop_byte
+    0xE7F2_3901,
+    // done: mvns r0, r2; bkpt #0
+    0xBE00_43D0,
+    CRC32_POLYNOMIAL,
+];
+
+/// Calculates the CRC-32 of `data` on the host, matching [`CRC32_ROUTINE`].
+pub(super) fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ CRC32_POLYNOMIAL
+            } else {
+                crc >> 1
+            };
+        }
+    }
+
+    !crc
+}
+
+/// Returns `true` if the CRC can be calculated on the core.
+pub(super) fn target_crc_supported(core: &mut Core) -> Result<bool, Error> {
+    Ok(core.instruction_set()? == InstructionSet::Thumb2)
+}
+
+/// Calculates the CRC-32 of the memory in `range` on the core, with the routine loaded to
+/// `routine_address` in RAM.
+///
+/// The RAM used by the routine and the registers of the core are restored afterwards, and the
+/// core is resumed if it was running before.
+pub(super) fn target_crc32(
+    core: &mut Core,
+    routine_address: u64,
+    range: Range<u64>,
+) -> Result<u32, Error> {
+    let was_running = !core.core_halted()?;
+    core.halt(Duration::from_millis(100))?;
+
+    let mut saved_ram = [0; CRC32_ROUTINE.len()];
+    core.read_32(routine_address, &mut saved_ram)?;
+
+    let regs = core.registers();
+    let mut saved_registers: Vec<(RegisterId, u32)> = Vec::new();
+    for register in regs.platform_registers().chain(regs.psr()) {
+        saved_registers.push((register.id, core.read_core_reg(register.id)?));
+    }
+
+    let result = run_crc32_routine(core, routine_address, range);
+
+    // Restore the state even if the routine failed, so the core isn't left inside of it.
+    core.halt(Duration::from_millis(100))?;
+    core.write_32(routine_address, &saved_ram)?;
+    for (id, value) in saved_registers {
+        core.write_core_reg(id, value)?;
+    }
+
+    if was_running {
+        core.run()?;
+    }
+
+    result
+}
+
+/// Loads the routine to `routine_address`, and runs it on the halted core.
+fn run_crc32_routine(
+    core: &mut Core,
+    routine_address: u64,
+    range: Range<u64>,
+) -> Result<u32, Error> {
+    core.write_32(routine_address, &CRC32_ROUTINE)?;
+
+    let regs = core.registers();
+    core.write_core_reg(regs.program_counter().id, routine_address as u32)?;
+    core.write_core_reg(regs.argument_register(0).id, range.start as u32)?;
+    core.write_core_reg(
+        regs.argument_register(1).id,
+        (range.end - range.start) as u32,
+    )?;
+
+    // The cores supported by `target_crc_supported` always halt on the final BKPT while they
+    // are debugged, so the software breakpoint configuration doesn't have to be changed.
+    core.run()?;
+    core.wait_for_core_halted(CRC_TIMEOUT)?;
+
+    core.read_core_reg(regs.result_register(0).id)
+}
+
+#[cfg(test)]
+mod test {
+    use super::crc32;
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(&[]), 0);
+    }
+}
@@ -2,22 +2,36 @@ use object::{
     elf::FileHeader32, elf::PT_LOAD, read::elf::FileHeader, read::elf::ProgramHeader, Endianness,
     Object, ObjectSection,
 };
-use probe_rs_target::MemoryRange;
+use probe_rs_target::{MemoryRange, MemoryRegion};
 
-use std::{fs::File, path::Path, str::FromStr};
+use std::{fs::File, ops::Range, path::Path, str::FromStr, time::Duration};
 
 use super::*;
 use crate::session::Session;
+use crate::Target;
 
 /// Extended options for flashing a binary file.
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
 pub struct BinOptions {
     /// The address in memory where the binary will be put at.
+    ///
+    /// If not set, the binary is put at the start of the boot memory of the chip.
     pub base_address: Option<u64>,
     /// The number of bytes to skip at the start of the binary file.
     pub skip: u32,
 }
 
+/// Extended options for flashing a UF2 file.
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub struct Uf2Options {
+    /// Only flash the blocks for the chip family with this ID.
+    ///
+    /// UF2 files can contain the images for several chip families. If not set, the family is
+    /// derived from the target, and loading fails if it is unknown and the file contains blocks
+    /// for several families. If set, it has to match the family of the target, if that is known.
+    pub family_id: Option<u32>,
+}
+
 /// A finite list of all the available binary formats probe-rs understands.
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
 pub enum Format {
@@ -29,6 +43,9 @@ pub enum Format {
     Hex,
     /// Marks a file in the [ELF](https://en.wikipedia.org/wiki/Executable_and_Linkable_Format) format.
     Elf,
+    /// Marks a file in the [UF2](https://github.com/microsoft/uf2) format.
+    /// [Uf2Options] can be used to select the blocks of a chip family.
+    Uf2(Uf2Options),
 }
 
 impl FromStr for Format {
@@ -42,6 +59,7 @@ impl FromStr for Format {
             })),
             "hex" | "ihex" | "intelhex" => Ok(Format::Hex),
             "elf" => Ok(Format::Elf),
+            "uf2" => Ok(Format::Uf2(Uf2Options::default())),
             _ => Err(format!("Format '{}' is unknown.", s)),
         }
     }
@@ -75,6 +93,84 @@ pub enum FileDownloadError {
     /// This is most likely because of a bad linker script.
     #[error("No loadable ELF sections were found.")]
     NoLoadableSegments,
+    /// No data records were found in the HEX file.
+    #[error("No data records were found in the HEX file.")]
+    NoHexData,
+    /// Reading and decoding the UF2 file has failed due to the given error.
+    #[error("Could not read UF2 format")]
+    Uf2Read(#[from] Uf2Error),
+    /// No blocks for the flash of the selected chip family were found in the UF2 file.
+    #[error("No blocks for the flash of the selected chip family were found in the UF2 file.")]
+    NoUf2Blocks,
+    /// The UF2 family ID selected in the [Uf2Options] is not the one of the target.
+    #[error("The UF2 family ID {selected:#010x} doesn't match the family ID {target:#010x} of the target.")]
+    Uf2FamilyMismatch {
+        /// The family ID selected in the options.
+        selected: u32,
+        /// The family ID of the target.
+        target: u32,
+    },
+    /// The UF2 file contains blocks for several chip families, and the family of the target is
+    /// unknown.
+    #[error("The UF2 file contains blocks for the chip families {0:#010x?}. Select the family of the target with its family ID.")]
+    Uf2FamilyRequired(Vec<u32>),
+    /// The target has no core with the given index.
+    #[error("The target has no core with index {0}.")]
+    InvalidCoreIndex(usize),
+    /// The data of a file is in a memory region which can't be accessed by the core it was given
+    /// for.
+    #[error("The data at {addresses:#010x?} can't be accessed by core {core_index}.")]
+    NoCoreAccess {
+        /// The index of the core the file was given for.
+        core_index: usize,
+        /// The addresses of the data.
+        addresses: Range<u64>,
+    },
+}
+
+/// The method used to verify the flash contents, see [`DownloadOptions::verify_method`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VerifyMethod {
+    /// Read back all the flashed data, and compare it with the data.
+    ReadBack,
+    /// Calculate a CRC of every flashed sector on the target, and compare it with the CRC of the
+    /// data.
+    ///
+    /// This loads a small routine into the start of the RAM of the core and runs it. The RAM
+    /// and the registers of the core are restored afterwards, and the core is resumed if it was
+    /// running before. If the core can't run the routine, the data is read back instead.
+    Crc,
+}
+
+impl Default for VerifyMethod {
+    fn default() -> Self {
+        VerifyMethod::ReadBack
+    }
+}
+
+/// The way the flash is erased before programming, see [`DownloadOptions::erase_strategy`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EraseStrategy {
+    /// Erase only the sectors which contain data, one by one.
+    SectorBySector,
+    /// Erase the whole flash, using the chip's built in method if one is available.
+    ///
+    /// This is often faster than erasing a lot of single sectors, but the old contents of the
+    /// whole flash are lost. If the chip doesn't support a chip erase, the sectors are erased one
+    /// by one instead.
+    ChipErase,
+    /// Use [`EraseStrategy::ChipErase`] if more than half of the flash has to be erased, and
+    /// [`EraseStrategy::SectorBySector`] otherwise.
+    ///
+    /// If [`DownloadOptions::keep_unwritten_bytes`] is set, the sectors are always erased one by
+    /// one, so the flash contents outside of the data are kept.
+    Auto,
+}
+
+impl Default for EraseStrategy {
+    fn default() -> Self {
+        EraseStrategy::SectorBySector
+    }
 }
 
 /// Options for downloading a file onto a target chip.
@@ -104,17 +200,90 @@ pub struct DownloadOptions<'progress> {
     pub keep_unwritten_bytes: bool,
     /// Perform a dry run. This prepares everything for flashing, but does not write anything to flash.
     pub dry_run: bool,
+    /// The way the flash is erased before programming.
+    ///
+    /// The selected strategy is reported with [`ProgressEvent::SelectedEraseStrategy`] for every
+    /// flash algorithm.
+    pub erase_strategy: EraseStrategy,
     /// If this flag is set to true, probe-rs will try to use the chips built in method to do a full chip erase if one is available.
-    /// This is often faster than erasing a lot of single sectors.
-    /// So if you do not need the old contents of the flash, this is a good option.
+    ///
+    /// This is the same as setting `erase_strategy` to [`EraseStrategy::ChipErase`], which takes
+    /// precedence over `erase_strategy`.
+    #[deprecated(note = "Use `erase_strategy` with `EraseStrategy::ChipErase` instead")]
     pub do_chip_erase: bool,
     /// If the chip was pre-erased with external erasers, this flag can set to true to skip erasing
     /// It may be useful for mass production.
     pub skip_erase: bool,
+    /// Skip erasing and programming the sectors which already contain the data.
+    ///
+    /// Each sector is read before it is erased, and skipped if all of its bytes match the
+    /// contents after flashing. Skipped sectors are reported with
+    /// [`ProgressEvent::SectorSkipped`]. This speeds up flashing if most of the image is
+    /// unchanged, but slows it down if most of the image changed. It has no effect if the chip
+    /// is erased with [`EraseStrategy::ChipErase`].
+    pub skip_erase_on_match: bool,
     /// After flashing, read back all the flashed data to verify it has been written correctly.
     pub verify: bool,
+    /// Only verify that the flash contains the data, without erasing or programming anything.
+    ///
+    /// The flashed data is read back and compared, and the first mismatching address is
+    /// reported with [`ProgressEvent::FailedVerifying`].
+    pub verify_only: bool,
+    /// Only write data to RAM, and fail with [`FlashError::DataInNvm`] if any data is in flash.
+    ///
+    /// Data in RAM is always written with plain memory writes, without loading a flash algorithm,
+    /// so this only guards against accidentally programming the flash, e.g. with an image which
+    /// is not linked for RAM.
+    pub ram_only: bool,
+    /// After writing the data, halt the core and set its stack pointer and program counter from
+    /// the vector table at this address.
+    ///
+    /// This can be used to run an image from RAM, which is not started by a reset.
+    /// This is only supported for ARM cores.
+    pub vector_table_address: Option<u64>,
+    /// The method used to verify the flash contents with `verify` or `verify_only`.
+    ///
+    /// Data in RAM is always read back.
+    pub verify_method: VerifyMethod,
     /// Disable double buffering when loading flash.
     pub disable_double_buffering: bool,
+    /// Override the stack size of the flash algorithm, in bytes.
+    ///
+    /// If this is `None`, the stack size from the target description is used. The stack size
+    /// must not be zero, and the stack and one page buffer have to fit into RAM next to the
+    /// flash algorithm.
+    pub flash_algorithm_stack_size: Option<u32>,
+    /// Override the number of page buffers placed in RAM next to the flash algorithm.
+    ///
+    /// Two buffers use more RAM, but allow downloading a page while the previous one is programmed.
+    /// If this is `None`, two buffers are used if they fit into RAM. Only 1 or 2 buffers are
+    /// supported. If the requested number of buffers does not fit, flashing fails with
+    /// [`FlashError::InsufficientRamForPageBuffers`].
+    pub page_buffer_count: Option<usize>,
+    /// Keep erasing and programming the remaining sectors if a single sector fails.
+    ///
+    /// Failed sectors of all flash regions are collected and reported at the end via
+    /// [`FlashError::SectorsFailed`], after the RAM data has been written. If any sector failed,
+    /// the device is neither started nor verified, as it is left inconsistently programmed and
+    /// should not be run before the affected sectors are flashed successfully.
+    ///
+    /// This disables double buffering, so failures can be attributed to single pages.
+    pub continue_on_error: bool,
+    /// Override the interval in which probe-rs checks whether a flash algorithm routine has completed.
+    ///
+    /// Shorter intervals finish each routine call sooner, but cause more traffic on the debug link, which
+    /// can slow down slow probes or connections. If this is `None`, the core is polled every millisecond.
+    pub completion_poll_interval: Option<Duration>,
+    /// Override the time a flash algorithm routine may take before flashing fails with a timeout.
+    ///
+    /// If this is `None`, the defaults per operation are used:
+    /// - 2 seconds for `init`, `uninit` and double buffered page programming,
+    /// - 30 seconds for a chip erase,
+    /// - the `erase_sector_timeout` and `program_page_timeout` of the target description
+    ///   for sector erases and single page programming.
+    ///
+    /// If set, this timeout is used for all routines.
+    pub completion_timeout: Option<Duration>,
 }
 
 impl<'progress> DownloadOptions<'progress> {
@@ -122,6 +291,23 @@ impl<'progress> DownloadOptions<'progress> {
     pub fn new() -> Self {
         Self::default()
     }
+
+    /// DownloadOptions which only write data to RAM, see [`DownloadOptions::ram_only`].
+    pub fn ram_only() -> Self {
+        Self {
+            ram_only: true,
+            ..Self::default()
+        }
+    }
+
+    /// DownloadOptions which only verify the contents of the flash, see
+    /// [`DownloadOptions::verify_only`].
+    pub fn verify_only() -> Self {
+        Self {
+            verify_only: true,
+            ..Self::default()
+        }
+    }
 }
 
 /// Downloads a file of given `format` at `path` to the flash of the target given in `session`.
@@ -147,23 +333,104 @@ pub fn download_file_with_options<P: AsRef<Path>>(
     path: P,
     format: Format,
     options: DownloadOptions<'_>,
+) -> Result<(), FileDownloadError> {
+    let mut loader = session.target().flash_loader();
+
+    load_file(&mut loader, path, format)?;
+
+    loader
+        .commit(session, options)
+        .map_err(FileDownloadError::Flash)
+}
+
+/// Downloads several files to the flash of the target given in `session`, e.g. the images for
+/// the cores of a multicore chip.
+///
+/// Each file is given with the index of the core it is meant for, and all of its data has to be
+/// in memory regions which can be accessed by that core. Files with overlapping data are
+/// rejected with [`FlashError::DataOverlaps`], before anything is written.
+///
+/// As all cores are accessed through the same probe, the files are not flashed at the same time.
+/// Instead, the data of all files is flashed in a single pass, so every flash algorithm is only
+/// loaded once, and sectors shared by several files are only erased once.
+pub fn download_files_with_options<P: AsRef<Path>>(
+    session: &mut Session,
+    files: &[(usize, P, Format)],
+    options: DownloadOptions<'_>,
+) -> Result<(), FileDownloadError> {
+    let loader = stage_files(session.target(), files)?;
+
+    loader
+        .commit(session, options)
+        .map_err(FileDownloadError::Flash)
+}
+
+/// Loads all `files` into a single [`FlashLoader`], checking that the data of every file is
+/// accessible by the core it was given for.
+fn stage_files<P: AsRef<Path>>(
+    target: &Target,
+    files: &[(usize, P, Format)],
+) -> Result<FlashLoader, FileDownloadError> {
+    let mut loader = target.flash_loader();
+
+    for (core_index, path, format) in files {
+        let core_name = &target
+            .cores
+            .get(*core_index)
+            .ok_or(FileDownloadError::InvalidCoreIndex(*core_index))?
+            .name;
+
+        let mut file_loader = target.flash_loader();
+        load_file(&mut file_loader, path, format.clone())?;
+
+        for (address, data) in file_loader.data() {
+            let addresses = address..address + data.len() as u64;
+
+            // The data can span several adjacent regions, which all have to be accessible.
+            let mut next = addresses.start;
+            while next < addresses.end {
+                let region = target
+                    .get_memory_region_by_address(next)
+                    .filter(|region| {
+                        let cores = match region {
+                            MemoryRegion::Ram(region) => &region.cores,
+                            MemoryRegion::Generic(region) => &region.cores,
+                            MemoryRegion::Nvm(region) => &region.cores,
+                        };
+                        cores.contains(core_name)
+                    })
+                    .ok_or_else(|| FileDownloadError::NoCoreAccess {
+                        core_index: *core_index,
+                        addresses: addresses.clone(),
+                    })?;
+
+                next = region.address_range().end;
+            }
+
+            loader.add_data(address, data)?;
+        }
+    }
+
+    Ok(loader)
+}
+
+/// Loads the file of given `format` at `path` into the `loader`.
+fn load_file(
+    loader: &mut FlashLoader,
+    path: impl AsRef<Path>,
+    format: Format,
 ) -> Result<(), FileDownloadError> {
     let mut file = match File::open(path.as_ref()) {
         Ok(file) => file,
         Err(e) => return Err(FileDownloadError::IO(e)),
     };
 
-    let mut loader = session.target().flash_loader();
-
     match format {
         Format::Bin(options) => loader.load_bin_data(&mut file, options),
         Format::Elf => loader.load_elf_data(&mut file),
         Format::Hex => loader.load_hex_data(&mut file),
-    }?;
-
-    loader
-        .commit(session, options)
-        .map_err(FileDownloadError::Flash)
+        Format::Uf2(options) => loader.load_uf2_data(&mut file, options),
+    }
 }
 
 /// Flash data which was extraced from an ELF file.
@@ -286,7 +553,122 @@ pub(super) fn extract_from_elf<'data>(
 mod tests {
     use std::str::FromStr;
 
-    use super::{BinOptions, Format};
+    use super::{stage_files, BinOptions, FileDownloadError, FlashError, Format, Uf2Options};
+    use crate::config::get_target_by_name;
+    use crate::flashing::uf2::{block, FLAG_FAMILY_ID_PRESENT};
+    use std::path::PathBuf;
+
+    /// Writes `data` to a file in the temporary directory, and returns its path.
+    fn temp_file(name: &str, data: &[u8]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("probe-rs-{}-{}", std::process::id(), name));
+        std::fs::write(&path, data).unwrap();
+        path
+    }
+
+    fn bin_at(base_address: u64) -> Format {
+        Format::Bin(BinOptions {
+            base_address: Some(base_address),
+            skip: 0,
+        })
+    }
+
+    #[test]
+    fn stage_files_rejects_overlapping_files() {
+        let target = get_target_by_name("nrf51822_Xxaa").unwrap();
+        let first = temp_file("overlap-first.bin", &[0xAA; 16]);
+        let second = temp_file("overlap-second.bin", &[0x55; 16]);
+
+        let files = [
+            (0, first.clone(), bin_at(0x100)),
+            (0, second, bin_at(0x108)),
+        ];
+        assert!(matches!(
+            stage_files(&target, &files),
+            Err(FileDownloadError::Flash(FlashError::DataOverlaps { .. }))
+        ));
+
+        let files = [(0, first.clone(), bin_at(0x100)), (0, first, bin_at(0x110))];
+        let loader = stage_files(&target, &files).unwrap();
+        assert_eq!(
+            loader
+                .data()
+                .map(|(address, data)| (address, data.len()))
+                .collect::<Vec<_>>(),
+            vec![(0x100, 32)]
+        );
+    }
+
+    #[test]
+    fn stage_files_rejects_invalid_core_index() {
+        let target = get_target_by_name("nrf51822_Xxaa").unwrap();
+        let file = temp_file("invalid-core.bin", &[0xAA; 16]);
+
+        assert!(matches!(
+            stage_files(&target, &[(1, file, bin_at(0x100))]),
+            Err(FileDownloadError::InvalidCoreIndex(1))
+        ));
+    }
+
+    /// A UF2 file with a block for the nRF52840 and one for the RP2040.
+    fn uf2_two_families() -> Vec<u8> {
+        let mut data = block(FLAG_FAMILY_ID_PRESENT, 0x100, &[0xAA; 16], 0, 0xADA5_2840);
+        data.extend(block(
+            FLAG_FAMILY_ID_PRESENT,
+            0x200,
+            &[0x55; 16],
+            1,
+            0xE48B_FF56,
+        ));
+        data
+    }
+
+    fn uf2(family_id: Option<u32>) -> Format {
+        Format::Uf2(Uf2Options { family_id })
+    }
+
+    #[test]
+    fn uf2_family_of_target() {
+        let target = get_target_by_name("nRF52840_xxAA").unwrap();
+        let file = temp_file("uf2-target.uf2", &uf2_two_families());
+
+        let loader = stage_files(&target, &[(0, file.clone(), uf2(None))]).unwrap();
+        assert_eq!(
+            loader
+                .data()
+                .map(|(address, data)| (address, data.len()))
+                .collect::<Vec<_>>(),
+            vec![(0x100, 16)]
+        );
+
+        assert!(matches!(
+            stage_files(&target, &[(0, file, uf2(Some(0xE48B_FF56)))]),
+            Err(FileDownloadError::Uf2FamilyMismatch {
+                selected: 0xE48B_FF56,
+                target: 0xADA5_2840,
+            })
+        ));
+    }
+
+    #[test]
+    fn uf2_family_of_unknown_target() {
+        let target = get_target_by_name("nrf51822_Xxaa").unwrap();
+        let file = temp_file("uf2-unknown.uf2", &uf2_two_families());
+
+        assert!(matches!(
+            stage_files(&target, &[(0, file.clone(), uf2(None))]),
+            Err(FileDownloadError::Uf2FamilyRequired(families))
+                if families == [0xADA5_2840, 0xE48B_FF56]
+        ));
+
+        let loader = stage_files(&target, &[(0, file, uf2(Some(0xE48B_FF56)))]).unwrap();
+        assert_eq!(
+            loader
+                .data()
+                .map(|(address, data)| (address, data.len()))
+                .collect::<Vec<_>>(),
+            vec![(0x200, 16)]
+        );
+    }
 
     #[test]
     fn parse_format() {
@@ -329,6 +711,10 @@ mod tests {
         );
         assert_eq!(Format::from_str("Elf"), Ok(Format::Elf));
         assert_eq!(Format::from_str("elf"), Ok(Format::Elf));
+        assert_eq!(
+            Format::from_str("UF2"),
+            Ok(Format::Uf2(Uf2Options { family_id: None }))
+        );
         assert_eq!(
             Format::from_str("elfbin"),
             Err("Format 'elfbin' is unknown.".to_string())
@@ -0,0 +1,474 @@
+//! Dumping memory ranges of the target to a file.
+//!
+//! The memory is read in chunks, which are written to the file right away, so large ranges can
+//! be dumped without buffering them on the host.
+
+use probe_rs_target::{Architecture, MemoryRegion};
+
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+use std::ops::Range;
+use std::path::Path;
+
+use super::{uf2, Format};
+use crate::{MemoryInterface, Session};
+
+/// The number of bytes which are read from the target at once.
+const CHUNK_SIZE: usize = 4096;
+
+/// The number of data bytes in a single Intel HEX record.
+const HEX_RECORD_SIZE: usize = 16;
+
+/// The payload size of a UF2 block, which is the size used by most UF2 tools.
+const UF2_PAYLOAD_SIZE: usize = 256;
+
+const ELF_HEADER_SIZE: u32 = 52;
+const ELF_PROGRAM_HEADER_SIZE: u32 = 32;
+const ELF_SECTION_HEADER_SIZE: u32 = 40;
+
+const EM_ARM: u16 = 40;
+const EM_RISCV: u16 = 243;
+
+/// An error which occurred while dumping memory to a file.
+#[derive(Debug, thiserror::Error)]
+pub enum FileDumpError {
+    /// Reading the memory of the target has failed.
+    #[error("Error while reading the memory")]
+    Memory(#[from] crate::Error),
+    /// An IO error has occurred while writing the file.
+    #[error("I/O error")]
+    IO(#[from] std::io::Error),
+    /// Encoding an Intel HEX record has failed.
+    #[error("Could not write ihex format")]
+    IhexWrite(#[from] ihex::WriterError),
+    /// The given range ends beyond the 32 bit address space, which is the limit of the file formats.
+    #[error("The memory range {0:#x?} does not fit into 32 bit addresses.")]
+    AddressTooLarge(Range<u64>),
+    /// The given ranges overlap, so the file would contain the same address twice.
+    #[error("The memory ranges {0:#x?} and {1:#x?} overlap.")]
+    OverlappingRanges(Range<u64>, Range<u64>),
+}
+
+/// Reads the memory `ranges` of the target and writes them to the file at `path` in the given
+/// `format`.
+///
+/// The ranges are written in ascending order. Each range is read with the first core which can
+/// access its memory region, or with the first core if the range is not in the memory map.
+///
+/// - [`Format::Elf`] writes a loadable segment and a section for each range.
+/// - [`Format::Hex`] writes data records with the addresses of the ranges.
+/// - [`Format::Bin`] writes the ranges starting at the address of the first range, and fills the
+///   gaps between the ranges with `0xFF`. The [`BinOptions`](super::BinOptions) are ignored.
+/// - [`Format::Uf2`] writes blocks with the family ID of the [`Uf2Options`](super::Uf2Options),
+///   if set.
+///
+/// The resulting file can be flashed again with [`download_file`](super::download_file) and the same
+/// format.
+pub fn read_memory_to_file(
+    session: &mut Session,
+    path: impl AsRef<Path>,
+    ranges: &[Range<u64>],
+    format: Format,
+) -> Result<(), FileDumpError> {
+    let ranges = sorted_ranges(ranges)?;
+
+    let machine = match session.target().architecture() {
+        Architecture::Arm => EM_ARM,
+        Architecture::Riscv => EM_RISCV,
+    };
+
+    let mut file = BufWriter::new(File::create(path)?);
+
+    let mut read = |address: u64, buffer: &mut [u8]| read_memory(session, address, buffer);
+
+    match format {
+        Format::Bin(_) => write_bin(&mut file, &ranges, &mut read)?,
+        Format::Hex => write_hex(&mut file, &ranges, &mut read)?,
+        Format::Elf => write_elf(&mut file, &ranges, machine, &mut read)?,
+        Format::Uf2(options) => write_uf2(&mut file, &ranges, options.family_id, &mut read)?,
+    }
+
+    file.flush()?;
+
+    Ok(())
+}
+
+/// Reads the memory at `address` with the core which can access it.
+fn read_memory(session: &mut Session, address: u64, buffer: &mut [u8]) -> Result<(), crate::Error> {
+    let target = session.target();
+    let core_index = target
+        .get_memory_region_by_address(address)
+        .and_then(|region| match region {
+            MemoryRegion::Ram(region) => region.cores.first(),
+            MemoryRegion::Generic(region) => region.cores.first(),
+            MemoryRegion::Nvm(region) => region.cores.first(),
+        })
+        .and_then(|name| target.core_index_by_name(name))
+        .unwrap_or(0);
+
+    session.core(core_index)?.read(address, buffer)
+}
+
+/// Sorts the ranges by address, removes empty ranges and checks that they are valid.
+fn sorted_ranges(ranges: &[Range<u64>]) -> Result<Vec<Range<u64>>, FileDumpError> {
+    let mut ranges: Vec<_> = ranges
+        .iter()
+        .filter(|range| range.start < range.end)
+        .cloned()
+        .collect();
+    ranges.sort_by_key(|range| range.start);
+
+    if let Some(range) = ranges.iter().find(|range| range.end > 1 << 32) {
+        return Err(FileDumpError::AddressTooLarge(range.clone()));
+    }
+
+    for pair in ranges.windows(2) {
+        if pair[0].end > pair[1].start {
+            return Err(FileDumpError::OverlappingRanges(
+                pair[0].clone(),
+                pair[1].clone(),
+            ));
+        }
+    }
+
+    Ok(ranges)
+}
+
+/// Reads `range` in chunks of at most `chunk_size` bytes, and passes each chunk with its address
+/// to `f`.
+fn for_each_chunk<R, F>(
+    range: &Range<u64>,
+    chunk_size: usize,
+    read: &mut R,
+    mut f: F,
+) -> Result<(), FileDumpError>
+where
+    R: FnMut(u64, &mut [u8]) -> Result<(), crate::Error>,
+    F: FnMut(u64, &[u8]) -> Result<(), FileDumpError>,
+{
+    let mut buffer = vec![0; chunk_size];
+    let mut address = range.start;
+
+    while address < range.end {
+        let size = ((range.end - address) as usize).min(chunk_size);
+        let chunk = &mut buffer[..size];
+
+        read(address, chunk)?;
+        f(address, chunk)?;
+
+        address += size as u64;
+    }
+
+    Ok(())
+}
+
+fn write_bin<W, R>(file: &mut W, ranges: &[Range<u64>], read: &mut R) -> Result<(), FileDumpError>
+where
+    W: Write,
+    R: FnMut(u64, &mut [u8]) -> Result<(), crate::Error>,
+{
+    let mut end = match ranges.first() {
+        Some(range) => range.start,
+        None => return Ok(()),
+    };
+
+    for range in ranges {
+        std::io::copy(
+            &mut std::io::repeat(0xFF).take(range.start - end),
+            &mut *file,
+        )?;
+
+        for_each_chunk(range, CHUNK_SIZE, read, |_, chunk| {
+            Ok(file.write_all(chunk)?)
+        })?;
+        end = range.end;
+    }
+
+    Ok(())
+}
+
+fn write_hex<W, R>(file: &mut W, ranges: &[Range<u64>], read: &mut R) -> Result<(), FileDumpError>
+where
+    W: Write,
+    R: FnMut(u64, &mut [u8]) -> Result<(), crate::Error>,
+{
+    let mut upper_address = None;
+
+    for range in ranges {
+        for_each_chunk(range, CHUNK_SIZE, read, |address, chunk| {
+            for (index, data) in chunk.chunks(HEX_RECORD_SIZE).enumerate() {
+                let address = address + (index * HEX_RECORD_SIZE) as u64;
+
+                // Records must not cross a 64 KiB boundary, as their offset is only 16 bit wide.
+                let split = (0x1_0000 - (address & 0xFFFF) as usize).min(data.len());
+
+                for (address, data) in [
+                    (address, &data[..split]),
+                    (address + split as u64, &data[split..]),
+                ] {
+                    if data.is_empty() {
+                        continue;
+                    }
+
+                    let upper = (address >> 16) as u16;
+                    if upper_address != Some(upper) {
+                        upper_address = Some(upper);
+                        writeln!(
+                            file,
+                            "{}",
+                            ihex::Record::ExtendedLinearAddress(upper).to_record_string()?
+                        )?;
+                    }
+
+                    let record = ihex::Record::Data {
+                        offset: address as u16,
+                        value: data.to_vec(),
+                    };
+                    writeln!(file, "{}", record.to_record_string()?)?;
+                }
+            }
+
+            Ok(())
+        })?;
+    }
+
+    writeln!(file, "{}", ihex::Record::EndOfFile.to_record_string()?)?;
+
+    Ok(())
+}
+
+fn write_elf<W, R>(
+    file: &mut W,
+    ranges: &[Range<u64>],
+    machine: u16,
+    read: &mut R,
+) -> Result<(), FileDumpError>
+where
+    W: Write,
+    R: FnMut(u64, &mut [u8]) -> Result<(), crate::Error>,
+{
+    // The section names, starting with the empty name of the null section.
+    let mut names = vec![0];
+    let mut name_offsets = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        name_offsets.push(names.len() as u32);
+        names.extend_from_slice(format!(".dump_{:08x}", range.start).as_bytes());
+        names.push(0);
+    }
+    let shstrtab_name = names.len() as u32;
+    names.extend_from_slice(b".shstrtab\0");
+
+    // The layout of the file is: header, program headers, data of the ranges, section names and
+    // section headers.
+    let data_offset = ELF_HEADER_SIZE + ELF_PROGRAM_HEADER_SIZE * ranges.len() as u32;
+    let data_size: u64 = ranges.iter().map(|range| range.end - range.start).sum();
+    let names_offset = u32::try_from(data_offset as u64 + data_size).map_err(|_| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "The memory ranges are too large for an ELF32 file",
+        )
+    })?;
+    let padding = (4 - names.len() % 4) % 4;
+    let section_headers_offset = names_offset + (names.len() + padding) as u32;
+    let section_count = ranges.len() as u16 + 2;
+
+    // ELF header
+    file.write_all(&[0x7F, b'E', b'L', b'F', 1, 1, 1, 0])?;
+    file.write_all(&[0; 8])?;
+    write_u16(file, 2)?; // e_type: ET_EXEC
+    write_u16(file, machine)?;
+    write_u32(file, 1)?; // e_version
+    write_u32(file, 0)?; // e_entry
+    write_u32(file, ELF_HEADER_SIZE)?;
+    write_u32(file, section_headers_offset)?;
+    write_u32(file, 0)?; // e_flags
+    write_u16(file, ELF_HEADER_SIZE as u16)?;
+    write_u16(file, ELF_PROGRAM_HEADER_SIZE as u16)?;
+    write_u16(file, ranges.len() as u16)?;
+    write_u16(file, ELF_SECTION_HEADER_SIZE as u16)?;
+    write_u16(file, section_count)?;
+    write_u16(file, section_count - 1)?; // e_shstrndx
+
+    // Program headers
+    let mut offset = data_offset;
+    for range in ranges {
+        let size = (range.end - range.start) as u32;
+
+        write_u32(file, 1)?; // p_type: PT_LOAD
+        write_u32(file, offset)?;
+        write_u32(file, range.start as u32)?; // p_vaddr
+        write_u32(file, range.start as u32)?; // p_paddr
+        write_u32(file, size)?; // p_filesz
+        write_u32(file, size)?; // p_memsz
+        write_u32(file, 0b111)?; // p_flags: RWX
+        write_u32(file, 1)?; // p_align
+
+        offset += size;
+    }
+
+    for range in ranges {
+        for_each_chunk(range, CHUNK_SIZE, read, |_, chunk| {
+            Ok(file.write_all(chunk)?)
+        })?;
+    }
+
+    file.write_all(&names)?;
+    file.write_all(&[0; 4][..padding])?;
+
+    // Section headers, starting with the null section.
+    file.write_all(&[0; ELF_SECTION_HEADER_SIZE as usize])?;
+
+    let mut offset = data_offset;
+    for (range, name) in ranges.iter().zip(name_offsets) {
+        let size = (range.end - range.start) as u32;
+
+        write_section_header(file, name, 1, 0b111, range.start as u32, offset, size)?;
+
+        offset += size;
+    }
+
+    write_section_header(
+        file,
+        shstrtab_name,
+        3,
+        0,
+        0,
+        names_offset,
+        names.len() as u32,
+    )?;
+
+    Ok(())
+}
+
+/// Writes an ELF32 section header.
+///
+/// The flags are the `SHF_*` flags, e.g. `SHF_ALLOC`.
+fn write_section_header(
+    file: &mut impl Write,
+    name: u32,
+    kind: u32,
+    flags: u32,
+    address: u32,
+    offset: u32,
+    size: u32,
+) -> std::io::Result<()> {
+    write_u32(file, name)?;
+    write_u32(file, kind)?;
+    write_u32(file, flags)?;
+    write_u32(file, address)?;
+    write_u32(file, offset)?;
+    write_u32(file, size)?;
+    write_u32(file, 0)?; // sh_link
+    write_u32(file, 0)?; // sh_info
+    write_u32(file, 1)?; // sh_addralign
+    write_u32(file, 0) // sh_entsize
+}
+
+fn write_uf2<W, R>(
+    file: &mut W,
+    ranges: &[Range<u64>],
+    family_id: Option<u32>,
+    read: &mut R,
+) -> Result<(), FileDumpError>
+where
+    W: Write,
+    R: FnMut(u64, &mut [u8]) -> Result<(), crate::Error>,
+{
+    let num_blocks: u64 = ranges
+        .iter()
+        .map(|range| {
+            (range.end - range.start + UF2_PAYLOAD_SIZE as u64 - 1) / UF2_PAYLOAD_SIZE as u64
+        })
+        .sum();
+
+    let (flags, family_id) = match family_id {
+        Some(family_id) => (uf2::FLAG_FAMILY_ID_PRESENT, family_id),
+        None => (0, 0),
+    };
+
+    let mut block_no = 0;
+
+    for range in ranges {
+        for_each_chunk(range, UF2_PAYLOAD_SIZE, read, |address, chunk| {
+            let mut block = [0; uf2::BLOCK_SIZE];
+
+            let header = [
+                uf2::MAGIC_START_0,
+                uf2::MAGIC_START_1,
+                flags,
+                address as u32,
+                chunk.len() as u32,
+                block_no,
+                num_blocks as u32,
+                family_id,
+            ];
+            for (index, word) in header.iter().enumerate() {
+                block[index * 4..][..4].copy_from_slice(&word.to_le_bytes());
+            }
+            block[uf2::PAYLOAD_OFFSET..][..chunk.len()].copy_from_slice(chunk);
+            block[uf2::BLOCK_SIZE - 4..].copy_from_slice(&uf2::MAGIC_END.to_le_bytes());
+
+            file.write_all(&block)?;
+            block_no += 1;
+
+            Ok(())
+        })?;
+    }
+
+    Ok(())
+}
+
+fn write_u16(file: &mut impl Write, value: u16) -> std::io::Result<()> {
+    file.write_all(&value.to_le_bytes())
+}
+
+fn write_u32(file: &mut impl Write, value: u32) -> std::io::Result<()> {
+    file.write_all(&value.to_le_bytes())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{sorted_ranges, write_bin, write_hex, FileDumpError};
+
+    fn read_pattern(address: u64, buffer: &mut [u8]) -> Result<(), crate::Error> {
+        for (index, byte) in buffer.iter_mut().enumerate() {
+            *byte = (address as usize + index) as u8;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn hex_records_split_at_64k_boundary() {
+        let mut file = Vec::new();
+        write_hex(&mut file, &[0xFFF8..0x1_0008], &mut read_pattern).unwrap();
+
+        assert_eq!(
+            String::from_utf8(file).unwrap(),
+            ":020000040000FA\n\
+             :08FFF800F8F9FAFBFCFDFEFF25\n\
+             :020000040001F9\n\
+             :080000000001020304050607DC\n\
+             :00000001FF\n"
+        );
+    }
+
+    #[test]
+    fn bin_fills_gaps() {
+        let mut file = Vec::new();
+        write_bin(&mut file, &[0x10..0x12, 0x14..0x15], &mut read_pattern).unwrap();
+
+        assert_eq!(file, [0x10, 0x11, 0xFF, 0xFF, 0x14]);
+    }
+
+    #[test]
+    fn reject_overlapping_ranges() {
+        assert!(matches!(
+            sorted_ranges(&[0x20..0x30, 0x10..0x21]),
+            Err(FileDumpError::OverlappingRanges(_, _))
+        ));
+        assert_eq!(
+            sorted_ranges(&[0x20..0x20, 0x10..0x20]).unwrap(),
+            [0x10..0x20]
+        );
+    }
+}
@@ -84,6 +84,40 @@ pub enum FlashError {
         /// The address where the algorithm was supposed to be loaded to.
         address: u64,
     },
+    /// A flash read overlaps the RAM occupied by the flash algorithm.
+    #[error("Flash read {range:#010x?} overlaps the flash algorithm RAM {ram_range:#010x?}.")]
+    ReadOverlapsFlashAlgorithm {
+        /// The address range which was read.
+        range: Range<u64>,
+        /// The RAM range occupied by the flash algorithm.
+        ram_range: Range<u64>,
+    },
+    /// The requested flash algorithm stack size is not valid.
+    #[error("Invalid flash algorithm stack size of {size} bytes. Must not be zero.")]
+    InvalidFlashAlgorithmStackSize {
+        /// The requested stack size in bytes.
+        size: u32,
+    },
+    /// The requested flash algorithm stack and a page buffer do not fit into the RAM region used for the flash algorithm.
+    #[error("Not enough RAM for a flash algorithm stack of {stack_size} bytes and a page buffer.")]
+    InsufficientRamForStack {
+        /// The requested stack size in bytes.
+        stack_size: u32,
+    },
+    /// The requested number of page buffers is not supported.
+    #[error("Invalid page buffer count {count}. Must be 1 or 2.")]
+    InvalidPageBufferCount {
+        /// The number of page buffers which was requested.
+        count: usize,
+    },
+    /// The requested number of page buffers does not fit into the RAM region used for the flash algorithm.
+    #[error("Not enough RAM for {requested} page buffers, only {available} fit next to the flash algorithm.")]
+    InsufficientRamForPageBuffers {
+        /// The number of page buffers which was requested.
+        requested: usize,
+        /// The number of page buffers which fit into RAM.
+        available: usize,
+    },
     /// The given page size is not valid. Only page sizes multiples of 4 bytes are allowed.
     #[error("Invalid page size {size:08X?}. Must be a multiple of 4 bytes.")]
     InvalidPageSize {
@@ -99,6 +133,19 @@ pub enum FlashError {
         /// The name of the chip.
         name: String,
     },
+    /// The target has flash algorithms, but none of them covers a flash region data is written to.
+    ///
+    /// This usually means that the target description is missing a flash algorithm, or that the
+    /// address range of a flash algorithm does not include the whole region.
+    #[error("No flash algorithm of {name} covers the flash region {region_name} {:#010x}..{:#010x}, which data for addresses {requested:#010X?} is written to.", region.range.start, region.range.end, region_name = region.name.as_deref().unwrap_or("<unnamed>"))]
+    NoFlashAlgorithmForRegion {
+        /// The name of the chip.
+        name: String,
+        /// The region which no flash algorithm covers.
+        region: NvmRegion,
+        /// The address range of the data which was requested to be written to the region.
+        requested: Range<u64>,
+    },
     /// More than one matching flash algorithm was found for the given memory range and all of them is marked as default.
     #[error("Trying to write flash, but found more than one suitable flash loader algorithim marked as default for {region:?}.")]
     MultipleDefaultFlashLoaderAlgorithms {
@@ -145,10 +192,61 @@ pub enum FlashError {
     /// No core can access this NVM region.
     #[error("No core can access the NVM region {0:?}.")]
     NoNvmCoreAccess(NvmRegion),
+    /// Data has to be written to flash in a download which only writes to RAM, see
+    /// [`DownloadOptions::ram_only`](super::DownloadOptions::ram_only).
+    #[error("The data for addresses {requested:#010X?} is in the flash region {region_name}, but only RAM may be written.", region_name = region.name.as_deref().unwrap_or("<unnamed>"))]
+    DataInNvm {
+        /// The flash region which contains the data.
+        region: NvmRegion,
+        /// The address range of the data in the region.
+        requested: Range<u64>,
+    },
     /// No core can access this RAM region.
     #[error("No core can access the ram region {0:?}.")]
     NoRamCoreAccess(RamRegion),
     /// The register value supplied for this flash algorithm is out of the supported range.
     #[error("The register value {0:08X?} is out of the supported range.")]
     RegisterValueNotSupported(u64),
+    /// Erasing or programming failed for some sectors while flashing with
+    /// [`DownloadOptions::continue_on_error`](super::DownloadOptions::continue_on_error).
+    ///
+    /// All other sectors were flashed, so the device is left inconsistently programmed.
+    #[error("Flashing failed for {} sector(s). The flash contents are inconsistent and the device should not be run.", .0.failures().len())]
+    SectorsFailed(FlashFailureReport),
+}
+
+/// A list of all sectors which could not be erased or programmed during a flash operation.
+///
+/// The flash contents of the failed sectors are undefined, while all other sectors
+/// contain the new data. This means the device is inconsistently programmed.
+#[derive(Debug, Default)]
+pub struct FlashFailureReport {
+    failures: Vec<SectorFailure>,
+}
+
+impl FlashFailureReport {
+    pub(super) fn extend(&mut self, failures: Vec<SectorFailure>) {
+        self.failures.extend(failures);
+    }
+
+    /// Returns `true` if no sector failed.
+    pub fn is_empty(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    /// All sectors which failed, in the order in which they were processed.
+    pub fn failures(&self) -> &[SectorFailure] {
+        &self.failures
+    }
+}
+
+/// A single sector which could not be erased or programmed.
+#[derive(Debug)]
+pub struct SectorFailure {
+    /// The address of the failed sector.
+    pub sector_address: u64,
+    /// The size of the failed sector in bytes.
+    pub sector_size: u64,
+    /// The error which occurred for this sector.
+    pub error: FlashError,
 }
@@ -4,6 +4,7 @@ use super::FlashError;
 use crate::core::Architecture;
 use crate::{architecture::riscv, Target};
 use std::convert::TryInto;
+use std::ops::Range;
 
 /// A flash algorithm, which has been assembled for a specific
 /// chip.
@@ -144,6 +145,26 @@ impl FlashAlgorithm {
         })
     }
 
+    /// The RAM range occupied by the flash algorithm while it is loaded.
+    ///
+    /// The range starts at [`load_address`](Self::load_address) with the algorithm header, code
+    /// and data, followed by the stack which grows down from [`begin_stack`](Self::begin_stack),
+    /// and ends after the last of the [`page_buffers`](Self::page_buffers). None of this RAM may
+    /// be written while the algorithm is loaded, otherwise the algorithm is corrupted.
+    pub fn ram_range(&self) -> Range<u64> {
+        let page_size = self.flash_properties.page_size as u64;
+        let end = self
+            .page_buffers
+            .iter()
+            .chain(std::iter::once(&self.begin_data))
+            .map(|buffer| buffer + page_size)
+            .max()
+            .unwrap_or(self.begin_stack)
+            .max(self.begin_stack);
+
+        self.load_address..end
+    }
+
     /// Returns true if the entire contents of the argument array equal the erased byte value.
     pub fn is_erased(&self, data: &[u8]) -> bool {
         for b in data {
@@ -157,6 +178,10 @@ impl FlashAlgorithm {
     const FLASH_ALGO_STACK_SIZE: u32 = 512;
     const FLASH_ALGO_STACK_DECREMENT: u32 = 64;
 
+    /// More than two page buffers give no benefit, as only one page is programmed
+    /// while the next one is downloaded.
+    const MAX_PAGE_BUFFERS: usize = 2;
+
     // Header for RISCV Flash Algorithms
     const RISCV_FLASH_BLOB_HEADER: [u32; 2] = [riscv::assembly::EBREAK, riscv::assembly::EBREAK];
 
@@ -183,9 +208,39 @@ impl FlashAlgorithm {
         raw: &RawFlashAlgorithm,
         ram_region: &RamRegion,
         target: &Target,
+    ) -> Result<Self, FlashError> {
+        Self::assemble_from_raw_with_overrides(raw, ram_region, target, None, None)
+    }
+
+    /// Constructs a complete flash algorithm, tailored to the flash and RAM sizes given,
+    /// with a `stack_size` byte stack and `page_buffer_count` page buffers.
+    ///
+    /// If `stack_size` is `None`, the stack size of the raw algorithm is used, and reduced
+    /// if it does not fit into the RAM region. A given stack size is used as is, and an error
+    /// is returned if it does not fit together with one page buffer.
+    ///
+    /// If `page_buffer_count` is `None`, two page buffers are used if they fit into the RAM
+    /// region, one otherwise. A given count must be 1 or 2, and an error is returned if that
+    /// many buffers do not fit.
+    pub fn assemble_from_raw_with_overrides(
+        raw: &RawFlashAlgorithm,
+        ram_region: &RamRegion,
+        target: &Target,
+        stack_size: Option<u32>,
+        page_buffer_count: Option<usize>,
     ) -> Result<Self, FlashError> {
         use std::mem::size_of;
 
+        if stack_size == Some(0) {
+            return Err(FlashError::InvalidFlashAlgorithmStackSize { size: 0 });
+        }
+
+        if let Some(count) = page_buffer_count {
+            if !(1..=Self::MAX_PAGE_BUFFERS).contains(&count) {
+                return Err(FlashError::InvalidPageBufferCount { count });
+            }
+        }
+
         if raw.flash_properties.page_size % 4 != 0 {
             // TODO move to yaml validation
             return Err(FlashError::InvalidPageSize {
@@ -218,7 +273,11 @@ impl FlashAlgorithm {
         let mut code_start = 0;
 
         // Try to find a stack size that fits with at least one page of data.
-        let stack_size = {
+        // A requested stack size is never reduced.
+        let stack_size_requested = stack_size.is_some();
+        let stack_size = if let Some(stack_size) = stack_size {
+            stack_size
+        } else {
             let stack_size = raw.stack_size.unwrap_or(Self::FLASH_ALGO_STACK_SIZE);
             if stack_size < Self::FLASH_ALGO_STACK_DECREMENT {
                 // If the stack size is less than one decrement, we
@@ -238,7 +297,13 @@ impl FlashAlgorithm {
         };
         tracing::debug!("The flash algorithm will be configured with {stack_size} bytes of stack");
 
-        for i in 0..stack_size / Self::FLASH_ALGO_STACK_DECREMENT {
+        let stack_size_attempts = if stack_size_requested {
+            1
+        } else {
+            stack_size / Self::FLASH_ALGO_STACK_DECREMENT
+        };
+
+        for i in 0..stack_size_attempts {
             // Load address
             addr_load = raw
                 .load_address
@@ -271,16 +336,33 @@ impl FlashAlgorithm {
             }
         }
 
+        let page_size = raw.flash_properties.page_size as u64;
+
+        if stack_size_requested && addr_data + page_size > ram_region.range.end {
+            return Err(FlashError::InsufficientRamForStack { stack_size });
+        }
+
         // Data buffer 2
-        let addr_data2 = addr_data + raw.flash_properties.page_size as u64;
-        offset += raw.flash_properties.page_size as u64;
+        //
+        // Determine how many buffers we can use by the remaining RAM region size.
+        let wanted_page_buffers = page_buffer_count.unwrap_or(Self::MAX_PAGE_BUFFERS);
+        let mut page_buffers = vec![addr_data];
+        while page_buffers.len() < wanted_page_buffers {
+            let addr_buffer = addr_data + page_buffers.len() as u64 * page_size;
+
+            if addr_buffer + page_size > ram_region.range.end {
+                break;
+            }
 
-        // Determine whether we can use double buffering or not by the remaining RAM region size.
-        let page_buffers = if offset <= ram_region.range.end - addr_load {
-            vec![addr_data, addr_data2]
-        } else {
-            vec![addr_data]
-        };
+            page_buffers.push(addr_buffer);
+        }
+
+        if page_buffer_count.is_some() && page_buffers.len() < wanted_page_buffers {
+            return Err(FlashError::InsufficientRamForPageBuffers {
+                requested: wanted_page_buffers,
+                available: page_buffers.len(),
+            });
+        }
 
         let name = raw.name.clone();
 
@@ -305,9 +387,11 @@ impl FlashAlgorithm {
 
 #[cfg(test)]
 mod test {
-    use probe_rs_target::{FlashProperties, SectorDescription, SectorInfo};
+    use probe_rs_target::{
+        FlashProperties, RamRegion, RawFlashAlgorithm, SectorDescription, SectorInfo,
+    };
 
-    use crate::flashing::FlashAlgorithm;
+    use crate::flashing::{FlashAlgorithm, FlashError};
 
     #[test]
     fn flash_sector_single_size() {
@@ -475,4 +559,110 @@ mod test {
         ];
         assert_eq!(&got, expected);
     }
+
+    #[test]
+    fn ram_range_covers_page_buffers() {
+        let config = FlashAlgorithm {
+            load_address: 0x2000_0000,
+            begin_stack: 0x2000_0400,
+            begin_data: 0x2000_0400,
+            page_buffers: vec![0x2000_0400, 0x2000_0500],
+            flash_properties: FlashProperties {
+                page_size: 0x100,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        assert_eq!(config.ram_range(), 0x2000_0000..0x2000_0600);
+    }
+
+    /// An algorithm with two words of code, which is placed at the start of a 1 KiB RAM region
+    /// after the 8 word ARM header.
+    fn assemble(
+        stack_size: Option<u32>,
+        page_buffer_count: Option<usize>,
+    ) -> Result<FlashAlgorithm, FlashError> {
+        let raw = RawFlashAlgorithm {
+            instructions: vec![0; 8],
+            stack_size: Some(0x200),
+            flash_properties: FlashProperties {
+                page_size: 0x100,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let ram = RamRegion {
+            name: None,
+            range: 0x2000_0000..0x2000_0400,
+            is_boot_memory: false,
+            cores: vec![],
+        };
+        let target = crate::config::get_target_by_name("nrf51822_xxAA").unwrap();
+
+        FlashAlgorithm::assemble_from_raw_with_overrides(
+            &raw,
+            &ram,
+            &target,
+            stack_size,
+            page_buffer_count,
+        )
+    }
+
+    #[test]
+    fn assemble_with_default_layout() {
+        let algorithm = assemble(None, None).unwrap();
+
+        // The second page buffer doesn't fit behind the stack.
+        assert_eq!(algorithm.begin_stack, 0x2000_0248);
+        assert_eq!(algorithm.page_buffers, vec![0x2000_0248]);
+    }
+
+    #[test]
+    fn assemble_with_stack_size_override() {
+        let algorithm = assemble(Some(0x100), Some(2)).unwrap();
+
+        assert_eq!(algorithm.begin_stack, 0x2000_0148);
+        assert_eq!(algorithm.page_buffers, vec![0x2000_0148, 0x2000_0248]);
+        assert_eq!(algorithm.ram_range(), 0x2000_0000..0x2000_0348);
+    }
+
+    #[test]
+    fn assemble_rejects_zero_stack_size() {
+        assert!(matches!(
+            assemble(Some(0), None),
+            Err(FlashError::InvalidFlashAlgorithmStackSize { size: 0 })
+        ));
+    }
+
+    #[test]
+    fn assemble_rejects_stack_size_larger_than_ram() {
+        assert!(matches!(
+            assemble(Some(0x400), None),
+            Err(FlashError::InsufficientRamForStack { stack_size: 0x400 })
+        ));
+    }
+
+    #[test]
+    fn assemble_rejects_invalid_page_buffer_count() {
+        assert!(matches!(
+            assemble(None, Some(0)),
+            Err(FlashError::InvalidPageBufferCount { count: 0 })
+        ));
+        assert!(matches!(
+            assemble(None, Some(3)),
+            Err(FlashError::InvalidPageBufferCount { count: 3 })
+        ));
+    }
+
+    #[test]
+    fn assemble_rejects_page_buffers_larger_than_ram() {
+        assert!(matches!(
+            assemble(Some(0x200), Some(2)),
+            Err(FlashError::InsufficientRamForPageBuffers {
+                requested: 2,
+                available: 1
+            })
+        ));
+    }
 }
@@ -2,11 +2,18 @@ use probe_rs_target::{MemoryRegion, RawFlashAlgorithm};
 
 use super::{
     FlashAlgorithm, FlashBuilder, FlashError, FlashFill, FlashLayout, FlashPage, FlashProgress,
+    SectorFailure,
 };
 use crate::config::NvmRegion;
 use crate::memory::MemoryInterface;
 use crate::{core::RegisterFile, session::Session, Core, InstructionSet};
-use std::{fmt::Debug, time::Duration};
+use std::{
+    fmt::Debug,
+    time::{Duration, Instant},
+};
+
+/// Default interval between two checks whether a flash algorithm routine has completed.
+const DEFAULT_COMPLETION_POLL_INTERVAL: Duration = Duration::from_millis(1);
 
 pub(super) trait Operation {
     fn operation() -> u32;
@@ -51,6 +58,8 @@ pub(super) struct Flasher<'session> {
     session: &'session mut Session,
     core_index: usize,
     flash_algorithm: FlashAlgorithm,
+    completion_poll_interval: Duration,
+    completion_timeout: Option<Duration>,
 }
 
 impl<'session> Flasher<'session> {
@@ -58,6 +67,8 @@ impl<'session> Flasher<'session> {
         session: &'session mut Session,
         core_index: usize,
         raw_flash_algorithm: &RawFlashAlgorithm,
+        stack_size: Option<u32>,
+        page_buffer_count: Option<usize>,
     ) -> Result<Self, FlashError> {
         let target = session.target();
 
@@ -80,12 +91,20 @@ impl<'session> Flasher<'session> {
 
         tracing::info!("Chosen RAM to run the algo: {:x?}", ram);
 
-        let flash_algorithm = FlashAlgorithm::assemble_from_raw(raw_flash_algorithm, ram, target)?;
+        let flash_algorithm = FlashAlgorithm::assemble_from_raw_with_overrides(
+            raw_flash_algorithm,
+            ram,
+            target,
+            stack_size,
+            page_buffer_count,
+        )?;
 
         let mut this = Self {
             session,
             core_index,
             flash_algorithm,
+            completion_poll_interval: DEFAULT_COMPLETION_POLL_INTERVAL,
+            completion_timeout: None,
         };
 
         this.load()?;
@@ -93,6 +112,18 @@ impl<'session> Flasher<'session> {
         Ok(this)
     }
 
+    /// Configure how the completion of flash algorithm routines is polled.
+    ///
+    /// If `timeout` is set, it replaces the per-operation timeouts for all routines.
+    pub(super) fn set_completion_polling(
+        &mut self,
+        poll_interval: Option<Duration>,
+        timeout: Option<Duration>,
+    ) {
+        self.completion_poll_interval = poll_interval.unwrap_or(DEFAULT_COMPLETION_POLL_INTERVAL);
+        self.completion_timeout = timeout;
+    }
+
     pub(super) fn flash_algorithm(&self) -> &FlashAlgorithm {
         &self.flash_algorithm
     }
@@ -172,6 +203,8 @@ impl<'session> Flasher<'session> {
         let mut flasher = ActiveFlasher::<O> {
             core,
             flash_algorithm: self.flash_algorithm.clone(),
+            completion_poll_interval: self.completion_poll_interval,
+            completion_timeout: self.completion_timeout,
             _operation: core::marker::PhantomData,
         };
 
@@ -237,6 +270,10 @@ impl<'session> Flasher<'session> {
     /// If `restore_unwritten_bytes` is `true`, all bytes of a sector,
     /// that are not to be written during flashing will be read from the flash first
     /// and written again once the sector is erased.
+    ///
+    /// If `skip_matching_sectors` is `true`, sectors which already contain the data are neither
+    /// erased nor programmed, see [`Flasher::skip_matching_sectors`].
+    #[allow(clippy::too_many_arguments)]
     pub(super) fn program(
         &mut self,
         region: &NvmRegion,
@@ -244,8 +281,10 @@ impl<'session> Flasher<'session> {
         restore_unwritten_bytes: bool,
         enable_double_buffering: bool,
         skip_erasing: bool,
+        skip_matching_sectors: bool,
+        continue_on_error: bool,
         progress: &FlashProgress,
-    ) -> Result<(), FlashError> {
+    ) -> Result<Vec<SectorFailure>, FlashError> {
         tracing::debug!("Starting program procedure.");
         // Convert the list of flash operations into flash sectors and pages.
         let mut flash_layout = flash_builder.build_sectors_and_pages(
@@ -273,9 +312,9 @@ impl<'session> Flasher<'session> {
                 let result = self.fill_page(page, &fill);
 
                 // If we encounter an error, catch it, gracefully report the failure and return the error.
-                if result.is_err() {
+                if let Err(error) = result {
                     progress.failed_filling();
-                    return result;
+                    return Err(error);
                 } else {
                     progress.page_filled(fill.size(), t.elapsed());
                 }
@@ -285,6 +324,14 @@ impl<'session> Flasher<'session> {
         // We successfully finished filling.
         progress.finished_filling();
 
+        if skip_matching_sectors {
+            self.skip_matching_sectors(&mut flash_layout, progress)?;
+        }
+
+        if continue_on_error {
+            return self.program_continue_on_error(&flash_layout, skip_erasing, progress);
+        }
+
         // Skip erase if necessary
         if !skip_erasing {
             // Erase all necessary sectors
@@ -298,9 +345,153 @@ impl<'session> Flasher<'session> {
             self.program_simple(&flash_layout, progress)?;
         };
 
+        Ok(Vec::new())
+    }
+
+    /// Removes all sectors from `flash_layout` whose contents already match the contents after
+    /// flashing, so they are neither erased nor programmed.
+    ///
+    /// A sector is only skipped if all of its bytes match, including the bytes which are not
+    /// written and would be erased otherwise. Every skipped sector is reported with
+    /// [`ProgressEvent::SectorSkipped`](super::ProgressEvent::SectorSkipped).
+    fn skip_matching_sectors(
+        &mut self,
+        flash_layout: &mut FlashLayout,
+        progress: &FlashProgress,
+    ) -> Result<(), FlashError> {
+        let erased_byte_value = self.flash_algorithm.flash_properties.erased_byte_value;
+
+        let matching = self.run_verify(|active| {
+            let mut matching = Vec::new();
+
+            for sector in flash_layout.sectors() {
+                let expected = flash_layout.sector_contents(sector, erased_byte_value);
+                let mut contents = vec![0; expected.len()];
+                active.read_flash(sector.address(), &mut contents)?;
+
+                if contents == expected {
+                    matching.push(sector.clone());
+                }
+            }
+
+            Ok(matching)
+        })?;
+
+        for sector in matching {
+            tracing::debug!(
+                "Skipping sector at {:#010x}, its contents already match",
+                sector.address()
+            );
+
+            let page_size = flash_layout.remove_sector(&sector);
+            progress.sector_skipped(sector.address(), sector.size(), page_size);
+        }
+
         Ok(())
     }
 
+    /// Erases and programs all sectors given in `flash_layout`, like [`Flasher::program`].
+    ///
+    /// Instead of aborting at the first failing sector, the failure is recorded and the remaining
+    /// sectors are still flashed. Pages in sectors which failed to erase are not programmed.
+    fn program_continue_on_error(
+        &mut self,
+        flash_layout: &FlashLayout,
+        skip_erasing: bool,
+        progress: &FlashProgress,
+    ) -> Result<Vec<SectorFailure>, FlashError> {
+        let mut failures = Vec::new();
+
+        if !skip_erasing {
+            progress.started_erasing();
+
+            let mut t = std::time::Instant::now();
+            let result = self.run_erase(|active| {
+                for sector in flash_layout.sectors() {
+                    if let Err(e) = active.erase_sector(sector.address()) {
+                        tracing::warn!(
+                            "Failed to erase sector at {:#010x}, continuing: {}",
+                            sector.address(),
+                            e
+                        );
+                        failures.push(SectorFailure {
+                            sector_address: sector.address(),
+                            sector_size: sector.size(),
+                            error: FlashError::EraseFailed {
+                                sector_address: sector.address(),
+                                source: Box::new(e),
+                            },
+                        });
+                    }
+
+                    progress.sector_erased(sector.size(), t.elapsed());
+                    t = std::time::Instant::now();
+                }
+                Ok(())
+            });
+
+            if result.is_ok() && failures.is_empty() {
+                progress.finished_erasing();
+            } else {
+                progress.failed_erasing();
+            }
+
+            result?;
+        }
+
+        progress.started_programming();
+
+        let mut t = std::time::Instant::now();
+        let result = self.run_program(|active| {
+            for page in flash_layout.pages() {
+                let sector = flash_layout.sectors().iter().find(|sector| {
+                    (sector.address()..sector.address() + sector.size()).contains(&page.address())
+                });
+
+                let already_failed = sector.map_or(false, |sector| {
+                    failures
+                        .iter()
+                        .any(|failure| failure.sector_address == sector.address())
+                });
+
+                if !already_failed {
+                    if let Err(error) = active.program_page(page.address(), page.data()) {
+                        tracing::warn!(
+                            "Failed to program page at {:#010x}, continuing: {}",
+                            page.address(),
+                            error
+                        );
+                        let (sector_address, sector_size) = sector
+                            .map(|sector| (sector.address(), sector.size()))
+                            .unwrap_or((page.address(), page.size() as u64));
+                        failures.push(SectorFailure {
+                            sector_address,
+                            sector_size,
+                            error: FlashError::PageWrite {
+                                page_address: page.address(),
+                                source: Box::new(error),
+                            },
+                        });
+                    }
+                }
+
+                progress.page_programmed(page.size(), t.elapsed());
+                t = std::time::Instant::now();
+            }
+            Ok(())
+        });
+
+        if result.is_ok() && failures.is_empty() {
+            progress.finished_programming();
+        } else {
+            progress.failed_programming();
+        }
+
+        result?;
+
+        Ok(failures)
+    }
+
     /// Fills all the bytes of `current_page`.
     ///
     /// If `restore_unwritten_bytes` is `true`, all bytes of the page,
@@ -313,12 +504,15 @@ impl<'session> Flasher<'session> {
     ) -> Result<(), FlashError> {
         let page_offset = (fill.address() - page.address()) as usize;
         let page_slice = &mut page.data_mut()[page_offset..page_offset + fill.size() as usize];
-        self.run_verify(|active| {
-            active
-                .core
-                .read(fill.address(), page_slice)
-                .map_err(FlashError::Core)
-        })
+        self.read_flash(fill.address(), page_slice)
+    }
+
+    /// Reads `data.len()` bytes of flash starting at `address`, while the flash algorithm stays
+    /// loaded in RAM.
+    ///
+    /// See [`ActiveFlasher::read_flash`] for details.
+    pub(super) fn read_flash(&mut self, address: u64, data: &mut [u8]) -> Result<(), FlashError> {
+        self.run_verify(|active| active.read_flash(address, data))
     }
 
     /// Programs the pages given in `flash_layout` into the flash.
@@ -401,6 +595,7 @@ impl<'session> Flasher<'session> {
         progress: &FlashProgress,
     ) -> Result<(), FlashError> {
         let mut current_buf = 0;
+        let buffer_count = self.flash_algorithm.page_buffers.len();
 
         progress.started_programming();
 
@@ -434,12 +629,8 @@ impl<'session> Flasher<'session> {
                 // Start the next copy process.
                 active.start_program_page_with_buffer(page.address(), current_buf)?;
 
-                // Swap the buffers
-                if current_buf == 1 {
-                    current_buf = 0;
-                } else {
-                    current_buf = 1;
-                }
+                // Move on to the next buffer
+                current_buf = (current_buf + 1) % buffer_count;
             }
 
             let result = active
@@ -505,6 +696,8 @@ impl From<FlashError> for crate::Error {
 pub(super) struct ActiveFlasher<'probe, O: Operation> {
     core: Core<'probe>,
     flash_algorithm: FlashAlgorithm,
+    completion_poll_interval: Duration,
+    completion_timeout: Option<Duration>,
     _operation: core::marker::PhantomData<O>,
 }
 
@@ -542,6 +735,27 @@ impl<'probe, O: Operation> ActiveFlasher<'probe, O> {
         Ok(())
     }
 
+    /// Reads `data.len()` bytes of flash starting at `address` via direct memory access.
+    ///
+    /// This does not call into the flash algorithm and does not touch the RAM occupied by it (see
+    /// [`FlashAlgorithm::ram_range`]), so reads can be interleaved with programming, e.g. to
+    /// verify already programmed pages. It must not be called while a flash algorithm routine is
+    /// still running. Reads overlapping the RAM of the flash algorithm return an error.
+    pub(super) fn read_flash(&mut self, address: u64, data: &mut [u8]) -> Result<(), FlashError> {
+        let ram_range = self.flash_algorithm.ram_range();
+        let read_range = address..address + data.len() as u64;
+
+        // Reading the algorithm RAM does not corrupt it, but it is never flash contents either.
+        if read_range.end > ram_range.start && read_range.start < ram_range.end {
+            return Err(FlashError::ReadOverlapsFlashAlgorithm {
+                range: read_range,
+                ram_range,
+            });
+        }
+
+        self.core.read(address, data).map_err(FlashError::Core)
+    }
+
     // pub(super) fn session_mut(&mut self) -> &mut Session {
     //     &mut self.session
     // }
@@ -654,8 +868,15 @@ impl<'probe, O: Operation> ActiveFlasher<'probe, O> {
     pub(super) fn wait_for_completion(&mut self, timeout: Duration) -> Result<u32, crate::Error> {
         tracing::debug!("Waiting for routine call completion.");
         let regs = self.core.registers();
+        let timeout = self.completion_timeout.unwrap_or(timeout);
 
-        self.core.wait_for_core_halted(timeout)?;
+        let start = Instant::now();
+        while !self.core.core_halted()? {
+            if start.elapsed() >= timeout {
+                return Err(crate::Error::Probe(crate::DebugProbeError::Timeout));
+            }
+            std::thread::sleep(self.completion_poll_interval);
+        }
 
         let r: u32 = self.core.read_core_reg(regs.result_register(0).id)?;
         Ok(r)
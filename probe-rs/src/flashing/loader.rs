@@ -1,19 +1,24 @@
 use ihex::Record;
 use probe_rs_target::{
-    MemoryRange, MemoryRegion, NvmRegion, RawFlashAlgorithm, TargetDescriptionSource,
+    FlashProperties, MemoryRange, MemoryRegion, NvmRegion, RawFlashAlgorithm,
+    TargetDescriptionSource,
 };
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use std::io::{Read, Seek, SeekFrom};
 use std::ops::Range;
+use std::time::Duration;
 
 use super::builder::FlashBuilder;
+use super::crc::{crc32, target_crc32, target_crc_supported};
+use super::uf2::{family_id_of_target, parse_uf2};
 use super::{
-    extract_from_elf, BinOptions, DownloadOptions, FileDownloadError, FlashError, FlashProgress,
-    Flasher,
+    extract_from_elf, BinOptions, DownloadOptions, EraseStrategy, FileDownloadError,
+    FlashAlgorithm, FlashError, FlashFailureReport, FlashProgress, Flasher, Uf2Options,
+    VerifyMethod,
 };
 use crate::memory::MemoryInterface;
 use crate::session::Session;
-use crate::Target;
+use crate::{Architecture, Core, Target};
 
 /// `FlashLoader` is a struct which manages the flashing of any chunks of data onto any sections of flash.
 ///
@@ -28,6 +33,9 @@ pub struct FlashLoader {
     /// Source of the flash description,
     /// used for diagnostics.
     source: TargetDescriptionSource,
+
+    /// The UF2 family ID of the target, if it is known.
+    uf2_family_id: Option<u32>,
 }
 
 impl FlashLoader {
@@ -37,6 +45,21 @@ impl FlashLoader {
             memory_map,
             builder: FlashBuilder::new(),
             source,
+            uf2_family_id: None,
+        }
+    }
+
+    /// Create a new flash loader for the target with the given name.
+    ///
+    /// The name is used to only load the blocks of UF2 files which are meant for the target.
+    pub(crate) fn for_target(
+        memory_map: Vec<MemoryRegion>,
+        source: TargetDescriptionSource,
+        target_name: &str,
+    ) -> Self {
+        Self {
+            uf2_family_id: family_id_of_target(target_name),
+            ..Self::new(memory_map, source)
         }
     }
 
@@ -91,7 +114,27 @@ impl FlashLoader {
         None
     }
 
+    /// The start address of the NVM region the chip boots from, or of the first NVM region if no
+    /// region is marked as boot memory.
+    fn boot_memory_address(&self) -> u64 {
+        let mut nvm_regions = self.memory_map.iter().filter_map(|region| match region {
+            MemoryRegion::Nvm(region) => Some(region),
+            _ => None,
+        });
+
+        nvm_regions
+            .clone()
+            .find(|region| region.is_boot_memory)
+            .or_else(|| nvm_regions.next())
+            .map(|region| region.range.start)
+            .unwrap_or(0)
+    }
+
     /// Reads the data from the binary file and adds it to the loader without splitting it into flash instructions yet.
+    ///
+    /// The data is placed at [`BinOptions::base_address`], or at the start of the boot memory if
+    /// no base address is given. The data may span several adjacent memory regions, but every
+    /// byte has to be inside a region of the memory map.
     pub fn load_bin_data<T: Read + Seek>(
         &mut self,
         file: &mut T,
@@ -103,24 +146,24 @@ impl FlashLoader {
         let mut buf = Vec::new();
         file.read_to_end(&mut buf)?;
 
-        self.add_data(
-            if let Some(address) = options.base_address {
-                address
-            } else {
-                // If no base address is specified use the start of the boot memory.
-                // TODO: Implement this as soon as we know targets.
-                0
-            },
-            &buf,
-        )?;
+        // If no base address is specified use the start of the boot memory.
+        let address = options
+            .base_address
+            .unwrap_or_else(|| self.boot_memory_address());
+
+        self.add_data(address, &buf)?;
 
         Ok(())
     }
 
     /// Reads the HEX data segments and adds them as loadable data blocks to the loader.
     /// This does not create and flash loader instructions yet.
+    ///
+    /// The records are parsed by the `ihex` reader, which validates their checksums and stops at
+    /// the end of file record.
     pub fn load_hex_data<T: Read + Seek>(&mut self, file: &mut T) -> Result<(), FileDownloadError> {
         let mut base_address = 0;
+        let mut has_data = false;
 
         let mut data = String::new();
         file.read_to_string(&mut data)?;
@@ -132,6 +175,7 @@ impl FlashLoader {
                 Data { offset, value } => {
                     let offset = base_address + offset as u64;
                     self.add_data(offset, &value)?;
+                    has_data = true;
                 }
                 EndOfFile => (),
                 ExtendedSegmentAddress(address) => {
@@ -144,6 +188,64 @@ impl FlashLoader {
                 StartLinearAddress(_) => (),
             };
         }
+
+        if !has_data {
+            return Err(FileDownloadError::NoHexData);
+        }
+
+        Ok(())
+    }
+
+    /// Reads the blocks of a UF2 file and adds them as loadable data blocks to the loader.
+    /// This does not create and flash loader instructions yet.
+    ///
+    /// Blocks which are not meant for the main flash, or for another chip family than the one
+    /// of the target, are skipped. The family is the one selected in `options`, or the one of the
+    /// target the loader was created for.
+    pub fn load_uf2_data<T: Read>(
+        &mut self,
+        file: &mut T,
+        options: Uf2Options,
+    ) -> Result<(), FileDownloadError> {
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)?;
+
+        let blocks = parse_uf2(&data)?;
+
+        let selected = match (options.family_id, self.uf2_family_id) {
+            (Some(selected), Some(target)) if selected != target => {
+                return Err(FileDownloadError::Uf2FamilyMismatch { selected, target });
+            }
+            (selected, target) => selected.or(target),
+        };
+
+        if selected.is_none() {
+            let families: BTreeSet<u32> = blocks.iter().filter_map(|b| b.family_id).collect();
+
+            if families.len() > 1 {
+                return Err(FileDownloadError::Uf2FamilyRequired(
+                    families.into_iter().collect(),
+                ));
+            }
+        }
+
+        let mut has_data = false;
+
+        for block in blocks {
+            if let (Some(selected), Some(family_id)) = (selected, block.family_id) {
+                if selected != family_id {
+                    continue;
+                }
+            }
+
+            self.add_data(block.address as u64, block.data)?;
+            has_data = true;
+        }
+
+        if !has_data {
+            return Err(FileDownloadError::NoUf2Blocks);
+        }
+
         Ok(())
     }
 
@@ -189,11 +291,38 @@ impl FlashLoader {
         Ok(())
     }
 
+    /// Returns `true` if the sectors containing data in `regions` make up more than half of the
+    /// flash of `flash_algorithm`.
+    fn erases_most_of_flash(
+        &self,
+        regions: &[NvmRegion],
+        flash_algorithm: &FlashAlgorithm,
+    ) -> Result<bool, FlashError> {
+        let mut erased_size = 0;
+        for region in regions {
+            let layout = self
+                .builder
+                .build_sectors_and_pages(region, flash_algorithm, false)?;
+            erased_size += layout.sectors().iter().map(|s| s.size()).sum::<u64>();
+        }
+
+        let range = &flash_algorithm.flash_properties.address_range;
+        let flash_size = range.end - range.start;
+
+        tracing::debug!(
+            "{} of {} bytes of flash have to be erased",
+            erased_size,
+            flash_size
+        );
+
+        Ok(erased_size * 2 > flash_size)
+    }
+
     /// Writes all the stored data chunks to flash.
     ///
     /// Requires a session with an attached target that has a known flash algorithm.
     ///
-    /// If `do_chip_erase` is `true` the entire flash will be erased.
+    /// The flash is erased according to [`DownloadOptions::erase_strategy`].
     pub fn commit(
         &self,
         session: &mut Session,
@@ -230,6 +359,25 @@ impl FlashLoader {
             tracing::warn!("Memory map of flash loader does not match memory map of target!");
         }
 
+        if options.verify_only {
+            // RAM is not written, so only the contents of the flash can be verified.
+            return self.verify(session, options.progress, false, options.verify_method);
+        }
+
+        if options.ram_only {
+            if let Some(region) = self.memory_map.iter().find_map(|region| match region {
+                MemoryRegion::Nvm(region) if self.builder.has_data_in_range(&region.range) => {
+                    Some(region)
+                }
+                _ => None,
+            }) {
+                return Err(FlashError::DataInNvm {
+                    region: region.clone(),
+                    requested: self.data_range_in(&region.range),
+                });
+            }
+        }
+
         let mut algos: HashMap<(String, String), Vec<NvmRegion>> = HashMap::new();
 
         // Commit NVM first
@@ -258,7 +406,20 @@ impl FlashLoader {
                     continue;
                 }
 
-                let algo = Self::get_flash_algorithm_for_region(region, session.target())?;
+                let algo = Self::get_flash_algorithm_for_region(region, session.target()).map_err(
+                    |error| match error {
+                        FlashError::NoFlashLoaderAlgorithmAttached { name }
+                            if !session.target().flash_algorithms.is_empty() =>
+                        {
+                            FlashError::NoFlashAlgorithmForRegion {
+                                name,
+                                region: region.clone(),
+                                requested: self.data_range_in(&region.range),
+                            }
+                        }
+                        error => error,
+                    },
+                )?;
 
                 let entry = algos
                     .entry((
@@ -288,6 +449,10 @@ impl FlashLoader {
             return Ok(());
         }
 
+        // The failed sectors of all flash algorithms, so that all regions are flashed
+        // before the failures are reported.
+        let mut failures = FlashFailureReport::default();
+
         // Iterate all flash algorithms we need to use.
         for ((algo_name, core_name), regions) in algos {
             tracing::debug!("Flashing ranges for algo: {}", algo_name);
@@ -302,9 +467,34 @@ impl FlashLoader {
                 .iter()
                 .position(|c| c.name == core_name)
                 .unwrap();
-            let mut flasher = Flasher::new(session, core, &algo)?;
+            let mut flasher = Flasher::new(
+                session,
+                core,
+                &algo,
+                options.flash_algorithm_stack_size,
+                options.page_buffer_count,
+            )?;
+            flasher.set_completion_polling(
+                options.completion_poll_interval,
+                options.completion_timeout,
+            );
 
-            let mut do_chip_erase = options.do_chip_erase;
+            // The deprecated flag still selects a chip erase for existing users.
+            #[allow(deprecated)]
+            let erase_strategy = if options.do_chip_erase {
+                EraseStrategy::ChipErase
+            } else {
+                options.erase_strategy
+            };
+
+            let mut do_chip_erase = match erase_strategy {
+                EraseStrategy::SectorBySector => false,
+                EraseStrategy::ChipErase => true,
+                EraseStrategy::Auto => {
+                    !options.keep_unwritten_bytes
+                        && self.erases_most_of_flash(&regions, flasher.flash_algorithm())?
+                }
+            };
 
             // If the flash algo doesn't support erase all, disable chip erase.
             if do_chip_erase && !flasher.is_chip_erase_supported() {
@@ -313,6 +503,14 @@ impl FlashLoader {
                 tracing::warn!("A manual sector erase will be performed.");
             }
 
+            if let Some(progress) = options.progress {
+                progress.selected_erase_strategy(if do_chip_erase {
+                    EraseStrategy::ChipErase
+                } else {
+                    EraseStrategy::SectorBySector
+                });
+            }
+
             if do_chip_erase {
                 tracing::debug!("    Doing chip erase...");
                 flasher.run_erase_all()?;
@@ -327,6 +525,10 @@ impl FlashLoader {
                 tracing::info!("Disabled double-buffering support for loader via passed option, though target supports it.");
                 do_use_double_buffering = false;
             }
+            if do_use_double_buffering && options.continue_on_error {
+                tracing::info!("Disabled double-buffering support for loader to continue flashing after errors.");
+                do_use_double_buffering = false;
+            }
 
             for region in regions {
                 tracing::debug!(
@@ -337,20 +539,87 @@ impl FlashLoader {
                 );
 
                 // Program the data.
-                flasher.program(
+                let region_failures = flasher.program(
                     &region,
                     &self.builder,
                     options.keep_unwritten_bytes,
                     do_use_double_buffering,
                     options.skip_erase || do_chip_erase,
+                    options.skip_erase_on_match && !do_chip_erase,
+                    options.continue_on_error,
                     options.progress.unwrap_or(&FlashProgress::new(|_| {})),
                 )?;
+                failures.extend(region_failures);
             }
         }
 
+        // Commit RAM last, because NVM flashing overwrites RAM
+        self.commit_ram(session, options.progress)?;
+
+        // The device is inconsistently programmed, so it is neither started nor verified.
+        if !failures.is_empty() {
+            tracing::error!(
+                "Flashing failed for {} sector(s), the device is inconsistently programmed.",
+                failures.failures().len()
+            );
+            return Err(FlashError::SectorsFailed(failures));
+        }
+
+        if let Some(address) = options.vector_table_address {
+            Self::start_from_vector_table(session, address)?;
+        }
+
+        if options.verify {
+            self.verify(session, options.progress, true, options.verify_method)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the stored data chunks in RAM with plain memory writes.
+    fn commit_ram(
+        &self,
+        session: &mut Session,
+        progress: Option<&FlashProgress>,
+    ) -> Result<(), FlashError> {
         tracing::debug!("committing RAM!");
 
-        // Commit RAM last, because NVM flashing overwrites RAM
+        let ram_data_size: u64 = self
+            .memory_map
+            .iter()
+            .filter_map(|region| match region {
+                MemoryRegion::Ram(region) => Some(
+                    self.builder
+                        .data_in_range(&region.range)
+                        .map(|(_, data)| data.len() as u64)
+                        .sum::<u64>(),
+                ),
+                _ => None,
+            })
+            .sum();
+        let progress = progress.filter(|_| ram_data_size > 0);
+
+        if let Some(progress) = progress {
+            progress.started_writing_ram(ram_data_size);
+        }
+
+        let result = self.write_ram(session, progress);
+
+        if let Some(progress) = progress {
+            match result {
+                Ok(()) => progress.finished_writing_ram(),
+                Err(_) => progress.failed_writing_ram(),
+            }
+        }
+
+        result
+    }
+
+    fn write_ram(
+        &self,
+        session: &mut Session,
+        progress: Option<&FlashProgress>,
+    ) -> Result<(), FlashError> {
         for region in &self.memory_map {
             if let MemoryRegion::Ram(region) = region {
                 tracing::debug!(
@@ -381,8 +650,14 @@ impl FlashLoader {
                         address + data.len() as u64,
                         data.len()
                     );
+
+                    let t = std::time::Instant::now();
                     // Write data to memory.
                     core.write_8(address, data).map_err(FlashError::Core)?;
+
+                    if let Some(progress) = progress {
+                        progress.ram_written(data.len() as u64, t.elapsed());
+                    }
                 }
 
                 if !some {
@@ -391,40 +666,189 @@ impl FlashLoader {
             }
         }
 
-        if options.verify {
-            tracing::debug!("Verifying!");
-            for (&address, data) in &self.builder.data {
-                tracing::debug!(
-                    "    data: {:08x}-{:08x} ({} bytes)",
-                    address,
-                    address + data.len() as u64,
-                    data.len()
-                );
+        Ok(())
+    }
 
-                let associated_region = session
-                    .target()
-                    .get_memory_region_by_address(address)
-                    .unwrap();
-                let core_name = match associated_region {
-                    MemoryRegion::Ram(r) => &r.cores,
-                    MemoryRegion::Generic(r) => &r.cores,
-                    MemoryRegion::Nvm(r) => &r.cores,
+    /// Halts the core which can access the vector table at `address`, and sets its stack pointer
+    /// and program counter to the initial values from the vector table.
+    fn start_from_vector_table(session: &mut Session, address: u64) -> Result<(), FlashError> {
+        let target = session.target();
+        let core_index = target
+            .get_memory_region_by_address(address)
+            .and_then(|region| match region {
+                MemoryRegion::Ram(region) => region.cores.first(),
+                MemoryRegion::Generic(region) => region.cores.first(),
+                MemoryRegion::Nvm(region) => region.cores.first(),
+            })
+            .and_then(|name| target.core_index_by_name(name))
+            .unwrap_or(0);
+
+        let mut core = session.core(core_index).map_err(FlashError::Core)?;
+
+        if core.architecture() != Architecture::Arm {
+            return Err(FlashError::Core(crate::Error::ArchitectureRequired(&[
+                "ARMv6-M", "ARMv7-M", "ARMv8-M",
+            ])));
+        }
+
+        let mut vector_table = [0; 2];
+        core.read_32(address, &mut vector_table)
+            .map_err(FlashError::Core)?;
+        let [stack_pointer, reset_vector] = vector_table;
+
+        tracing::debug!(
+            "Starting from vector table at {:#010x}: SP = {:#010x}, PC = {:#010x}",
+            address,
+            stack_pointer,
+            reset_vector
+        );
+
+        core.halt(Duration::from_millis(100))
+            .map_err(FlashError::Core)?;
+
+        let regs = core.registers();
+        core.write_core_reg(regs.stack_pointer().id, stack_pointer)
+            .map_err(FlashError::Core)?;
+        // The lowest bit of the reset vector marks Thumb code, it is not part of the address.
+        core.write_core_reg(regs.program_counter().id, reset_vector & !1)
+            .map_err(FlashError::Core)?;
+
+        Ok(())
+    }
+
+    /// Verifies the stored data chunks with `method`, sector by sector.
+    ///
+    /// If `include_ram` is `false`, only the chunks in flash are verified.
+    fn verify(
+        &self,
+        session: &mut Session,
+        progress: Option<&FlashProgress>,
+        include_ram: bool,
+        method: VerifyMethod,
+    ) -> Result<(), FlashError> {
+        tracing::debug!("Verifying!");
+
+        if let Some(progress) = progress {
+            let target = session.target();
+            let size = self
+                .builder
+                .data
+                .iter()
+                .filter(|(&address, _)| {
+                    include_ram
+                        || matches!(
+                            target.get_memory_region_by_address(address),
+                            Some(MemoryRegion::Nvm(_))
+                        )
+                })
+                .map(|(_, data)| data.len() as u64)
+                .sum();
+
+            progress.started_verifying(size);
+        }
+
+        for (&address, data) in &self.builder.data {
+            tracing::debug!(
+                "    data: {:08x}-{:08x} ({} bytes)",
+                address,
+                address + data.len() as u64,
+                data.len()
+            );
+
+            let range = address..address + data.len() as u64;
+
+            let target = session.target();
+            let associated_region = target.get_memory_region_by_address(address).unwrap();
+            let (cores, ranges) = match associated_region {
+                MemoryRegion::Ram(_) | MemoryRegion::Generic(_) if !include_ram => continue,
+                MemoryRegion::Ram(r) => (&r.cores, vec![range]),
+                MemoryRegion::Generic(r) => (&r.cores, vec![range]),
+                MemoryRegion::Nvm(r) => {
+                    let ranges = match Self::get_flash_algorithm_for_region(r, target) {
+                        Ok(algo) => sector_ranges(&algo.flash_properties, range),
+                        Err(_) => vec![range],
+                    };
+                    (&r.cores, ranges)
                 }
-                .first()
-                .unwrap();
-                let core_index = session.target().core_index_by_name(core_name).unwrap();
-                let mut core = session.core(core_index).map_err(FlashError::Core)?;
+            };
+            let core_name = cores.first().unwrap();
+            let core_index = target.core_index_by_name(core_name).unwrap();
+
+            // The CRC routine is placed at the start of the RAM of the core, whose contents are
+            // restored afterwards.
+            let routine_address = match (method, associated_region) {
+                (VerifyMethod::Crc, MemoryRegion::Nvm(_)) => {
+                    target.memory_map.iter().find_map(|region| match region {
+                        MemoryRegion::Ram(ram) if ram.cores.contains(core_name) => {
+                            Some(ram.range.start)
+                        }
+                        _ => None,
+                    })
+                }
+                _ => None,
+            };
+
+            let mut core = session.core(core_index).map_err(FlashError::Core)?;
 
-                let mut written_data = vec![0; data.len()];
-                core.read(address, &mut written_data)
-                    .map_err(FlashError::Core)?;
+            let routine_address = match routine_address {
+                Some(routine_address)
+                    if target_crc_supported(&mut core).map_err(FlashError::Core)? =>
+                {
+                    Some(routine_address)
+                }
+                Some(_) => {
+                    tracing::debug!("The core can't calculate the CRC, reading back instead.");
+                    None
+                }
+                None => None,
+            };
+
+            for range in ranges {
+                let offset = (range.start - address) as usize;
+                let expected = &data[offset..offset + (range.end - range.start) as usize];
+
+                let mismatch = match routine_address {
+                    Some(routine_address) => {
+                        let crc = target_crc32(&mut core, routine_address, range.clone())
+                            .map_err(FlashError::Core)?;
+
+                        if crc == crc32(expected) {
+                            None
+                        } else {
+                            // Read back the sector to find the mismatching address.
+                            Some(
+                                first_mismatch(&mut core, range.start, expected)?
+                                    .unwrap_or(range.start),
+                            )
+                        }
+                    }
+                    None => first_mismatch(&mut core, range.start, expected)?,
+                };
+
+                if let Some(progress) = progress {
+                    progress.sector_verified(
+                        range.start,
+                        range.end - range.start,
+                        mismatch.is_none(),
+                    );
+                }
+
+                if let Some(mismatch) = mismatch {
+                    tracing::error!("Flash contents don't match at {:#010x}", mismatch);
+
+                    if let Some(progress) = progress {
+                        progress.failed_verifying(mismatch);
+                    }
 
-                if data != &written_data {
                     return Err(FlashError::Verify);
                 }
             }
         }
 
+        if let Some(progress) = progress {
+            progress.finished_verifying();
+        }
+
         Ok(())
     }
 
@@ -473,6 +897,23 @@ impl FlashLoader {
         }
     }
 
+    /// The address range spanned by the data stored for the given range.
+    fn data_range_in(&self, range: &Range<u64>) -> Range<u64> {
+        let mut chunks = self.builder.data_in_range(range);
+
+        match chunks.next() {
+            Some((start, data)) => {
+                let end = chunks
+                    .last()
+                    .map_or(start + data.len() as u64, |(address, data)| {
+                        address + data.len() as u64
+                    });
+                start..end
+            }
+            None => range.start..range.start,
+        }
+    }
+
     /// Return data chunks stored in the `FlashLoader` as pairs of address and bytes.
     pub fn data(&self) -> impl Iterator<Item = (u64, &[u8])> {
         self.builder
@@ -481,3 +922,45 @@ impl FlashLoader {
             .map(|(address, data)| (*address, data.as_slice()))
     }
 }
+
+/// Splits `range` at the boundaries of the sectors of the flash described by `properties`.
+fn sector_ranges(properties: &FlashProperties, range: Range<u64>) -> Vec<Range<u64>> {
+    let mut ranges = Vec::new();
+    let mut address = range.start;
+
+    while address < range.end {
+        let sector_end = properties
+            .address_range
+            .contains(&address)
+            .then(|| address - properties.address_range.start)
+            .and_then(|offset| {
+                let sector = properties.sectors.iter().rfind(|s| s.address <= offset)?;
+                let index = (offset - sector.address) / sector.size;
+                Some(properties.address_range.start + sector.address + (index + 1) * sector.size)
+            });
+
+        let end = sector_end.map_or(range.end, |end| end.min(range.end));
+        ranges.push(address..end);
+        address = end;
+    }
+
+    ranges
+}
+
+/// Reads back `expected.len()` bytes at `address`, and returns the first address at which they
+/// don't match `expected`.
+fn first_mismatch(
+    core: &mut Core,
+    address: u64,
+    expected: &[u8],
+) -> Result<Option<u64>, FlashError> {
+    let mut written_data = vec![0; expected.len()];
+    core.read(address, &mut written_data)
+        .map_err(FlashError::Core)?;
+
+    Ok(expected
+        .iter()
+        .zip(&written_data)
+        .position(|(expected, written)| expected != written)
+        .map(|offset| address + offset as u64))
+}
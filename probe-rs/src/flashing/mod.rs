@@ -44,22 +44,27 @@
 //!
 
 mod builder;
+mod crc;
 mod download;
+mod dump;
 mod erase;
 mod error;
 mod flash_algorithm;
 mod flasher;
 mod loader;
 mod progress;
+mod uf2;
 mod visualizer;
 
 use builder::*;
 use flasher::*;
 
 pub use download::*;
+pub use dump::*;
 pub use erase::*;
 pub use error::*;
 pub use flash_algorithm::*;
 pub use loader::*;
 pub use progress::*;
+pub use uf2::Uf2Error;
 pub use visualizer::*;
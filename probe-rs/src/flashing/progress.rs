@@ -1,5 +1,6 @@
-use super::FlashLayout;
-use std::time::Duration;
+use super::{EraseStrategy, FlashLayout};
+use std::cell::RefCell;
+use std::time::{Duration, Instant};
 
 /// A structure to manage the flashing procedure progress reporting.
 ///
@@ -26,6 +27,32 @@ impl FlashProgress {
         }
     }
 
+    /// Create a new `FlashProgress` structure with a given `handler` to be called on events.
+    ///
+    /// In addition to the event, the `handler` receives the progress of the current phase of the
+    /// flashing procedure, which can be used to show the throughput and the estimated remaining
+    /// time. The progress is `None` for events which don't belong to a phase.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use probe_rs::flashing::FlashProgress;
+    ///
+    /// let progress = FlashProgress::with_eta(|_event, progress| {
+    ///     if let Some(progress) = progress {
+    ///         println!("{:?}: {:?} remaining", progress.phase, progress.remaining_time());
+    ///     }
+    /// });
+    /// ```
+    pub fn with_eta(handler: impl Fn(ProgressEvent, Option<PhaseProgress>) + 'static) -> Self {
+        let tracker = RefCell::new(EtaTracker::default());
+
+        Self::new(move |event| {
+            let progress = tracker.borrow_mut().update(&event);
+            handler(event, progress);
+        })
+    }
+
     /// Emit a flashing progress event.
     fn emit(&self, event: ProgressEvent) {
         (self.handler)(event);
@@ -36,6 +63,11 @@ impl FlashProgress {
         self.emit(ProgressEvent::Initialized { flash_layout });
     }
 
+    /// Signalize which strategy is used to erase the flash.
+    pub(super) fn selected_erase_strategy(&self, strategy: EraseStrategy) {
+        self.emit(ProgressEvent::SelectedEraseStrategy { strategy });
+    }
+
     /// Signalize that the erasing procedure started.
     pub(super) fn started_erasing(&self) {
         self.emit(ProgressEvent::StartedErasing);
@@ -61,6 +93,15 @@ impl FlashProgress {
         self.emit(ProgressEvent::SectorErased { size, time });
     }
 
+    /// Signalize that a sector was skipped, because it already contains the data.
+    pub(super) fn sector_skipped(&self, address: u64, size: u64, page_size: u64) {
+        self.emit(ProgressEvent::SectorSkipped {
+            address,
+            size,
+            page_size,
+        });
+    }
+
     /// Signalize that the page filling procedure has made progress.
     pub(super) fn page_filled(&self, size: u64, time: Duration) {
         self.emit(ProgressEvent::PageFilled { size, time });
@@ -95,6 +136,46 @@ impl FlashProgress {
     pub(super) fn finished_filling(&self) {
         self.emit(ProgressEvent::FinishedFilling);
     }
+
+    /// Signalize that writing `size` bytes of data in RAM started.
+    pub(super) fn started_writing_ram(&self, size: u64) {
+        self.emit(ProgressEvent::StartedWritingRam { size });
+    }
+
+    /// Signalize that writing the data in RAM has made progress.
+    pub(super) fn ram_written(&self, size: u64, time: Duration) {
+        self.emit(ProgressEvent::RamWritten { size, time });
+    }
+
+    /// Signalize that writing the data in RAM failed.
+    pub(super) fn failed_writing_ram(&self) {
+        self.emit(ProgressEvent::FailedWritingRam);
+    }
+
+    /// Signalize that writing the data in RAM completed successfully.
+    pub(super) fn finished_writing_ram(&self) {
+        self.emit(ProgressEvent::FinishedWritingRam);
+    }
+
+    pub(super) fn started_verifying(&self, size: u64) {
+        self.emit(ProgressEvent::StartedVerifying { size });
+    }
+
+    pub(super) fn sector_verified(&self, address: u64, size: u64, matched: bool) {
+        self.emit(ProgressEvent::SectorVerified {
+            address,
+            size,
+            matched,
+        });
+    }
+
+    pub(super) fn failed_verifying(&self, address: u64) {
+        self.emit(ProgressEvent::FailedVerifying { address });
+    }
+
+    pub(super) fn finished_verifying(&self) {
+        self.emit(ProgressEvent::FinishedVerifying);
+    }
 }
 
 /// Possible events during the flashing process.
@@ -106,12 +187,20 @@ impl FlashProgress {
 /// * `StartedFilling`
 /// * `PageFilled` for every page
 /// * `FinishedFilling`
+/// * `SectorSkipped` for every sector which already contains the data, if
+///   [`DownloadOptions::skip_erase_on_match`](super::DownloadOptions::skip_erase_on_match) is set
+/// * `SelectedEraseStrategy`
 /// * `StartedErasing`
 /// * `SectorErased` for every sector
 /// * `FinishedErasing`
 /// * `StartedProgramming`
 /// * `PageProgrammed` for every page
 /// * `FinishedProgramming`
+/// * `StartedWritingRam`, if there is data in RAM
+/// * `RamWritten` for every chunk of data in RAM
+/// * `FinishedWritingRam`
+///
+/// If all data is in RAM, only the events for writing RAM are emitted.
 ///
 /// If an erorr occurs in any stage, one of the `Failed*` event will be returned,
 /// and no further events will be returned.
@@ -138,6 +227,12 @@ pub enum ProgressEvent {
     FailedFilling,
     /// Filling of the pages has finished successfully.
     FinishedFilling,
+    /// The strategy to erase the flash has been selected.
+    SelectedEraseStrategy {
+        /// The selected strategy, which is either [`EraseStrategy::SectorBySector`] or
+        /// [`EraseStrategy::ChipErase`].
+        strategy: EraseStrategy,
+    },
     /// Erasing of flash has started.
     StartedErasing,
     /// A sector has been erased successfully.
@@ -147,6 +242,15 @@ pub enum ProgressEvent {
         /// The time it took to erase this sector.
         time: Duration,
     },
+    /// A sector was neither erased nor programmed, because it already contains the data.
+    SectorSkipped {
+        /// The address of the sector.
+        address: u64,
+        /// The size of the sector in bytes.
+        size: u64,
+        /// The size of the pages in the sector, which were not programmed, in bytes.
+        page_size: u64,
+    },
     /// Erasing of the flash has failed.
     FailedErasing,
     /// Erasing of the flash has finished successfully.
@@ -164,4 +268,283 @@ pub enum ProgressEvent {
     FailedProgramming,
     /// Programming of the flash has finished successfully.
     FinishedProgramming,
+    /// Writing the data in RAM has started.
+    StartedWritingRam {
+        /// The total size of the data in RAM in bytes.
+        size: u64,
+    },
+    /// A chunk of data has been written to RAM.
+    RamWritten {
+        /// The size of the chunk in bytes.
+        size: u64,
+        /// The time it took to write the chunk.
+        time: Duration,
+    },
+    /// Writing the data in RAM has failed.
+    FailedWritingRam,
+    /// Writing the data in RAM has finished successfully.
+    FinishedWritingRam,
+    /// Verifying of the flash contents has started.
+    StartedVerifying {
+        /// The total size of the data to verify in bytes.
+        size: u64,
+    },
+    /// The data in a flash sector, or in a range of RAM, has been verified.
+    SectorVerified {
+        /// The address of the verified data.
+        address: u64,
+        /// The size of the verified data in bytes.
+        size: u64,
+        /// The contents match the data.
+        matched: bool,
+    },
+    /// The flash contents don't match the data.
+    FailedVerifying {
+        /// The first address at which the contents don't match.
+        address: u64,
+    },
+    /// Verifying of the flash contents has finished successfully.
+    FinishedVerifying,
+}
+
+/// A phase of the flashing procedure, see [`PhaseProgress`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlashPhase {
+    /// Reading the unwritten bytes of the flash, which are restored afterwards.
+    Filling,
+    /// Erasing the flash sectors.
+    Erasing,
+    /// Programming the flash pages.
+    Programming,
+    /// Writing the data in RAM.
+    WritingRam,
+    /// Verifying the written data.
+    Verifying,
+}
+
+/// The progress of a phase of the flashing procedure, see [`FlashProgress::with_eta`].
+///
+/// The progress includes all flash regions, so a phase which is run once per region only
+/// completes with the last region.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PhaseProgress {
+    /// The phase.
+    pub phase: FlashPhase,
+    /// The number of bytes processed in this phase, as far as known yet.
+    pub total_bytes: u64,
+    /// The number of bytes which are already processed.
+    pub done_bytes: u64,
+    /// The time spent in this phase.
+    pub elapsed: Duration,
+}
+
+impl PhaseProgress {
+    /// The average number of bytes processed per second, or `None` if no time elapsed yet.
+    pub fn throughput(&self) -> Option<f64> {
+        let elapsed = self.elapsed.as_secs_f64();
+
+        if elapsed > 0.0 {
+            Some(self.done_bytes as f64 / elapsed)
+        } else {
+            None
+        }
+    }
+
+    /// The estimated time until the phase is completed, based on the average throughput, or
+    /// `None` if nothing was processed yet.
+    pub fn remaining_time(&self) -> Option<Duration> {
+        if self.done_bytes == 0 {
+            return None;
+        }
+
+        let remaining = self.total_bytes.saturating_sub(self.done_bytes);
+        Some(
+            self.elapsed
+                .mul_f64(remaining as f64 / self.done_bytes as f64),
+        )
+    }
+}
+
+/// The state of a single phase in an [`EtaTracker`].
+#[derive(Debug, Default)]
+struct PhaseState {
+    total_bytes: u64,
+    done_bytes: u64,
+    /// The time spent in previous runs of the phase.
+    elapsed: Duration,
+    /// The start of the current run of the phase.
+    started: Option<Instant>,
+}
+
+impl PhaseState {
+    fn start(&mut self) {
+        self.started = Some(Instant::now());
+    }
+
+    fn stop(&mut self) {
+        if let Some(started) = self.started.take() {
+            self.elapsed += started.elapsed();
+        }
+    }
+
+    fn elapsed(&self) -> Duration {
+        self.elapsed
+            + self
+                .started
+                .map_or(Duration::ZERO, |started| started.elapsed())
+    }
+}
+
+/// Accumulates the progress events into the progress of the phases.
+#[derive(Debug, Default)]
+struct EtaTracker {
+    filling: PhaseState,
+    erasing: PhaseState,
+    programming: PhaseState,
+    writing_ram: PhaseState,
+    verifying: PhaseState,
+}
+
+impl EtaTracker {
+    /// Updates the phases with `event`, and returns the progress of the phase of the event.
+    fn update(&mut self, event: &ProgressEvent) -> Option<PhaseProgress> {
+        use ProgressEvent::*;
+
+        let phase = match event {
+            Initialized { flash_layout } => {
+                self.filling.total_bytes +=
+                    flash_layout.fills().iter().map(|f| f.size()).sum::<u64>();
+                self.erasing.total_bytes +=
+                    flash_layout.sectors().iter().map(|s| s.size()).sum::<u64>();
+                self.programming.total_bytes += flash_layout
+                    .pages()
+                    .iter()
+                    .map(|p| p.size() as u64)
+                    .sum::<u64>();
+                return None;
+            }
+            SelectedEraseStrategy { .. } => return None,
+            StartedFilling => {
+                self.filling.start();
+                FlashPhase::Filling
+            }
+            PageFilled { size, .. } => {
+                self.filling.done_bytes += size;
+                FlashPhase::Filling
+            }
+            FailedFilling | FinishedFilling => {
+                self.filling.stop();
+                FlashPhase::Filling
+            }
+            StartedErasing => {
+                self.erasing.start();
+                FlashPhase::Erasing
+            }
+            SectorErased { size, .. } => {
+                self.erasing.done_bytes += size;
+                FlashPhase::Erasing
+            }
+            SectorSkipped {
+                size, page_size, ..
+            } => {
+                self.erasing.done_bytes += size;
+                self.programming.done_bytes += page_size;
+                FlashPhase::Erasing
+            }
+            FailedErasing | FinishedErasing => {
+                self.erasing.stop();
+                FlashPhase::Erasing
+            }
+            StartedProgramming => {
+                self.programming.start();
+                FlashPhase::Programming
+            }
+            PageProgrammed { size, .. } => {
+                self.programming.done_bytes += *size as u64;
+                FlashPhase::Programming
+            }
+            FailedProgramming | FinishedProgramming => {
+                self.programming.stop();
+                FlashPhase::Programming
+            }
+            StartedWritingRam { size } => {
+                self.writing_ram.total_bytes += size;
+                self.writing_ram.start();
+                FlashPhase::WritingRam
+            }
+            RamWritten { size, .. } => {
+                self.writing_ram.done_bytes += size;
+                FlashPhase::WritingRam
+            }
+            FailedWritingRam | FinishedWritingRam => {
+                self.writing_ram.stop();
+                FlashPhase::WritingRam
+            }
+            StartedVerifying { size } => {
+                self.verifying.total_bytes += size;
+                self.verifying.start();
+                FlashPhase::Verifying
+            }
+            SectorVerified { size, .. } => {
+                self.verifying.done_bytes += size;
+                FlashPhase::Verifying
+            }
+            FailedVerifying { .. } | FinishedVerifying => {
+                self.verifying.stop();
+                FlashPhase::Verifying
+            }
+        };
+
+        let state = match phase {
+            FlashPhase::Filling => &self.filling,
+            FlashPhase::Erasing => &self.erasing,
+            FlashPhase::Programming => &self.programming,
+            FlashPhase::WritingRam => &self.writing_ram,
+            FlashPhase::Verifying => &self.verifying,
+        };
+
+        Some(PhaseProgress {
+            phase,
+            total_bytes: state.total_bytes,
+            done_bytes: state.done_bytes,
+            elapsed: state.elapsed(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{EtaTracker, FlashPhase, PhaseProgress, ProgressEvent};
+    use std::time::Duration;
+
+    #[test]
+    fn remaining_time() {
+        let progress = PhaseProgress {
+            phase: FlashPhase::Programming,
+            total_bytes: 4096,
+            done_bytes: 1024,
+            elapsed: Duration::from_secs(1),
+        };
+
+        assert_eq!(progress.throughput(), Some(1024.0));
+        assert_eq!(progress.remaining_time(), Some(Duration::from_secs(3)));
+    }
+
+    #[test]
+    fn track_verifying() {
+        let mut tracker = EtaTracker::default();
+
+        tracker.update(&ProgressEvent::StartedVerifying { size: 300 });
+        let progress = tracker
+            .update(&ProgressEvent::SectorVerified {
+                address: 0,
+                size: 100,
+                matched: true,
+            })
+            .unwrap();
+
+        assert_eq!(progress.phase, FlashPhase::Verifying);
+        assert_eq!(progress.total_bytes, 300);
+        assert_eq!(progress.done_bytes, 100);
+    }
 }
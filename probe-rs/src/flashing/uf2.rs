@@ -0,0 +1,235 @@
+//! Parsing of files in the [UF2](https://github.com/microsoft/uf2) format.
+//!
+//! A UF2 file consists of 512 byte blocks, each of which contains the target address and up to
+//! 476 bytes of payload.
+
+use scroll::{Pread, LE};
+
+/// The size of a single UF2 block.
+pub(super) const BLOCK_SIZE: usize = 512;
+/// The offset of the payload in a block.
+pub(super) const PAYLOAD_OFFSET: usize = 32;
+/// The maximum size of the payload of a block.
+const MAX_PAYLOAD_SIZE: usize = 476;
+
+pub(super) const MAGIC_START_0: u32 = 0x0A32_4655;
+pub(super) const MAGIC_START_1: u32 = 0x9E5D_5157;
+pub(super) const MAGIC_END: u32 = 0x0AB1_6F30;
+
+/// The block is not meant for the main flash, e.g. it contains debug information.
+const FLAG_NOT_MAIN_FLASH: u32 = 0x0000_0001;
+/// The block is part of a file, and the target address is an offset in that file.
+const FLAG_FILE_CONTAINER: u32 = 0x0000_1000;
+/// The family ID field contains the ID of the chip family the block is meant for.
+pub(super) const FLAG_FAMILY_ID_PRESENT: u32 = 0x0000_2000;
+
+/// The UF2 family IDs of the chip families, with the part of the target name identifying them.
+///
+/// The IDs are taken from <https://github.com/microsoft/uf2/blob/master/utils/uf2families.json>.
+/// More specific names come first.
+const FAMILY_IDS: &[(&str, u32)] = &[
+    ("RP2040", 0xE48B_FF56),
+    ("SAMD21", 0x68ED_2B88),
+    ("SAMD51", 0x5511_4460),
+    ("SAML21", 0x1851_780A),
+    ("NRF52840", 0xADA5_2840),
+    ("NRF52833", 0x621E_937A),
+    ("NRF52", 0x1B57_745F),
+    ("STM32F0", 0x6478_24B6),
+    ("STM32F1", 0x5EE2_1072),
+    ("STM32F2", 0x5D1A_0A2E),
+    ("STM32F3", 0x6B84_6188),
+    ("STM32F4", 0x5775_5A57),
+    ("STM32F7", 0x53B8_0F00),
+    ("STM32G0", 0x300F_5633),
+    ("STM32G4", 0x4C71_240A),
+    ("STM32H7", 0x6DB6_6082),
+    ("STM32L1", 0x1E1F_432D),
+    ("STM32L4", 0x00FF_6919),
+    ("STM32WB", 0x70D1_6653),
+    ("MIMXRT10", 0x4FB2_D5BD),
+];
+
+/// Returns the UF2 family ID of the target with the given name, if it is known.
+pub(super) fn family_id_of_target(name: &str) -> Option<u32> {
+    let name = name.to_uppercase();
+
+    FAMILY_IDS
+        .iter()
+        .find(|(family, _)| name.contains(family))
+        .map(|(_, family_id)| *family_id)
+}
+
+/// An error while parsing a UF2 file.
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum Uf2Error {
+    /// The size of the file is not a multiple of the block size.
+    #[error("The size of the UF2 file ({0} bytes) is not a multiple of 512 bytes.")]
+    InvalidLength(usize),
+    /// A block doesn't start or end with the UF2 magic numbers.
+    #[error("Block {0} of the UF2 file has invalid magic numbers.")]
+    InvalidMagic(usize),
+    /// The payload of a block is larger than the space in the block.
+    #[error("Block {block} of the UF2 file has an invalid payload size of {size} bytes.")]
+    InvalidPayloadSize {
+        /// The index of the block in the file.
+        block: usize,
+        /// The payload size specified by the block.
+        size: u32,
+    },
+    /// The block number of a block is not smaller than the total number of blocks.
+    #[error("Block {block} of the UF2 file has block number {block_no} of {num_blocks}.")]
+    InvalidBlockNumber {
+        /// The index of the block in the file.
+        block: usize,
+        /// The block number specified by the block.
+        block_no: u32,
+        /// The total number of blocks specified by the block.
+        num_blocks: u32,
+    },
+}
+
+/// A block of a UF2 file, which has to be written to the flash.
+#[derive(Debug, PartialEq, Eq)]
+pub(super) struct Uf2Block<'data> {
+    /// The address to write the payload to.
+    pub(super) address: u32,
+    /// The family ID of the chip the block is meant for.
+    pub(super) family_id: Option<u32>,
+    /// The payload of the block.
+    pub(super) data: &'data [u8],
+}
+
+/// Parses the blocks of a UF2 file, and returns the blocks which have to be written to the flash.
+///
+/// Blocks which are not meant for the main flash, or which are part of a file contained in the
+/// UF2 file, are skipped.
+pub(super) fn parse_uf2(data: &[u8]) -> Result<Vec<Uf2Block<'_>>, Uf2Error> {
+    if data.len() % BLOCK_SIZE != 0 {
+        return Err(Uf2Error::InvalidLength(data.len()));
+    }
+
+    let mut blocks = Vec::new();
+
+    for (index, block) in data.chunks_exact(BLOCK_SIZE).enumerate() {
+        let word = |offset: usize| -> u32 {
+            // NOTE(unwrap): All offsets are within the block.
+            block.pread_with(offset, LE).unwrap()
+        };
+
+        if word(0) != MAGIC_START_0 || word(4) != MAGIC_START_1 || word(508) != MAGIC_END {
+            return Err(Uf2Error::InvalidMagic(index));
+        }
+
+        let flags = word(8);
+        let address = word(12);
+        let size = word(16);
+        let block_no = word(20);
+        let num_blocks = word(24);
+
+        if size as usize > MAX_PAYLOAD_SIZE {
+            return Err(Uf2Error::InvalidPayloadSize { block: index, size });
+        }
+
+        if block_no >= num_blocks {
+            return Err(Uf2Error::InvalidBlockNumber {
+                block: index,
+                block_no,
+                num_blocks,
+            });
+        }
+
+        if flags & (FLAG_NOT_MAIN_FLASH | FLAG_FILE_CONTAINER) != 0 {
+            tracing::debug!(
+                "Skipping block {} of the UF2 file (flags {:#x})",
+                index,
+                flags
+            );
+            continue;
+        }
+
+        blocks.push(Uf2Block {
+            address,
+            family_id: (flags & FLAG_FAMILY_ID_PRESENT != 0).then(|| word(28)),
+            data: &block[PAYLOAD_OFFSET..PAYLOAD_OFFSET + size as usize],
+        });
+    }
+
+    Ok(blocks)
+}
+
+/// Builds block `block_no` of a UF2 file with two blocks.
+#[cfg(test)]
+pub(super) fn block(
+    flags: u32,
+    address: u32,
+    payload: &[u8],
+    block_no: u32,
+    family_id: u32,
+) -> Vec<u8> {
+    let mut block = vec![0; BLOCK_SIZE];
+    let words = [
+        MAGIC_START_0,
+        MAGIC_START_1,
+        flags,
+        address,
+        payload.len() as u32,
+        block_no,
+        2,
+        family_id,
+    ];
+    for (i, word) in words.iter().enumerate() {
+        block[i * 4..i * 4 + 4].copy_from_slice(&word.to_le_bytes());
+    }
+    block[PAYLOAD_OFFSET..PAYLOAD_OFFSET + payload.len()].copy_from_slice(payload);
+    block[508..].copy_from_slice(&MAGIC_END.to_le_bytes());
+    block
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        block, family_id_of_target, parse_uf2, Uf2Block, Uf2Error, FLAG_FAMILY_ID_PRESENT,
+        FLAG_NOT_MAIN_FLASH,
+    };
+
+    #[test]
+    fn family_of_target() {
+        assert_eq!(family_id_of_target("RP2040"), Some(0xE48B_FF56));
+        assert_eq!(family_id_of_target("nRF52840_xxAA"), Some(0xADA5_2840));
+        assert_eq!(family_id_of_target("nRF52832_xxAA"), Some(0x1B57_745F));
+        assert_eq!(family_id_of_target("STM32F401RETx"), Some(0x5775_5A57));
+        assert_eq!(family_id_of_target("nrf51822_Xxaa"), None);
+    }
+
+    #[test]
+    fn parse_blocks() {
+        let mut data = block(
+            FLAG_FAMILY_ID_PRESENT,
+            0x1000_0000,
+            &[1, 2, 3],
+            0,
+            0xE48B_FF56,
+        );
+        data.extend(block(FLAG_NOT_MAIN_FLASH, 0x2000_0000, &[4], 1, 0));
+
+        assert_eq!(
+            parse_uf2(&data),
+            Ok(vec![Uf2Block {
+                address: 0x1000_0000,
+                family_id: Some(0xE48B_FF56),
+                data: &[1, 2, 3],
+            }])
+        );
+    }
+
+    #[test]
+    fn reject_corrupted_block() {
+        let mut data = block(0, 0x1000_0000, &[1, 2, 3], 0, 0);
+        data.extend(block(0, 0x1000_0100, &[4], 1, 0));
+        data[512 + 508] = 0;
+
+        assert_eq!(parse_uf2(&data), Err(Uf2Error::InvalidMagic(1)));
+        assert_eq!(parse_uf2(&data[..100]), Err(Uf2Error::InvalidLength(100)));
+    }
+}
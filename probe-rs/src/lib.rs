@@ -86,15 +86,16 @@ mod session;
 
 pub use crate::config::{CoreType, InstructionSet, Target};
 pub use crate::core::{
-    Architecture, BreakpointCause, BreakpointId, CommunicationInterface, Core, CoreInformation,
-    CoreInterface, CoreState, CoreStatus, HaltReason, MemoryMappedRegister, RegisterDescription,
-    RegisterFile, RegisterId, RegisterValue, SpecificCoreState,
+    ActiveStackPointer, Architecture, BreakpointCause, BreakpointId, CommunicationInterface, Core,
+    CoreContext, CoreInformation, CoreInterface, CoreState, CoreStatus, HaltReason,
+    MemoryMappedRegister, RegisterDescription, RegisterFile, RegisterId, RegisterValue,
+    SpecificCoreState, WatchpointAccess,
 };
 pub use crate::error::Error;
 pub use crate::memory::MemoryInterface;
 pub use crate::probe::{
     AttachMethod, DebugProbe, DebugProbeError, DebugProbeInfo, DebugProbeSelector, DebugProbeType,
-    Probe, ProbeCreationError, WireProtocol,
+    PacketInfo, Probe, ProbeCreationError, WireProtocol,
 };
 pub use crate::session::{Permissions, Session};
 
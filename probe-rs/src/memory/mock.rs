@@ -0,0 +1,135 @@
+//! A [`MemoryInterface`] backed by a byte array, for testing code which accesses target memory.
+
+use super::MemoryInterface;
+use crate::error::Error;
+use anyhow::anyhow;
+use std::ops::Range;
+
+#[derive(Debug)]
+pub struct MockMemory {
+    /// The address of the first byte of `memory`.
+    pub base_address: u64,
+    pub memory: Vec<u8>,
+    /// The number of read operations, each of which is a round trip to a real probe.
+    pub reads: usize,
+}
+
+impl MockMemory {
+    /// Creates a MockMemory of `size` zero bytes, starting at `base_address`.
+    ///
+    /// Accesses outside of the memory fail.
+    pub fn new(base_address: u64, size: usize) -> Self {
+        Self {
+            base_address,
+            memory: vec![0; size],
+            reads: 0,
+        }
+    }
+
+    fn range(&self, address: u64, len: usize) -> Result<Range<usize>, Error> {
+        address
+            .checked_sub(self.base_address)
+            .map(|offset| offset as usize..offset as usize + len)
+            .filter(|range| range.end <= self.memory.len())
+            .ok_or_else(|| {
+                Error::Other(anyhow!(
+                    "Access of {} bytes at {:#010x} is outside of the mock memory",
+                    len,
+                    address
+                ))
+            })
+    }
+}
+
+impl MemoryInterface for MockMemory {
+    fn supports_native_64bit_access(&mut self) -> bool {
+        false
+    }
+
+    fn read_word_64(&mut self, address: u64) -> Result<u64, Error> {
+        let mut data = [0];
+        self.read_64(address, &mut data)?;
+        Ok(data[0])
+    }
+
+    fn read_word_32(&mut self, address: u64) -> Result<u32, Error> {
+        let mut data = [0];
+        self.read_32(address, &mut data)?;
+        Ok(data[0])
+    }
+
+    fn read_word_8(&mut self, address: u64) -> Result<u8, Error> {
+        let mut data = [0];
+        self.read_8(address, &mut data)?;
+        Ok(data[0])
+    }
+
+    fn read_64(&mut self, address: u64, data: &mut [u64]) -> Result<(), Error> {
+        let mut bytes = vec![0; data.len() * 8];
+        self.read_8(address, &mut bytes)?;
+        for (word, bytes) in data.iter_mut().zip(bytes.chunks_exact(8)) {
+            *word = u64::from_le_bytes(bytes.try_into().unwrap());
+        }
+        Ok(())
+    }
+
+    fn read_32(&mut self, address: u64, data: &mut [u32]) -> Result<(), Error> {
+        let mut bytes = vec![0; data.len() * 4];
+        self.read_8(address, &mut bytes)?;
+        for (word, bytes) in data.iter_mut().zip(bytes.chunks_exact(4)) {
+            *word = u32::from_le_bytes(bytes.try_into().unwrap());
+        }
+        Ok(())
+    }
+
+    fn read_8(&mut self, address: u64, data: &mut [u8]) -> Result<(), Error> {
+        let range = self.range(address, data.len())?;
+        self.reads += 1;
+        data.copy_from_slice(&self.memory[range]);
+        Ok(())
+    }
+
+    fn read(&mut self, address: u64, data: &mut [u8]) -> Result<(), Error> {
+        self.read_8(address, data)
+    }
+
+    fn write_word_64(&mut self, address: u64, data: u64) -> Result<(), Error> {
+        self.write_8(address, &data.to_le_bytes())
+    }
+
+    fn write_word_32(&mut self, address: u64, data: u32) -> Result<(), Error> {
+        self.write_8(address, &data.to_le_bytes())
+    }
+
+    fn write_word_8(&mut self, address: u64, data: u8) -> Result<(), Error> {
+        self.write_8(address, &[data])
+    }
+
+    fn write_64(&mut self, address: u64, data: &[u64]) -> Result<(), Error> {
+        let bytes: Vec<u8> = data.iter().flat_map(|word| word.to_le_bytes()).collect();
+        self.write_8(address, &bytes)
+    }
+
+    fn write_32(&mut self, address: u64, data: &[u32]) -> Result<(), Error> {
+        let bytes: Vec<u8> = data.iter().flat_map(|word| word.to_le_bytes()).collect();
+        self.write_8(address, &bytes)
+    }
+
+    fn write_8(&mut self, address: u64, data: &[u8]) -> Result<(), Error> {
+        let range = self.range(address, data.len())?;
+        self.memory[range].copy_from_slice(data);
+        Ok(())
+    }
+
+    fn write(&mut self, address: u64, data: &[u8]) -> Result<(), Error> {
+        self.write_8(address, data)
+    }
+
+    fn supports_8bit_transfers(&self) -> Result<bool, Error> {
+        Ok(true)
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+}
@@ -4,6 +4,9 @@ use crate::error;
 use anyhow::{anyhow, Result};
 use scroll::Pread;
 
+#[cfg(test)]
+pub(crate) mod mock;
+
 /// An interface to be implemented for drivers that allow target memory access.
 pub trait MemoryInterface {
     /// Does this interface support native 64-bit wide accesses
@@ -104,6 +107,85 @@ pub trait MemoryInterface {
         Ok(())
     }
 
+    /// Read a null terminated C string at `address`, which is at most `max_len` bytes long,
+    /// excluding the terminator.
+    ///
+    /// The memory is read in small chunks, so no memory far behind the terminator is accessed.
+    /// Invalid UTF-8 sequences are replaced. Returns an error if no terminator is found within
+    /// `max_len` bytes.
+    ///
+    /// A `max_len` of `usize::MAX` doesn't limit the length.
+    fn read_cstring(&mut self, address: u64, max_len: usize) -> Result<String, error::Error> {
+        let string = read_cstring_bytes(self, address, max_len, false)?;
+        Ok(String::from_utf8_lossy(&string).into_owned())
+    }
+
+    /// Read several, possibly non-contiguous, memory ranges given as `(address, length)` pairs.
+    ///
+    /// Overlapping and adjacent ranges are combined into a single read, which reduces the number
+    /// of round trips to the probe. The result contains the data of each range in the order of
+    /// `ranges`. Errors are reported per range, so an inaccessible address doesn't prevent reading
+    /// the other ranges.
+    fn read_scattered(&mut self, ranges: &[(u64, usize)]) -> Vec<Result<Vec<u8>, error::Error>> {
+        let mut results: Vec<Option<Result<Vec<u8>, error::Error>>> =
+            (0..ranges.len()).map(|_| None).collect();
+
+        // Ranges which extend past the end of the address space are never read.
+        let mut order = Vec::with_capacity(ranges.len());
+        for (index, &(address, length)) in ranges.iter().enumerate() {
+            if address.checked_add(length as u64).is_some() {
+                order.push(index);
+            } else {
+                results[index] = Some(Err(anyhow!(
+                    "The range of {} bytes at {:#010x} extends past the end of the address space",
+                    length,
+                    address
+                )
+                .into()));
+            }
+        }
+        order.sort_by_key(|&index| ranges[index].0);
+
+        let mut index = 0;
+        while index < order.len() {
+            // Collect all ranges which overlap or touch the first one.
+            let start = ranges[order[index]].0;
+            let mut end = start + ranges[order[index]].1 as u64;
+            let mut group_end = index + 1;
+            while group_end < order.len() && ranges[order[group_end]].0 <= end {
+                let (address, length) = ranges[order[group_end]];
+                end = end.max(address + length as u64);
+                group_end += 1;
+            }
+
+            let group = &order[index..group_end];
+            let mut data = vec![0; (end - start) as usize];
+
+            match self.read(start, &mut data) {
+                Ok(()) => {
+                    for &range in group {
+                        let (address, length) = ranges[range];
+                        let offset = (address - start) as usize;
+                        results[range] = Some(Ok(data[offset..offset + length].to_vec()));
+                    }
+                }
+                // Read the ranges separately, to find out which of them failed.
+                Err(_) if group.len() > 1 => {
+                    for &range in group {
+                        let (address, length) = ranges[range];
+                        let mut data = vec![0; length];
+                        results[range] = Some(self.read(address, &mut data).map(|_| data));
+                    }
+                }
+                Err(error) => results[group[0]] = Some(Err(error)),
+            }
+
+            index = group_end;
+        }
+
+        results.into_iter().flatten().collect()
+    }
+
     /// Write a 64bit word at `address`.
     ///
     /// The address where the write should be performed at has to be word aligned.
@@ -186,38 +268,26 @@ pub trait MemoryInterface {
     /// so should only be used if reading memory locations that don't have side
     /// effects. Generally faster than [`MemoryInterface::write_8`].
     fn write(&mut self, address: u64, data: &[u8]) -> Result<(), error::Error> {
-        let len = data.len();
-        let start_extra_count = 4 - (address % 4) as usize;
-        let end_extra_count = (len - start_extra_count) % 4;
-        let inbetween_count = len - start_extra_count - end_extra_count;
-        assert!(start_extra_count < 4);
-        assert!(end_extra_count < 4);
-        assert!(inbetween_count % 4 == 0);
-
-        // If we do not have 32 bit aligned access we first check that we can do 8 bit aligned access on this platform.
-        // If we cannot we throw an error.
-        // If we can we read the first n < 4 bytes up until the word aligned address that comes next.
-        if address % 4 != 0 || len % 4 != 0 {
-            // If we do not support 8 bit transfers we have to bail because we can only do 32 bit word aligned transers.
-            if !self.supports_8bit_transfers()? {
-                return Err(error::Error::ArchitectureSpecific(Box::new(
-                    AccessPortError::alignment_error(address, 4),
-                )));
-            }
+        let (head, body, tail) = split_at_words(address, data);
 
-            // We first do an 8 bit write of the first < 4 bytes up until the 4 byte aligned boundary.
-            self.write_8(address, &data[..start_extra_count])?;
+        // The bytes before the first and after the last full word have to be written with 8 bit
+        // transfers, so they don't overwrite the neighbouring bytes.
+        if !(head.is_empty() && tail.is_empty()) && !self.supports_8bit_transfers()? {
+            return Err(error::Error::ArchitectureSpecific(Box::new(
+                AccessPortError::alignment_error(address, 4),
+            )));
         }
 
-        let mut buffer = vec![0u32; inbetween_count / 4];
-        for (bytes, value) in data.chunks_exact(4).zip(buffer.iter_mut()) {
-            *value = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        if !head.is_empty() {
+            self.write_8(address, head)?;
         }
-        self.write_32(address, &buffer)?;
 
-        // We read the remaining bytes that we did not read yet which is always n < 4.
-        if end_extra_count > 0 {
-            self.write_8(address, &data[..start_extra_count])?;
+        if !body.is_empty() {
+            self.write_mem_32bit(address + head.len() as u64, body)?;
+        }
+
+        if !tail.is_empty() {
+            self.write_8(address + (head.len() + body.len()) as u64, tail)?;
         }
 
         Ok(())
@@ -308,6 +378,18 @@ where
     }
 }
 
+/// Splits `data`, which is written to `address`, into the bytes before the first word aligned
+/// address, the full words, and the bytes after the last full word.
+pub(crate) fn split_at_words(address: u64, data: &[u8]) -> (&[u8], &[u8], &[u8]) {
+    let head_len = (((4 - address % 4) % 4) as usize).min(data.len());
+    let body_len = (data.len() - head_len) / 4 * 4;
+
+    let (head, rest) = data.split_at(head_len);
+    let (body, tail) = rest.split_at(body_len);
+
+    (head, body, tail)
+}
+
 // Helper functions to validate address space constraints
 
 /// Validate that an input address is valid for 32-bit only systems
@@ -318,3 +400,157 @@ pub(crate) fn valid_32bit_address(address: u64) -> Result<u32, error::Error> {
 
     Ok(address)
 }
+
+/// Reads the bytes of a null terminated string at `address`, which is at most `max_len` bytes
+/// long, excluding the terminator, see [`MemoryInterface::read_cstring`].
+///
+/// If no terminator is found within `max_len` bytes, the first `max_len` bytes are returned if
+/// `truncate` is set, and an error otherwise.
+pub(crate) fn read_cstring_bytes<M: MemoryInterface + ?Sized>(
+    memory: &mut M,
+    address: u64,
+    max_len: usize,
+    truncate: bool,
+) -> Result<Vec<u8>, error::Error> {
+    const CHUNK_SIZE: u64 = 64;
+
+    let mut string = Vec::new();
+    let mut chunk_address = Some(address);
+
+    // Without truncation, the terminator has to be found within `max_len + 1` bytes.
+    let limit = if truncate {
+        max_len
+    } else {
+        max_len.saturating_add(1)
+    };
+
+    while let Some(address) = chunk_address.filter(|_| string.len() < limit) {
+        // Align the chunks, so the reads don't cross into a following memory region.
+        let remaining = (limit - string.len()) as u64;
+        let mut chunk = vec![0; (CHUNK_SIZE - address % CHUNK_SIZE).min(remaining) as usize];
+        memory.read(address, &mut chunk)?;
+
+        if let Some(end) = chunk.iter().position(|&byte| byte == 0) {
+            string.extend_from_slice(&chunk[..end]);
+            return Ok(string);
+        }
+
+        string.extend_from_slice(&chunk);
+        // The string ends at the end of the address space.
+        chunk_address = address.checked_add(chunk.len() as u64);
+    }
+
+    if truncate {
+        return Ok(string);
+    }
+
+    Err(anyhow!(
+        "No null terminator found within {} bytes of the string at {:#010x}",
+        max_len,
+        address
+    )
+    .into())
+}
+
+#[cfg(test)]
+mod test {
+    use super::mock::MockMemory;
+    use super::{read_cstring_bytes, MemoryInterface};
+
+    #[test]
+    fn read_cstring_stops_at_terminator() {
+        let mut memory = MockMemory::new(0x1000, 0x100);
+        memory.write_8(0x1010, b"hello\0world\0").unwrap();
+
+        assert_eq!(memory.read_cstring(0x1010, 100).unwrap(), "hello");
+        assert_eq!(memory.reads, 1);
+    }
+
+    #[test]
+    fn read_cstring_across_chunks() {
+        let mut memory = MockMemory::new(0x1000, 0x100);
+        memory.write_8(0x103C, b"crosses a chunk\0").unwrap();
+
+        assert_eq!(memory.read_cstring(0x103C, 100).unwrap(), "crosses a chunk");
+        assert_eq!(memory.reads, 2);
+    }
+
+    #[test]
+    fn read_cstring_reads_at_most_max_len() {
+        // The terminator is the last byte of the memory, so reading behind it fails.
+        let mut memory = MockMemory::new(0x1000, 0x8);
+        memory.write_8(0x1004, b"abc\0").unwrap();
+
+        assert_eq!(memory.read_cstring(0x1004, 3).unwrap(), "abc");
+        assert!(memory.read_cstring(0x1004, 2).is_err());
+    }
+
+    #[test]
+    fn read_cstring_replaces_invalid_utf8() {
+        let mut memory = MockMemory::new(0x1000, 0x10);
+        memory.write_8(0x1000, b"a\xFFb\0").unwrap();
+
+        assert_eq!(memory.read_cstring(0x1000, 10).unwrap(), "a\u{FFFD}b");
+    }
+
+    #[test]
+    fn read_scattered_combines_ranges() {
+        let mut memory = MockMemory::new(0x1000, 0x100);
+        let data: Vec<u8> = (0..0x100).map(|i| i as u8).collect();
+        memory.write_8(0x1000, &data).unwrap();
+
+        // Two overlapping ranges, and an adjacent one, given out of order.
+        let results = memory.read_scattered(&[(0x1020, 4), (0x1010, 8), (0x1014, 8), (0x1018, 2)]);
+
+        assert_eq!(memory.reads, 2);
+        assert_eq!(
+            results.into_iter().map(Result::unwrap).collect::<Vec<_>>(),
+            [
+                vec![0x20, 0x21, 0x22, 0x23],
+                vec![0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17],
+                vec![0x14, 0x15, 0x16, 0x17, 0x18, 0x19, 0x1A, 0x1B],
+                vec![0x18, 0x19],
+            ]
+        );
+    }
+
+    #[test]
+    fn read_scattered_reports_errors_per_range() {
+        let mut memory = MockMemory::new(0x1000, 0x100);
+
+        let results =
+            memory.read_scattered(&[(0x10F8, 8), (0x10FC, 8), (0x2000, 4), (u64::MAX - 1, 4)]);
+
+        assert_eq!(results[0].as_ref().unwrap(), &[0; 8]);
+        assert!(results[1].is_err());
+        assert!(results[2].is_err());
+        assert!(results[3].is_err());
+    }
+
+    #[test]
+    fn read_cstring_without_limit() {
+        let mut memory = MockMemory::new(0x1000, 0x100);
+        memory.write_8(0x1000, &[0xAA; 0x100]).unwrap();
+        memory.write_8(0x10E0, b"unlimited\0").unwrap();
+
+        assert_eq!(
+            memory.read_cstring(0x10E0, usize::MAX).unwrap(),
+            "unlimited"
+        );
+
+        // Without a terminator, the read fails at the end of the memory.
+        assert!(memory.read_cstring(0x10F0, usize::MAX).is_err());
+    }
+
+    #[test]
+    fn read_cstring_bytes_truncates() {
+        let mut memory = MockMemory::new(0x1000, 0x100);
+        memory.write_8(0x1000, b"truncated\0").unwrap();
+
+        assert_eq!(
+            read_cstring_bytes(&mut memory, 0x1000, 5, true).unwrap(),
+            b"trunc"
+        );
+        assert!(read_cstring_bytes(&mut memory, 0x1000, 5, false).is_err());
+    }
+}
@@ -19,7 +19,7 @@ use crate::{
         arm::{
             communication_interface::DapProbe,
             sequences::{ArmDebugSequence, DefaultArmSequence},
-            PortType, SwoAccess,
+            PortType, SwdSettings, SwoAccess,
         },
         riscv::communication_interface::RiscvCommunicationInterface,
     },
@@ -432,6 +432,38 @@ impl Probe {
         }
     }
 
+    /// Configure the SWD turnaround period and data phase behaviour.
+    ///
+    /// The settings are applied when attaching to the target. This is only supported by probes
+    /// which implement the ARM debug interface, and not by all of them.
+    pub fn set_swd_settings(&mut self, settings: SwdSettings) -> Result<(), DebugProbeError> {
+        match self.inner.try_as_dap_probe() {
+            Some(probe) => probe.set_swd_settings(settings),
+            None => Err(DebugProbeError::InterfaceNotAvailable("ARM")),
+        }
+    }
+
+    /// Configure the number of extra idle clock cycles after each SWD or JTAG transfer.
+    ///
+    /// Targets with slow internal buses can need additional idle cycles to complete a transfer.
+    /// This is only supported by probes which implement the ARM debug interface, and not by all
+    /// of them.
+    pub fn set_transfer_idle_cycles(&mut self, idle_cycles: u8) -> Result<(), DebugProbeError> {
+        match self.inner.try_as_dap_probe() {
+            Some(probe) => probe.set_transfer_idle_cycles(idle_cycles),
+            None => Err(DebugProbeError::InterfaceNotAvailable("ARM")),
+        }
+    }
+
+    /// Get the number of extra idle clock cycles after each SWD or JTAG transfer, see
+    /// [`Probe::set_transfer_idle_cycles`].
+    pub fn transfer_idle_cycles(&mut self) -> Result<u8, DebugProbeError> {
+        match self.inner.try_as_dap_probe() {
+            Some(probe) => probe.transfer_idle_cycles(),
+            None => Err(DebugProbeError::InterfaceNotAvailable("ARM")),
+        }
+    }
+
     /// Get the currently used maximum speed for the debug protocol in kHz.
     ///
     /// Not all probes report which speed is used, meaning this value is not
@@ -441,6 +473,17 @@ impl Probe {
         self.inner.speed_khz()
     }
 
+    /// Reset the probe itself, to recover a probe which got into a bad state.
+    ///
+    /// This is distinct from [`Probe::target_reset`], the target is not reset. The probe is detached
+    /// from the target afterwards. Not all probes support this, for CMSIS-DAP probes it is only
+    /// available for v2 probes, which are reset through USB.
+    pub fn reset_probe(&mut self) -> Result<(), DebugProbeError> {
+        self.inner.reset_probe()?;
+        self.attached = false;
+        Ok(())
+    }
+
     /// Check if the probe has an interface to
     /// debug ARM chips.
     pub fn has_arm_interface(&self) -> bool {
@@ -513,6 +556,14 @@ impl Probe {
     pub fn get_target_voltage(&mut self) -> Result<Option<f32>, DebugProbeError> {
         self.inner.get_target_voltage()
     }
+
+    /// Returns the size and number of the packets used to communicate with the probe, as
+    /// negotiated when the probe was opened.
+    ///
+    /// This does not work on all probes.
+    pub fn packet_info(&self) -> Option<PacketInfo> {
+        self.inner.packet_info()
+    }
 }
 
 /// An abstraction over general debug probe functionality.
@@ -638,6 +689,30 @@ pub trait DebugProbe: Send + fmt::Debug {
     fn get_target_voltage(&mut self) -> Result<Option<f32>, DebugProbeError> {
         Ok(None)
     }
+
+    /// Returns the size and number of the packets used to communicate with the probe, or `None`
+    /// if the probe doesn't use a negotiated packet size.
+    fn packet_info(&self) -> Option<PacketInfo> {
+        None
+    }
+
+    /// Reset the probe itself, e.g. with a USB reset.
+    ///
+    /// This does not reset the target. Returns [`DebugProbeError::CommandNotSupportedByProbe`] if the
+    /// probe cannot be reset.
+    fn reset_probe(&mut self) -> Result<(), DebugProbeError> {
+        Err(DebugProbeError::CommandNotSupportedByProbe("reset_probe"))
+    }
+}
+
+/// The packets used to communicate with a debug probe, see [`Probe::packet_info`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PacketInfo {
+    /// The maximum size of a packet in bytes.
+    pub size: u16,
+    /// The number of packets the probe can buffer, i.e. how many commands can be sent before
+    /// a response has to be read.
+    pub count: u8,
 }
 
 /// Denotes the type of a given [`DebugProbe`].
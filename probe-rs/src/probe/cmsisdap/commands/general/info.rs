@@ -55,7 +55,8 @@ impl Request for TestDomainTimeCommand {
         Ok(1)
     }
     fn parse_response(&self, buffer: &[u8]) -> Result<Self::Response, SendError> {
-        if buffer[0] == 0x08 {
+        // The response contains the frequency of the test domain timer as a 4 byte value.
+        if buffer[0] == 0x04 {
             let res = buffer
                 .pread_with::<u32>(1, LE)
                 .map_err(|_| SendError::NotEnoughData)?;
@@ -131,7 +132,7 @@ pub struct Capabilities {
     pub(crate) swo_uart_implemented: bool,
     pub(crate) swo_manchester_implemented: bool,
     pub(crate) _atomic_commands_implemented: bool,
-    pub(crate) _test_domain_timer_implemented: bool,
+    pub(crate) test_domain_timer_implemented: bool,
     pub(crate) swo_streaming_trace_implemented: bool,
     pub(crate) _uart_communication_port_implemented: bool,
     pub(crate) uart_com_port_implemented: bool,
@@ -148,7 +149,7 @@ impl ParseFromResponse for Capabilities {
                 swo_uart_implemented: buffer[1] & 0x04 > 0,
                 swo_manchester_implemented: buffer[1] & 0x08 > 0,
                 _atomic_commands_implemented: buffer[1] & 0x10 > 0,
-                _test_domain_timer_implemented: buffer[1] & 0x20 > 0,
+                test_domain_timer_implemented: buffer[1] & 0x20 > 0,
                 swo_streaming_trace_implemented: buffer[1] & 0x40 > 0,
                 _uart_communication_port_implemented: buffer[1] & 0x80 > 0,
                 uart_com_port_implemented: false,
@@ -7,6 +7,7 @@ pub mod transfer;
 use crate::probe::cmsisdap::commands::general::info::PacketSizeCommand;
 use crate::DebugProbeError;
 use std::str::Utf8Error;
+use std::sync::Arc;
 use std::time::Duration;
 
 #[derive(Debug, thiserror::Error)]
@@ -24,12 +25,19 @@ pub enum CmsisDapError {
     SwoBaudrateNotConfigured,
     #[error("Probe reported an error while streaming SWO")]
     SwoTraceStreamError,
+    #[error("The SWO buffer of the probe overflowed, and trace data was lost")]
+    SwoTraceOverrun,
     #[error("Requested SWO mode is not available on this probe")]
     SwoModeNotAvailable,
     #[error("USB Error reading SWO data.")]
     SwoReadError(#[source] rusb::Error),
     #[error("Could not determine a suitable packet size for this probe")]
     NoPacketSize,
+    #[error("Only {transfer_count} of {expected} transfers of a transfer block were executed")]
+    IncompleteTransferBlock {
+        expected: usize,
+        transfer_count: u16,
+    },
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -85,7 +93,7 @@ pub enum CmsisDapDevice {
     /// Stores an rusb device handle, out/in EP addresses, maximum DAP packet size,
     /// and an optional SWO streaming EP address and SWO maximum packet size.
     V2 {
-        handle: rusb::DeviceHandle<rusb::Context>,
+        handle: Arc<rusb::DeviceHandle<rusb::Context>>,
         out_ep: u8,
         in_ep: u8,
         max_packet_size: usize,
@@ -230,24 +238,66 @@ impl CmsisDapDevice {
         match self {
             CmsisDapDevice::V1 { .. } => Err(CmsisDapError::SwoModeNotAvailable),
             CmsisDapDevice::V2 { handle, swo_ep, .. } => match swo_ep {
-                Some((ep, len)) => {
-                    let mut buf = vec![0u8; *len];
-                    match handle.read_bulk(*ep, &mut buf, timeout) {
-                        Ok(n) => {
-                            buf.truncate(n);
-                            Ok(buf)
-                        }
-                        Err(rusb::Error::Timeout) => {
-                            buf.truncate(0);
-                            Ok(buf)
-                        }
-                        Err(e) => Err(CmsisDapError::SwoReadError(e)),
-                    }
-                }
+                Some((ep, len)) => read_swo_endpoint(handle, *ep, *len, timeout),
                 None => Err(CmsisDapError::SwoModeNotAvailable),
             },
         }
     }
+
+    /// Returns a reader of the SWO streaming endpoint, which can be used from another thread.
+    ///
+    /// Returns `None` if this device does not support SWO streaming.
+    pub(super) fn swo_stream_endpoint(&self) -> Option<SwoStreamEndpoint> {
+        match self {
+            CmsisDapDevice::V1 { .. } => None,
+            CmsisDapDevice::V2 { handle, swo_ep, .. } => {
+                swo_ep.map(|(ep, len)| SwoStreamEndpoint {
+                    handle: handle.clone(),
+                    ep,
+                    len,
+                })
+            }
+        }
+    }
+}
+
+/// The SWO streaming endpoint of a CMSIS-DAP v2 device.
+///
+/// The endpoint is separate from the command endpoints, so it can be read while commands are
+/// sent to the device.
+pub(crate) struct SwoStreamEndpoint {
+    handle: Arc<rusb::DeviceHandle<rusb::Context>>,
+    ep: u8,
+    len: usize,
+}
+
+impl SwoStreamEndpoint {
+    /// Read from the SWO streaming endpoint.
+    ///
+    /// On timeout, returns a zero-length buffer.
+    pub(crate) fn read(&self, timeout: Duration) -> Result<Vec<u8>, CmsisDapError> {
+        read_swo_endpoint(&self.handle, self.ep, self.len, timeout)
+    }
+}
+
+fn read_swo_endpoint(
+    handle: &rusb::DeviceHandle<rusb::Context>,
+    ep: u8,
+    len: usize,
+    timeout: Duration,
+) -> Result<Vec<u8>, CmsisDapError> {
+    let mut buf = vec![0u8; len];
+    match handle.read_bulk(ep, &mut buf, timeout) {
+        Ok(n) => {
+            buf.truncate(n);
+            Ok(buf)
+        }
+        Err(rusb::Error::Timeout) => {
+            buf.truncate(0);
+            Ok(buf)
+        }
+        Err(e) => Err(CmsisDapError::SwoReadError(e)),
+    }
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -330,21 +380,106 @@ pub(crate) fn send_command<Req: Request>(
     })
 }
 
+/// Send `requests` to the probe, with up to `depth` requests queued in the probe, and return
+/// the responses in the order of the requests.
+///
+/// The probe executes a queued request while the response of the previous one is transferred,
+/// which hides the USB latency. `depth` must not exceed the packet count reported by the probe.
+///
+/// No further requests are queued once a response is `failed`. The requests which were already
+/// queued in the probe at that point are still executed, so their responses are read as well,
+/// so that they are not mixed up with the responses of later commands. This means that fewer
+/// responses than requests are returned if a request failed, and the responses after the
+/// failed one have to be discarded by the caller.
+pub(crate) fn send_pipelined<Req: Request>(
+    device: &mut CmsisDapDevice,
+    requests: &[Req],
+    depth: usize,
+    failed: impl Fn(&Req::Response) -> bool,
+) -> Result<Vec<Req::Response>, CmsisDapError> {
+    pipeline(
+        device,
+        requests.len(),
+        depth,
+        |device, i| send_request(device, &requests[i]),
+        |device, i| receive_response(device, &requests[i]),
+        failed,
+    )
+    .map_err(|source| CmsisDapError::Send {
+        command_id: Req::COMMAND_ID,
+        source,
+    })
+}
+
+/// Schedules the `send` and `receive` calls of `count` pipelined requests, see [`send_pipelined`].
+fn pipeline<D, Resp>(
+    device: &mut D,
+    count: usize,
+    depth: usize,
+    mut send: impl FnMut(&mut D, usize) -> Result<(), SendError>,
+    mut receive: impl FnMut(&mut D, usize) -> Result<Resp, SendError>,
+    failed: impl Fn(&Resp) -> bool,
+) -> Result<Vec<Resp>, SendError> {
+    let depth = depth.max(1);
+
+    let mut responses = Vec::with_capacity(count);
+    let mut sent = 0;
+    let mut send_error = None;
+    let mut stopped = false;
+
+    while responses.len() < sent || (sent < count && !stopped) {
+        while !stopped && sent < count && sent - responses.len() < depth {
+            match send(device, sent) {
+                Ok(()) => sent += 1,
+                Err(e) => {
+                    send_error = Some(e);
+                    stopped = true;
+                }
+            }
+        }
+
+        if responses.len() < sent {
+            match receive(device, responses.len()) {
+                Ok(response) => {
+                    stopped |= failed(&response);
+                    responses.push(response);
+                }
+                // The probe is out of sync, so the outstanding responses can't be read.
+                Err(e) => return Err(send_error.unwrap_or(e)),
+            }
+        }
+    }
+
+    match send_error {
+        Some(error) => Err(error),
+        None => Ok(responses),
+    }
+}
+
 fn send_command_inner<Req: Request>(
     device: &mut CmsisDapDevice,
     request: Req,
 ) -> Result<Req::Response, SendError> {
-    // Size the buffer for the maximum packet size.
-    // On v1, we always send this full-sized report, while
-    // on v2 we can truncate to just the required data.
-    // Add one byte for HID report ID.
+    send_request(device, &request)?;
+    receive_response(device, &request)
+}
+
+/// Size a buffer for the maximum packet size, plus one byte for the HID report ID.
+fn packet_buffer(device: &CmsisDapDevice) -> Vec<u8> {
     let buffer_len: usize = match device {
         CmsisDapDevice::V1 { report_size, .. } => *report_size + 1,
         CmsisDapDevice::V2 {
             max_packet_size, ..
         } => *max_packet_size + 1,
     };
-    let mut buffer = vec![0; buffer_len];
+    vec![0; buffer_len]
+}
+
+/// Send a request to the probe, without waiting for the response.
+fn send_request<Req: Request>(device: &mut CmsisDapDevice, request: &Req) -> Result<(), SendError> {
+    // On v1, we always send a full-sized report, while
+    // on v2 we can truncate to just the required data.
+    let mut buffer = packet_buffer(device);
 
     // Leave byte 0 as the HID report, and write the command and request to the buffer.
     buffer[1] = Req::COMMAND_ID as u8;
@@ -362,7 +497,16 @@ fn send_command_inner<Req: Request>(
     let _ = device.write(&buffer[..size])?;
     trace_buffer("Transmit buffer", &buffer[..size]);
 
-    // Read back response.
+    Ok(())
+}
+
+/// Read the response to a previously sent request from the probe.
+fn receive_response<Req: Request>(
+    device: &mut CmsisDapDevice,
+    request: &Req,
+) -> Result<Req::Response, SendError> {
+    let mut buffer = packet_buffer(device);
+
     let bytes_read = device.read(&mut buffer)?;
     let response_data = &buffer[..bytes_read];
     trace_buffer("Receive buffer", response_data);
@@ -391,3 +535,160 @@ fn trace_buffer(name: &str, buf: &[u8]) {
         tracing::trace!("{}: {:02X?}...", name, &buf[..end]);
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{pipeline, SendError};
+
+    /// Records the order of the pipelined `send` and `receive` calls.
+    #[derive(Default)]
+    struct Recorder {
+        events: Vec<String>,
+        queued: usize,
+        max_queued: usize,
+    }
+
+    impl Recorder {
+        fn send(&mut self, i: usize) -> Result<(), SendError> {
+            self.events.push(format!("send {i}"));
+            self.queued += 1;
+            self.max_queued = self.max_queued.max(self.queued);
+            Ok(())
+        }
+
+        fn receive(&mut self, i: usize) -> Result<usize, SendError> {
+            self.events.push(format!("receive {i}"));
+            self.queued -= 1;
+            Ok(i)
+        }
+    }
+
+    #[test]
+    fn pipeline_limits_queued_requests_to_depth() {
+        let mut recorder = Recorder::default();
+
+        let responses = pipeline(
+            &mut recorder,
+            5,
+            2,
+            Recorder::send,
+            Recorder::receive,
+            |_| false,
+        )
+        .unwrap();
+
+        assert_eq!(responses, [0, 1, 2, 3, 4]);
+        assert_eq!(recorder.max_queued, 2);
+        assert_eq!(
+            recorder.events,
+            [
+                "send 0",
+                "send 1",
+                "receive 0",
+                "send 2",
+                "receive 1",
+                "send 3",
+                "receive 2",
+                "send 4",
+                "receive 3",
+                "receive 4",
+            ]
+        );
+    }
+
+    #[test]
+    fn pipeline_depth_of_zero_sends_one_request_at_a_time() {
+        let mut recorder = Recorder::default();
+
+        pipeline(
+            &mut recorder,
+            3,
+            0,
+            Recorder::send,
+            Recorder::receive,
+            |_| false,
+        )
+        .unwrap();
+
+        assert_eq!(recorder.max_queued, 1);
+    }
+
+    #[test]
+    fn pipeline_stops_queueing_after_failed_response() {
+        let mut recorder = Recorder::default();
+
+        let responses = pipeline(
+            &mut recorder,
+            6,
+            3,
+            Recorder::send,
+            Recorder::receive,
+            |&response| response == 1,
+        )
+        .unwrap();
+
+        // The requests which were queued before the failure was received are drained.
+        assert_eq!(responses, [0, 1, 2, 3]);
+        assert_eq!(
+            recorder.events,
+            [
+                "send 0",
+                "send 1",
+                "send 2",
+                "receive 0",
+                "send 3",
+                "receive 1",
+                "receive 2",
+                "receive 3",
+            ]
+        );
+    }
+
+    #[test]
+    fn pipeline_reports_send_error_after_draining() {
+        let mut recorder = Recorder::default();
+
+        let result = pipeline(
+            &mut recorder,
+            4,
+            4,
+            |recorder, i| {
+                if i == 2 {
+                    Err(SendError::NotEnoughData)
+                } else {
+                    recorder.send(i)
+                }
+            },
+            Recorder::receive,
+            |_| false,
+        );
+
+        assert!(matches!(result, Err(SendError::NotEnoughData)));
+        assert_eq!(
+            recorder.events,
+            ["send 0", "send 1", "receive 0", "receive 1"]
+        );
+    }
+
+    #[test]
+    fn pipeline_reports_send_error_before_receive_error() {
+        let mut recorder = Recorder::default();
+
+        let result = pipeline(
+            &mut recorder,
+            3,
+            3,
+            |recorder, i| {
+                if i == 1 {
+                    Err(SendError::NotEnoughData)
+                } else {
+                    recorder.send(i)
+                }
+            },
+            |_, _| Err::<usize, _>(SendError::CommandIdMismatch(0)),
+            |_| false,
+        );
+
+        assert!(matches!(result, Err(SendError::NotEnoughData)));
+    }
+}
@@ -1,7 +1,13 @@
 use super::super::{CommandId, Request, SendError, Status};
 
+/// Configures the SWD protocol, see `DAP_SWD_Configure`.
 #[derive(Debug)]
-pub struct ConfigureRequest;
+pub struct ConfigureRequest {
+    /// The number of turnaround clock cycles, between 1 and 4.
+    pub turnaround: u8,
+    /// Always generate a data phase, also for WAIT and FAULT responses.
+    pub data_phase: bool,
+}
 
 impl Request for ConfigureRequest {
     const COMMAND_ID: CommandId = CommandId::SwdConfigure;
@@ -9,8 +15,9 @@ impl Request for ConfigureRequest {
     type Response = ConfigureResponse;
 
     fn to_bytes(&self, buffer: &mut [u8]) -> Result<usize, SendError> {
-        // TODO: Allow configuration
-        buffer[0] = 0;
+        // Bit 1..0: Turnaround clock period - 1
+        // Bit 2: DataPhase
+        buffer[0] = (self.turnaround.saturating_sub(1) & 0b11) | (u8::from(self.data_phase) << 2);
         Ok(1)
     }
 
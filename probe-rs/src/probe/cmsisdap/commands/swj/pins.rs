@@ -6,7 +6,10 @@ pub struct SWJPinsRequest {
     pub(crate) output: Pins,
     /// A mask to select all the pins that should be toggled.
     pub(crate) select: Pins,
-    /// A timeout to wait for until the pin state is read back.
+    /// The time in microseconds to wait for the selected pins to reach their output values,
+    /// before the pin state is read back.
+    ///
+    /// This is limited to 3 seconds by the CMSIS-DAP specification.
     pub(crate) wait: u32,
 }
 
@@ -131,7 +134,10 @@ impl Request for SWJPinsRequest {
     }
 
     fn parse_response(&self, buffer: &[u8]) -> Result<Self::Response, SendError> {
-        Ok(Pins(buffer[0]))
+        buffer
+            .first()
+            .map(|&pins| Pins(pins))
+            .ok_or(SendError::NotEnoughData)
     }
 }
 
@@ -1,6 +1,6 @@
 use scroll::{Pread, LE};
 
-use super::{CommandId, Request, SendError, Status};
+use super::{CmsisDapError, CommandId, Request, SendError, Status};
 use std::convert::TryInto;
 
 #[repr(u8)]
@@ -131,10 +131,7 @@ impl Request for StatusRequest {
                 .try_into()
                 .map_err(|_| SendError::NotEnoughData)?,
         );
-        Ok(StatusResponse {
-            _status: status,
-            _count: count,
-        })
+        Ok(StatusResponse { status, count })
     }
 }
 
@@ -142,7 +139,20 @@ impl Request for StatusRequest {
 pub struct TraceStatus {
     pub(crate) _active: bool,
     pub(crate) error: bool,
-    pub(crate) _overrun: bool,
+    pub(crate) overrun: bool,
+}
+
+impl TraceStatus {
+    /// Returns an error if the probe failed to decode the SWO signal, or lost trace data.
+    pub(crate) fn check(&self) -> Result<(), CmsisDapError> {
+        if self.error {
+            Err(CmsisDapError::SwoTraceStreamError)
+        } else if self.overrun {
+            Err(CmsisDapError::SwoTraceOverrun)
+        } else {
+            Ok(())
+        }
+    }
 }
 
 impl From<u8> for TraceStatus {
@@ -150,15 +160,15 @@ impl From<u8> for TraceStatus {
         Self {
             _active: value & (1 << 0) != 0,
             error: value & (1 << 6) != 0,
-            _overrun: value & (1 << 7) != 0,
+            overrun: value & (1 << 7) != 0,
         }
     }
 }
 
 #[derive(Debug)]
 pub struct StatusResponse {
-    pub(crate) _status: TraceStatus,
-    pub(crate) _count: u32,
+    pub(crate) status: TraceStatus,
+    pub(crate) count: u32,
 }
 
 #[derive(Debug)]
@@ -203,20 +213,20 @@ impl Request for ExtendedStatusRequest {
                 .map_err(|_| SendError::NotEnoughData)?,
         );
         Ok(ExtendedStatusResponse {
-            _status: status,
+            status,
             _count: count,
             _index: index,
-            _timestamp: timestamp,
+            timestamp,
         })
     }
 }
 
 #[derive(Debug)]
 pub struct ExtendedStatusResponse {
-    pub(crate) _status: TraceStatus,
+    pub(crate) status: TraceStatus,
     pub(crate) _count: u32,
     pub(crate) _index: u32,
-    pub(crate) _timestamp: u32,
+    pub(crate) timestamp: u32,
 }
 
 #[derive(Debug)]
@@ -1,7 +1,8 @@
 pub mod configure;
 
-use super::{CommandId, Request, SendError};
-use crate::architecture::arm::PortType;
+use super::{CmsisDapError, CommandId, Request, SendError};
+use crate::architecture::arm::{DapError, PortType};
+use crate::DebugProbeError;
 use scroll::{Pread, Pwrite, LE};
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
@@ -182,17 +183,7 @@ impl Request for TransferRequest {
             return Err(SendError::UnexpectedAnswer);
         }
 
-        let last_transfer_response = LastTransferResponse {
-            ack: match buffer[1] & 0x7 {
-                1 => Ack::Ok,
-                2 => Ack::Wait,
-                4 => Ack::Fault,
-                7 => Ack::NoAck,
-                _ => Ack::NoAck,
-            },
-            protocol_error: buffer[1] & 0x8 > 1,
-            value_missmatch: buffer[1] & 0x10 > 1,
-        };
+        let last_transfer_response = LastTransferResponse::from_byte(buffer[1]);
 
         buffer = &buffer[2..];
         let mut transfers = Vec::new();
@@ -233,10 +224,54 @@ pub enum Ack {
 #[derive(Debug)]
 pub struct LastTransferResponse {
     pub ack: Ack,
+    /// Set on a SWD protocol error, which is reported for a parity error in the read data or
+    /// for an invalid ACK.
     pub protocol_error: bool,
     pub value_missmatch: bool,
 }
 
+impl LastTransferResponse {
+    /// Parse the transfer response byte.
+    ///
+    /// Bits 2..0 contain the ACK of the target, bit 3 is set on a protocol error and bit 4 on a
+    /// value mismatch.
+    pub(crate) fn from_byte(byte: u8) -> Self {
+        let (ack, invalid_ack) = match byte & 0x7 {
+            1 => (Ack::Ok, false),
+            2 => (Ack::Wait, false),
+            4 => (Ack::Fault, false),
+            7 => (Ack::NoAck, false),
+            _ => (Ack::NoAck, true),
+        };
+
+        Self {
+            ack,
+            protocol_error: byte & 0x8 != 0 || invalid_ack,
+            value_missmatch: byte & 0x10 != 0,
+        }
+    }
+
+    /// Classify an unsuccessful response into the matching error.
+    ///
+    /// Returns `None` if the transfer was acknowledged with OK.
+    pub(crate) fn error(&self) -> Option<DapError> {
+        if self.protocol_error {
+            // A valid ACK with a protocol error means the parity of the read data was wrong.
+            return Some(match self.ack {
+                Ack::Ok => DapError::IncorrectParity,
+                _ => DapError::SwdProtocol,
+            });
+        }
+
+        match self.ack {
+            Ack::Ok => None,
+            Ack::Wait => Some(DapError::WaitResponse),
+            Ack::Fault => Some(DapError::FaultResponse),
+            Ack::NoAck => Some(DapError::NoAcknowledge),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct TransferResponse {
     /// Number of transfers: 1 .. 255 that are executed.
@@ -297,9 +332,11 @@ impl Request for TransferBlockRequest {
         let transfer_count = buffer
             .pread_with(0, LE)
             .map_err(|_| SendError::NotEnoughData)?;
-        let transfer_response = buffer
-            .pread_with(2, LE)
-            .map_err(|_| SendError::NotEnoughData)?;
+        let transfer_response = LastTransferResponse::from_byte(
+            buffer
+                .pread_with(2, LE)
+                .map_err(|_| SendError::NotEnoughData)?,
+        );
 
         let mut data = Vec::with_capacity(transfer_count as usize);
 
@@ -324,7 +361,7 @@ impl Request for TransferBlockRequest {
         }
 
         Ok(TransferBlockResponse {
-            _transfer_count: transfer_count,
+            transfer_count,
             transfer_response,
             transfer_data: data,
         })
@@ -385,7 +422,127 @@ impl InnerTransferBlockRequest {
 
 #[derive(Debug)]
 pub(crate) struct TransferBlockResponse {
-    _transfer_count: u16,
-    pub transfer_response: u8,
+    /// Number of transfers which were executed, which is smaller than the requested count if a
+    /// transfer failed.
+    pub transfer_count: u16,
+    /// The response of the last executed transfer.
+    pub transfer_response: LastTransferResponse,
+    /// The data of the executed read transfers.
     pub transfer_data: Vec<u32>,
 }
+
+impl TransferBlockResponse {
+    /// Checks that all `expected` transfers were executed successfully.
+    ///
+    /// A failed transfer is reported as the [`DapError`] of its response.
+    pub(crate) fn check(&self, expected: usize) -> Result<(), DebugProbeError> {
+        if let Some(error) = self.transfer_response.error() {
+            tracing::debug!(
+                "Transfer block failed after {} transfers: {:?}",
+                self.transfer_count,
+                error
+            );
+            return Err(error.into());
+        }
+
+        if self.transfer_count as usize != expected {
+            return Err(CmsisDapError::IncompleteTransferBlock {
+                expected,
+                transfer_count: self.transfer_count,
+            }
+            .into());
+        }
+
+        Ok(())
+    }
+}
+
+#[test]
+fn transfer_block_read_request_to_bytes() {
+    let request = TransferBlockRequest::read_request(0xC, PortType::AccessPort, 3);
+
+    let mut buffer = [0; 8];
+    let size = request.to_bytes(&mut buffer).unwrap();
+
+    assert_eq!(&buffer[..size], &[0x00, 0x03, 0x00, 0b1111]);
+}
+
+#[test]
+fn transfer_block_write_request_to_bytes() {
+    let request =
+        TransferBlockRequest::write_request(0xC, PortType::AccessPort, vec![0x1234_5678, 0xAB]);
+
+    let mut buffer = [0; 16];
+    let size = request.to_bytes(&mut buffer).unwrap();
+
+    assert_eq!(
+        &buffer[..size],
+        &[0x00, 0x02, 0x00, 0b1101, 0x78, 0x56, 0x34, 0x12, 0xAB, 0x00, 0x00, 0x00]
+    );
+}
+
+#[test]
+fn incomplete_transfer_block_response() {
+    let request = TransferBlockRequest::read_request(0xC, PortType::AccessPort, 3);
+
+    // Two reads were executed, before the third one was answered with FAULT.
+    let response = request
+        .parse_response(&[0x02, 0x00, 0x04, 1, 0, 0, 0, 2, 0, 0, 0])
+        .unwrap();
+
+    assert_eq!(response.transfer_count, 2);
+    assert_eq!(response.transfer_data, vec![1, 2]);
+    assert!(response.check(3).is_err());
+
+    // All three reads were acknowledged, but only two were executed.
+    let response = request
+        .parse_response(&[0x02, 0x00, 0x01, 1, 0, 0, 0, 2, 0, 0, 0])
+        .unwrap();
+
+    assert!(matches!(
+        response.check(3),
+        Err(DebugProbeError::ProbeSpecific(_))
+    ));
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Ack, LastTransferResponse};
+    use crate::architecture::arm::DapError;
+
+    #[test]
+    fn parse_response_byte() {
+        let response = LastTransferResponse::from_byte(0x01);
+        assert_eq!(response.ack, Ack::Ok);
+        assert!(!response.protocol_error);
+        assert!(!response.value_missmatch);
+
+        let response = LastTransferResponse::from_byte(0x12);
+        assert_eq!(response.ack, Ack::Wait);
+        assert!(!response.protocol_error);
+        assert!(response.value_missmatch);
+
+        assert_eq!(LastTransferResponse::from_byte(0x04).ack, Ack::Fault);
+        assert_eq!(LastTransferResponse::from_byte(0x07).ack, Ack::NoAck);
+    }
+
+    #[test]
+    fn invalid_ack_is_protocol_error() {
+        let response = LastTransferResponse::from_byte(0x03);
+        assert_eq!(response.ack, Ack::NoAck);
+        assert!(response.protocol_error);
+        assert_eq!(response.error(), Some(DapError::SwdProtocol));
+    }
+
+    #[test]
+    fn classify_errors() {
+        let error = |byte| LastTransferResponse::from_byte(byte).error();
+
+        assert_eq!(error(0x01), None);
+        assert_eq!(error(0x09), Some(DapError::IncorrectParity));
+        assert_eq!(error(0x02), Some(DapError::WaitResponse));
+        assert_eq!(error(0x04), Some(DapError::FaultResponse));
+        assert_eq!(error(0x07), Some(DapError::NoAcknowledge));
+        assert_eq!(error(0x0C), Some(DapError::SwdProtocol));
+    }
+}
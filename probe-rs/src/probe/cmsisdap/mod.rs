@@ -3,19 +3,23 @@ pub mod tools;
 
 use crate::{
     architecture::arm::{
+        ap::{DRW, TAR},
         communication_interface::DapProbe,
         communication_interface::UninitializedArmProbe,
         dp::{Abort, Ctrl},
         swo::poll_interval_from_buf_size,
         ArmCommunicationInterface, DapError, DpAddress, Pins, PortType, RawDapAccess, Register,
-        SwoAccess, SwoConfig, SwoMode,
+        SwdSettings, SwoAccess, SwoChunk, SwoConfig, SwoMode, SwoStreamSource,
     },
     probe::{
         cmsisdap::commands::{
-            general::info::{CapabilitiesCommand, PacketCountCommand, SWOTraceBufferSizeCommand},
-            CmsisDapError,
+            general::info::{
+                CapabilitiesCommand, PacketCountCommand, SWOTraceBufferSizeCommand,
+                TestDomainTimeCommand,
+            },
+            CmsisDapError, SwoStreamEndpoint,
         },
-        BatchCommand,
+        BatchCommand, PacketInfo,
     },
     DebugProbe, DebugProbeError, DebugProbeSelector, Error as ProbeRsError, WireProtocol,
 };
@@ -43,7 +47,23 @@ use commands::{
     CmsisDapDevice, Status,
 };
 
-use std::time::Duration;
+use std::{ops::Range, sync::Arc, time::Duration};
+
+/// The dormant-to-SWD sequence, followed by a line reset and two idle cycles.
+///
+/// This consists of 8 cycles high, the 128 bit selection alert, 4 cycles low, the SWD activation
+/// code, and the line reset, see "ARM Debug Interface Architecture Specification ADIv5.0 to
+/// ADIv5.2", section B5.3.4.
+const DORMANT_TO_SWD: [u8; 28] = [
+    0xff, 0x92, 0xf3, 0x09, 0x62, 0x95, 0x2d, 0x85, 0x86, 0xe9, 0xaf, 0xdd, 0xe3, 0xa2, 0x0e, 0xbc,
+    0x19, 0xa0, 0xf1, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00,
+];
+
+/// How often the selection of a DP on a multidrop bus is attempted.
+const TARGETSEL_ATTEMPTS: usize = 5;
+
+/// The time to wait between SWO data requests in polled mode, while no data was received.
+const SWO_POLL_INTERVAL: Duration = Duration::from_millis(1);
 
 pub struct CmsisDap {
     pub device: CmsisDapDevice,
@@ -55,13 +75,21 @@ pub struct CmsisDap {
     packet_count: u8,
     capabilities: Capabilities,
     swo_buffer_size: Option<usize>,
+    /// Frequency of the test domain timer in Hz, used to timestamp SWO data.
+    timestamp_frequency: Option<u32>,
     swo_active: bool,
     swo_streaming: bool,
+    /// The SWO baud rate the probe was configured to, which can differ from the requested one.
+    swo_baud: Option<u32>,
     connected: bool,
 
     /// Speed in kHz
     speed_khz: u32,
 
+    swd_settings: SwdSettings,
+    /// The number of extra idle cycles after each transfer, see `DAP_TransferConfigure`.
+    transfer_idle_cycles: u8,
+
     batch: Vec<BatchCommand>,
 }
 
@@ -73,9 +101,13 @@ impl std::fmt::Debug for CmsisDap {
             .field("packet_count", &self.packet_count)
             .field("capabilities", &self.capabilities)
             .field("swo_buffer_size", &self.swo_buffer_size)
+            .field("timestamp_frequency", &self.timestamp_frequency)
             .field("swo_active", &self.swo_active)
             .field("swo_streaming", &self.swo_streaming)
+            .field("swo_baud", &self.swo_baud)
             .field("speed_khz", &self.speed_khz)
+            .field("swd_settings", &self.swd_settings)
+            .field("transfer_idle_cycles", &self.transfer_idle_cycles)
             .finish()
     }
 }
@@ -92,6 +124,11 @@ impl CmsisDap {
 
         // Read remaining probe information.
         let packet_count = commands::send_command(&mut device, PacketCountCommand {})?;
+        tracing::debug!(
+            "Probe uses {} packets of up to {} bytes",
+            packet_count,
+            packet_size
+        );
         let caps: Capabilities = commands::send_command(&mut device, CapabilitiesCommand {})?;
         tracing::debug!("Detected probe capabilities: {:?}", caps);
         let mut swo_buffer_size = None;
@@ -101,6 +138,17 @@ impl CmsisDap {
             tracing::debug!("Probe SWO buffer size: {}", swo_size);
         }
 
+        let mut timestamp_frequency = None;
+        if caps.test_domain_timer_implemented {
+            match commands::send_command(&mut device, TestDomainTimeCommand {}) {
+                Ok(frequency) => {
+                    tracing::debug!("Probe test domain timer frequency: {} Hz", frequency);
+                    timestamp_frequency = Some(frequency);
+                }
+                Err(e) => tracing::debug!("Failed to read test domain timer frequency: {:?}", e),
+            }
+        }
+
         Ok(Self {
             device,
             _hw_version: 0,
@@ -110,10 +158,14 @@ impl CmsisDap {
             packet_size,
             capabilities: caps,
             swo_buffer_size,
+            timestamp_frequency,
             swo_active: false,
             swo_streaming: false,
+            swo_baud: None,
             connected: false,
             speed_khz: 1_000,
+            swd_settings: SwdSettings::default(),
+            transfer_idle_cycles: 0,
             batch: Vec::new(),
         })
     }
@@ -204,7 +256,12 @@ impl CmsisDap {
             tracing::debug!("{:?} of batch of {} items suceeded", count, batch.len());
 
             if response.last_transfer_response.protocol_error {
-                return Err(DapError::SwdProtocol.into());
+                let error = response
+                    .last_transfer_response
+                    .error()
+                    .unwrap_or(DapError::SwdProtocol);
+                tracing::debug!("Transfer status: protocol error ({:?})", error);
+                return Err(error.into());
             } else {
                 match response.last_transfer_response.ack {
                     Ack::Ok => {
@@ -280,6 +337,120 @@ impl CmsisDap {
         }
     }
 
+    /// The maximum number of words transferred by a single `DAP_TransferBlock` command.
+    fn transfer_block_len(&self) -> usize {
+        // the overhead for a single packet is 6 bytes
+        //
+        // [0]: HID overhead
+        // [1]: Category
+        // [2]: DAP Index
+        // [3]: Len 1
+        // [4]: Len 2
+        // [5]: Request type
+        //
+        (self.packet_size as usize - 6) / 4
+    }
+
+    /// Transfers `len` words with pipelined `DAP_TransferBlock` commands.
+    ///
+    /// `request` builds the command for a range of the words, and `complete` is called with the
+    /// response of each successful command.
+    ///
+    /// If a block of a DRW access fails with a WAIT response, the blocks which were already
+    /// queued in the probe are executed at the wrong addresses. The TAR is re-armed to the
+    /// start of the failed block, and the transfer is retried from there, which also overwrites
+    /// any data written to the wrong addresses.
+    fn transfer_blocks(
+        &mut self,
+        port: PortType,
+        register_address: u8,
+        len: usize,
+        mut request: impl FnMut(Range<usize>) -> TransferBlockRequest,
+        mut complete: impl FnMut(Range<usize>, TransferBlockResponse),
+    ) -> Result<(), DebugProbeError> {
+        self.process_batch()?;
+
+        let chunk_len = self.transfer_block_len();
+        let failed =
+            |response: &TransferBlockResponse| response.transfer_response.error().is_some();
+
+        let mut offset = 0;
+
+        for retry in (0..5).rev() {
+            let ranges: Vec<_> = (offset..len)
+                .step_by(chunk_len)
+                .map(|start| start..(start + chunk_len).min(len))
+                .collect();
+            let requests: Vec<_> = ranges.iter().cloned().map(&mut request).collect();
+
+            tracing::debug!(
+                "Transfer block: {} chunks, len={} bytes",
+                requests.len(),
+                (len - offset) * 4
+            );
+
+            let mut responses = commands::send_pipelined(
+                &mut self.device,
+                &requests,
+                self.packet_count as usize,
+                failed,
+            )?;
+
+            let failed_chunk = responses.iter().position(failed);
+
+            // The responses after the failed one belong to blocks which were already queued.
+            let discarded = failed_chunk
+                .map(|i| responses.split_off(i + 1))
+                .unwrap_or_default();
+            let failed_response = failed_chunk.and_then(|_| responses.pop());
+
+            for (range, response) in ranges.iter().cloned().zip(responses) {
+                response.check(range.len())?;
+                complete(range, response);
+            }
+
+            let (failed_chunk, failed_response) = match (failed_chunk, failed_response) {
+                (Some(i), Some(response)) => (i, response),
+                _ => return Ok(()),
+            };
+
+            tracing::debug!(
+                "Transfer block failed in chunk {}, discarding {} queued chunks",
+                failed_chunk,
+                discarded.len()
+            );
+
+            let is_drw_access = port == PortType::AccessPort && register_address == DRW::ADDRESS;
+
+            if retry == 0
+                || !is_drw_access
+                || failed_response.transfer_response.error() != Some(DapError::WaitResponse)
+            {
+                return failed_response.check(ranges[failed_chunk].len());
+            }
+
+            // All executed transfers incremented the TAR, also those of the discarded blocks.
+            let executed = std::iter::once(&failed_response)
+                .chain(&discarded)
+                .map(|response| response.transfer_count as u32)
+                .sum::<u32>();
+
+            // A block doesn't cross the auto-increment boundary of the TAR, so the start
+            // of the failed block can be calculated from the current TAR value.
+            let tar = RawDapAccess::raw_read_register(self, PortType::AccessPort, TAR::ADDRESS)?;
+            let start = tar.wrapping_sub(executed * 4);
+
+            tracing::debug!("Retrying transfer block at {:#010x}", start);
+
+            RawDapAccess::raw_write_register(self, PortType::AccessPort, TAR::ADDRESS, start)?;
+            self.process_batch()?;
+
+            offset = ranges[failed_chunk].start;
+        }
+
+        Err(DapError::WaitResponse.into())
+    }
+
     /// Set SWO port to use requested transport.
     ///
     /// Check the probe capabilities to determine which transports are available.
@@ -340,7 +511,6 @@ impl CmsisDap {
     }
 
     /// Fetch current SWO trace status.
-    #[allow(dead_code)]
     fn get_swo_status(&mut self) -> Result<swo::StatusResponse, DebugProbeError> {
         Ok(commands::send_command(
             &mut self.device,
@@ -353,7 +523,6 @@ impl CmsisDap {
     /// request.request_status: request trace status
     /// request.request_count: request remaining bytes in trace buffer
     /// request.request_index: request sequence number and timestamp of next trace sequence
-    #[allow(dead_code)]
     fn get_swo_extended_status(
         &mut self,
         request: swo::ExtendedStatusRequest,
@@ -372,11 +541,8 @@ impl CmsisDap {
 
                 let response: swo::DataResponse =
                     commands::send_command(&mut self.device, swo::DataRequest { max_count: n })?;
-                if response.status.error {
-                    Err(CmsisDapError::SwoTraceStreamError.into())
-                } else {
-                    Ok(response.data)
-                }
+                response.status.check()?;
+                Ok(response.data)
             }
             None => Ok(Vec::new()),
         }
@@ -458,12 +624,15 @@ impl DebugProbe for CmsisDap {
         self.set_speed(self.speed_khz)?;
 
         self.transfer_configure(ConfigureRequest {
-            idle_cycles: 0,
+            idle_cycles: self.transfer_idle_cycles,
             wait_retry: 0xffff,
             match_retry: 0,
         })?;
 
-        self.configure_swd(swd::configure::ConfigureRequest {})?;
+        self.configure_swd(swd::configure::ConfigureRequest {
+            turnaround: self.swd_settings.turnaround,
+            data_phase: self.swd_settings.data_phase,
+        })?;
 
         // Tell the probe we are connected so it can turn on an LED.
         let _: Result<HostStatusResponse, _> =
@@ -566,30 +735,102 @@ impl DebugProbe for CmsisDap {
     fn try_as_dap_probe(&mut self) -> Option<&mut dyn DapProbe> {
         Some(self)
     }
+
+    fn packet_info(&self) -> Option<PacketInfo> {
+        Some(PacketInfo {
+            size: self.packet_size,
+            count: self.packet_count,
+        })
+    }
+
+    fn reset_probe(&mut self) -> Result<(), DebugProbeError> {
+        // HID devices cannot be reset through hidapi.
+        let handle = match &mut self.device {
+            CmsisDapDevice::V1 { .. } => {
+                return Err(DebugProbeError::CommandNotSupportedByProbe("reset_probe"))
+            }
+            // The handle is shared with an active SWO stream, which would be invalidated.
+            CmsisDapDevice::V2 { handle, .. } => match Arc::get_mut(handle) {
+                Some(handle) => handle,
+                None => {
+                    return Err(DebugProbeError::Other(anyhow::anyhow!(
+                        "The probe cannot be reset while an SWO stream is active"
+                    )))
+                }
+            },
+        };
+
+        let selector = tools::selector_from_handle(handle);
+
+        match handle.reset() {
+            // The handle stays valid, and the claimed interface is restored.
+            Ok(()) => {
+                self.device.drain();
+                self.connected = false;
+                self.swo_active = false;
+                self.swo_streaming = false;
+                self.batch.clear();
+                Ok(())
+            }
+            // The probe re-enumerated, so it has to be opened again.
+            Err(rusb::Error::NotFound) => {
+                let selector = selector.ok_or(DebugProbeError::Usb(None))?;
+                tracing::debug!("Probe re-enumerated after reset, opening {}", selector);
+
+                let start = std::time::Instant::now();
+                let device = loop {
+                    match tools::open_device_from_selector(selector.clone()) {
+                        Ok(device) => break device,
+                        Err(error) if start.elapsed() > Duration::from_secs(5) => {
+                            return Err(error.into())
+                        }
+                        Err(_) => std::thread::sleep(Duration::from_millis(100)),
+                    }
+                };
+
+                let mut probe = Self::new_from_device(device)?;
+                probe.protocol = self.protocol;
+                probe.speed_khz = self.speed_khz;
+                probe.swd_settings = self.swd_settings;
+                probe.transfer_idle_cycles = self.transfer_idle_cycles;
+
+                // The old device is gone, so no commands have to be sent to it anymore.
+                self.swo_active = false;
+                self.batch.clear();
+                *self = probe;
+
+                Ok(())
+            }
+            Err(error) => Err(DebugProbeError::Usb(Some(Box::new(error)))),
+        }
+    }
 }
 
 impl RawDapAccess for CmsisDap {
+    /// Selects the DP to talk to.
+    ///
+    /// On a multidrop bus, all DPs are woken from the dormant state and reset, and only the one
+    /// matching `targetsel` stays selected. This is done with `DAP_SWJ_Sequence`, before the first
+    /// access to the DP.
     fn select_dp(&mut self, dp: DpAddress) -> Result<(), DebugProbeError> {
         match dp {
             DpAddress::Default => Ok(()), // nop
             DpAddress::Multidrop(targetsel) => {
-                for _i in 0..5 {
+                for _ in 0..TARGETSEL_ATTEMPTS {
                     // Flush just in case there were writes queued from before.
                     self.process_batch()?;
 
-                    // dormant-to-swd + line reset
                     self.send_swj_sequences(SequenceRequest::new(
-                        &[
-                            0xff, 0x92, 0xf3, 0x09, 0x62, 0x95, 0x2d, 0x85, 0x86, 0xe9, 0xaf, 0xdd,
-                            0xe3, 0xa2, 0x0e, 0xbc, 0x19, 0xa0, 0xf1, 0xff, 0xff, 0xff, 0xff, 0xff,
-                            0xff, 0xff, 0xff, 0x00,
-                        ],
-                        28 * 8,
+                        &DORMANT_TO_SWD,
+                        DORMANT_TO_SWD.len() as u8 * 8,
                     )?)?;
 
                     // TARGETSEL write.
                     // The TARGETSEL write is not ACKed by design. We can't use a normal register write
                     // because many probes don't even send the data phase when NAK.
+                    //
+                    // The packet request (0x99) is followed by 5 undriven cycles for the turnaround
+                    // and the ACK, the data, and the parity bit.
                     let parity = targetsel.count_ones() % 2;
                     let data = &((parity as u64) << 45 | (targetsel as u64) << 13 | 0x1f99)
                         .to_le_bytes()[..6];
@@ -639,37 +880,15 @@ impl RawDapAccess for CmsisDap {
         register_address: u8,
         values: &[u32],
     ) -> Result<(), DebugProbeError> {
-        self.process_batch()?;
-
-        // the overhead for a single packet is 6 bytes
-        //
-        // [0]: HID overhead
-        // [1]: Category
-        // [2]: DAP Index
-        // [3]: Len 1
-        // [4]: Len 2
-        // [5]: Request type
-        //
-
-        let max_packet_size_words = (self.packet_size - 6) / 4;
-
-        let data_chunk_len = max_packet_size_words as usize;
-
-        for (i, chunk) in values.chunks(data_chunk_len).enumerate() {
-            let request =
-                TransferBlockRequest::write_request(register_address, port, Vec::from(chunk));
-
-            tracing::debug!("Transfer block: chunk={}, len={} bytes", i, chunk.len() * 4);
-
-            let resp: TransferBlockResponse =
-                commands::send_command(&mut self.device, request).map_err(DebugProbeError::from)?;
-
-            if resp.transfer_response != 1 {
-                return Err(CmsisDapError::ErrorResponse.into());
-            }
-        }
-
-        Ok(())
+        self.transfer_blocks(
+            port,
+            register_address,
+            values.len(),
+            |range| {
+                TransferBlockRequest::write_request(register_address, port, values[range].to_vec())
+            },
+            |_, _| (),
+        )
     }
 
     fn raw_read_block(
@@ -678,39 +897,13 @@ impl RawDapAccess for CmsisDap {
         register_address: u8,
         values: &mut [u32],
     ) -> Result<(), DebugProbeError> {
-        self.process_batch()?;
-
-        // the overhead for a single packet is 6 bytes
-        //
-        // [0]: HID overhead
-        // [1]: Category
-        // [2]: DAP Index
-        // [3]: Len 1
-        // [4]: Len 2
-        // [5]: Request type
-        //
-
-        let max_packet_size_words = (self.packet_size - 6) / 4;
-
-        let data_chunk_len = max_packet_size_words as usize;
-
-        for (i, chunk) in values.chunks_mut(data_chunk_len).enumerate() {
-            let request =
-                TransferBlockRequest::read_request(register_address, port, chunk.len() as u16);
-
-            tracing::debug!("Transfer block: chunk={}, len={} bytes", i, chunk.len() * 4);
-
-            let resp: TransferBlockResponse =
-                commands::send_command(&mut self.device, request).map_err(DebugProbeError::from)?;
-
-            if resp.transfer_response != 1 {
-                return Err(CmsisDapError::ErrorResponse.into());
-            }
-
-            chunk.clone_from_slice(&resp.transfer_data[..]);
-        }
-
-        Ok(())
+        self.transfer_blocks(
+            port,
+            register_address,
+            values.len(),
+            |range| TransferBlockRequest::read_request(register_address, port, range.len() as u16),
+            |range, response| values[range].copy_from_slice(&response.transfer_data),
+        )
     }
 
     fn raw_flush(&mut self) -> Result<(), DebugProbeError> {
@@ -746,6 +939,48 @@ impl RawDapAccess for CmsisDap {
 
         Ok(response as u32)
     }
+
+    fn set_swd_settings(&mut self, settings: SwdSettings) -> Result<(), DebugProbeError> {
+        if !(1..=4).contains(&settings.turnaround) {
+            return Err(DebugProbeError::Other(anyhow::anyhow!(
+                "Invalid SWD turnaround period of {} cycles, must be between 1 and 4",
+                settings.turnaround
+            )));
+        }
+
+        self.swd_settings = settings;
+
+        // Apply the settings right away if we are already connected, otherwise they are applied
+        // when attaching.
+        if self.connected {
+            self.configure_swd(swd::configure::ConfigureRequest {
+                turnaround: settings.turnaround,
+                data_phase: settings.data_phase,
+            })?;
+        }
+
+        Ok(())
+    }
+
+    fn set_transfer_idle_cycles(&mut self, idle_cycles: u8) -> Result<(), DebugProbeError> {
+        self.transfer_idle_cycles = idle_cycles;
+
+        // Apply the setting right away if we are already connected, otherwise it is applied
+        // when attaching.
+        if self.connected {
+            self.transfer_configure(ConfigureRequest {
+                idle_cycles,
+                wait_retry: 0xffff,
+                match_retry: 0,
+            })?;
+        }
+
+        Ok(())
+    }
+
+    fn transfer_idle_cycles(&self) -> Result<u8, DebugProbeError> {
+        Ok(self.transfer_idle_cycles)
+    }
 }
 
 impl DapProbe for CmsisDap {}
@@ -802,6 +1037,7 @@ impl SwoAccess for CmsisDap {
                 baud
             );
         }
+        self.swo_baud = Some(baud);
 
         self.start_swo_capture()?;
 
@@ -854,6 +1090,83 @@ impl SwoAccess for CmsisDap {
     fn swo_buffer_size(&mut self) -> Option<usize> {
         self.swo_buffer_size
     }
+
+    fn swo_stream_source(&mut self) -> Option<Box<dyn SwoStreamSource>> {
+        // Only the streaming transport has an endpoint which is independent of the commands.
+        if !self.swo_active || !self.swo_streaming {
+            return None;
+        }
+
+        self.device
+            .swo_stream_endpoint()
+            .map(|endpoint| Box::new(endpoint) as Box<dyn SwoStreamSource>)
+    }
+
+    fn read_swo_timestamped(&mut self, timeout: Duration) -> Result<SwoChunk, ProbeRsError> {
+        // Timestamps are only available through the extended status in polled mode.
+        if !self.swo_active || self.swo_streaming || self.timestamp_frequency.is_none() {
+            return Ok(SwoChunk {
+                data: self.read_swo_timeout(timeout)?,
+                timestamp: None,
+            });
+        }
+
+        let deadline = std::time::Instant::now() + timeout;
+
+        loop {
+            // The extended status returns the timestamp of the next trace data, which is the
+            // first byte returned by the following data request.
+            let status = self.get_swo_extended_status(swo::ExtendedStatusRequest {
+                request_status: true,
+                request_count: false,
+                request_index_timestamp: true,
+            })?;
+            status.status.check().map_err(DebugProbeError::from)?;
+
+            let data = self.get_swo_data()?;
+            tracing::trace!("SWO polled data at {}: {:?}", status.timestamp, data);
+
+            let now = std::time::Instant::now();
+            if !data.is_empty() || now >= deadline {
+                return Ok(SwoChunk {
+                    timestamp: (!data.is_empty()).then_some(status.timestamp),
+                    data,
+                });
+            }
+
+            std::thread::sleep(SWO_POLL_INTERVAL.min(deadline - now));
+        }
+    }
+
+    fn swo_buffered_bytes(&mut self) -> Result<usize, ProbeRsError> {
+        if !self.swo_active {
+            return Ok(0);
+        }
+
+        let response = self.get_swo_status()?;
+        response.status.check().map_err(DebugProbeError::from)?;
+
+        Ok(response.count as usize)
+    }
+
+    fn swo_baud_rate(&mut self) -> Option<u32> {
+        self.swo_baud
+    }
+
+    fn swo_timestamp_frequency(&mut self) -> Option<u32> {
+        // Streaming transport does not provide timestamps.
+        if self.swo_streaming {
+            None
+        } else {
+            self.timestamp_frequency
+        }
+    }
+}
+
+impl SwoStreamSource for SwoStreamEndpoint {
+    fn read_swo_stream(&mut self, timeout: Duration) -> Result<Vec<u8>, ProbeRsError> {
+        Ok(self.read(timeout).map_err(anyhow::Error::from)?)
+    }
 }
 
 impl Drop for CmsisDap {
@@ -5,7 +5,7 @@ use crate::{
 };
 use hidapi::HidApi;
 use rusb::{constants::LIBUSB_CLASS_HID, Device, DeviceDescriptor, UsbContext};
-use std::time::Duration;
+use std::{sync::Arc, time::Duration};
 
 /// Finds all CMSIS-DAP devices, either v1 (HID) or v2 (WinUSB Bulk).
 ///
@@ -146,6 +146,29 @@ fn get_cmsisdap_hid_info(device: &hidapi::DeviceInfo) -> Option<DebugProbeInfo>
     None
 }
 
+/// Build a selector which matches the device of the given handle, if its descriptors can be read.
+pub(super) fn selector_from_handle(
+    handle: &rusb::DeviceHandle<rusb::Context>,
+) -> Option<DebugProbeSelector> {
+    let timeout = Duration::from_millis(100);
+    let d_desc = handle.device().device_descriptor().ok()?;
+    let serial_number = handle
+        .read_languages(timeout)
+        .ok()
+        .and_then(|langs| langs.get(0).cloned())
+        .and_then(|lang| {
+            handle
+                .read_serial_number_string(lang, &d_desc, timeout)
+                .ok()
+        });
+
+    Some(DebugProbeSelector {
+        vendor_id: d_desc.vendor_id(),
+        product_id: d_desc.product_id(),
+        serial_number,
+    })
+}
+
 /// Attempt to open the given device in CMSIS-DAP v2 mode
 pub fn open_v2_device(device: Device<rusb::Context>) -> Option<CmsisDapDevice> {
     // Open device handle and read basic information
@@ -209,7 +232,7 @@ pub fn open_v2_device(device: Device<rusb::Context>) -> Option<CmsisDapDevice> {
                 Ok(()) => {
                     tracing::debug!("Opening {:04x}:{:04x} in CMSIS-DAPv2 mode", vid, pid);
                     return Some(CmsisDapDevice::V2 {
-                        handle,
+                        handle: Arc::new(handle),
                         out_ep: eps[0].address(),
                         in_ep: eps[1].address(),
                         swo_ep,
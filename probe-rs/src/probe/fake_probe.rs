@@ -2,7 +2,7 @@ use std::{fmt::Debug, sync::Arc};
 
 use crate::{
     architecture::arm::{
-        ap::{memory_ap::mock::MockMemoryAp, AccessPort, MemoryAp},
+        ap::{memory_ap::mock::MockMemoryAp, AccessPort, ApType, MemoryAp},
         communication_interface::{
             ArmDebugState, Initialized, SwdSequence, Uninitialized, UninitializedArmProbe,
         },
@@ -280,6 +280,7 @@ impl ArmProbeInterface for FakeArmInterface<Initialized> {
     fn memory_interface(&mut self, access_port: MemoryAp) -> Result<Box<dyn ArmProbe + '_>, Error> {
         let ap_information = MemoryApInformation {
             address: access_port.ap_address(),
+            ap_type: ApType::AmbaAhb3,
             supports_only_32bit_data_size: false,
             debug_base_address: 0xf000_0000,
             supports_hnonsec: false,
@@ -1,24 +1,37 @@
 use crate::architecture::arm::sequences::{ArmDebugSequence, DefaultArmSequence};
 use crate::architecture::arm::{ApAddress, DpAddress};
 use crate::config::{ChipInfo, RegistryError, Target, TargetSelector};
-use crate::core::{Architecture, CoreState, SpecificCoreState};
+use crate::core::{share_breakpoint_units, Architecture, CoreState, SpecificCoreState};
 use crate::{
     architecture::{
         arm::{
             ap::{GenericAp, MemoryAp},
             communication_interface::{ArmProbeInterface, MemoryApInformation},
-            component::TraceSink,
+            component::{
+                ItmDecoder, ItmPacket, PcSampleRate, TmcVariant, TraceCapture, TraceConfig,
+                TraceConfiguration, TraceDiagnosis, TraceSetupReport, TraceSink, TraceStage,
+                WatchpointHit,
+            },
+            core::trace_counters::TraceCounters,
             memory::{Component, CoresightComponent},
-            ApInformation, SwoReader,
+            ApInformation, SwoChunk, SwoReader, SwoStream,
         },
-        riscv::communication_interface::RiscvCommunicationInterface,
+        riscv::communication_interface::{RiscvCommunicationInterface, RiscvError},
     },
     config::DebugSequence,
 };
-use crate::{AttachMethod, Core, CoreType, DebugProbeError, Error, FakeProbe, Probe};
+use crate::{
+    AttachMethod, Core, CoreInformation, CoreStatus, CoreType, DebugProbeError, Error, FakeProbe,
+    HaltReason, Probe,
+};
 use anyhow::anyhow;
+use std::collections::BTreeMap;
 use std::ops::DerefMut;
-use std::{fmt, sync::Arc, time::Duration};
+use std::{
+    fmt,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 /// The `Session` struct represents an active debug session.
 ///
@@ -44,6 +57,15 @@ pub struct Session {
     interface: ArchitectureInterface,
     cores: Vec<(SpecificCoreState, CoreState)>,
     configured_trace_sink: Option<TraceSink>,
+    /// Whether the traced core produces trace data at a high bandwidth, e.g. a Cortex-M7.
+    high_bandwidth_trace: bool,
+    /// Whether the TMC was full when trace memory was last read.
+    trace_memory_overflowed: bool,
+    /// Decodes the ITM data for [Session::read_trace_ports], holding back incomplete packets.
+    itm_decoder: ItmDecoder,
+    /// The ITM stimulus ports which were enabled with the trace configuration, with one bit per
+    /// port.
+    itm_stimulus_ports: u32,
 }
 
 enum ArchitectureInterface {
@@ -105,7 +127,22 @@ impl ArchitectureInterface {
 
                 core.attach_arm(core_state, memory, target)
             }
-            ArchitectureInterface::Riscv(state) => core.attach_riscv(core_state, state),
+            ArchitectureInterface::Riscv(state) => {
+                let config = target
+                    .cores
+                    .get(core_state.id())
+                    .ok_or_else(|| Error::CoreNotFound(core_state.id()))?;
+                let hart = match &config.core_access_options {
+                    probe_rs_target::CoreAccessOptions::Riscv(opt) => opt.hart_id.unwrap_or(0),
+                    probe_rs_target::CoreAccessOptions::Arm(_) => {
+                        unreachable!("This should never happen. Please file a bug if it does.")
+                    }
+                };
+
+                state.select_hart(hart)?;
+
+                core.attach_riscv(core_state, state)
+            }
         }
     }
 }
@@ -120,7 +157,7 @@ impl Session {
     ) -> Result<Self, Error> {
         let (mut probe, target) = get_target_from_selector(target, attach_method, probe)?;
 
-        let cores = target
+        let mut cores: Vec<_> = target
             .cores
             .iter()
             .enumerate()
@@ -132,6 +169,8 @@ impl Session {
             })
             .collect();
 
+        share_breakpoint_units(&mut cores);
+
         let mut session = match target.architecture() {
             Architecture::Arm => {
                 let config = target.cores[0].clone();
@@ -269,6 +308,10 @@ impl Session {
                         interface: ArchitectureInterface::Arm(interface),
                         cores,
                         configured_trace_sink: None,
+                        high_bandwidth_trace: false,
+                        trace_memory_overflowed: false,
+                        itm_decoder: ItmDecoder::new(),
+                        itm_stimulus_ports: u32::MAX,
                     };
 
                     {
@@ -300,6 +343,10 @@ impl Session {
                         interface: ArchitectureInterface::Arm(interface),
                         cores,
                         configured_trace_sink: None,
+                        high_bandwidth_trace: false,
+                        trace_memory_overflowed: false,
+                        itm_decoder: ItmDecoder::new(),
+                        itm_stimulus_ports: u32::MAX,
                     }
                 }
             }
@@ -324,6 +371,10 @@ impl Session {
                     interface: ArchitectureInterface::Riscv(Box::new(interface)),
                     cores,
                     configured_trace_sink: None,
+                    high_bandwidth_trace: false,
+                    trace_memory_overflowed: false,
+                    itm_decoder: ItmDecoder::new(),
+                    itm_stimulus_ports: u32::MAX,
                 };
 
                 {
@@ -372,6 +423,39 @@ impl Session {
             .collect()
     }
 
+    /// Returns the numbers of the other cores, which share the hardware breakpoint units with the
+    /// core `core_index`.
+    ///
+    /// Hardware breakpoints are always set in the breakpoint unit of the [`Core`] they are set on,
+    /// e.g. the FPB behind the AP of a Cortex-M core. On some multicore chips, multiple cores are
+    /// accessed through the same AP, and then share this unit. A breakpoint set on one of these
+    /// cores then also applies to the others, and they compete for the same breakpoint units.
+    ///
+    /// The session records which of these cores set each breakpoint. A core never uses a unit
+    /// which holds a breakpoint of another core, and [`Core::clear_hw_breakpoint`] and
+    /// [`Core::clear_all_hw_breakpoints`] only clear the breakpoints set on the core itself.
+    pub fn cores_sharing_breakpoints(&self, core_index: usize) -> Result<Vec<usize>, Error> {
+        let (core, state) = self
+            .cores
+            .get(core_index)
+            .ok_or(Error::CoreNotFound(core_index))?;
+
+        Ok(self
+            .cores
+            .iter()
+            .enumerate()
+            .filter(|(index, (other_core, other_state))| {
+                *index != core_index
+                    && state.shares_breakpoints_with(
+                        core.core_type(),
+                        other_state,
+                        other_core.core_type(),
+                    )
+            })
+            .map(|(index, _)| index)
+            .collect())
+    }
+
     /// Attaches to the core with the given number.
     ///
     /// ## Usage
@@ -396,6 +480,153 @@ impl Session {
         self.interface.attach(core, core_state, &self.target)
     }
 
+    /// Halt all cores of the target.
+    ///
+    /// On ARM targets, the halt requests are written to the cores one after another, before
+    /// waiting for any of them to halt. The halts are not synchronized, e.g. with a cross trigger
+    /// interface, so the cores halt at slightly different times.
+    ///
+    /// On RISC-V targets, all harts are halted with a single request if the debug module supports
+    /// hart arrays, and the halt request of every hart is kept until all of them halted, or the
+    /// timeout elapsed.
+    ///
+    /// Returns whether the halt succeeded for each core, in the order of [`Session::list_cores`].
+    #[tracing::instrument(skip(self))]
+    pub fn halt_all(
+        &mut self,
+        timeout: Duration,
+    ) -> Result<Vec<Result<CoreInformation, Error>>, Error> {
+        let core_count = self.cores.len();
+
+        if matches!(self.interface, ArchitectureInterface::Riscv(_)) {
+            return self.halt_all_harts(timeout);
+        }
+
+        let requests: Vec<Result<(), Error>> = (0..core_count)
+            .map(|core_index| self.core(core_index)?.request_halt())
+            .collect();
+
+        let results = requests
+            .into_iter()
+            .enumerate()
+            .map(|(core_index, request)| {
+                request?;
+
+                let mut core = self.core(core_index)?;
+                core.wait_for_core_halted(timeout)?;
+
+                let pc = core.read_core_reg(core.registers().program_counter())?;
+
+                Ok(CoreInformation { pc })
+            })
+            .collect();
+
+        Ok(results)
+    }
+
+    /// [Session::halt_all] for RISC-V targets.
+    fn halt_all_harts(
+        &mut self,
+        timeout: Duration,
+    ) -> Result<Vec<Result<CoreInformation, Error>>, Error> {
+        let interface = self.get_riscv_interface()?;
+        interface.request_halt_all_harts()?;
+
+        // Selecting a hart to access it clears its halt request, so no core is accessed until
+        // all harts halted.
+        let halted = interface.wait_for_all_harts_halted(timeout);
+        interface.clear_halt_request_all_harts()?;
+        let halted = halted?;
+
+        let results = (0..self.cores.len())
+            .map(|core_index| {
+                let hart = self.riscv_hart(core_index)?;
+
+                if !halted.get(hart as usize).copied().unwrap_or(false) {
+                    return Err(Error::Probe(DebugProbeError::Timeout));
+                }
+
+                let mut core = self.core(core_index)?;
+                let pc = core.read_core_reg(core.registers().program_counter())?;
+
+                Ok(CoreInformation { pc })
+            })
+            .collect();
+
+        Ok(results)
+    }
+
+    /// The hart of the RISC-V core with the given index.
+    fn riscv_hart(&self, core_index: usize) -> Result<u32, Error> {
+        let core_id = self.cores[core_index].1.id();
+        let config = self
+            .target
+            .cores
+            .get(core_id)
+            .ok_or(Error::CoreNotFound(core_id))?;
+
+        match &config.core_access_options {
+            probe_rs_target::CoreAccessOptions::Riscv(opt) => Ok(opt.hart_id.unwrap_or(0)),
+            probe_rs_target::CoreAccessOptions::Arm(_) => {
+                unreachable!("This should never happen. Please file a bug if it does.")
+            }
+        }
+    }
+
+    /// Resume all cores of the target.
+    ///
+    /// On ARM targets, the cores are resumed one after another with [`Core::run`], so they don't
+    /// start running at the same time.
+    ///
+    /// On RISC-V targets, every core first executes a single instruction step, like [`Core::run`]
+    /// does, so that it doesn't halt again on a breakpoint at the current instruction. Every hart
+    /// therefore executes one instruction before the harts are resumed. The harts are then resumed
+    /// with a single request if the debug module supports hart arrays, and one after another
+    /// otherwise. A core whose step failed is not resumed, and the error of the step is returned
+    /// for it.
+    ///
+    /// Returns whether each core acknowledged the resume request, in the order of [`Session::list_cores`].
+    #[tracing::instrument(skip(self))]
+    pub fn resume_all(&mut self) -> Result<Vec<Result<(), Error>>, Error> {
+        let core_count = self.cores.len();
+
+        if !matches!(self.interface, ArchitectureInterface::Riscv(_)) {
+            return Ok((0..core_count)
+                .map(|core_index| self.core(core_index)?.run())
+                .collect());
+        }
+
+        let steps: Vec<Result<u32, Error>> = (0..core_count)
+            .map(|core_index| {
+                self.core(core_index)?.step()?;
+                self.riscv_hart(core_index)
+            })
+            .collect();
+
+        let harts: Vec<u32> = steps
+            .iter()
+            .filter_map(|step| step.as_ref().ok().copied())
+            .collect();
+
+        let acknowledged = self.get_riscv_interface()?.resume_harts(&harts)?;
+
+        let results = steps
+            .into_iter()
+            .map(|step| {
+                let hart = step?;
+
+                let index = harts.iter().position(|&resumed| resumed == hart);
+                if index.and_then(|index| acknowledged.get(index).copied()) == Some(true) {
+                    Ok(())
+                } else {
+                    Err(RiscvError::RequestNotAcknowledged.into())
+                }
+            })
+            .collect();
+
+        Ok(results)
+    }
+
     /// Read available trace data from the specified data sink.
     ///
     /// This method is only supported for ARM-based targets, and will
@@ -417,14 +648,208 @@ impl Session {
                 panic!("Probe-rs does not yet support reading parallel trace ports");
             }
 
-            TraceSink::TraceMemory => {
+            TraceSink::TracePort { .. } => Err(Error::Probe(
+                DebugProbeError::CommandNotSupportedByProbe("reading the parallel trace port"),
+            )),
+
+            TraceSink::TraceMemory
+            | TraceSink::CircularMemory
+            | TraceSink::TriggeredMemory { .. }
+            | TraceSink::SystemMemory { .. } => {
+                let high_bandwidth = self.high_bandwidth_trace;
+                let system_memory_ap = self.system_memory_ap();
+                let components = self.get_arm_components()?;
+                let interface = self.get_arm_interface()?;
+                let overflowed = crate::architecture::arm::component::trace_memory_overflowed(
+                    interface,
+                    &components,
+                )?;
+                let trace = crate::architecture::arm::component::read_trace_memory(
+                    interface,
+                    &components,
+                    high_bandwidth,
+                    system_memory_ap,
+                )?;
+                self.trace_memory_overflowed = overflowed;
+                Ok(trace)
+            }
+        }
+    }
+
+    /// Read available ITM trace data, separated by the stimulus port it was written to.
+    ///
+    /// The data is read with [Session::read_trace_data], so this is supported for the same trace
+    /// sinks. Packets which are split between two reads are returned by the next read. Packets
+    /// other than instrumentation packets, e.g. DWT hardware packets, are skipped.
+    #[tracing::instrument(skip(self))]
+    pub fn read_trace_ports(&mut self) -> Result<BTreeMap<u8, Vec<u8>>, Error> {
+        let data = self.read_trace_data()?;
+        self.itm_decoder.feed(&data);
+
+        let mut ports: BTreeMap<u8, Vec<u8>> = BTreeMap::new();
+        for packet in &mut self.itm_decoder {
+            if let ItmPacket::Instrumentation { port, data } = packet {
+                ports.entry(port).or_default().extend(data);
+            }
+        }
+
+        Ok(ports)
+    }
+
+    /// Enable only the ITM stimulus ports set in `mask`, with one bit per port, e.g. to reduce
+    /// the trace bandwidth.
+    ///
+    /// Every bit of `privilege_mask` restricts a group of 8 ports to privileged code, see
+    /// [`Itm::set_privilege`](crate::architecture::arm::component::Itm::set_privilege). Tracing
+    /// has to be set up with [Session::setup_tracing] before, which enables all ports.
+    pub fn enable_itm_ports(&mut self, mask: u32, privilege_mask: u8) -> Result<(), Error> {
+        let components = self.get_arm_components()?;
+        let interface = self.get_arm_interface()?;
+        crate::architecture::arm::component::configure_itm_ports(
+            interface,
+            &components,
+            mask,
+            privilege_mask,
+        )?;
+
+        self.itm_stimulus_ports = mask;
+
+        Ok(())
+    }
+
+    /// Read the trace data of all trace sources from trace memory, separated by their ATID.
+    ///
+    /// This is only supported if tracing has been configured with [TraceSink::TraceMemory],
+    /// [TraceSink::CircularMemory], [TraceSink::TriggeredMemory] or [TraceSink::SystemMemory]. If
+    /// the data doesn't look like formatted frames, which happens if the formatter is
+    /// misconfigured, an error is returned instead of garbage data.
+    #[tracing::instrument(skip(self))]
+    pub fn read_trace_sources(&mut self) -> Result<BTreeMap<u8, Vec<u8>>, Error> {
+        match self.configured_trace_sink {
+            Some(
+                TraceSink::TraceMemory
+                | TraceSink::CircularMemory
+                | TraceSink::TriggeredMemory { .. }
+                | TraceSink::SystemMemory { .. },
+            ) => {
+                let high_bandwidth = self.high_bandwidth_trace;
+                let system_memory_ap = self.system_memory_ap();
+                let components = self.get_arm_components()?;
+                let interface = self.get_arm_interface()?;
+                let overflowed = crate::architecture::arm::component::trace_memory_overflowed(
+                    interface,
+                    &components,
+                )?;
+                let trace = crate::architecture::arm::component::read_trace_memory_sources(
+                    interface,
+                    &components,
+                    high_bandwidth,
+                    system_memory_ap,
+                )?;
+                self.trace_memory_overflowed = overflowed;
+                Ok(trace)
+            }
+            Some(_) => Err(Error::Other(anyhow!(
+                "Reading separate trace sources requires the trace memory sink"
+            ))),
+            None => Err(Error::Other(anyhow!("Tracing has not been configured"))),
+        }
+    }
+
+    /// Read all deformatted trace data from trace memory as `(ATID, byte)` pairs, without
+    /// filtering by trace source.
+    ///
+    /// This is intended for debugging the trace setup, and is only supported if tracing has
+    /// been configured with [TraceSink::TraceMemory], [TraceSink::CircularMemory],
+    /// [TraceSink::TriggeredMemory] or [TraceSink::SystemMemory].
+    #[tracing::instrument(skip(self))]
+    pub fn read_trace_memory_raw(&mut self) -> Result<Vec<(u8, u8)>, Error> {
+        match self.configured_trace_sink {
+            Some(
+                TraceSink::TraceMemory
+                | TraceSink::CircularMemory
+                | TraceSink::TriggeredMemory { .. }
+                | TraceSink::SystemMemory { .. },
+            ) => {
+                let high_bandwidth = self.high_bandwidth_trace;
+                let system_memory_ap = self.system_memory_ap();
                 let components = self.get_arm_components()?;
                 let interface = self.get_arm_interface()?;
-                crate::architecture::arm::component::read_trace_memory(interface, &components)
+                let overflowed = crate::architecture::arm::component::trace_memory_overflowed(
+                    interface,
+                    &components,
+                )?;
+                let trace = crate::architecture::arm::component::read_trace_memory_raw(
+                    interface,
+                    &components,
+                    high_bandwidth,
+                    system_memory_ap,
+                )?;
+                self.trace_memory_overflowed = overflowed;
+                Ok(trace)
             }
+            Some(_) => Err(Error::Other(anyhow!(
+                "Reading raw trace memory requires the trace memory sink"
+            ))),
+            None => Err(Error::Other(anyhow!("Tracing has not been configured"))),
         }
     }
 
+    /// Returns `true` if the trace memory controller (TMC) was full when trace memory was last
+    /// read, which means that trace data was lost at the sink.
+    ///
+    /// This is distinct from overflows of the trace sources, e.g. the ITM, which are reported in
+    /// the trace data of the source itself. With [TraceSink::TraceMemory], a full ETF stalls the
+    /// trace stream, so the sources drop data. With [TraceSink::CircularMemory],
+    /// [TraceSink::TriggeredMemory] or [TraceSink::SystemMemory], and with an ETB, the oldest data was overwritten, which is
+    /// expected for a long running capture.
+    pub fn trace_memory_overflowed(&self) -> bool {
+        self.trace_memory_overflowed
+    }
+
+    /// The memory AP through which the buffer of [TraceSink::SystemMemory] is read, if it is
+    /// configured.
+    fn system_memory_ap(&self) -> Option<u8> {
+        match self.configured_trace_sink {
+            Some(TraceSink::SystemMemory { memory_ap, .. }) => Some(memory_ap),
+            _ => None,
+        }
+    }
+
+    /// Detect whether the trace memory controller (TMC) is an ETB, ETF or ETR.
+    ///
+    /// The variant determines the supported trace sinks: An ETR writes trace data into system
+    /// memory and requires [TraceSink::SystemMemory], while an ETB or ETF use their own trace
+    /// RAM with [TraceSink::TraceMemory], [TraceSink::CircularMemory] or
+    /// [TraceSink::TriggeredMemory].
+    pub fn trace_memory_variant(&mut self) -> Result<TmcVariant, Error> {
+        let components = self.get_arm_components()?;
+        let interface = self.get_arm_interface()?;
+        crate::architecture::arm::component::trace_memory_variant(interface, &components)
+    }
+
+    /// Read the trace data of all trace sources from trace memory into a [TraceCapture],
+    /// which can be exported for external trace decoders.
+    ///
+    /// `trace_clock` is the frequency of the trace clock in Hz, if known.
+    #[tracing::instrument(skip(self))]
+    pub fn read_trace_capture(
+        &mut self,
+        core_index: usize,
+        trace_clock: Option<u32>,
+    ) -> Result<TraceCapture, Error> {
+        let core_type = self
+            .list_cores()
+            .into_iter()
+            .find(|(index, _)| *index == core_index)
+            .map(|(_, core_type)| core_type)
+            .ok_or(Error::CoreNotFound(core_index))?;
+
+        let streams = self.read_trace_sources()?;
+
+        Ok(TraceCapture::new(core_type, trace_clock, streams))
+    }
+
     /// Returns an implementation of [std::io::Read] that wraps [SwoAccess::read_swo].
     ///
     /// The implementation buffers all available bytes from
@@ -438,6 +863,45 @@ impl Session {
         Ok(SwoReader::new(interface))
     }
 
+    /// Returns a stream of the ITM packets received over SWO, which doesn't borrow the session.
+    ///
+    /// If the probe supports it, e.g. with the SWO streaming endpoint of a CMSIS-DAP v2 probe, the
+    /// SWO data is received by a background thread, so it keeps flowing while the session is used
+    /// to e.g. step the core. Otherwise, the probe is polled by [`SwoStream::read_packets`].
+    /// Tracing has to be set up with [Session::setup_tracing] before.
+    pub fn swo_stream(&mut self) -> Result<SwoStream, Error> {
+        let interface = self.get_arm_interface()?;
+        Ok(SwoStream::new(interface.swo_stream_source()))
+    }
+
+    /// Read SWO data for up to `timeout`, together with the probe-side timestamp of the first
+    /// received byte.
+    ///
+    /// The timestamp is only available if the probe supports timestamping SWO data. The
+    /// frequency of the timestamp timer is returned by [Session::swo_timestamp_frequency].
+    pub fn read_swo_timestamped(&mut self, timeout: Duration) -> Result<SwoChunk, Error> {
+        let interface = self.get_arm_interface()?;
+        interface.read_swo_timestamped(timeout)
+    }
+
+    /// The number of bytes of SWO data currently buffered in the probe.
+    ///
+    /// This is `0` if SWO is not available, or the probe does not report how much data it
+    /// has buffered.
+    pub fn swo_buffered_bytes(&mut self) -> Result<usize, Error> {
+        match &mut self.interface {
+            ArchitectureInterface::Arm(interface) => interface.swo_buffered_bytes(),
+            // RISC-V targets have no SWO.
+            ArchitectureInterface::Riscv(_) => Ok(0),
+        }
+    }
+
+    /// The frequency in Hz of the probe timer used to timestamp SWO data, if supported.
+    pub fn swo_timestamp_frequency(&mut self) -> Result<Option<u32>, Error> {
+        let interface = self.get_arm_interface()?;
+        Ok(interface.swo_timestamp_frequency())
+    }
+
     /// Get the Arm probe interface.
     pub fn get_arm_interface(&mut self) -> Result<&mut dyn ArmProbeInterface, Error> {
         let interface = match &mut self.interface {
@@ -572,6 +1036,24 @@ impl Session {
         }
     }
 
+    /// Mass erase the target using its chip erase sequence, without programming anything.
+    ///
+    /// Depending on the device, this also removes read protection and resets the device to its
+    /// factory state, which can be the only way to unlock it.
+    ///
+    /// # Errors
+    /// Returns [Error::MassEraseNotSupported] if the target definition has no mass erase
+    /// sequence. In that case, [erase_all](crate::flashing::erase_all) can be used to erase the
+    /// flash using the flash algorithm instead.
+    #[tracing::instrument(skip(self))]
+    pub fn mass_erase(&mut self) -> Result<(), Error> {
+        if !self.has_sequence_erase_all() {
+            return Err(Error::MassEraseNotSupported(self.target.name.clone()));
+        }
+
+        self.sequence_erase_all()
+    }
+
     /// Reads all the available ARM CoresightComponents of the currently attached target.
     ///
     /// This will recursively parse the Romtable of the attached target
@@ -632,17 +1114,44 @@ impl Session {
         &self.target
     }
 
+    /// Determine the current frequency of the core clock in Hz, from the clock configuration
+    /// registers of the target.
+    ///
+    /// This is only supported for targets whose debug sequence models the clock tree, see
+    /// [`ArmDebugSequence::core_clock_hz`].
+    /// If the core clock is derived from an external oscillator, its frequency has to be passed as
+    /// `external_clock_hz`, as it can't be read from the target.
+    ///
+    /// On most Cortex-M devices, the TPIU is clocked by the core clock, so the result can be used
+    /// as the `tpiu_clk` of a [`SwoConfig`](crate::architecture::arm::SwoConfig).
+    pub fn core_clock_hz(&mut self, external_clock_hz: Option<u32>) -> Result<u32, Error> {
+        let sequence_handle = match &self.target.debug_sequence {
+            DebugSequence::Arm(sequence) => sequence.clone(),
+            DebugSequence::Riscv(_) => {
+                return Err(Error::ArchitectureRequired(&["ARMv6", "ARMv7", "ARMv8"]))
+            }
+        };
+
+        let interface = self.get_arm_interface()?;
+        sequence_handle.core_clock_hz(interface, external_clock_hz)
+    }
+
     /// Configure the target and probe for serial wire view (SWV) tracing.
+    ///
+    /// The trace pins and clocks are set up by the `trace_prepare` sequence of the target, see
+    /// [`ArmDebugSequence::trace_prepare`](crate::architecture::arm::sequences::ArmDebugSequence::trace_prepare),
+    /// before the probe and the trace components are configured.
     pub fn setup_tracing(
         &mut self,
         core_index: usize,
         destination: TraceSink,
     ) -> Result<(), Error> {
         // Enable tracing on the target
-        {
+        let high_bandwidth = {
             let mut core = self.core(core_index)?;
             crate::architecture::arm::component::enable_tracing(&mut core)?;
-        }
+            crate::architecture::arm::component::is_high_bandwidth_trace_core(&mut core)?
+        };
 
         let sequence_handle = match &self.target.debug_sequence {
             DebugSequence::Arm(sequence) => sequence.clone(),
@@ -654,26 +1163,299 @@ impl Session {
         let components = self.get_arm_components()?;
         let interface = self.get_arm_interface()?;
 
+        // Set up the trace pins and clocks before the probe starts capturing.
+        sequence_handle.trace_prepare(interface, &components, &destination)?;
+
         // Configure SWO on the probe when the trace sink is configured for a serial output. Note
         // that on some architectures, the TPIU is configured to drive SWO.
-        match destination {
-            TraceSink::Swo(ref config) => {
-                interface.enable_swo(config)?;
+        let destination = match destination {
+            TraceSink::Swo(config) if config.auto_baud() => TraceSink::Swo(
+                crate::architecture::arm::component::negotiate_swo_baud(interface, &config)?,
+            ),
+            TraceSink::Tpiu(config) if config.auto_baud() => TraceSink::Tpiu(
+                crate::architecture::arm::component::negotiate_swo_baud(interface, &config)?,
+            ),
+            TraceSink::Swo(config) | TraceSink::Tpiu(config) => {
+                interface.enable_swo(&config)?;
+                destination
             }
-            TraceSink::Tpiu(ref config) => {
-                interface.enable_swo(config)?;
+            TraceSink::TracePort { port_width } => {
+                interface.enable_trace_port(port_width)?;
+                destination
             }
-            TraceSink::TraceMemory => {}
-        }
+            TraceSink::TraceMemory
+            | TraceSink::CircularMemory
+            | TraceSink::TriggeredMemory { .. }
+            | TraceSink::SystemMemory { .. } => destination,
+        };
 
         sequence_handle.trace_start(interface, &components, &destination)?;
         crate::architecture::arm::component::setup_tracing(interface, &components, &destination)?;
 
         self.configured_trace_sink.replace(destination);
+        self.high_bandwidth_trace = high_bandwidth;
+        self.itm_stimulus_ports = u32::MAX;
+
+        Ok(())
+    }
+
+    /// Configure tracing as described by a [`TraceConfig`], e.g. one loaded with
+    /// [`TraceConfig::load`].
+    ///
+    /// This sets up tracing to the configured sink with [Session::setup_tracing], then applies the
+    /// stimulus port and timestamp settings to the ITM, and starts the data traces and PC
+    /// sampling.
+    pub fn setup_tracing_from_config(
+        &mut self,
+        core_index: usize,
+        config: &TraceConfig,
+    ) -> Result<(), Error> {
+        self.setup_tracing(core_index, config.sink)?;
+
+        let components = self.get_arm_components()?;
+        let interface = self.get_arm_interface()?;
+        crate::architecture::arm::component::configure_itm(interface, &components, config)?;
+        self.itm_stimulus_ports = config.stimulus_ports;
+
+        for data_trace in &config.data_traces {
+            self.add_swv_data_trace(data_trace.unit, data_trace.address)?;
+        }
+
+        if let Some(rate) = config.pc_sampling {
+            self.enable_pc_sampling(rate)?;
+        }
 
         Ok(())
     }
 
+    /// The SWO baud rate used by [Session::setup_tracing].
+    ///
+    /// If the baud rate was negotiated with the probe, see
+    /// [`SwoConfig::set_auto_baud`](crate::architecture::arm::SwoConfig::set_auto_baud), this is
+    /// the negotiated baud rate. Returns `None` if tracing is not configured for a serial output.
+    pub fn swo_baud_rate(&self) -> Option<u32> {
+        match self.configured_trace_sink {
+            Some(TraceSink::Swo(config) | TraceSink::Tpiu(config)) => Some(config.baud()),
+            _ => None,
+        }
+    }
+
+    /// Read back the configuration of the trace components, and compare it to the configuration
+    /// done by [Session::setup_tracing].
+    ///
+    /// The enabled ITM stimulus ports are compared to the ports enabled by the last call of
+    /// [Session::setup_tracing], which enables all ports, [Session::enable_itm_ports] or
+    /// [Session::setup_tracing_from_config].
+    ///
+    /// This only reads from the target, and can be used to find out why no trace data is received.
+    pub fn verify_trace_setup(&mut self) -> Result<TraceSetupReport, Error> {
+        let sink = self
+            .configured_trace_sink
+            .ok_or_else(|| anyhow!("Tracing has not been configured"))?;
+        let stimulus_ports = self.itm_stimulus_ports;
+
+        let components = self.get_arm_components()?;
+        let interface = self.get_arm_interface()?;
+
+        crate::architecture::arm::component::verify_trace_setup(
+            interface,
+            &components,
+            &sink,
+            stimulus_ports,
+        )
+    }
+
+    /// Set up tracing to `sink` like [Session::setup_tracing], and check each stage of the trace
+    /// chain in order, to find out why no trace data is received.
+    ///
+    /// The stages are described by [`TraceStage`]. The last stage writes a marker to ITM stimulus
+    /// port 31 from the host, and waits for up to `timeout` until it is received in the trace
+    /// data. Trace data received in the meantime is discarded. This stage is skipped for
+    /// [TraceSink::Tpiu] and [TraceSink::TracePort], as the trace data of a parallel trace port
+    /// cannot be read.
+    ///
+    /// Errors which are not specific to a stage, e.g. a failed probe transfer, are returned as
+    /// an error instead of a failed stage.
+    pub fn diagnose_trace(
+        &mut self,
+        core_index: usize,
+        sink: TraceSink,
+        timeout: Duration,
+    ) -> Result<TraceDiagnosis, Error> {
+        let mut diagnosis = TraceDiagnosis::default();
+
+        let components = self.get_arm_components()?;
+        let interface = self.get_arm_interface()?;
+        if let Some(reason) =
+            crate::architecture::arm::component::check_trace_authentication(interface, &components)?
+        {
+            return Ok(diagnosis.fail(TraceStage::DebugAuthentication, reason));
+        }
+        diagnosis.pass(TraceStage::DebugAuthentication);
+
+        let trace_enabled = {
+            let mut core = self.core(core_index)?;
+            crate::architecture::arm::component::enable_tracing(&mut core)?;
+            crate::architecture::arm::component::is_tracing_enabled(&mut core)?
+        };
+        if !trace_enabled {
+            return Ok(diagnosis.fail(TraceStage::TraceClock, "TRCENA in DEMCR cannot be set"));
+        }
+        if let Some(reason) = crate::architecture::arm::component::check_trace_clock(&sink) {
+            return Ok(diagnosis.fail(TraceStage::TraceClock, reason));
+        }
+        diagnosis.pass(TraceStage::TraceClock);
+
+        // The setup mostly fails when configuring the probe or the trace sink.
+        if let Err(error) = self.setup_tracing(core_index, sink) {
+            return Ok(diagnosis.fail(TraceStage::Sink, error.to_string()));
+        }
+
+        let report = self.verify_trace_setup()?;
+        for stage in [TraceStage::Dwt, TraceStage::Itm, TraceStage::Sink] {
+            let mismatch = report
+                .mismatches()
+                .find(|check| crate::architecture::arm::component::stage_of_check(check) == stage);
+
+            if let Some(check) = mismatch {
+                let reason = format!(
+                    "{} is {:#010x}, expected {:#010x} in the bits {:#010x}",
+                    check.name, check.actual, check.expected, check.mask
+                );
+                return Ok(diagnosis.fail(stage, reason));
+            }
+            diagnosis.pass(stage);
+        }
+
+        if matches!(sink, TraceSink::Tpiu(_) | TraceSink::TracePort { .. }) {
+            return Ok(diagnosis);
+        }
+
+        let components = self.get_arm_components()?;
+        let interface = self.get_arm_interface()?;
+        let stimulus_ports =
+            match crate::architecture::arm::component::inject_marker(interface, &components) {
+                Ok(stimulus_ports) => stimulus_ports,
+                Err(error) => return Ok(diagnosis.fail(TraceStage::RoundTrip, error.to_string())),
+            };
+
+        let received = self.wait_for_trace_marker(timeout);
+
+        let components = self.get_arm_components()?;
+        let interface = self.get_arm_interface()?;
+        crate::architecture::arm::component::restore_stimulus_ports(
+            interface,
+            &components,
+            stimulus_ports,
+        )?;
+
+        if received? {
+            diagnosis.pass(TraceStage::RoundTrip);
+            Ok(diagnosis)
+        } else {
+            Ok(diagnosis.fail(
+                TraceStage::RoundTrip,
+                "The marker written to the ITM was not received",
+            ))
+        }
+    }
+
+    /// Read trace data until the marker written by [Session::diagnose_trace] is received, and
+    /// return `false` if it was not received within `timeout`.
+    fn wait_for_trace_marker(&mut self, timeout: Duration) -> Result<bool, Error> {
+        let mut decoder = ItmDecoder::new();
+        let start = Instant::now();
+        loop {
+            let data = self.read_trace_data()?;
+            if crate::architecture::arm::component::find_marker(&mut decoder, &data) {
+                return Ok(true);
+            }
+
+            if start.elapsed() >= timeout {
+                return Ok(false);
+            }
+
+            // Give the sink time to collect more data, instead of polling the probe continuously.
+            std::thread::sleep(crate::architecture::arm::component::MARKER_POLL_INTERVAL);
+        }
+    }
+
+    /// Sample the profiling counters of the DWT for `duration` while the core with the given index
+    /// is running, and return the accumulated counts, e.g. to see how many cycles are spent in
+    /// exception handling.
+    ///
+    /// The core has to be running, as the counters don't count while it is halted. The DWT is
+    /// enabled for the sampling, and the original DEMCR and DWT_CTRL are restored afterwards, so
+    /// this can be called repeatedly during a debug session.
+    ///
+    /// It is only supported on ARMv7-M and ARMv8-M cores which implement the profiling counters.
+    pub fn trace_counters(
+        &mut self,
+        core_index: usize,
+        duration: Duration,
+    ) -> Result<TraceCounters, Error> {
+        crate::architecture::arm::core::trace_counters::sample_trace_counters(
+            self, core_index, duration,
+        )
+    }
+
+    /// Reads which access triggered a watchpoint on the core with the given index.
+    ///
+    /// This reports the DWT comparators which matched, and the auxiliary fault status of the core.
+    /// Returns `None` if the core is not halted because of a watchpoint, i.e. the `DWTTRAP` bit
+    /// of the DFSR was not set. As the `MATCHED` flags of the comparators are cleared when they are
+    /// read, the comparators of a hit are only reported once.
+    pub fn read_watchpoint_hit(
+        &mut self,
+        core_index: usize,
+    ) -> Result<Option<WatchpointHit>, Error> {
+        let auxiliary_fault_status = {
+            let mut core = self.core(core_index)?;
+
+            // The DWT only exists on M-profile cores.
+            if !core.core_type().is_cortex_m() {
+                return Err(Error::ArchitectureRequired(&[
+                    "ARMv6-M", "ARMv7-M", "ARMv8-M",
+                ]));
+            }
+
+            if core.status()? != CoreStatus::Halted(HaltReason::Watchpoint) {
+                return Ok(None);
+            }
+
+            crate::architecture::arm::component::read_auxiliary_fault_status(&mut core)?
+        };
+
+        let components = self.get_arm_components()?;
+        let interface = self.get_arm_interface()?;
+        let comparators =
+            crate::architecture::arm::component::read_dwt_matches(interface, &components)?;
+
+        Ok(Some(WatchpointHit {
+            comparators,
+            auxiliary_fault_status,
+        }))
+    }
+
+    /// Reads the current configuration of the DWT, ITM, TPIU and SWO components.
+    ///
+    /// This allows temporarily using trace components, e.g. the DWT comparators, for other
+    /// purposes and putting the previous configuration back with [Session::restore_trace_config].
+    pub fn save_trace_config(&mut self) -> Result<TraceConfiguration, Error> {
+        let components = self.get_arm_components()?;
+        let interface = self.get_arm_interface()?;
+
+        crate::architecture::arm::component::save_trace_config(interface, &components)
+    }
+
+    /// Restores a trace configuration read with [Session::save_trace_config].
+    pub fn restore_trace_config(&mut self, config: &TraceConfiguration) -> Result<(), Error> {
+        let components = self.get_arm_components()?;
+        let interface = self.get_arm_interface()?;
+
+        crate::architecture::arm::component::restore_trace_config(interface, &components, config)
+    }
+
     /// Configure the target to stop emitting SWV trace data.
     #[tracing::instrument(skip(self))]
     pub fn disable_swv(&mut self, core_index: usize) -> Result<(), Error> {
@@ -699,6 +1481,52 @@ impl Session {
         crate::architecture::arm::component::remove_swv_data_trace(interface, &components, unit)
     }
 
+    /// Enable periodic PC samples, which are emitted as part of the trace data and can be used
+    /// for statistical profiling.
+    ///
+    /// Tracing has to be set up with [Session::setup_tracing] before.
+    pub fn enable_pc_sampling(&mut self, rate: PcSampleRate) -> Result<(), Error> {
+        let components = self.get_arm_components()?;
+        let interface = self.get_arm_interface()?;
+        crate::architecture::arm::component::enable_pc_sampling(interface, &components, rate)
+    }
+
+    /// Disable periodic PC samples.
+    pub fn disable_pc_sampling(&mut self) -> Result<(), Error> {
+        let components = self.get_arm_components()?;
+        let interface = self.get_arm_interface()?;
+        crate::architecture::arm::component::disable_pc_sampling(interface, &components)
+    }
+
+    /// Enable the DWT cycle counter of the given core and reset it to zero, e.g. to measure the
+    /// number of cycles spent in a loop.
+    ///
+    /// Returns an error if the core does not implement the optional cycle counter.
+    pub fn enable_cycle_counter(&mut self, core_index: usize) -> Result<(), Error> {
+        // The DWT is only clocked if trace is enabled in the DEMCR.
+        crate::architecture::arm::component::enable_tracing(&mut self.core(core_index)?)?;
+
+        let components = self.get_arm_components()?;
+        let interface = self.get_arm_interface()?;
+        crate::architecture::arm::component::enable_cycle_counter(interface, &components)
+    }
+
+    /// Read the DWT cycle counter, see [Session::enable_cycle_counter].
+    ///
+    /// The counter wraps around after `u32::MAX` cycles.
+    pub fn cycle_count(&mut self) -> Result<u32, Error> {
+        let components = self.get_arm_components()?;
+        let interface = self.get_arm_interface()?;
+        crate::architecture::arm::component::cycle_count(interface, &components)
+    }
+
+    /// Reset the DWT cycle counter to zero.
+    pub fn reset_cycle_counter(&mut self) -> Result<(), Error> {
+        let components = self.get_arm_components()?;
+        let interface = self.get_arm_interface()?;
+        crate::architecture::arm::component::reset_cycle_counter(interface, &components)
+    }
+
     /// Return the `Architecture` of the currently connected chip.
     pub fn architecture(&self) -> Architecture {
         match self.interface {
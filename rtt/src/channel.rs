@@ -245,6 +245,18 @@ impl UpChannel {
         Ok(self.read_core(core, buf)?.1)
     }
 
+    /// Returns the number of bytes currently waiting in the channel buffer, without consuming
+    /// them.
+    pub fn bytes_available(&self, core: &mut Core) -> Result<usize, Error> {
+        let (write, read) = self.0.read_pointers(core, "up")?;
+
+        Ok(if read > write {
+            self.0.size - read + write
+        } else {
+            write - read
+        } as usize)
+    }
+
     /// Calculates amount of contiguous data available for reading
     fn readable_contiguous(&self, write: u32, read: u32) -> usize {
         (if read > write {
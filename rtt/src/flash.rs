@@ -0,0 +1,61 @@
+use crate::{Error, Rtt};
+use probe_rs::flashing::{download_file, Format};
+use probe_rs::Session;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// How long to wait for the core to halt after the reset.
+const RESET_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// How long to wait for the firmware to initialize the RTT control block after it was started.
+const ATTACH_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Downloads a file of given `format` at `path` to the target, runs it from reset and attaches
+/// to its RTT control block.
+///
+/// Attaching starts right after the firmware was started, which captures the output written
+/// during startup. As long as the size of the up buffers is not exceeded before the control block
+/// is found, no output is lost.
+///
+/// The firmware is run on the first core of the target. Control blocks of previous firmware
+/// which are still in RAM are cleared before the firmware is started, so only the control block
+/// of the new firmware is found. If the firmware does not initialize RTT within a short time,
+/// [`Error::ControlBlockNotFound`] is returned, and the firmware keeps running.
+///
+/// Use the returned [`Rtt`] with [`Session::core`] to read from the channels.
+pub fn flash_and_run_with_rtt(
+    session: &mut Session,
+    path: impl AsRef<Path>,
+    format: Format,
+) -> Result<Rtt, Error> {
+    download_file(session, path, format)?;
+
+    let memory_map = session.target().memory_map.clone();
+    let mut core = session.core(0)?;
+
+    core.reset_and_halt(RESET_TIMEOUT)?;
+
+    // The firmware has not run yet, so any control block in RAM is a leftover.
+    match Rtt::attach(&mut core, &memory_map) {
+        Ok(rtt) => Rtt::clear_control_block(&mut core, rtt.ptr())?,
+        Err(Error::MultipleControlBlocksFound(pointers)) => {
+            for ptr in pointers {
+                Rtt::clear_control_block(&mut core, ptr)?;
+            }
+        }
+        Err(Error::ControlBlockNotFound) => (),
+        Err(e) => return Err(e),
+    }
+
+    core.run()?;
+
+    let start = Instant::now();
+    loop {
+        match Rtt::attach(&mut core, &memory_map) {
+            Err(Error::ControlBlockNotFound) if start.elapsed() < ATTACH_TIMEOUT => {
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            result => return result,
+        }
+    }
+}
@@ -49,6 +49,9 @@ pub use channel::*;
 pub mod channels;
 pub use channels::Channels;
 
+mod flash;
+pub use flash::*;
+
 mod rtt;
 pub use rtt::*;
 
@@ -81,6 +84,10 @@ pub enum Error {
     #[error("Error communicating with probe: {0}")]
     Probe(#[from] probe_rs::Error),
 
+    /// Wraps errors propagated up from downloading a file to the target.
+    #[error("Error downloading the file: {0}")]
+    Download(#[from] probe_rs::flashing::FileDownloadError),
+
     /// Wraps errors propagated up from reading memory on the target.
     #[error("Unexpected error while reading {0} from target memory. Please report this as a bug.")]
     MemoryRead(String),
@@ -1,10 +1,13 @@
 use crate::channel::*;
 use crate::{Channels, Error};
-use probe_rs::{config::MemoryRegion, Core, MemoryInterface};
+use probe_rs::{
+    config::MemoryRegion, Core, CoreStatus, DebugProbeError, HaltReason, MemoryInterface,
+};
 use scroll::{Pread, LE};
 use std::borrow::Cow;
 use std::collections::BTreeMap;
 use std::ops::Range;
+use std::time::{Duration, Instant};
 
 /// The RTT interface.
 ///
@@ -252,6 +255,12 @@ impl Rtt {
         self.ptr
     }
 
+    /// Overwrites the ID of the control block at `ptr`, so it can't be detected anymore.
+    pub(crate) fn clear_control_block(core: &mut Core, ptr: u32) -> Result<(), Error> {
+        core.write_8((ptr + Self::O_ID as u32).into(), &[0; Self::RTT_ID.len()])?;
+        Ok(())
+    }
+
     /// Gets the detected up channels.
     pub fn up_channels(&mut self) -> &mut Channels<UpChannel> {
         &mut self.up_channels
@@ -261,6 +270,53 @@ impl Rtt {
     pub fn down_channels(&mut self) -> &mut Channels<DownChannel> {
         &mut self.down_channels
     }
+
+    /// Waits until one of the up channels has data available or the core halts, whichever happens
+    /// first.
+    ///
+    /// The up channels are checked before the core status, so output written by the target right
+    /// before halting is reported first. No data is consumed, use [`UpChannel::read`] to fetch it.
+    ///
+    /// If neither happens within `timeout`, a
+    /// [`DebugProbeError::Timeout`](probe_rs::DebugProbeError::Timeout) error is returned.
+    pub fn wait_for_output_or_halt(
+        &mut self,
+        core: &mut Core,
+        timeout: Duration,
+    ) -> Result<WaitEvent, Error> {
+        let start = Instant::now();
+
+        loop {
+            for channel in self.up_channels.iter() {
+                if channel.bytes_available(core)? > 0 {
+                    return Ok(WaitEvent::Output(channel.number()));
+                }
+            }
+
+            if let CoreStatus::Halted(reason) = core.status()? {
+                return Ok(WaitEvent::Halted(reason));
+            }
+
+            if start.elapsed() >= timeout {
+                return Err(Error::Probe(probe_rs::Error::Probe(
+                    DebugProbeError::Timeout,
+                )));
+            }
+
+            // Wait a bit before polling again.
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+}
+
+/// The event which ended an [`Rtt::wait_for_output_or_halt`] call.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum WaitEvent {
+    /// The up channel with the given number has data available.
+    Output(usize),
+
+    /// The core halted for the given reason.
+    Halted(HaltReason),
 }
 
 /// Used to specify which memory regions to scan for the RTT control block.
@@ -3,8 +3,8 @@ use std::{marker::PhantomData, path::Path, time::Duration};
 use crate::{
     dut_definition::{DefinitionSource, DutDefinition},
     tests::{
-        stepping::test_stepping, test_flashing, test_hw_breakpoints, test_memory_access,
-        test_register_access,
+        flash_test_format, stepping::test_stepping, test_flashing, test_hw_breakpoints,
+        test_memory_access, test_register_access, test_watchpoints,
     },
 };
 use anyhow::{Context, Result};
@@ -110,6 +110,11 @@ fn main() -> Result<()> {
                 Ok(())
             })?;
 
+            tracker.run_test(|tracker| {
+                test_watchpoints(tracker, &mut core, &memory_regions)?;
+                Ok(())
+            })?;
+
             tracker.run_test(|_tracker| {
                 test_stepping(&mut core, &memory_regions)?;
                 Ok(())
@@ -121,7 +126,12 @@ fn main() -> Result<()> {
 
         if let Some(flash_binary) = &definition.flash_test_binary {
             tracker.run_test(|tracker| {
-                test_flashing(tracker, &mut session, flash_binary)?;
+                test_flashing(
+                    tracker,
+                    &mut session,
+                    flash_binary,
+                    flash_test_format(flash_binary),
+                )?;
                 Ok(())
             })?;
         }
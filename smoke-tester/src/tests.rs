@@ -1,10 +1,10 @@
-use std::{path::Path, time::Instant};
+use std::{cell::Cell, path::Path, rc::Rc, time::Instant};
 
 use colored::Colorize;
 use probe_rs::{
     config::MemoryRegion,
-    flashing::{download_file_with_options, DownloadOptions, FlashProgress, Format},
-    Architecture, Core, MemoryInterface, Session,
+    flashing::{download_file_with_options, DownloadOptions, FlashProgress, Format, ProgressEvent},
+    Architecture, Core, MemoryInterface, Session, WatchpointAccess,
 };
 
 pub mod stepping;
@@ -41,6 +41,44 @@ pub fn test_register_access(tracker: &TestTracker, core: &mut Core) -> Result<()
         test_value = test_value.wrapping_shl(1);
     }
 
+    if core.core_type().is_cortex_m() && core.fpu_support()? {
+        println_test_status!(tracker, blue, "Testing FPU register access...");
+
+        if let Some(fpu_registers) = register.fpu_registers() {
+            let mut test_value: u32 = 1;
+
+            for register in fpu_registers {
+                core.write_core_reg(register.into(), test_value)?;
+
+                let readback: u32 = core.read_core_reg(register)?;
+
+                assert_eq!(
+                    test_value, readback,
+                    "Error writing register {:?}, read value does not match written value.",
+                    register
+                );
+
+                test_value = test_value.rotate_left(1);
+            }
+        }
+
+        if let Some(fpu_double_registers) = register.fpu_double_registers() {
+            for (index, register) in fpu_double_registers.enumerate() {
+                let test_value = 0x0123_4567_89AB_CDEFu64.rotate_left(4 * index as u32);
+
+                core.write_core_reg(register.into(), test_value)?;
+
+                let readback: u64 = core.read_core_reg(register)?;
+
+                assert_eq!(
+                    test_value, readback,
+                    "Error writing register {:?}, read value does not match written value.",
+                    register
+                );
+            }
+        }
+    }
+
     Ok(())
 }
 
@@ -153,28 +191,125 @@ pub fn test_hw_breakpoints(
     Ok(())
 }
 
+pub fn test_watchpoints(
+    tracker: &TestTracker,
+    core: &mut Core,
+    memory_regions: &[MemoryRegion],
+) -> Result<()> {
+    println_test_status!(tracker, blue, "Testing watchpoints");
+
+    // The watchpoints are set on the first words of the RAM, they are not hit during the test.
+    let ram = memory_regions.iter().find_map(|region| match region {
+        MemoryRegion::Ram(ram) => Some(ram),
+        _ => None,
+    });
+
+    if let Some(ram) = ram {
+        let initial_watchpoint_addr = ram.range.start;
+
+        let num_watchpoints = core.available_watchpoint_units()?;
+
+        println_test_status!(tracker, blue, "{} watchpoints supported", num_watchpoints);
+
+        // Not every RISC-V trigger can match a range of addresses.
+        let size = if core.architecture() == Architecture::Riscv {
+            1
+        } else {
+            4
+        };
+
+        for i in 0..num_watchpoints {
+            core.set_watchpoint(
+                initial_watchpoint_addr + 4 * i as u64,
+                size,
+                WatchpointAccess::ReadWrite,
+            )?;
+        }
+
+        // Try to set an additional watchpoint, which should fail
+        core.set_watchpoint(
+            initial_watchpoint_addr + num_watchpoints as u64 * 4,
+            size,
+            WatchpointAccess::Write,
+        )
+        .expect_err("Trying to use more than supported number of watchpoints should fail.");
+
+        // Clear all watchpoints again
+        for i in 0..num_watchpoints {
+            core.clear_watchpoint(initial_watchpoint_addr + 4 * i as u64)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// The format of a flash test binary, based on its extension.
+///
+/// Binaries with the extension `hex` or `ihex` are flashed as Intel HEX, all others as ELF.
+pub fn flash_test_format(test_binary: &Path) -> Format {
+    match test_binary
+        .extension()
+        .and_then(|extension| extension.to_str())
+    {
+        Some("hex" | "ihex") => Format::Hex,
+        _ => Format::Elf,
+    }
+}
+
 pub fn test_flashing(
     tracker: &TestTracker,
     session: &mut Session,
     test_binary: &Path,
+    format: Format,
 ) -> Result<()> {
-    let progress = FlashProgress::new(|event| {
-        log::debug!("Flash Event: {:?}", event);
-        eprint!(".");
-    });
+    let erase_strategy = Rc::new(Cell::new(None));
+
+    let progress = {
+        let erase_strategy = erase_strategy.clone();
+
+        FlashProgress::with_eta(move |event, progress| {
+            log::debug!("Flash Event: {:?}", event);
+            eprint!(".");
+
+            match event {
+                ProgressEvent::SelectedEraseStrategy { strategy } => {
+                    erase_strategy.set(Some(strategy));
+                }
+                ProgressEvent::FinishedErasing
+                | ProgressEvent::FinishedProgramming
+                | ProgressEvent::FinishedVerifying => {
+                    if let Some(progress) = progress {
+                        log::info!(
+                            "{:?}: {} bytes in {:.2?} ({:.0} bytes/s)",
+                            progress.phase,
+                            progress.done_bytes,
+                            progress.elapsed,
+                            progress.throughput().unwrap_or_default()
+                        );
+                    }
+                }
+                _ => {}
+            }
+        })
+    };
 
     let mut options = DownloadOptions::default();
     options.progress = Some(&progress);
 
     println_test_status!(tracker, blue, "Starting flashing test");
     println_test_status!(tracker, blue, "Binary: {}", test_binary.display());
+    println_test_status!(tracker, blue, "Format: {:?}", format);
 
     let start_time = Instant::now();
 
-    download_file_with_options(session, test_binary, Format::Elf, options)?;
+    download_file_with_options(session, test_binary, format, options)?;
 
     println!();
 
+    if let Some(strategy) = erase_strategy.get() {
+        println_test_status!(tracker, blue, "Erase strategy: {:?}", strategy);
+    }
+
     println_test_status!(
         tracker,
         blue,